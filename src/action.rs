@@ -1,42 +1,1176 @@
+use std::{borrow::Cow, collections::HashMap, sync::Arc, time::Duration};
+
+use twilight_cache_inmemory::InMemoryCache;
 use twilight_http::{
+    api_error::{ApiError, GeneralApiError},
+    error::ErrorType,
     request::{channel::reaction::RequestReactionType, AuditLogReason},
     Client,
 };
 use twilight_mention::Mention;
 use twilight_model::{
-    channel::message::ReactionType,
+    channel::{
+        message::{Embed, ReactionType},
+        ChannelType,
+    },
     id::{
-        marker::{ChannelMarker, GuildMarker, MessageMarker, UserMarker},
+        marker::{ChannelMarker, GuildMarker, MessageMarker, RoleMarker, UserMarker},
         Id,
     },
     util::Timestamp,
 };
-use twilight_util::builder::embed::{EmbedBuilder, EmbedFieldBuilder};
+use twilight_util::builder::embed::{
+    EmbedBuilder, EmbedFieldBuilder, EmbedFooterBuilder, ImageSource,
+};
 
 use eyre::Result;
+use futures::future::{BoxFuture, FutureExt};
+use serde::Serialize;
+
+use tokio::{sync::Mutex, time::Instant};
+
+use crate::{
+    component::{moderation_buttons, review_buttons},
+    config::{MessageFilterAction, MAX_TIMEOUT_SECONDS},
+    quarantine::QuarantineStore,
+    tempban::{ScheduledUnban, TempbanStore},
+    warnings::WarningStore,
+};
+
+// These limits are measured in characters, not bytes.
+const EMBED_DESCRIPTION_LENGTH: usize = 4096;
+const EMBED_FIELD_VALUE_LENGTH: usize = 1024;
+pub(crate) const AUDIT_LOG_REASON_LENGTH: usize = 512;
+const CODE_BLOCK_DELIMITERS_LENGTH: usize = 6;
+const EMBED_FOOTER_LENGTH: usize = 2048;
+/// Discord rejects embeds whose text adds up to more than this many
+/// characters, however long each part is on its own.
+const EMBED_TOTAL_LENGTH: usize = 6000;
+/// How much of a filtered message's content is sent to webhooks.
+const WEBHOOK_CONTENT_PREVIEW_LENGTH: usize = 1000;
+/// How long to wait for a webhook to respond, so that a slow endpoint can't
+/// hold up the rest of the actions.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+const WEBHOOK_SIGNATURE_HEADER: &str = "X-Chrysanthemum-Signature";
+
+/// Log embed colors, from the most to the least severe actions a filter can
+/// take.
+pub(crate) const LOG_COLOR_BAN: u32 = 0xED_42_45;
+pub(crate) const LOG_COLOR_PUNISH: u32 = 0xE6_7E_22;
+pub(crate) const LOG_COLOR_LOG: u32 = 0xFE_E7_5C;
+
+/// Discord's error code for messages that can't be sent to a user, usually
+/// because they don't accept DMs from server members.
+const CANNOT_SEND_MESSAGES_TO_USER: u64 = 50007;
+/// Discord's error code for messages that don't exist, which includes messages
+/// that have already been deleted.
+const UNKNOWN_MESSAGE: u64 = 10008;
+/// Discord's error code for bans that don't exist, such as when a user has
+/// already been unbanned by hand.
+const UNKNOWN_BAN: u64 = 10026;
+/// How long to collect deletes in a channel for before deleting them all at
+/// once.
+const DELETE_BATCH_WINDOW: Duration = Duration::from_secs(1);
+/// The most messages Discord will bulk delete in one request.
+const BULK_DELETE_MAX_MESSAGES: usize = 100;
+/// Discord won't bulk delete messages older than two weeks. This leaves a
+/// minute of leeway for messages that are about to become too old.
+const BULK_DELETE_MAX_AGE_SECONDS: i64 = 14 * 24 * 60 * 60 - 60;
+
+/// Truncates `text` to at most `max_chars` characters, replacing the end with
+/// an ellipsis if anything had to be cut off.
+pub(crate) fn truncate_with_ellipsis(text: &str, max_chars: usize) -> Cow<'_, str> {
+    const ELLIPSIS: char = '…';
+
+    match text.char_indices().nth(max_chars) {
+        Some(_) => {
+            let (last_index, _) = text.char_indices().nth(max_chars - 1).unwrap();
+            Cow::Owned(format!("{}{}", &text[..last_index], ELLIPSIS))
+        }
+        None => Cow::Borrowed(text),
+    }
+}
+
+/// Sends `content` to `user_id` in a DM. Users who have DMs closed are skipped
+/// rather than treated as an error, since there's nothing we can do about it.
+async fn send_dm(http: &Client, user_id: Id<UserMarker>, content: &str) -> Result<()> {
+    let channel = http.create_private_channel(user_id).await?.model().await?;
+    let result = http.create_message(channel.id).content(content)?.await;
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(err)
+            if matches!(
+                err.kind(),
+                ErrorType::Response {
+                    error: ApiError::General(GeneralApiError {
+                        code: CANNOT_SEND_MESSAGES_TO_USER,
+                        ..
+                    }),
+                    ..
+                }
+            ) =>
+        {
+            tracing::trace!(%user_id, "Unable to send DM; user has DMs closed");
+            Ok(())
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Whether Discord rejected a request because the message it was for doesn't
+/// exist, usually because it's already been deleted.
+fn is_unknown_message(error: &ApiError) -> bool {
+    matches!(
+        error,
+        ApiError::General(GeneralApiError {
+            code: UNKNOWN_MESSAGE,
+            ..
+        })
+    )
+}
+
+/// Whether a request failed because its message has already been deleted, by
+/// its author or by another bot. There's nothing left to do in that case, so
+/// it isn't treated as an error.
+fn already_deleted(err: &twilight_http::Error) -> bool {
+    matches!(err.kind(), ErrorType::Response { error, .. } if is_unknown_message(error))
+}
+
+/// Whether a request failed because the user it was for isn't banned. There's
+/// nothing left to do when lifting a ban in that case.
+pub(crate) fn not_banned(err: &twilight_http::Error) -> bool {
+    matches!(
+        err.kind(),
+        ErrorType::Response {
+            error: ApiError::General(GeneralApiError {
+                code: UNKNOWN_BAN,
+                ..
+            }),
+            ..
+        }
+    )
+}
+
+async fn delete_message(
+    http: &Client,
+    channel_id: Id<ChannelMarker>,
+    message_id: Id<MessageMarker>,
+    reason: Option<&str>,
+) -> Result<()> {
+    let request = http.delete_message(channel_id, message_id);
+    let request = match reason {
+        Some(reason) => request.reason(reason)?,
+        None => request,
+    };
+
+    match request.await {
+        Ok(_) => Ok(()),
+        Err(err) if already_deleted(&err) => {
+            tracing::trace!(%channel_id, %message_id, "Message was already deleted");
+            Ok(())
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Deletes a message after a delay, in the background.
+fn delete_message_later(
+    http: Arc<Client>,
+    channel_id: Id<ChannelMarker>,
+    message_id: Id<MessageMarker>,
+    delay_seconds: u64,
+    reason: Option<String>,
+) {
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(delay_seconds)).await;
+
+        if let Err(err) = delete_message(&http, channel_id, message_id, reason.as_deref()).await {
+            tracing::warn!(?err, %channel_id, %message_id, "Unable to delete message after delay");
+        }
+    });
+}
+
+/// Replies to a message, deleting the reply after `delete_after_seconds` if
+/// it's set. If the message has already been deleted, which it usually has by
+/// the time this runs, the reply is sent as a plain message instead.
+async fn reply(
+    context: &ActionContext,
+    channel_id: Id<ChannelMarker>,
+    message_id: Id<MessageMarker>,
+    content: &str,
+    delete_after_seconds: Option<u64>,
+) -> Result<()> {
+    let reply = context
+        .http
+        .create_message(channel_id)
+        .content(content)?
+        .reply(message_id)
+        .fail_if_not_exists(false)
+        .await?
+        .model()
+        .await?;
+
+    if let Some(delete_after_seconds) = delete_after_seconds {
+        delete_message_later(
+            context.http.clone(),
+            channel_id,
+            reply.id,
+            delete_after_seconds,
+            None,
+        );
+    }
+
+    Ok(())
+}
+
+/// An attachment on a filtered message, as shown in message logs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LoggedAttachment {
+    pub(crate) filename: String,
+    pub(crate) url: String,
+    pub(crate) proxy_url: String,
+    pub(crate) is_image: bool,
+}
+
+/// Picks a color for log embeds from the most severe of the actions they're
+/// logged alongside: red for bans, orange for other punishments, and yellow
+/// otherwise.
+pub(crate) fn default_log_color(actions: &[MessageFilterAction]) -> u32 {
+    actions
+        .iter()
+        .map(|action| match action {
+            MessageFilterAction::Ban { .. } => LOG_COLOR_BAN,
+            MessageFilterAction::Kick { .. }
+            | MessageFilterAction::Timeout { .. }
+            | MessageFilterAction::EscalatingTimeout { .. }
+            | MessageFilterAction::Quarantine { .. } => LOG_COLOR_PUNISH,
+            MessageFilterAction::Escalate { thresholds } => thresholds
+                .iter()
+                .map(|threshold| default_log_color(&threshold.actions))
+                .max_by_key(|color| log_color_severity(*color))
+                .unwrap_or(LOG_COLOR_LOG),
+            MessageFilterAction::EscalateOffenses { tiers, .. } => tiers
+                .iter()
+                .map(|tier| default_log_color(&tier.actions))
+                .max_by_key(|color| log_color_severity(*color))
+                .unwrap_or(LOG_COLOR_LOG),
+            _ => LOG_COLOR_LOG,
+        })
+        .max_by_key(|color| log_color_severity(*color))
+        .unwrap_or(LOG_COLOR_LOG)
+}
+
+fn log_color_severity(color: u32) -> u8 {
+    match color {
+        LOG_COLOR_BAN => 2,
+        LOG_COLOR_PUNISH => 1,
+        _ => 0,
+    }
+}
+
+/// Joins `items` into lines for an embed field, leaving off as many as it
+/// takes to fit and saying how many were left off.
+fn embed_field_list(items: &[String]) -> String {
+    let mut value = String::new();
+    let mut value_chars = 0;
+
+    for (index, item) in items.iter().enumerate() {
+        let remaining = items.len() - index - 1;
+        // Leave room for the note about what was left off, unless this is the
+        // last item.
+        let note_chars = if remaining == 0 {
+            0
+        } else {
+            format!("\n…and {} more", remaining).chars().count()
+        };
+        let item_chars = item.chars().count() + if value.is_empty() { 0 } else { 1 };
+
+        if value_chars + item_chars + note_chars > EMBED_FIELD_VALUE_LENGTH {
+            let note = format!("…and {} more", items.len() - index);
+            if !value.is_empty() {
+                value.push('\n');
+            }
+            value.push_str(&note);
+            return value;
+        }
+
+        if !value.is_empty() {
+            value.push('\n');
+        }
+        value.push_str(item);
+        value_chars += item_chars;
+    }
+
+    value
+}
+
+/// Counts the characters in an embed that count towards
+/// [`EMBED_TOTAL_LENGTH`].
+fn embed_length(embed: &Embed) -> usize {
+    let chars = |text: &str| text.chars().count();
+
+    embed.title.as_deref().map_or(0, chars)
+        + embed.description.as_deref().map_or(0, chars)
+        + embed
+            .fields
+            .iter()
+            .map(|field| chars(&field.name) + chars(&field.value))
+            .sum::<usize>()
+        + embed
+            .footer
+            .as_ref()
+            .map_or(0, |footer| chars(&footer.text))
+        + embed
+            .author
+            .as_ref()
+            .map_or(0, |author| chars(&author.name))
+}
+
+/// Wraps `content` in a code block, truncating it so that the whole block is
+/// at most `max_chars` characters long.
+fn code_block(content: &str, max_chars: usize) -> String {
+    format!(
+        "```{}```",
+        truncate_with_ellipsis(
+            content,
+            max_chars
+                .saturating_sub(CODE_BLOCK_DELIMITERS_LENGTH)
+                .max(1)
+        )
+    )
+}
+
+/// Formats one side of an edit for a message log field, in at most
+/// `max_chars` characters.
+fn edit_field_value(content: &str, max_chars: usize) -> String {
+    if content.is_empty() {
+        return "(empty)".to_owned();
+    }
+
+    code_block(content, max_chars.min(EMBED_FIELD_VALUE_LENGTH))
+}
+
+/// Builds the embed for a message log. Each part is cut down to fit its own
+/// limit, and the message's content gets whatever's left of the limit on the
+/// whole embed, with the footer fit in after it.
+#[allow(clippy::too_many_arguments)]
+fn message_log_embed(
+    title: &str,
+    color: u32,
+    filter_name: &str,
+    guild_id: Id<GuildMarker>,
+    message_channel: Id<ChannelMarker>,
+    message_id: Id<MessageMarker>,
+    content: &str,
+    previous_content: Option<Option<&str>>,
+    attachments: &[LoggedAttachment],
+    stickers: &[String],
+    filter_reason: &str,
+    author: Id<UserMarker>,
+    context: &str,
+    footer: Option<&str>,
+) -> Result<Embed> {
+    let mut embed_builder = EmbedBuilder::new()
+        .title(title)
+        .color(color)
+        .field(EmbedFieldBuilder::new(
+            "Filter",
+            truncate_with_ellipsis(filter_name, EMBED_FIELD_VALUE_LENGTH),
+        ))
+        .field(EmbedFieldBuilder::new("Author", author.mention().to_string()).build())
+        .field(EmbedFieldBuilder::new("Channel", message_channel.mention().to_string()).build())
+        .field(
+            EmbedFieldBuilder::new("Message", jump_link(guild_id, message_channel, message_id))
+                .build(),
+        )
+        .field(
+            EmbedFieldBuilder::new(
+                "Reason",
+                truncate_with_ellipsis(filter_reason, EMBED_FIELD_VALUE_LENGTH),
+            )
+            .build(),
+        )
+        .field(EmbedFieldBuilder::new("Context", context).build());
+
+    // Use the proxy URL, since it's more likely to still work if the message
+    // has been deleted by the time Discord fetches the thumbnail.
+    if let Some(image) = attachments.iter().find(|attachment| attachment.is_image) {
+        embed_builder = embed_builder.thumbnail(ImageSource::url(image.proxy_url.as_str())?);
+    }
+
+    let mut embed = embed_builder.build();
+    // Edits are shown in fields after the context, before the attachments.
+    let edit_fields_index = embed.fields.len();
+
+    if !attachments.is_empty() {
+        let links: Vec<_> = attachments
+            .iter()
+            .map(|attachment| format!("[{}]({})", attachment.filename, attachment.url))
+            .collect();
+        embed
+            .fields
+            .push(EmbedFieldBuilder::new("Attachments", embed_field_list(&links)).build());
+    }
+
+    if !stickers.is_empty() {
+        embed
+            .fields
+            .push(EmbedFieldBuilder::new("Stickers", embed_field_list(stickers)).build());
+    }
+
+    // The footer is configured by the guild, so it gets its space before the
+    // content, which is truncated to whatever is left.
+    if let Some(footer) = footer {
+        let remaining = EMBED_TOTAL_LENGTH.saturating_sub(embed_length(&embed));
+        if remaining > 0 {
+            embed.footer = Some(
+                EmbedFooterBuilder::new(truncate_with_ellipsis(
+                    footer,
+                    remaining.min(EMBED_FOOTER_LENGTH),
+                ))
+                .build(),
+            );
+        }
+    }
+
+    let remaining = EMBED_TOTAL_LENGTH.saturating_sub(embed_length(&embed));
+    if let Some(previous_content) = previous_content {
+        // Show edits side by side instead of in the description, so that
+        // it's clear what changed.
+        let value_length = remaining.saturating_sub("Original".len() + "Edited".len()) / 2;
+        let original = match previous_content {
+            Some(previous_content) => edit_field_value(previous_content, value_length),
+            None => "(not cached)".to_owned(),
+        };
+        let edited = edit_field_value(content, value_length);
+        embed.fields.splice(
+            edit_fields_index..edit_fields_index,
+            [
+                EmbedFieldBuilder::new("Original", original).build(),
+                EmbedFieldBuilder::new("Edited", edited).build(),
+            ],
+        );
+    } else if !content.is_empty() {
+        // Embeds can't have empty descriptions, so omit it entirely if
+        // there's no content (e.g. for messages with only attachments).
+        embed.description = Some(code_block(content, remaining.min(EMBED_DESCRIPTION_LENGTH)));
+    }
+
+    Ok(embed)
+}
+
+/// Builds a link that jumps to a message in the Discord client.
+pub(crate) fn jump_link(
+    guild_id: Id<GuildMarker>,
+    channel_id: Id<ChannelMarker>,
+    message_id: Id<MessageMarker>,
+) -> String {
+    format!(
+        "https://discord.com/channels/{}/{}/{}",
+        guild_id, channel_id, message_id
+    )
+}
+
+/// What actions need access to while they're executed, besides Discord.
+#[derive(Clone)]
+pub(crate) struct ActionContext {
+    pub(crate) http: Arc<Client>,
+    pub(crate) warnings: Arc<WarningStore>,
+    pub(crate) quarantines: Arc<QuarantineStore>,
+    pub(crate) cache: Arc<InMemoryCache>,
+    pub(crate) web_client: reqwest::Client,
+    pub(crate) slowmodes: Arc<Mutex<Slowmodes>>,
+    pub(crate) pending_deletes: Arc<Mutex<PendingDeletes>>,
+    pub(crate) tempbans: Arc<TempbanStore>,
+}
+
+#[derive(Debug)]
+struct ActiveSlowmode {
+    /// What the channel's slowmode was before any `set_slowmode` action
+    /// changed it.
+    original_seconds: u16,
+    /// The slowmode the channel was set to, which is the longest any action
+    /// has asked for since the slowmode was first set.
+    seconds: u16,
+    revert_at: Instant,
+}
+
+/// Channels whose slowmode was set by a `set_slowmode` action and will be
+/// reverted later.
+#[derive(Debug, Default)]
+pub(crate) struct Slowmodes {
+    active: HashMap<Id<ChannelMarker>, ActiveSlowmode>,
+}
+
+/// Publishes a message to the channels following its channel, if its channel
+/// is an announcement channel. Messages in other channels can't be published,
+/// so they're skipped rather than treated as failures.
+async fn crosspost(
+    context: &ActionContext,
+    channel_id: Id<ChannelMarker>,
+    message_id: Id<MessageMarker>,
+) -> Result<()> {
+    let kind = match context.cache.channel(channel_id) {
+        Some(channel) => channel.kind,
+        None => context.http.channel(channel_id).await?.model().await?.kind,
+    };
+
+    if kind != ChannelType::GuildAnnouncement {
+        tracing::debug!(%channel_id, %message_id, ?kind, "Not crossposting message outside an announcement channel");
+        return Ok(());
+    }
+
+    context
+        .http
+        .crosspost_message(channel_id, message_id)
+        .await?;
+    Ok(())
+}
+
+/// Sets a channel's slowmode. If several filtered messages set the same
+/// channel's slowmode, the revert is pushed back rather than scheduled again,
+/// and the channel is always reverted to what it was before the first one.
+async fn set_slowmode(
+    context: &ActionContext,
+    channel_id: Id<ChannelMarker>,
+    seconds: u16,
+    revert_after: Option<u64>,
+) -> Result<()> {
+    let mut slowmodes = context.slowmodes.lock().await;
+
+    // When actions ask for different slowmodes while one is active, the
+    // longest wins, so that a milder filter can't speed the channel back up.
+    let (original_seconds, seconds) = match slowmodes.active.get(&channel_id) {
+        Some(active) if active.seconds >= seconds => (active.original_seconds, active.seconds),
+        Some(active) => {
+            context
+                .http
+                .update_channel(channel_id)
+                .rate_limit_per_user(seconds)?
+                .await?;
+
+            (active.original_seconds, seconds)
+        }
+        None => {
+            let channel = context.http.channel(channel_id).await?.model().await?;
+            context
+                .http
+                .update_channel(channel_id)
+                .rate_limit_per_user(seconds)?
+                .await?;
+
+            (channel.rate_limit_per_user.unwrap_or(0), seconds)
+        }
+    };
+
+    let revert_after = match revert_after {
+        Some(revert_after) => revert_after,
+        None => {
+            // The slowmode is permanent now, so there's nothing to revert.
+            slowmodes.active.remove(&channel_id);
+            return Ok(());
+        }
+    };
+
+    let revert_at = Instant::now() + Duration::from_secs(revert_after);
+    let previous = slowmodes.active.insert(
+        channel_id,
+        ActiveSlowmode {
+            original_seconds,
+            seconds,
+            revert_at,
+        },
+    );
+
+    if previous.is_none() {
+        tokio::spawn(revert_slowmode(context.clone(), channel_id));
+    }
+
+    Ok(())
+}
+
+async fn revert_slowmode(context: ActionContext, channel_id: Id<ChannelMarker>) {
+    loop {
+        let revert_at = match context.slowmodes.lock().await.active.get(&channel_id) {
+            Some(active) => active.revert_at,
+            None => return,
+        };
+
+        tokio::time::sleep_until(revert_at).await;
+
+        let mut slowmodes = context.slowmodes.lock().await;
+        match slowmodes.active.get(&channel_id) {
+            // Another action pushed the revert back while we were waiting.
+            Some(active) if active.revert_at > Instant::now() => continue,
+            Some(_) => {}
+            None => return,
+        }
+
+        let active = slowmodes.active.remove(&channel_id).unwrap();
+        let result = match context
+            .http
+            .update_channel(channel_id)
+            .rate_limit_per_user(active.original_seconds)
+        {
+            Ok(request) => request.await.map(|_| ()).map_err(eyre::Report::from),
+            Err(err) => Err(err.into()),
+        };
+
+        if let Err(err) = result {
+            tracing::warn!(?err, %channel_id, "Unable to revert slowmode");
+        }
+
+        return;
+    }
+}
+
+/// Messages waiting to be deleted, by channel. A channel has an entry while a
+/// batch of deletes is being collected for it.
+#[derive(Debug, Default)]
+pub(crate) struct PendingDeletes {
+    channels: HashMap<Id<ChannelMarker>, HashMap<Id<MessageMarker>, Option<String>>>,
+}
+
+/// Deletes a message. The first delete in a channel happens right away; any
+/// others in the same channel over the next [`DELETE_BATCH_WINDOW`], like the
+/// rest of a spam burst, are collected and bulk deleted together afterwards.
+async fn queue_delete(
+    context: &ActionContext,
+    channel_id: Id<ChannelMarker>,
+    message_id: Id<MessageMarker>,
+    reason: Option<&str>,
+) -> Result<()> {
+    {
+        let mut pending = context.pending_deletes.lock().await;
+        if let Some(messages) = pending.channels.get_mut(&channel_id) {
+            messages.insert(message_id, reason.map(str::to_owned));
+            return Ok(());
+        }
+
+        pending.channels.insert(channel_id, HashMap::new());
+    }
+
+    tokio::spawn(flush_deletes(context.clone(), channel_id));
+    delete_message(&context.http, channel_id, message_id, reason).await
+}
+
+async fn flush_deletes(context: ActionContext, channel_id: Id<ChannelMarker>) {
+    tokio::time::sleep(DELETE_BATCH_WINDOW).await;
+
+    let reasons = context
+        .pending_deletes
+        .lock()
+        .await
+        .channels
+        .remove(&channel_id)
+        .unwrap_or_default();
+
+    let (batches, mut singles) = delete_batches(
+        reasons.keys().copied().collect(),
+        chrono::Utc::now().timestamp(),
+    );
+
+    for batch in batches {
+        tracing::debug!(%channel_id, count = batch.len(), "Bulk deleting messages");
+        let reason = bulk_delete_reason(
+            batch
+                .iter()
+                .filter_map(|message_id| reasons[message_id].as_deref()),
+        );
+        let request = context.http.delete_messages(channel_id, &batch);
+        let result = match reason.as_deref() {
+            Some(reason) => request.reason(reason).map_err(eyre::Report::from),
+            None => Ok(request),
+        };
+
+        let result = match result {
+            Ok(request) => request.await.map(|_| ()).map_err(eyre::Report::from),
+            Err(err) => Err(err),
+        };
+
+        if let Err(err) = result {
+            tracing::warn!(?err, %channel_id, "Unable to bulk delete messages, deleting them one at a time");
+            singles.extend(batch);
+        }
+    }
+
+    for message_id in singles {
+        let reason = reasons[&message_id].as_deref();
+        if let Err(err) = delete_message(&context.http, channel_id, message_id, reason).await {
+            tracing::warn!(?err, %channel_id, %message_id, "Unable to delete message");
+        }
+    }
+}
+
+/// Combines the audit log reasons for the messages in a bulk delete, since the
+/// audit log only has room for one.
+fn bulk_delete_reason<'a>(reasons: impl Iterator<Item = &'a str>) -> Option<String> {
+    let mut distinct: Vec<&str> = Vec::new();
+    for reason in reasons {
+        if !distinct.contains(&reason) {
+            distinct.push(reason);
+        }
+    }
+
+    if distinct.is_empty() {
+        None
+    } else {
+        Some(truncate_with_ellipsis(&distinct.join("; "), AUDIT_LOG_REASON_LENGTH).into_owned())
+    }
+}
+
+/// Splits the messages to delete into batches that can be bulk deleted at
+/// `now`, a Unix timestamp in seconds, and messages that have to be deleted
+/// one at a time, because they're too old or there's only one left over.
+fn delete_batches(
+    mut message_ids: Vec<Id<MessageMarker>>,
+    now: i64,
+) -> (Vec<Vec<Id<MessageMarker>>>, Vec<Id<MessageMarker>>) {
+    message_ids.sort_unstable();
+    message_ids.dedup();
+
+    let (recent, mut singles): (Vec<_>, Vec<_>) = message_ids.into_iter().partition(|message_id| {
+        now - crate::model::snowflake_timestamp(*message_id).as_secs() < BULK_DELETE_MAX_AGE_SECONDS
+    });
+
+    let mut batches = Vec::new();
+    for batch in recent.chunks(BULK_DELETE_MAX_MESSAGES) {
+        if batch.len() == 1 {
+            singles.push(batch[0]);
+        } else {
+            batches.push(batch.to_vec());
+        }
+    }
+
+    (batches, singles)
+}
+
+/// Clamps a timeout's duration to the longest that Discord allows.
+fn clamp_timeout_duration(duration_seconds: i64) -> i64 {
+    duration_seconds.min(MAX_TIMEOUT_SECONDS)
+}
+
+/// Times a member out for `duration_seconds` from `now`. Discord rejects
+/// timeouts longer than 28 days outright, so longer ones are shortened to 28
+/// days rather than letting the member off entirely.
+async fn timeout(
+    http: &Client,
+    guild_id: Id<GuildMarker>,
+    user_id: Id<UserMarker>,
+    now: i64,
+    duration_seconds: i64,
+    reason: &str,
+) -> Result<()> {
+    let clamped_duration = clamp_timeout_duration(duration_seconds);
+    if clamped_duration != duration_seconds {
+        tracing::warn!(
+            %guild_id,
+            %user_id,
+            duration_seconds,
+            clamped_duration,
+            "Timeout is longer than Discord allows; shortening it to 28 days"
+        );
+    }
+
+    let timeout_expires_at = Timestamp::from_secs(now + clamped_duration)?;
+    http.update_guild_member(guild_id, user_id)
+        .communication_disabled_until(Some(timeout_expires_at))?
+        .reason(reason)?
+        .await?;
+
+    Ok(())
+}
+
+/// Makes a `ban` action temporary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Tempban {
+    pub(crate) duration_seconds: u64,
+    /// The filter that issued the ban, for the audit log reasons.
+    pub(crate) filter_name: String,
+}
+
+async fn ban(
+    context: &ActionContext,
+    guild_id: Id<GuildMarker>,
+    user_id: Id<UserMarker>,
+    delete_message_seconds: u32,
+    reason: &str,
+    tempban: Option<&Tempban>,
+) -> Result<()> {
+    let reason = match tempban {
+        Some(tempban) => Cow::Owned(format!(
+            "{} (temporary ban for {} by filter {})",
+            reason,
+            format_duration(tempban.duration_seconds),
+            tempban.filter_name
+        )),
+        None => Cow::Borrowed(reason),
+    };
+
+    context
+        .http
+        .create_ban(guild_id, user_id)
+        .delete_message_seconds(delete_message_seconds)?
+        .reason(&reason)?
+        .await?;
+
+    match tempban {
+        Some(tempban) => {
+            context
+                .tempbans
+                .schedule(ScheduledUnban {
+                    guild_id,
+                    user_id,
+                    filter_name: tempban.filter_name.clone(),
+                    unban_at: crate::tempban::unban_at(
+                        chrono::Utc::now().timestamp(),
+                        tempban.duration_seconds,
+                    ),
+                })
+                .await?;
+        }
+        // A permanent ban replaces any temporary one, which mustn't lift it.
+        None => context.tempbans.cancel(guild_id, user_id).await?,
+    }
+
+    Ok(())
+}
+
+/// Takes away all of a member's roles and gives them just the quarantine role.
+/// Their roles are remembered first, so that `/chrysanthemum-unquarantine` can
+/// give them back.
+async fn quarantine(
+    context: &ActionContext,
+    guild_id: Id<GuildMarker>,
+    user_id: Id<UserMarker>,
+    quarantine_role: Id<RoleMarker>,
+    restore_on_unquarantine: bool,
+    reason: &str,
+) -> Result<()> {
+    let roles = match context.cache.member(guild_id, user_id) {
+        Some(member) => member.roles().to_owned(),
+        None => {
+            context
+                .http
+                .guild_member(guild_id, user_id)
+                .await?
+                .model()
+                .await?
+                .roles
+        }
+    };
+
+    let roles_to_restore = if restore_on_unquarantine {
+        roles
+            .into_iter()
+            .filter(|role_id| *role_id != quarantine_role)
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    context
+        .quarantines
+        .quarantine(guild_id, user_id, roles_to_restore)
+        .await?;
+
+    context
+        .http
+        .update_guild_member(guild_id, user_id)
+        .roles(&[quarantine_role])
+        .reason(reason)?
+        .await?;
+
+    Ok(())
+}
+
+/// What's sent to webhooks when a message or reaction is filtered.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct WebhookPayload {
+    pub(crate) guild_id: Id<GuildMarker>,
+    pub(crate) channel_id: Id<ChannelMarker>,
+    pub(crate) message_id: Id<MessageMarker>,
+    pub(crate) author_id: Id<UserMarker>,
+    pub(crate) filter_name: String,
+    pub(crate) reason: String,
+    pub(crate) context: &'static str,
+    /// The start of the filtered message's content, for message filters.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) content_preview: Option<String>,
+    /// The filtered emoji, for reaction filters.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) reaction: Option<String>,
+}
+
+pub(crate) fn webhook_content_preview(content: &str) -> String {
+    truncate_with_ellipsis(content, WEBHOOK_CONTENT_PREVIEW_LENGTH).into_owned()
+}
+
+/// Signs a webhook body with HMAC-SHA256, in the format sent in the signature
+/// header.
+fn webhook_signature(secret: &str, body: &[u8]) -> String {
+    let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, secret.as_bytes());
+    format!(
+        "sha256={}",
+        hex::encode(ring::hmac::sign(&key, body).as_ref())
+    )
+}
+
+async fn send_webhook(
+    web_client: &reqwest::Client,
+    url: &str,
+    secret: Option<&str>,
+    payload: &WebhookPayload,
+) -> Result<()> {
+    let body = serde_json::to_vec(payload)?;
+    let mut request = web_client
+        .post(url)
+        .timeout(WEBHOOK_TIMEOUT)
+        .header(reqwest::header::CONTENT_TYPE, "application/json");
+
+    if let Some(secret) = secret {
+        request = request.header(WEBHOOK_SIGNATURE_HEADER, webhook_signature(secret, &body));
+    }
+
+    request.body(body).send().await?.error_for_status()?;
+    Ok(())
+}
+
+/// Formats a duration in seconds in the largest whole unit that fits it, like
+/// `10m` or `24h`.
+fn format_duration(seconds: u64) -> String {
+    if seconds % 3600 == 0 {
+        format!("{}h", seconds / 3600)
+    } else if seconds % 60 == 0 {
+        format!("{}m", seconds / 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+/// How long to wait before each retry of a failed action. Only failures that
+/// might go away by themselves are retried; see [`is_retryable`].
+const ACTION_RETRY_DELAYS: [Duration; 3] = [
+    Duration::from_millis(250),
+    Duration::from_secs(1),
+    Duration::from_secs(4),
+];
+
+/// Whether a request that got this status code might succeed if sent again.
+fn is_retryable_status(status: u16) -> bool {
+    status == 429 || (500..600).contains(&status)
+}
+
+fn is_retryable_http_error(kind: &ErrorType) -> bool {
+    match kind {
+        ErrorType::Response { status, .. } => is_retryable_status(status.get()),
+        ErrorType::RequestError
+        | ErrorType::RequestTimedOut
+        | ErrorType::ServiceUnavailable { .. } => true,
+        _ => false,
+    }
+}
+
+/// Whether an action that failed with `err` is worth retrying: Discord or a
+/// webhook being rate limited, having an outage, or timing out. Anything else,
+/// like missing permissions or the message already being gone, would just
+/// fail again.
+fn is_retryable(err: &eyre::Report) -> bool {
+    if let Some(err) = err.downcast_ref::<twilight_http::Error>() {
+        is_retryable_http_error(err.kind())
+    } else if let Some(err) = err.downcast_ref::<reqwest::Error>() {
+        err.is_timeout()
+            || err.is_connect()
+            || err
+                .status()
+                .map_or(false, |status| is_retryable_status(status.as_u16()))
+    } else {
+        false
+    }
+}
+
+fn is_unsent_http_error(kind: &ErrorType) -> bool {
+    matches!(kind, ErrorType::Response { status, .. } if status.get() == 429)
+}
+
+/// Whether an action that failed with `err` certainly wasn't carried out, so
+/// that retrying it can't do anything twice: it was rate limited, or the
+/// connection couldn't be made at all. Actions that aren't safe to repeat are
+/// only retried for these.
+fn is_unsent(err: &eyre::Report) -> bool {
+    if let Some(err) = err.downcast_ref::<twilight_http::Error>() {
+        is_unsent_http_error(err.kind())
+    } else if let Some(err) = err.downcast_ref::<reqwest::Error>() {
+        err.is_connect() || err.status().map_or(false, |status| status.as_u16() == 429)
+    } else {
+        false
+    }
+}
+
+/// Runs `attempt` until it succeeds, fails in a way that `should_retry` says
+/// isn't worth retrying, or runs out of retries.
+async fn with_retries<F, Fut>(should_retry: fn(&eyre::Report) -> bool, mut attempt: F) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<()>>,
+{
+    let mut delays = ACTION_RETRY_DELAYS.iter();
+    let mut attempts = 1;
+
+    loop {
+        match attempt().await {
+            Ok(()) => return Ok(()),
+            Err(err) if should_retry(&err) => match delays.next() {
+                Some(delay) => {
+                    tracing::debug!(?err, attempts, "Action failed; retrying");
+                    tokio::time::sleep(*delay).await;
+                    attempts += 1;
+                }
+                None => return Err(err.wrap_err(format!("failed after {} attempts", attempts))),
+            },
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Gets the actions for the highest threshold that `count` has reached, if
+/// any.
+fn escalation_actions<A>(thresholds: &[(usize, Vec<A>)], count: usize) -> &[A] {
+    thresholds
+        .iter()
+        .filter(|(threshold, _)| count >= *threshold)
+        .max_by_key(|(threshold, _)| *threshold)
+        .map(|(_, actions)| &actions[..])
+        .unwrap_or(&[])
+}
+
+/// What an escalation's thresholds are compared against.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum EscalationCount {
+    /// The user's unexpired warnings, from `escalate` actions.
+    Warnings,
+    /// The user's recent offenses against a filter, from `escalate_offenses`
+    /// actions. The current offense is recorded when the escalation is taken.
+    FilterOffenses {
+        filter_name: String,
+        window_seconds: u64,
+    },
+}
+
+impl EscalationCount {
+    async fn get(
+        &self,
+        warnings: &WarningStore,
+        guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+    ) -> Result<usize> {
+        let now = chrono::Utc::now().timestamp();
+        match self {
+            EscalationCount::Warnings => Ok(warnings.count(guild_id, user_id, now).await),
+            EscalationCount::FilterOffenses {
+                filter_name,
+                window_seconds,
+            } => {
+                warnings
+                    .record_filter_offense(guild_id, user_id, filter_name, now, *window_seconds)
+                    .await
+            }
+        }
+    }
+}
 
 #[derive(Debug, PartialEq, Eq)]
 pub(crate) enum MessageAction {
     Delete {
         message_id: Id<MessageMarker>,
         channel_id: Id<ChannelMarker>,
+        delay_seconds: Option<u64>,
+        /// Why the message was deleted, for the audit log.
+        reason: Option<String>,
     },
     SendMessage {
         to: Id<ChannelMarker>,
         content: String,
         requires_armed: bool,
     },
+    Reply {
+        channel_id: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+        content: String,
+        delete_after_seconds: Option<u64>,
+        requires_armed: bool,
+    },
+    SendDm {
+        user_id: Id<UserMarker>,
+        content: String,
+        requires_armed: bool,
+    },
     Ban {
         user_id: Id<UserMarker>,
         guild_id: Id<GuildMarker>,
         delete_message_seconds: u32,
         reason: String,
+        tempban: Option<Tempban>,
     },
     Kick {
         user_id: Id<UserMarker>,
         guild_id: Id<GuildMarker>,
         reason: String,
     },
+    AddRole {
+        user_id: Id<UserMarker>,
+        guild_id: Id<GuildMarker>,
+        role_id: Id<RoleMarker>,
+        reason: String,
+    },
+    RemoveRole {
+        user_id: Id<UserMarker>,
+        guild_id: Id<GuildMarker>,
+        role_id: Id<RoleMarker>,
+        reason: String,
+    },
+    EscalatingTimeout {
+        user_id: Id<UserMarker>,
+        guild_id: Id<GuildMarker>,
+        reason: String,
+        durations: Vec<u64>,
+        window_seconds: u64,
+    },
+    Webhook {
+        url: String,
+        secret: Option<String>,
+        payload: WebhookPayload,
+    },
+    Quarantine {
+        user_id: Id<UserMarker>,
+        guild_id: Id<GuildMarker>,
+        quarantine_role: Id<RoleMarker>,
+        restore_on_unquarantine: bool,
+        reason: String,
+    },
+    SetSlowmode {
+        channel_id: Id<ChannelMarker>,
+        seconds: u16,
+        revert_after: Option<u64>,
+    },
+    Crosspost {
+        channel_id: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+    },
+    Warn {
+        user_id: Id<UserMarker>,
+        guild_id: Id<GuildMarker>,
+        expiry_seconds: u64,
+    },
+    /// Nested actions are taken in the order they're listed in, and aren't
+    /// checked against the armed state individually.
+    Escalate {
+        user_id: Id<UserMarker>,
+        guild_id: Id<GuildMarker>,
+        count: EscalationCount,
+        thresholds: Vec<(usize, Vec<Self>)>,
+    },
     Timeout {
         user_id: Id<UserMarker>,
         guild_id: Id<GuildMarker>,
@@ -46,46 +1180,222 @@ pub(crate) enum MessageAction {
     SendLog {
         to: Id<ChannelMarker>,
         filter_name: String,
+        guild_id: Id<GuildMarker>,
         message_channel: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
         content: String,
+        /// For edits, the content before the edit, or `None` if it wasn't
+        /// cached.
+        previous_content: Option<Option<String>>,
+        attachments: Vec<LoggedAttachment>,
+        /// The names of the message's stickers.
+        stickers: Vec<String>,
         filter_reason: String,
         author: Id<UserMarker>,
         context: &'static str,
+        color: u32,
+        footer: Option<String>,
+        /// Whether this is being logged instead of acted on, because
+        /// Chrysanthemum is running with `dry_run_globally`.
+        dry_run: bool,
+    },
+    FlagForReview {
+        to: Id<ChannelMarker>,
+        filter_name: String,
+        guild_id: Id<GuildMarker>,
+        message_channel: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+        content: String,
+        filter_reason: String,
+        author: Id<UserMarker>,
     },
 }
 
 impl MessageAction {
-    #[tracing::instrument(skip(http))]
-    pub(crate) async fn execute(&self, http: &Client) -> Result<()> {
+    #[tracing::instrument(skip(context))]
+    pub(crate) async fn execute(&self, context: &ActionContext) -> Result<()> {
+        let http = &*context.http;
+        let warnings = &*context.warnings;
+
         match self {
             Self::Delete {
                 message_id,
                 channel_id,
+                delay_seconds: None,
+                reason,
+            } => {
+                queue_delete(context, *channel_id, *message_id, reason.as_deref()).await?;
+            }
+            Self::Delete {
+                message_id,
+                channel_id,
+                delay_seconds: Some(delay_seconds),
+                reason,
             } => {
-                http.delete_message(*channel_id, *message_id).await?;
+                delete_message_later(
+                    context.http.clone(),
+                    *channel_id,
+                    *message_id,
+                    *delay_seconds,
+                    reason.clone(),
+                );
             }
             Self::SendMessage { to, content, .. } => {
                 http.create_message(*to).content(content)?.await?;
             }
-            Self::Ban {
+            Self::Reply {
+                channel_id,
+                message_id,
+                content,
+                delete_after_seconds,
+                ..
+            } => {
+                reply(
+                    context,
+                    *channel_id,
+                    *message_id,
+                    content,
+                    *delete_after_seconds,
+                )
+                .await?;
+            }
+            Self::SendDm {
+                user_id, content, ..
+            } => {
+                send_dm(http, *user_id, content).await?;
+            }
+            Self::Ban {
+                user_id,
+                guild_id,
+                delete_message_seconds,
+                reason,
+                tempban,
+            } => {
+                ban(
+                    context,
+                    *guild_id,
+                    *user_id,
+                    *delete_message_seconds,
+                    reason,
+                    tempban.as_ref(),
+                )
+                .await?;
+            }
+            Self::Kick {
+                user_id,
+                guild_id,
+                reason,
+            } => {
+                http.remove_guild_member(*guild_id, *user_id)
+                    .reason(reason)?
+                    .await?;
+            }
+            Self::AddRole {
+                user_id,
+                guild_id,
+                role_id,
+                reason,
+            } => {
+                http.add_guild_member_role(*guild_id, *user_id, *role_id)
+                    .reason(reason)?
+                    .await?;
+            }
+            Self::RemoveRole {
                 user_id,
                 guild_id,
-                delete_message_seconds,
+                role_id,
                 reason,
             } => {
-                http.create_ban(*guild_id, *user_id)
-                    .delete_message_seconds(*delete_message_seconds)?
+                http.remove_guild_member_role(*guild_id, *user_id, *role_id)
                     .reason(reason)?
                     .await?;
             }
-            Self::Kick {
+            Self::EscalatingTimeout {
                 user_id,
                 guild_id,
                 reason,
+                durations,
+                window_seconds,
             } => {
-                http.remove_guild_member(*guild_id, *user_id)
-                    .reason(reason)?
+                let now = chrono::Utc::now().timestamp();
+                let offense = warnings
+                    .record_timeout_offense(*guild_id, *user_id, now, *window_seconds)
+                    .await?;
+                let duration = durations[offense.min(durations.len()) - 1];
+                let reason = format!(
+                    "{} (offense {}/{}: {})",
+                    reason,
+                    offense,
+                    durations.len(),
+                    format_duration(duration)
+                );
+
+                // The offense has been recorded, so only the timeout itself is
+                // retried.
+                with_retries(is_retryable, || {
+                    timeout(http, *guild_id, *user_id, now, duration as i64, &reason)
+                })
+                .await?;
+            }
+            Self::Webhook {
+                url,
+                secret,
+                payload,
+            } => {
+                send_webhook(&context.web_client, url, secret.as_deref(), payload).await?;
+            }
+            Self::Quarantine {
+                user_id,
+                guild_id,
+                quarantine_role,
+                restore_on_unquarantine,
+                reason,
+            } => {
+                quarantine(
+                    context,
+                    *guild_id,
+                    *user_id,
+                    *quarantine_role,
+                    *restore_on_unquarantine,
+                    reason,
+                )
+                .await?;
+            }
+            Self::SetSlowmode {
+                channel_id,
+                seconds,
+                revert_after,
+            } => {
+                set_slowmode(context, *channel_id, *seconds, *revert_after).await?;
+            }
+            Self::Crosspost {
+                channel_id,
+                message_id,
+            } => {
+                crosspost(context, *channel_id, *message_id).await?;
+            }
+            Self::Warn {
+                user_id,
+                guild_id,
+                expiry_seconds,
+            } => {
+                let now = chrono::Utc::now().timestamp();
+                let count = warnings
+                    .warn(*guild_id, *user_id, now, *expiry_seconds)
                     .await?;
+                tracing::trace!(%user_id, %guild_id, count, "Warned user");
+            }
+            Self::Escalate {
+                user_id,
+                guild_id,
+                count,
+                thresholds,
+            } => {
+                let count = count.get(warnings, *guild_id, *user_id).await?;
+
+                for action in escalation_actions(thresholds, count) {
+                    action.execute_with_retries_boxed(context).await?;
+                }
             }
             Self::Timeout {
                 user_id,
@@ -93,41 +1403,102 @@ impl MessageAction {
                 duration,
                 reason,
             } => {
-                let timeout_expires_at =
-                    Timestamp::from_secs(chrono::Utc::now().timestamp() + *duration)?;
-
-                http.update_guild_member(*guild_id, *user_id)
-                    .communication_disabled_until(Some(timeout_expires_at))?
-                    .reason(reason)?
-                    .await?;
+                timeout(
+                    http,
+                    *guild_id,
+                    *user_id,
+                    chrono::Utc::now().timestamp(),
+                    *duration,
+                    reason,
+                )
+                .await?;
             }
             Self::SendLog {
                 to,
                 filter_name,
+                guild_id,
                 message_channel,
+                message_id,
                 content,
+                previous_content,
+                attachments,
+                stickers,
                 filter_reason,
                 author,
                 context,
+                color,
+                footer,
+                dry_run,
             } => {
-                let mut embed_builder = EmbedBuilder::new()
-                    .title("Message filtered")
-                    .field(EmbedFieldBuilder::new("Filter", filter_name))
+                let title = if *dry_run {
+                    "Message filtered (dry run)"
+                } else {
+                    "Message filtered"
+                };
+                let embed = message_log_embed(
+                    title,
+                    *color,
+                    filter_name,
+                    *guild_id,
+                    *message_channel,
+                    *message_id,
+                    content,
+                    previous_content.as_ref().map(Option::as_deref),
+                    attachments,
+                    stickers,
+                    filter_reason,
+                    *author,
+                    context,
+                    footer.as_deref(),
+                )?;
+
+                http.create_message(*to)
+                    .embeds(&[embed])?
+                    .components(&[moderation_buttons(*author, *message_id)])?
+                    .await?;
+            }
+            Self::FlagForReview {
+                to,
+                filter_name,
+                guild_id,
+                message_channel,
+                message_id,
+                content,
+                filter_reason,
+                author,
+            } => {
+                let embed_builder = EmbedBuilder::new()
+                    .title("Message flagged for review")
+                    .field(EmbedFieldBuilder::new(
+                        "Filter",
+                        truncate_with_ellipsis(filter_name, EMBED_FIELD_VALUE_LENGTH),
+                    ))
                     .field(EmbedFieldBuilder::new("Author", author.mention().to_string()).build())
                     .field(
-                        EmbedFieldBuilder::new("Channel", message_channel.mention().to_string())
-                            .build(),
+                        EmbedFieldBuilder::new(
+                            "Message",
+                            jump_link(*guild_id, *message_channel, *message_id),
+                        )
+                        .build(),
                     )
-                    .field(EmbedFieldBuilder::new("Reason", filter_reason).build())
-                    .field(EmbedFieldBuilder::new("Context", *context).build());
+                    .field(
+                        EmbedFieldBuilder::new(
+                            "Reason",
+                            truncate_with_ellipsis(filter_reason, EMBED_FIELD_VALUE_LENGTH),
+                        )
+                        .build(),
+                    );
 
+                let mut embed = embed_builder.build();
                 if !content.is_empty() {
-                    embed_builder = embed_builder.description(format!("```{}```", content));
+                    let remaining = EMBED_TOTAL_LENGTH.saturating_sub(embed_length(&embed));
+                    embed.description =
+                        Some(code_block(content, remaining.min(EMBED_DESCRIPTION_LENGTH)));
                 }
 
                 http.create_message(*to)
-                    .embeds(&[embed_builder.build()])
-                    .unwrap()
+                    .embeds(&[embed])?
+                    .components(&[review_buttons(*message_channel, *message_id)])?
                     .await?;
             }
         };
@@ -135,13 +1506,77 @@ impl MessageAction {
         Ok(())
     }
 
+    /// Escalations can nest actions, so executing them recursively needs a
+    /// boxed future.
+    fn execute_with_retries_boxed<'a>(
+        &'a self,
+        context: &'a ActionContext,
+    ) -> BoxFuture<'a, Result<()>> {
+        self.execute_with_retries(context).boxed()
+    }
+
+    /// Whether taking this action twice has the same effect as taking it
+    /// once, so that it can be retried even when it might have worked.
+    fn is_idempotent(&self) -> bool {
+        matches!(
+            self,
+            Self::Delete { .. }
+                | Self::Ban { .. }
+                | Self::Kick { .. }
+                | Self::AddRole { .. }
+                | Self::RemoveRole { .. }
+                | Self::Timeout { .. }
+                | Self::SetSlowmode { .. }
+        )
+    }
+
+    /// Like [`execute`](Self::execute), but retries transient failures with
+    /// backoff. Actions that send something are only retried if they
+    /// certainly weren't carried out, so that nothing is sent twice.
+    pub(crate) async fn execute_with_retries(&self, context: &ActionContext) -> Result<()> {
+        match self {
+            // These retry the requests they make themselves, after the parts
+            // that mustn't be repeated.
+            Self::EscalatingTimeout { .. } | Self::Escalate { .. } => self.execute(context).await,
+            _ if self.is_idempotent() => with_retries(is_retryable, || self.execute(context)).await,
+            _ => with_retries(is_unsent, || self.execute(context)).await,
+        }
+    }
+
+    /// Gets what should happen instead of this action when running with
+    /// `dry_run_globally`: logs are still sent, marked as a dry run, and
+    /// everything else is skipped.
+    pub(crate) fn into_dry_run(mut self) -> Option<Self> {
+        match &mut self {
+            MessageAction::SendLog { dry_run, .. } => {
+                *dry_run = true;
+                Some(self)
+            }
+            _ => None,
+        }
+    }
+
     pub(crate) fn requires_armed(&self) -> bool {
         match self {
             MessageAction::Delete { .. } => true,
             MessageAction::Ban { .. } => true,
             MessageAction::Kick { .. } => true,
+            MessageAction::AddRole { .. } => true,
+            MessageAction::RemoveRole { .. } => true,
+            MessageAction::EscalatingTimeout { .. } => true,
+            MessageAction::Quarantine { .. } => true,
+            MessageAction::SetSlowmode { .. } => true,
+            // Publishing a message doesn't take anything away from anyone.
+            MessageAction::Crosspost { .. } => false,
+            MessageAction::Warn { .. } => true,
+            MessageAction::Escalate { thresholds, .. } => thresholds
+                .iter()
+                .flat_map(|(_, actions)| actions)
+                .any(Self::requires_armed),
             MessageAction::Timeout { .. } => true,
             MessageAction::SendMessage { requires_armed, .. } => *requires_armed,
+            MessageAction::Reply { requires_armed, .. } => *requires_armed,
+            MessageAction::SendDm { requires_armed, .. } => *requires_armed,
             _ => false,
         }
     }
@@ -159,17 +1594,83 @@ pub(crate) enum ReactionAction {
         content: String,
         requires_armed: bool,
     },
+    Reply {
+        channel_id: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+        content: String,
+        delete_after_seconds: Option<u64>,
+        requires_armed: bool,
+    },
+    SendDm {
+        user_id: Id<UserMarker>,
+        content: String,
+        requires_armed: bool,
+    },
     Ban {
         user_id: Id<UserMarker>,
         guild_id: Id<GuildMarker>,
         delete_message_seconds: u32,
         reason: String,
+        tempban: Option<Tempban>,
     },
     Kick {
         user_id: Id<UserMarker>,
         guild_id: Id<GuildMarker>,
         reason: String,
     },
+    AddRole {
+        user_id: Id<UserMarker>,
+        guild_id: Id<GuildMarker>,
+        role_id: Id<RoleMarker>,
+        reason: String,
+    },
+    RemoveRole {
+        user_id: Id<UserMarker>,
+        guild_id: Id<GuildMarker>,
+        role_id: Id<RoleMarker>,
+        reason: String,
+    },
+    EscalatingTimeout {
+        user_id: Id<UserMarker>,
+        guild_id: Id<GuildMarker>,
+        reason: String,
+        durations: Vec<u64>,
+        window_seconds: u64,
+    },
+    Webhook {
+        url: String,
+        secret: Option<String>,
+        payload: WebhookPayload,
+    },
+    Quarantine {
+        user_id: Id<UserMarker>,
+        guild_id: Id<GuildMarker>,
+        quarantine_role: Id<RoleMarker>,
+        restore_on_unquarantine: bool,
+        reason: String,
+    },
+    SetSlowmode {
+        channel_id: Id<ChannelMarker>,
+        seconds: u16,
+        revert_after: Option<u64>,
+    },
+    Crosspost {
+        channel_id: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+    },
+    Warn {
+        user_id: Id<UserMarker>,
+        guild_id: Id<GuildMarker>,
+        expiry_seconds: u64,
+    },
+    /// Nested actions are taken in the order they're listed in, and aren't
+    /// checked against the armed state individually.
+    Escalate {
+        user_id: Id<UserMarker>,
+        guild_id: Id<GuildMarker>,
+        count: EscalationCount,
+        thresholds: Vec<(usize, Vec<Self>)>,
+    },
     Timeout {
         user_id: Id<UserMarker>,
         guild_id: Id<GuildMarker>,
@@ -179,17 +1680,28 @@ pub(crate) enum ReactionAction {
     SendLog {
         to: Id<ChannelMarker>,
         filter_name: String,
+        guild_id: Id<GuildMarker>,
         message: Id<MessageMarker>,
         channel: Id<ChannelMarker>,
         filter_reason: String,
         author: Id<UserMarker>,
         reaction: ReactionType,
+        /// Whether the user removed the reaction before it could be acted on.
+        removed: bool,
+        color: u32,
+        footer: Option<String>,
+        /// Whether this is being logged instead of acted on, because
+        /// Chrysanthemum is running with `dry_run_globally`.
+        dry_run: bool,
     },
 }
 
 impl ReactionAction {
-    #[tracing::instrument(skip(http))]
-    pub(crate) async fn execute(&self, http: &Client) -> Result<()> {
+    #[tracing::instrument(skip(context))]
+    pub(crate) async fn execute(&self, context: &ActionContext) -> Result<()> {
+        let http = &*context.http;
+        let warnings = &*context.warnings;
+
         match self {
             Self::Delete {
                 message_id,
@@ -204,22 +1716,57 @@ impl ReactionAction {
                     ReactionType::Unicode { name } => RequestReactionType::Unicode { name },
                 };
 
-                http.delete_all_reaction(*channel_id, *message_id, &request_emoji)
-                    .await?;
+                match http
+                    .delete_all_reaction(*channel_id, *message_id, &request_emoji)
+                    .await
+                {
+                    Ok(_) => {}
+                    Err(err) if already_deleted(&err) => {
+                        tracing::trace!(%channel_id, %message_id, "Reacted message was already deleted");
+                    }
+                    Err(err) => return Err(err.into()),
+                }
             }
             Self::SendMessage { to, content, .. } => {
                 http.create_message(*to).content(content)?.await?;
             }
+            Self::Reply {
+                channel_id,
+                message_id,
+                content,
+                delete_after_seconds,
+                ..
+            } => {
+                reply(
+                    context,
+                    *channel_id,
+                    *message_id,
+                    content,
+                    *delete_after_seconds,
+                )
+                .await?;
+            }
+            Self::SendDm {
+                user_id, content, ..
+            } => {
+                send_dm(http, *user_id, content).await?;
+            }
             Self::Ban {
                 user_id,
                 guild_id,
                 delete_message_seconds,
                 reason,
+                tempban,
             } => {
-                http.create_ban(*guild_id, *user_id)
-                    .delete_message_seconds(*delete_message_seconds)?
-                    .reason(reason)?
-                    .await?;
+                ban(
+                    context,
+                    *guild_id,
+                    *user_id,
+                    *delete_message_seconds,
+                    reason,
+                    tempban.as_ref(),
+                )
+                .await?;
             }
             Self::Kick {
                 user_id,
@@ -230,56 +1777,174 @@ impl ReactionAction {
                     .reason(reason)?
                     .await?;
             }
-            Self::Timeout {
+            Self::AddRole {
                 user_id,
                 guild_id,
-                duration,
+                role_id,
                 reason,
             } => {
-                let timeout_expires_at =
-                    Timestamp::from_secs(chrono::Utc::now().timestamp() + *duration)?;
-
-                http.update_guild_member(*guild_id, *user_id)
-                    .communication_disabled_until(Some(timeout_expires_at))?
+                http.add_guild_member_role(*guild_id, *user_id, *role_id)
+                    .reason(reason)?
+                    .await?;
+            }
+            Self::RemoveRole {
+                user_id,
+                guild_id,
+                role_id,
+                reason,
+            } => {
+                http.remove_guild_member_role(*guild_id, *user_id, *role_id)
                     .reason(reason)?
                     .await?;
             }
+            Self::EscalatingTimeout {
+                user_id,
+                guild_id,
+                reason,
+                durations,
+                window_seconds,
+            } => {
+                let now = chrono::Utc::now().timestamp();
+                let offense = warnings
+                    .record_timeout_offense(*guild_id, *user_id, now, *window_seconds)
+                    .await?;
+                let duration = durations[offense.min(durations.len()) - 1];
+                let reason = format!(
+                    "{} (offense {}/{}: {})",
+                    reason,
+                    offense,
+                    durations.len(),
+                    format_duration(duration)
+                );
+
+                // The offense has been recorded, so only the timeout itself is
+                // retried.
+                with_retries(is_retryable, || {
+                    timeout(http, *guild_id, *user_id, now, duration as i64, &reason)
+                })
+                .await?;
+            }
+            Self::Webhook {
+                url,
+                secret,
+                payload,
+            } => {
+                send_webhook(&context.web_client, url, secret.as_deref(), payload).await?;
+            }
+            Self::Quarantine {
+                user_id,
+                guild_id,
+                quarantine_role,
+                restore_on_unquarantine,
+                reason,
+            } => {
+                quarantine(
+                    context,
+                    *guild_id,
+                    *user_id,
+                    *quarantine_role,
+                    *restore_on_unquarantine,
+                    reason,
+                )
+                .await?;
+            }
+            Self::SetSlowmode {
+                channel_id,
+                seconds,
+                revert_after,
+            } => {
+                set_slowmode(context, *channel_id, *seconds, *revert_after).await?;
+            }
+            Self::Crosspost {
+                channel_id,
+                message_id,
+            } => {
+                crosspost(context, *channel_id, *message_id).await?;
+            }
+            Self::Warn {
+                user_id,
+                guild_id,
+                expiry_seconds,
+            } => {
+                let now = chrono::Utc::now().timestamp();
+                let count = warnings
+                    .warn(*guild_id, *user_id, now, *expiry_seconds)
+                    .await?;
+                tracing::trace!(%user_id, %guild_id, count, "Warned user");
+            }
+            Self::Escalate {
+                user_id,
+                guild_id,
+                count,
+                thresholds,
+            } => {
+                let count = count.get(warnings, *guild_id, *user_id).await?;
+
+                for action in escalation_actions(thresholds, count) {
+                    action.execute_with_retries_boxed(context).await?;
+                }
+            }
+            Self::Timeout {
+                user_id,
+                guild_id,
+                duration,
+                reason,
+            } => {
+                timeout(
+                    http,
+                    *guild_id,
+                    *user_id,
+                    chrono::Utc::now().timestamp(),
+                    *duration,
+                    reason,
+                )
+                .await?;
+            }
             Self::SendLog {
                 to,
                 filter_name,
+                guild_id,
                 message,
                 channel,
                 filter_reason,
                 author,
                 reaction,
+                removed,
+                color,
+                footer,
+                dry_run,
             } => {
                 let rxn_string = match reaction {
                     ReactionType::Custom { id, .. } => id.mention().to_string(),
                     ReactionType::Unicode { name } => name.clone(),
                 };
+                let title = match (*removed, *dry_run) {
+                    (true, _) => "Filtered reaction removed before action",
+                    (false, true) => "Reaction filtered (dry run)",
+                    (false, false) => "Reaction filtered",
+                };
 
-                http.create_message(*to)
-                    .embeds(&[EmbedBuilder::new()
-                        .title("Reaction filtered")
-                        .field(EmbedFieldBuilder::new("Filter", filter_name))
-                        .field(
-                            EmbedFieldBuilder::new("Author", author.mention().to_string()).build(),
-                        )
-                        .field(
-                            EmbedFieldBuilder::new("Channel", channel.mention().to_string())
-                                .build(),
-                        )
-                        .field(
-                            EmbedFieldBuilder::new(
-                                "Message",
-                                format!("https://discordapp.com/{}/{}", channel, message),
-                            )
+                let mut embed_builder = EmbedBuilder::new()
+                    .title(title)
+                    .color(*color)
+                    .field(EmbedFieldBuilder::new("Filter", filter_name))
+                    .field(EmbedFieldBuilder::new("Author", author.mention().to_string()).build())
+                    .field(EmbedFieldBuilder::new("Channel", channel.mention().to_string()).build())
+                    .field(
+                        EmbedFieldBuilder::new("Message", jump_link(*guild_id, *channel, *message))
                             .build(),
-                        )
-                        .field(EmbedFieldBuilder::new("Reason", filter_reason).build())
-                        .field(EmbedFieldBuilder::new("Reaction", rxn_string).build())
-                        .build()])
-                    .unwrap()
+                    )
+                    .field(EmbedFieldBuilder::new("Reason", filter_reason).build())
+                    .field(EmbedFieldBuilder::new("Reaction", rxn_string).build());
+
+                if let Some(footer) = footer {
+                    embed_builder = embed_builder.footer(EmbedFooterBuilder::new(
+                        truncate_with_ellipsis(footer, EMBED_FOOTER_LENGTH),
+                    ));
+                }
+
+                http.create_message(*to)
+                    .embeds(&[embed_builder.build()])?
                     .await?;
             }
         };
@@ -287,14 +1952,393 @@ impl ReactionAction {
         Ok(())
     }
 
+    /// Escalations can nest actions, so executing them recursively needs a
+    /// boxed future.
+    fn execute_with_retries_boxed<'a>(
+        &'a self,
+        context: &'a ActionContext,
+    ) -> BoxFuture<'a, Result<()>> {
+        self.execute_with_retries(context).boxed()
+    }
+
+    /// Whether taking this action twice has the same effect as taking it
+    /// once, so that it can be retried even when it might have worked.
+    fn is_idempotent(&self) -> bool {
+        matches!(
+            self,
+            Self::Delete { .. }
+                | Self::Ban { .. }
+                | Self::Kick { .. }
+                | Self::AddRole { .. }
+                | Self::RemoveRole { .. }
+                | Self::Timeout { .. }
+                | Self::SetSlowmode { .. }
+        )
+    }
+
+    /// Like [`execute`](Self::execute), but retries transient failures with
+    /// backoff. Actions that send something are only retried if they
+    /// certainly weren't carried out, so that nothing is sent twice.
+    pub(crate) async fn execute_with_retries(&self, context: &ActionContext) -> Result<()> {
+        match self {
+            // These retry the requests they make themselves, after the parts
+            // that mustn't be repeated.
+            Self::EscalatingTimeout { .. } | Self::Escalate { .. } => self.execute(context).await,
+            _ if self.is_idempotent() => with_retries(is_retryable, || self.execute(context)).await,
+            _ => with_retries(is_unsent, || self.execute(context)).await,
+        }
+    }
+
+    /// Gets what should happen instead of this action when running with
+    /// `dry_run_globally`, as with [`MessageAction::into_dry_run`].
+    pub(crate) fn into_dry_run(mut self) -> Option<Self> {
+        match &mut self {
+            ReactionAction::SendLog { dry_run, .. } => {
+                *dry_run = true;
+                Some(self)
+            }
+            _ => None,
+        }
+    }
+
     pub(crate) fn requires_armed(&self) -> bool {
         match self {
             ReactionAction::Delete { .. } => true,
             ReactionAction::Ban { .. } => true,
             ReactionAction::Kick { .. } => true,
+            ReactionAction::AddRole { .. } => true,
+            ReactionAction::RemoveRole { .. } => true,
+            ReactionAction::EscalatingTimeout { .. } => true,
+            ReactionAction::Quarantine { .. } => true,
+            ReactionAction::SetSlowmode { .. } => true,
+            // Publishing a message doesn't take anything away from anyone.
+            ReactionAction::Crosspost { .. } => false,
+            ReactionAction::Warn { .. } => true,
+            ReactionAction::Escalate { thresholds, .. } => thresholds
+                .iter()
+                .flat_map(|(_, actions)| actions)
+                .any(Self::requires_armed),
             ReactionAction::Timeout { .. } => true,
             ReactionAction::SendMessage { requires_armed, .. } => *requires_armed,
+            ReactionAction::Reply { requires_armed, .. } => *requires_armed,
+            ReactionAction::SendDm { requires_armed, .. } => *requires_armed,
             _ => false,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use twilight_http::{api_error::ApiError, error::ErrorType};
+    use twilight_model::id::Id;
+
+    use super::{
+        bulk_delete_reason, clamp_timeout_duration, default_log_color, delete_batches,
+        embed_field_list, embed_length, escalation_actions, format_duration,
+        is_retryable_http_error, is_retryable_status, is_unknown_message, is_unsent_http_error,
+        message_log_embed, truncate_with_ellipsis, webhook_signature, LoggedAttachment,
+        MessageAction, EMBED_TOTAL_LENGTH,
+    };
+
+    #[test]
+    fn embed_field_list_fits_limit() {
+        let short = vec!["a".to_owned(), "b".to_owned()];
+        assert_eq!(embed_field_list(&short), "a\nb");
+
+        let long: Vec<_> = (0..100).map(|i| format!("{:020}", i)).collect();
+        let value = embed_field_list(&long);
+        assert!(value.chars().count() <= super::EMBED_FIELD_VALUE_LENGTH);
+        assert!(value.ends_with("…and 52 more"));
+    }
+
+    #[test]
+    fn batch_deletes() {
+        use twilight_model::id::{marker::MessageMarker, Id};
+
+        const DISCORD_EPOCH_SECONDS: i64 = 1_420_070_400;
+
+        /// A message ID for a message sent `age` seconds before `now`.
+        fn sent_before(now: i64, age: i64, sequence: u64) -> Id<MessageMarker> {
+            Id::new((((now - age - DISCORD_EPOCH_SECONDS) as u64 * 1000) << 22) + sequence)
+        }
+
+        let now = 1_700_000_000;
+
+        // A lone message is deleted on its own.
+        let single = sent_before(now, 10, 1);
+        assert_eq!(delete_batches(vec![single], now), (vec![], vec![single]));
+
+        // Duplicates are only deleted once.
+        let recent: Vec<_> = (1..=3).map(|i| sent_before(now, 10, i)).collect();
+        assert_eq!(
+            delete_batches([&recent[..], &recent[..1]].concat(), now),
+            (vec![recent.clone()], vec![])
+        );
+
+        // Messages older than two weeks can't be bulk deleted.
+        let old = sent_before(now, 15 * 24 * 60 * 60, 1);
+        assert_eq!(
+            delete_batches(vec![recent[0], old, recent[1]], now),
+            (vec![recent[..2].to_vec()], vec![old])
+        );
+
+        // Bulk deletes are limited to 100 messages.
+        let flood: Vec<_> = (1..=201).map(|i| sent_before(now, 10, i)).collect();
+        assert_eq!(
+            delete_batches(flood.clone(), now),
+            (
+                vec![flood[..100].to_vec(), flood[100..200].to_vec()],
+                vec![flood[200]]
+            )
+        );
+    }
+
+    #[test]
+    fn combine_bulk_delete_reasons() {
+        assert_eq!(bulk_delete_reason(std::iter::empty()), None);
+        assert_eq!(
+            bulk_delete_reason(IntoIterator::into_iter([
+                "Filter spam: sent too many links",
+                "Filter spam: sent too many links",
+                "Filter words: contains word `bad`",
+            ])),
+            Some("Filter spam: sent too many links; Filter words: contains word `bad`".to_owned())
+        );
+        assert_eq!(
+            bulk_delete_reason(IntoIterator::into_iter([
+                "a".repeat(300).as_str(),
+                "b".repeat(300).as_str(),
+            ]))
+            .unwrap()
+            .chars()
+            .count(),
+            super::AUDIT_LOG_REASON_LENGTH
+        );
+    }
+
+    #[test]
+    fn log_color_follows_severity() {
+        use crate::config::{EscalationThreshold, MessageFilterAction};
+
+        let delete = || MessageFilterAction::Delete {
+            delay_seconds: None,
+        };
+        let kick = || MessageFilterAction::Kick {
+            reason: "spam".to_owned(),
+        };
+        let ban = || MessageFilterAction::Ban {
+            delete_message_seconds: 0,
+            reason: "spam".to_owned(),
+            duration_seconds: None,
+        };
+
+        assert_eq!(default_log_color(&[]), super::LOG_COLOR_LOG);
+        assert_eq!(default_log_color(&[delete()]), super::LOG_COLOR_LOG);
+        assert_eq!(
+            default_log_color(&[delete(), kick()]),
+            super::LOG_COLOR_PUNISH
+        );
+        assert_eq!(
+            default_log_color(&[kick(), ban(), delete()]),
+            super::LOG_COLOR_BAN
+        );
+        // Escalations are as severe as their most severe threshold.
+        assert_eq!(
+            default_log_color(&[
+                delete(),
+                MessageFilterAction::Escalate {
+                    thresholds: vec![
+                        EscalationThreshold {
+                            warnings: 1,
+                            actions: vec![kick()],
+                        },
+                        EscalationThreshold {
+                            warnings: 3,
+                            actions: vec![ban()],
+                        },
+                    ],
+                },
+            ]),
+            super::LOG_COLOR_BAN
+        );
+    }
+
+    #[test]
+    fn clamp_long_timeouts() {
+        assert_eq!(clamp_timeout_duration(3600), 3600);
+        assert_eq!(clamp_timeout_duration(2_419_200), 2_419_200);
+        assert_eq!(clamp_timeout_duration(2_592_000), 2_419_200);
+    }
+
+    #[test]
+    fn classify_unknown_message_errors() {
+        let error = |code| {
+            serde_json::from_str::<ApiError>(&format!(
+                r#"{{"code": {}, "message": "error"}}"#,
+                code
+            ))
+            .unwrap()
+        };
+
+        assert!(is_unknown_message(&error(10008)));
+        // Missing Access and Missing Permissions should still be reported.
+        assert!(!is_unknown_message(&error(50001)));
+        assert!(!is_unknown_message(&error(50013)));
+    }
+
+    #[test]
+    fn retry_transient_statuses() {
+        assert!(is_retryable_status(429));
+        assert!(is_retryable_status(500));
+        assert!(is_retryable_status(503));
+        assert!(!is_retryable_status(400));
+        assert!(!is_retryable_status(403));
+        assert!(!is_retryable_status(404));
+    }
+
+    #[test]
+    fn retry_transient_http_errors() {
+        assert!(is_retryable_http_error(&ErrorType::RequestTimedOut));
+        assert!(is_retryable_http_error(&ErrorType::RequestError));
+        assert!(!is_retryable_http_error(&ErrorType::Unauthorized));
+        assert!(!is_retryable_http_error(&ErrorType::BuildingRequest));
+        assert!(!is_retryable_http_error(&ErrorType::Json));
+    }
+
+    #[test]
+    fn only_retry_unsent_requests_for_unsafe_actions() {
+        // A request that timed out might still have been carried out.
+        assert!(!is_unsent_http_error(&ErrorType::RequestTimedOut));
+        assert!(!is_unsent_http_error(&ErrorType::RequestError));
+
+        let send = MessageAction::SendMessage {
+            to: Id::new(1),
+            content: "hello".to_owned(),
+            requires_armed: false,
+        };
+        let delete = MessageAction::Delete {
+            message_id: Id::new(1),
+            channel_id: Id::new(2),
+            delay_seconds: None,
+            reason: None,
+        };
+        assert!(!send.is_idempotent());
+        assert!(delete.is_idempotent());
+    }
+
+    #[tokio::test]
+    async fn dont_retry_permanent_failures() {
+        let mut attempts = 0;
+        let result = super::with_retries(super::is_retryable, || {
+            attempts += 1;
+            async { Err(eyre::eyre!("missing permissions")) }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn fit_longest_message_log_in_embed_limit() {
+        let long = "x".repeat(4000);
+        let attachments: Vec<_> = (0..50)
+            .map(|i| LoggedAttachment {
+                filename: format!("{}-{}.png", "a".repeat(50), i),
+                url: format!("https://cdn.discordapp.com/attachments/1/2/{}.png", i),
+                proxy_url: format!("https://media.discordapp.net/attachments/1/2/{}.png", i),
+                is_image: true,
+            })
+            .collect();
+        let stickers: Vec<_> = (0..50).map(|i| format!("sticker {}", i)).collect();
+        let embed = |previous_content| {
+            message_log_embed(
+                "Message filtered",
+                0,
+                &long,
+                Id::new(1),
+                Id::new(2),
+                Id::new(3),
+                &long,
+                previous_content,
+                &attachments,
+                &stickers,
+                &long,
+                Id::new(4),
+                "message edit",
+                Some(&long),
+            )
+            .unwrap()
+        };
+
+        let edit = embed(Some(Some(&long)));
+        assert!(embed_length(&edit) <= EMBED_TOTAL_LENGTH);
+        let names: Vec<_> = edit
+            .fields
+            .iter()
+            .map(|field| field.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "Filter",
+                "Author",
+                "Channel",
+                "Message",
+                "Reason",
+                "Context",
+                "Original",
+                "Edited",
+                "Attachments",
+                "Stickers"
+            ]
+        );
+        // Both sides of the edit get the same share of what's left.
+        assert_eq!(edit.fields[6].value, edit.fields[7].value);
+
+        let message = embed(None);
+        assert!(embed_length(&message) <= EMBED_TOTAL_LENGTH);
+        assert!(message.description.unwrap().ends_with("…```"));
+        assert!(message.footer.is_some());
+    }
+
+    #[test]
+    fn truncate_short_text() {
+        assert_eq!(truncate_with_ellipsis("", 4), "");
+        assert_eq!(truncate_with_ellipsis("abcd", 4), "abcd");
+    }
+
+    #[test]
+    fn truncate_long_text() {
+        assert_eq!(truncate_with_ellipsis("abcde", 4), "abc…");
+        // Multi-byte characters count as one character each.
+        assert_eq!(truncate_with_ellipsis("ééééé", 4), "ééé…");
+    }
+
+    #[test]
+    fn escalate_to_highest_threshold_reached() {
+        let thresholds = vec![(3, vec!["timeout"]), (1, vec!["warn"]), (5, vec!["ban"])];
+
+        assert_eq!(escalation_actions(&thresholds, 0), &[] as &[&str]);
+        assert_eq!(escalation_actions(&thresholds, 1), &["warn"]);
+        assert_eq!(escalation_actions(&thresholds, 4), &["timeout"]);
+        assert_eq!(escalation_actions(&thresholds, 9), &["ban"]);
+    }
+
+    #[test]
+    fn format_durations() {
+        assert_eq!(format_duration(600), "10m");
+        assert_eq!(format_duration(86_400), "24h");
+        assert_eq!(format_duration(90), "90s");
+    }
+
+    #[test]
+    fn sign_webhook_body() {
+        // Test case 2 from RFC 4231.
+        assert_eq!(
+            webhook_signature("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
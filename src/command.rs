@@ -1,16 +1,20 @@
 use color_eyre::eyre::Result;
-use twilight_http::client::InteractionClient;
+use twilight_http::{client::InteractionClient, request::AuditLogReason};
+use twilight_mention::Mention;
 use twilight_model::application::command::CommandType;
 use twilight_model::application::interaction::InteractionData;
 use twilight_model::{
     application::{
-        command::{CommandOption, CommandOptionType},
+        command::{CommandOption, CommandOptionChoice, CommandOptionChoiceData, CommandOptionType},
         interaction::{
             application_command::{CommandData, CommandOptionValue},
             Interaction,
         },
     },
-    channel::{message::MessageFlags, ChannelType},
+    channel::{
+        message::{Embed, MessageFlags},
+        ChannelType,
+    },
     guild::Permissions,
     http::interaction::{InteractionResponse, InteractionResponseType},
     id::{marker::GuildMarker, Id},
@@ -21,12 +25,34 @@ use twilight_util::builder::{
     InteractionResponseDataBuilder,
 };
 
-use crate::config::SlashCommands;
+use crate::{
+    action::truncate_with_ellipsis,
+    config::{GuildConfigError, MessageFilter, SlashCommands},
+    message::MessageFilterFailure,
+};
 
 const TEST_COMMAND: &str = "chrysanthemum-test";
 const ARM_COMMAND: &str = "chrysanthemum-arm";
 const DISARM_COMMAND: &str = "chrysanthemum-disarm";
 const RELOAD_COMMAND: &str = "chrysanthemum-reload";
+const WARNINGS_COMMAND: &str = "chrysanthemum-warnings";
+const UNQUARANTINE_COMMAND: &str = "chrysanthemum-unquarantine";
+const TOGGLE_FILTER_COMMAND: &str = "chrysanthemum-toggle-filter";
+/// Message context-menu commands are shown to users by their name, so this
+/// one's is written out like a menu item.
+const TEST_MESSAGE_COMMAND: &str = "Test with Chrysanthemum";
+/// How many failed guilds the reload command lists individually. Embeds are
+/// limited to 6000 characters in total, so this and the length of each reason
+/// have to be kept in check together.
+const MAX_LISTED_RELOAD_FAILURES: usize = 5;
+const RELOAD_FAILURE_REASON_LENGTH: usize = 900;
+/// Discord shows at most 25 autocomplete choices, and each choice's value can
+/// be at most 100 characters long.
+const MAX_AUTOCOMPLETE_CHOICES: usize = 25;
+const MAX_CHOICE_LENGTH: usize = 100;
+/// Embeds can have at most 25 fields; one is kept for the status.
+const MAX_LISTED_MESSAGE_FAILURES: usize = 23;
+const FIELD_VALUE_LENGTH: usize = 1024;
 
 #[tracing::instrument(skip(http))]
 pub(crate) async fn create_commands_for_guild(
@@ -63,6 +89,22 @@ pub(crate) async fn create_commands_for_guild(
                 options: None,
                 required: Some(true),
             })
+            .option(CommandOption {
+                name: "filter".to_owned(),
+                description: "Only test against this filter.".to_owned(),
+                channel_types: None,
+                kind: CommandOptionType::String,
+                max_length: None,
+                min_length: Some(1),
+                autocomplete: Some(true),
+                choices: None,
+                description_localizations: None,
+                max_value: None,
+                min_value: None,
+                name_localizations: None,
+                options: None,
+                required: Some(false),
+            })
             .build(),
             CommandBuilder::new(ARM_COMMAND, "Arms Chrysanthemum.", CommandType::ChatInput)
                 .default_member_permissions(Permissions::ADMINISTRATOR)
@@ -81,6 +123,94 @@ pub(crate) async fn create_commands_for_guild(
             )
             .default_member_permissions(Permissions::ADMINISTRATOR)
             .build(),
+            CommandBuilder::new(
+                WARNINGS_COMMAND,
+                "Shows how many active warnings a user has.",
+                CommandType::ChatInput,
+            )
+            .default_member_permissions(Permissions::MANAGE_MESSAGES)
+            .option(CommandOption {
+                name: "user".to_owned(),
+                description: "The user to show warnings for.".to_owned(),
+                channel_types: None,
+                kind: CommandOptionType::User,
+                max_length: None,
+                min_length: None,
+                autocomplete: None,
+                choices: None,
+                description_localizations: None,
+                max_value: None,
+                min_value: None,
+                name_localizations: None,
+                options: None,
+                required: Some(true),
+            })
+            .build(),
+            CommandBuilder::new(
+                UNQUARANTINE_COMMAND,
+                "Releases a user from quarantine, giving their roles back.",
+                CommandType::ChatInput,
+            )
+            .default_member_permissions(Permissions::MANAGE_ROLES)
+            .option(CommandOption {
+                name: "user".to_owned(),
+                description: "The user to release from quarantine.".to_owned(),
+                channel_types: None,
+                kind: CommandOptionType::User,
+                max_length: None,
+                min_length: None,
+                autocomplete: None,
+                choices: None,
+                description_localizations: None,
+                max_value: None,
+                min_value: None,
+                name_localizations: None,
+                options: None,
+                required: Some(true),
+            })
+            .build(),
+            CommandBuilder::new(
+                TOGGLE_FILTER_COMMAND,
+                "Enables or disables a filter until configurations are next reloaded.",
+                CommandType::ChatInput,
+            )
+            .default_member_permissions(Permissions::ADMINISTRATOR)
+            .option(CommandOption {
+                name: "name".to_owned(),
+                description: "The name of the filter.".to_owned(),
+                channel_types: None,
+                kind: CommandOptionType::String,
+                max_length: None,
+                min_length: Some(1),
+                autocomplete: Some(true),
+                choices: None,
+                description_localizations: None,
+                max_value: None,
+                min_value: None,
+                name_localizations: None,
+                options: None,
+                required: Some(true),
+            })
+            .option(CommandOption {
+                name: "enabled".to_owned(),
+                description: "Whether the filter should be enabled.".to_owned(),
+                channel_types: None,
+                kind: CommandOptionType::Boolean,
+                max_length: None,
+                min_length: None,
+                autocomplete: None,
+                choices: None,
+                description_localizations: None,
+                max_value: None,
+                min_value: None,
+                name_localizations: None,
+                options: None,
+                required: Some(true),
+            })
+            .build(),
+            CommandBuilder::new(TEST_MESSAGE_COMMAND, "", CommandType::Message)
+                .default_member_permissions(Permissions::MANAGE_MESSAGES)
+                .build(),
         ],
     )
     .await?;
@@ -108,6 +238,98 @@ pub(crate) async fn update_guild_commands(
     }
 }
 
+/// Builds the reload command's response when some guilds' configurations
+/// failed to reload, listing each one with its reason.
+fn reload_failure_embed(errors: &[GuildConfigError]) -> Embed {
+    let mut builder = EmbedBuilder::new().title("Reload failure").description(format!(
+        "{} guild configuration(s) failed to reload and have **not** been updated. Any other guilds were reloaded successfully.",
+        errors.len()
+    ));
+
+    for (guild_id, report) in errors.iter().take(MAX_LISTED_RELOAD_FAILURES) {
+        let reason = format!("{:#}", report);
+        builder = builder.field(
+            EmbedFieldBuilder::new(
+                format!("Guild {}", guild_id),
+                format!(
+                    "```{}```",
+                    truncate_with_ellipsis(&reason, RELOAD_FAILURE_REASON_LENGTH)
+                ),
+            )
+            .build(),
+        );
+    }
+
+    if errors.len() > MAX_LISTED_RELOAD_FAILURES {
+        builder = builder.field(
+            EmbedFieldBuilder::new(
+                "More failures",
+                format!(
+                    "…and {} more; see the logs for details.",
+                    errors.len() - MAX_LISTED_RELOAD_FAILURES
+                ),
+            )
+            .build(),
+        );
+    }
+
+    builder.build()
+}
+
+/// Builds the response to the message test command, listing every filter the
+/// message failed. `failures` is `None` if the message wouldn't be filtered at
+/// all.
+fn message_test_embed(failures: Option<&[MessageFilterFailure]>) -> Embed {
+    let builder = EmbedBuilder::new().title("Test message");
+    let failures = match failures {
+        None => {
+            return builder
+                .field(EmbedFieldBuilder::new(
+                    "Status",
+                    "⚠️ This message wouldn't be filtered at all",
+                ))
+                .build();
+        }
+        Some([]) => {
+            return builder
+                .field(EmbedFieldBuilder::new("Status", "✅ Passed all filters"))
+                .build();
+        }
+        Some(failures) => failures,
+    };
+
+    let mut builder = builder.field(EmbedFieldBuilder::new(
+        "Status",
+        match failures.len() {
+            1 => "❌ Failed 1 filter".to_owned(),
+            count => format!("❌ Failed {} filters", count),
+        },
+    ));
+
+    for failure in failures.iter().take(MAX_LISTED_MESSAGE_FAILURES) {
+        let name = match &failure.tier {
+            Some(tier) => format!("{} (tier {})", failure.filter_name, tier),
+            None => failure.filter_name.clone(),
+        };
+        builder = builder.field(EmbedFieldBuilder::new(
+            name,
+            truncate_with_ellipsis(&failure.reason, FIELD_VALUE_LENGTH),
+        ));
+    }
+
+    if failures.len() > MAX_LISTED_MESSAGE_FAILURES {
+        builder = builder.field(EmbedFieldBuilder::new(
+            "More failures",
+            format!(
+                "…and {} more.",
+                failures.len() - MAX_LISTED_MESSAGE_FAILURES
+            ),
+        ));
+    }
+
+    builder.build()
+}
+
 #[tracing::instrument(skip(state))]
 pub(crate) async fn handle_command(
     state: crate::State,
@@ -139,16 +361,29 @@ pub(crate) async fn handle_command(
     match cmd_data {
         Some(cmd_data) => match cmd_data.name.as_str() {
             TEST_COMMAND => {
-                if cmd.options.is_empty() {
-                    return Ok(());
-                }
+                let option = |name: &str| {
+                    cmd.options
+                        .iter()
+                        .find(|option| option.name == name)
+                        .map(|option| &option.value)
+                };
 
-                if let CommandOptionValue::String(message) = &cmd.options[0].value {
+                let filter_name = match option("filter") {
+                    Some(CommandOptionValue::String(filter_name)) => Some(filter_name),
+                    _ => None,
+                };
+
+                if let Some(CommandOptionValue::String(message)) = option("message") {
                     let guild_cfgs = state.guild_cfgs.read().await;
 
                     if let Some(guild_config) = guild_cfgs.get(&guild_id) {
                         if let Some(message_filters) = &guild_config.messages {
-                            let result = message_filters
+                            let filters_to_test: Vec<_> = message_filters
+                                .iter()
+                                .filter(|f| filter_name.map_or(true, |name| &f.name == name))
+                                .collect();
+
+                            let result = filters_to_test
                                 .iter()
                                 .map(|f| f.filter_text(&message[..]).map_err(|e| (f, e)))
                                 .find(Result::is_err)
@@ -159,8 +394,8 @@ pub(crate) async fn handle_command(
                                     .build(),
                             );
 
-                            match result {
-                                Some((filter, reason)) => {
+                            match (result, filter_name) {
+                                (Some((filter, reason)), _) => {
                                     builder = builder
                                         .field(EmbedFieldBuilder::new(
                                             "Status",
@@ -168,7 +403,19 @@ pub(crate) async fn handle_command(
                                         ))
                                         .field(EmbedFieldBuilder::new("Filter", &filter.name));
                                 }
-                                None => {
+                                (None, Some(filter_name)) if filters_to_test.is_empty() => {
+                                    builder = builder.field(EmbedFieldBuilder::new(
+                                        "Status",
+                                        format!("⚠️ There's no filter named **{}**", filter_name),
+                                    ));
+                                }
+                                (None, Some(filter_name)) => {
+                                    builder = builder.field(EmbedFieldBuilder::new(
+                                        "Status",
+                                        format!("✅ Passed filter **{}**", filter_name),
+                                    ));
+                                }
+                                (None, None) => {
                                     builder = builder.field(EmbedFieldBuilder::new(
                                         "Status",
                                         "✅ Passed all filters",
@@ -239,22 +486,14 @@ pub(crate) async fn handle_command(
                     .unwrap();
             }
             RELOAD_COMMAND => {
-                let result = crate::reload_guild_configs(&state).await;
-                let embed = match result {
-                    Ok(()) => EmbedBuilder::new()
+                let errors = crate::reload_guild_configs(&state).await;
+                let embed = if errors.is_empty() {
+                    EmbedBuilder::new()
                         .title("Reload successful")
                         .color(0x32_a8_52)
-                        .build(),
-                    Err((_, report)) => {
-                        let report = report.to_string();
-                        EmbedBuilder::new()
-                            .title("Reload failure")
-                            .field(
-                                EmbedFieldBuilder::new("Reason", format!("```{}```", report))
-                                    .build(),
-                            )
-                            .build()
-                    }
+                        .build()
+                } else {
+                    reload_failure_embed(&errors)
                 };
 
                 interaction_http
@@ -274,6 +513,207 @@ pub(crate) async fn handle_command(
                     .await
                     .unwrap();
             }
+            WARNINGS_COMMAND => {
+                if cmd.options.is_empty() {
+                    return Ok(());
+                }
+
+                if let CommandOptionValue::User(user_id) = &cmd.options[0].value {
+                    let now = chrono::Utc::now().timestamp();
+                    let count = state.warnings.count(guild_id, *user_id, now).await;
+
+                    interaction_http
+                        .create_response(
+                            interaction.id,
+                            &interaction.token,
+                            &InteractionResponse {
+                                kind: InteractionResponseType::ChannelMessageWithSource,
+                                data: Some(
+                                    InteractionResponseDataBuilder::new()
+                                        .flags(MessageFlags::EPHEMERAL)
+                                        .content(format!(
+                                            "{} has **{}** active warning{}.",
+                                            user_id.mention(),
+                                            count,
+                                            if count == 1 { "" } else { "s" }
+                                        ))
+                                        .build(),
+                                ),
+                            },
+                        )
+                        .await
+                        .unwrap();
+                }
+            }
+            UNQUARANTINE_COMMAND => {
+                if cmd.options.is_empty() {
+                    return Ok(());
+                }
+
+                if let CommandOptionValue::User(user_id) = &cmd.options[0].value {
+                    let content = match state.quarantines.release(guild_id, *user_id).await? {
+                        Some(roles) => {
+                            let result = match state
+                                .http
+                                .update_guild_member(guild_id, *user_id)
+                                .roles(&roles)
+                                .reason("Released from quarantine")
+                            {
+                                Ok(request) => {
+                                    request.await.map(|_| ()).map_err(eyre::Report::from)
+                                }
+                                Err(err) => Err(err.into()),
+                            };
+
+                            match result {
+                                Ok(()) => format!(
+                                    "{} has been released from quarantine.",
+                                    user_id.mention()
+                                ),
+                                Err(err) => {
+                                    tracing::warn!(?err, %user_id, "Unable to release user from quarantine");
+                                    // Keep the quarantine around so that releasing
+                                    // them can be tried again.
+                                    state
+                                        .quarantines
+                                        .quarantine(guild_id, *user_id, roles)
+                                        .await?;
+                                    format!(
+                                        "Couldn't release {} from quarantine: {}",
+                                        user_id.mention(),
+                                        err
+                                    )
+                                }
+                            }
+                        }
+                        None => format!("{} isn't quarantined.", user_id.mention()),
+                    };
+
+                    interaction_http
+                        .create_response(
+                            interaction.id,
+                            &interaction.token,
+                            &InteractionResponse {
+                                kind: InteractionResponseType::ChannelMessageWithSource,
+                                data: Some(
+                                    InteractionResponseDataBuilder::new()
+                                        .flags(MessageFlags::EPHEMERAL)
+                                        .content(content)
+                                        .build(),
+                                ),
+                            },
+                        )
+                        .await
+                        .unwrap();
+                }
+            }
+            TOGGLE_FILTER_COMMAND => {
+                // Discord doesn't promise to send options in the order they
+                // were declared in, so look them up by name.
+                let option = |name: &str| {
+                    cmd.options
+                        .iter()
+                        .find(|option| option.name == name)
+                        .map(|option| &option.value)
+                };
+
+                if let (
+                    Some(CommandOptionValue::String(name)),
+                    Some(CommandOptionValue::Boolean(enabled)),
+                ) = (option("name"), option("enabled"))
+                {
+                    let filter_exists = state
+                        .guild_cfgs
+                        .read()
+                        .await
+                        .get(&guild_id)
+                        .and_then(|guild_config| guild_config.messages.as_ref())
+                        .map_or(false, |filters| filters.iter().any(|f| &f.name == name));
+
+                    let content = if !filter_exists {
+                        format!("There's no filter named **{}**.", name)
+                    } else {
+                        let mut disabled_filters = state.disabled_filters.write().await;
+                        let disabled = disabled_filters.entry(guild_id).or_default();
+                        if *enabled {
+                            disabled.remove(name);
+                        } else {
+                            disabled.insert(name.clone());
+                        }
+
+                        format!(
+                            "Filter **{}** is now **{}** until configurations are reloaded.",
+                            name,
+                            if *enabled { "enabled" } else { "disabled" }
+                        )
+                    };
+
+                    interaction_http
+                        .create_response(
+                            interaction.id,
+                            &interaction.token,
+                            &InteractionResponse {
+                                kind: InteractionResponseType::ChannelMessageWithSource,
+                                data: Some(
+                                    InteractionResponseDataBuilder::new()
+                                        .flags(MessageFlags::EPHEMERAL)
+                                        .content(content)
+                                        .build(),
+                                ),
+                            },
+                        )
+                        .await
+                        .unwrap();
+                }
+            }
+            TEST_MESSAGE_COMMAND => {
+                let message = cmd
+                    .target_id
+                    .and_then(|target_id| cmd.resolved.as_ref()?.messages.get(&target_id.cast()));
+                let message = match message {
+                    Some(message) => message,
+                    None => {
+                        tracing::trace!("No resolved message for message test command");
+                        return Ok(());
+                    }
+                };
+
+                // Testing can involve fetching the author and following links,
+                // which can take longer than Discord waits for a response.
+                interaction_http
+                    .create_response(
+                        interaction.id,
+                        &interaction.token,
+                        &InteractionResponse {
+                            kind: InteractionResponseType::DeferredChannelMessageWithSource,
+                            data: Some(
+                                InteractionResponseDataBuilder::new()
+                                    .flags(MessageFlags::EPHEMERAL)
+                                    .build(),
+                            ),
+                        },
+                    )
+                    .await?;
+
+                let embed = match crate::test_existing_message(message, guild_id, &state).await {
+                    Ok(failures) => message_test_embed(failures.as_deref()),
+                    Err(err) => {
+                        tracing::warn!(?err, %message.id, "Unable to test message");
+                        EmbedBuilder::new()
+                            .title("Test message")
+                            .field(EmbedFieldBuilder::new(
+                                "Status",
+                                "⚠️ Unable to test this message; see the logs for details",
+                            ))
+                            .build()
+                    }
+                };
+
+                interaction_http
+                    .update_response(&interaction.token)
+                    .embeds(Some(&[embed]))?
+                    .await?;
+            }
             _ => {
                 tracing::trace!("Received unhandleable interaction: unknown command name.");
             }
@@ -285,3 +725,237 @@ pub(crate) async fn handle_command(
 
     Ok(())
 }
+
+/// Suggests the names of the guild's message filters that contain what's been
+/// typed so far, ignoring case.
+fn filter_name_choices(filters: &[MessageFilter], typed: &str) -> Vec<CommandOptionChoice> {
+    let typed = typed.to_lowercase();
+    filters
+        .iter()
+        .filter(|filter| filter.name.len() <= MAX_CHOICE_LENGTH)
+        .filter(|filter| filter.name.to_lowercase().contains(&typed))
+        .take(MAX_AUTOCOMPLETE_CHOICES)
+        .map(|filter| {
+            CommandOptionChoice::String(CommandOptionChoiceData {
+                name: filter.name.clone(),
+                name_localizations: None,
+                value: filter.name.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Responds to autocomplete requests for command options that take a filter
+/// name.
+#[tracing::instrument(skip(state))]
+pub(crate) async fn handle_autocomplete(
+    state: crate::State,
+    interaction: &Interaction,
+    cmd: &CommandData,
+) -> Result<()> {
+    let guild_id = match cmd.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let application_id = match *state.application_id.read().await {
+        Some(application_id) => application_id,
+        None => {
+            tracing::trace!("No application ID yet");
+            return Ok(());
+        }
+    };
+
+    let focused_option = match cmd.name.as_str() {
+        TEST_COMMAND => "filter",
+        TOGGLE_FILTER_COMMAND => "name",
+        _ => {
+            tracing::trace!("Received autocomplete for a command without autocomplete options.");
+            return Ok(());
+        }
+    };
+
+    let typed = cmd.options.iter().find_map(|option| match &option.value {
+        CommandOptionValue::Focused(typed, _) if option.name == focused_option => Some(typed),
+        _ => None,
+    });
+    let typed = match typed {
+        Some(typed) => typed,
+        None => return Ok(()),
+    };
+
+    let choices = state
+        .guild_cfgs
+        .read()
+        .await
+        .get(&guild_id)
+        .and_then(|guild_config| guild_config.messages.as_deref())
+        .map_or_else(Vec::new, |filters| filter_name_choices(filters, typed));
+
+    state
+        .http
+        .interaction(application_id)
+        .create_response(
+            interaction.id,
+            &interaction.token,
+            &InteractionResponse {
+                kind: InteractionResponseType::ApplicationCommandAutocompleteResult,
+                data: Some(
+                    InteractionResponseDataBuilder::new()
+                        .choices(choices)
+                        .build(),
+                ),
+            },
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use twilight_model::id::Id;
+
+    use twilight_model::application::command::CommandOptionChoice;
+
+    use super::{
+        filter_name_choices, message_test_embed, reload_failure_embed, MAX_AUTOCOMPLETE_CHOICES,
+        MAX_LISTED_MESSAGE_FAILURES, MAX_LISTED_RELOAD_FAILURES,
+    };
+    use crate::{config::MessageFilter, message::MessageFilterFailure};
+
+    #[test]
+    fn list_every_reload_failure() {
+        let errors: Vec<_> = (1..=2)
+            .map(|guild_id| {
+                (
+                    Id::new(guild_id),
+                    eyre::eyre!("invalid regex").wrap_err("Unable to load configuration"),
+                )
+            })
+            .collect();
+
+        let embed = reload_failure_embed(&errors);
+        let fields: Vec<_> = embed
+            .fields
+            .iter()
+            .map(|field| (field.name.as_str(), field.value.as_str()))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                (
+                    "Guild 1",
+                    "```Unable to load configuration: invalid regex```"
+                ),
+                (
+                    "Guild 2",
+                    "```Unable to load configuration: invalid regex```"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn summarize_excess_reload_failures() {
+        let errors: Vec<_> = (1..=MAX_LISTED_RELOAD_FAILURES as u64 + 2)
+            .map(|guild_id| (Id::new(guild_id), eyre::eyre!("invalid regex")))
+            .collect();
+
+        let embed = reload_failure_embed(&errors);
+        assert_eq!(embed.fields.len(), MAX_LISTED_RELOAD_FAILURES + 1);
+        assert_eq!(
+            embed.fields.last().unwrap().value,
+            "…and 2 more; see the logs for details."
+        );
+    }
+
+    #[test]
+    fn suggest_matching_filter_names() {
+        let filter = |name: &str| MessageFilter {
+            name: name.to_owned(),
+            ..Default::default()
+        };
+        let names = |choices: Vec<CommandOptionChoice>| -> Vec<String> {
+            choices
+                .into_iter()
+                .map(|choice| match choice {
+                    CommandOptionChoice::String(choice) => choice.value,
+                    _ => panic!("filter names should be string choices"),
+                })
+                .collect()
+        };
+
+        let filters = vec![
+            filter("Invites"),
+            filter("Scam links"),
+            filter("Slurs"),
+            filter(&"x".repeat(101)),
+        ];
+        assert_eq!(
+            names(filter_name_choices(&filters, "")),
+            vec!["Invites", "Scam links", "Slurs"]
+        );
+        assert_eq!(
+            names(filter_name_choices(&filters, "S")),
+            vec!["Invites", "Scam links", "Slurs"]
+        );
+        assert_eq!(names(filter_name_choices(&filters, "sl")), vec!["Slurs"]);
+        assert!(filter_name_choices(&filters, "nothing").is_empty());
+
+        let filters: Vec<_> = (0..30).map(|i| filter(&format!("filter {}", i))).collect();
+        assert_eq!(
+            filter_name_choices(&filters, "filter").len(),
+            MAX_AUTOCOMPLETE_CHOICES
+        );
+    }
+
+    #[test]
+    fn list_every_message_test_failure() {
+        let failure = |name: &str, tier: Option<&str>| MessageFilterFailure {
+            actions: vec![],
+            filter_name: name.to_owned(),
+            reason: "contains word `bad`".to_owned(),
+            tier: tier.map(str::to_owned),
+            context: "message test",
+        };
+        let fields = |failures: Option<&[MessageFilterFailure]>| -> Vec<(String, String)> {
+            message_test_embed(failures)
+                .fields
+                .into_iter()
+                .map(|field| (field.name, field.value))
+                .collect()
+        };
+
+        assert_eq!(
+            fields(Some(&[])),
+            vec![("Status".to_owned(), "✅ Passed all filters".to_owned())]
+        );
+        assert_eq!(
+            fields(None)[0].1,
+            "⚠️ This message wouldn't be filtered at all"
+        );
+        assert_eq!(
+            fields(Some(&[
+                failure("Words", Some("severe")),
+                failure("Spam", None)
+            ])),
+            vec![
+                ("Status".to_owned(), "❌ Failed 2 filters".to_owned()),
+                (
+                    "Words (tier severe)".to_owned(),
+                    "contains word `bad`".to_owned()
+                ),
+                ("Spam".to_owned(), "contains word `bad`".to_owned()),
+            ]
+        );
+
+        let failures: Vec<_> = (0..MAX_LISTED_MESSAGE_FAILURES + 3)
+            .map(|i| failure(&format!("filter {}", i), None))
+            .collect();
+        let embed = message_test_embed(Some(&failures));
+        assert_eq!(embed.fields.len(), MAX_LISTED_MESSAGE_FAILURES + 2);
+        assert_eq!(embed.fields.last().unwrap().value, "…and 3 more.");
+    }
+}
@@ -0,0 +1,614 @@
+use std::sync::atomic::Ordering;
+
+use color_eyre::eyre::Result;
+use twilight_http::client::InteractionClient;
+use twilight_mention::Mention;
+use twilight_model::{
+    application::interaction::{message_component::MessageComponentInteractionData, Interaction},
+    channel::message::{
+        component::{ActionRow, Button, ButtonStyle},
+        Component, MessageFlags,
+    },
+    guild::Permissions,
+    http::interaction::{InteractionResponse, InteractionResponseType},
+    id::{
+        marker::{ChannelMarker, GuildMarker, MessageMarker, UserMarker},
+        Id,
+    },
+    user::User,
+};
+use twilight_util::builder::{
+    embed::{EmbedBuilder, EmbedFieldBuilder, EmbedFooterBuilder},
+    InteractionResponseDataBuilder,
+};
+
+use crate::action::MessageAction;
+
+/// Prefixes the custom IDs of Chrysanthemum's buttons, so that they can be
+/// told apart from anything else's.
+const CUSTOM_ID_PREFIX: &str = "chrysanthemum";
+/// Prefixes the custom IDs of the buttons on messages flagged for review.
+const REVIEW_CUSTOM_ID_PREFIX: &str = "chrysanthemum-review";
+const TIMEOUT_SECONDS: i64 = 60 * 60;
+
+/// The buttons attached to message logs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ModerationButton {
+    Ban,
+    Timeout,
+    Pardon,
+}
+
+impl ModerationButton {
+    fn name(self) -> &'static str {
+        match self {
+            ModerationButton::Ban => "ban",
+            ModerationButton::Timeout => "timeout",
+            ModerationButton::Pardon => "pardon",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ban" => Some(ModerationButton::Ban),
+            "timeout" => Some(ModerationButton::Timeout),
+            "pardon" => Some(ModerationButton::Pardon),
+            _ => None,
+        }
+    }
+}
+
+/// What a moderation button does, and to whom. This is round-tripped through
+/// the button's custom ID, since that's all Discord gives back when it's
+/// clicked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ModerationRequest {
+    button: ModerationButton,
+    user_id: Id<UserMarker>,
+    message_id: Id<MessageMarker>,
+}
+
+impl ModerationRequest {
+    fn to_custom_id(self) -> String {
+        format!(
+            "{}:{}:{}:{}",
+            CUSTOM_ID_PREFIX,
+            self.button.name(),
+            self.user_id,
+            self.message_id
+        )
+    }
+
+    fn from_custom_id(custom_id: &str) -> Option<Self> {
+        let mut parts = custom_id.split(':');
+        if parts.next()? != CUSTOM_ID_PREFIX {
+            return None;
+        }
+
+        let request = ModerationRequest {
+            button: ModerationButton::from_name(parts.next()?)?,
+            user_id: parts.next()?.parse().ok()?,
+            message_id: parts.next()?.parse().ok()?,
+        };
+
+        if parts.next().is_some() {
+            return None;
+        }
+
+        Some(request)
+    }
+
+    /// The action to take for this request, if any. Pardoning doesn't do
+    /// anything besides updating the log.
+    fn action(self, guild_id: Id<GuildMarker>, moderator: Id<UserMarker>) -> Option<MessageAction> {
+        match self.button {
+            ModerationButton::Ban => Some(MessageAction::Ban {
+                user_id: self.user_id,
+                guild_id,
+                delete_message_seconds: 0,
+                tempban: None,
+                reason: format!(
+                    "Banned by {} for filtered message {}",
+                    moderator, self.message_id
+                ),
+            }),
+            ModerationButton::Timeout => Some(MessageAction::Timeout {
+                user_id: self.user_id,
+                guild_id,
+                reason: format!(
+                    "Timed out by {} for filtered message {}",
+                    moderator, self.message_id
+                ),
+                duration: TIMEOUT_SECONDS,
+            }),
+            ModerationButton::Pardon => None,
+        }
+    }
+
+    fn outcome(self, moderator: Id<UserMarker>) -> String {
+        let verb = match self.button {
+            ModerationButton::Ban => "Banned",
+            ModerationButton::Timeout => "Timed out for 1 hour",
+            ModerationButton::Pardon => "Pardoned",
+        };
+
+        format!("{} by {}", verb, moderator.mention())
+    }
+}
+
+/// The buttons attached to messages flagged for review.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReviewButton {
+    Approve,
+    Remove,
+}
+
+impl ReviewButton {
+    fn name(self) -> &'static str {
+        match self {
+            ReviewButton::Approve => "approve",
+            ReviewButton::Remove => "remove",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "approve" => Some(ReviewButton::Approve),
+            "remove" => Some(ReviewButton::Remove),
+            _ => None,
+        }
+    }
+}
+
+/// A moderator's verdict on a message flagged for review.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ReviewRequest {
+    button: ReviewButton,
+    channel_id: Id<ChannelMarker>,
+    message_id: Id<MessageMarker>,
+}
+
+impl ReviewRequest {
+    fn to_custom_id(self) -> String {
+        format!(
+            "{}:{}:{}:{}",
+            REVIEW_CUSTOM_ID_PREFIX,
+            self.button.name(),
+            self.channel_id,
+            self.message_id
+        )
+    }
+
+    fn from_custom_id(custom_id: &str) -> Option<Self> {
+        let mut parts = custom_id.split(':');
+        if parts.next()? != REVIEW_CUSTOM_ID_PREFIX {
+            return None;
+        }
+
+        let request = ReviewRequest {
+            button: ReviewButton::from_name(parts.next()?)?,
+            channel_id: parts.next()?.parse().ok()?,
+            message_id: parts.next()?.parse().ok()?,
+        };
+
+        if parts.next().is_some() {
+            return None;
+        }
+
+        Some(request)
+    }
+
+    /// The action to take for this request, if any. Approving leaves the
+    /// message alone.
+    fn action(self) -> Option<MessageAction> {
+        match self.button {
+            ReviewButton::Approve => None,
+            ReviewButton::Remove => Some(MessageAction::Delete {
+                message_id: self.message_id,
+                channel_id: self.channel_id,
+                delay_seconds: None,
+                reason: Some("Removed after review".to_owned()),
+            }),
+        }
+    }
+
+    fn outcome(self, reviewer: &User) -> String {
+        let verb = match self.button {
+            ReviewButton::Approve => "Approved",
+            ReviewButton::Remove => "Removed",
+        };
+
+        format!("{} by {} ({})", verb, reviewer.name, reviewer.id)
+    }
+}
+
+/// Any of the buttons Chrysanthemum attaches to its messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ComponentRequest {
+    Moderation(ModerationRequest),
+    Review(ReviewRequest),
+}
+
+impl ComponentRequest {
+    fn from_custom_id(custom_id: &str) -> Option<Self> {
+        ModerationRequest::from_custom_id(custom_id)
+            .map(ComponentRequest::Moderation)
+            .or_else(|| ReviewRequest::from_custom_id(custom_id).map(ComponentRequest::Review))
+    }
+
+    fn action(self, guild_id: Id<GuildMarker>, moderator: Id<UserMarker>) -> Option<MessageAction> {
+        match self {
+            ComponentRequest::Moderation(request) => request.action(guild_id, moderator),
+            ComponentRequest::Review(request) => request.action(),
+        }
+    }
+
+    /// The permissions a moderator needs to use the button, and how to name
+    /// them if they're missing. Banning and timing out also need the
+    /// permission to do so by hand, so that the buttons can't be used to get
+    /// around it.
+    fn required_permissions(self) -> (Permissions, &'static str) {
+        match self {
+            ComponentRequest::Moderation(ModerationRequest {
+                button: ModerationButton::Ban,
+                ..
+            }) => (
+                Permissions::MANAGE_MESSAGES | Permissions::BAN_MEMBERS,
+                "the Manage Messages and Ban Members permissions",
+            ),
+            ComponentRequest::Moderation(ModerationRequest {
+                button: ModerationButton::Timeout,
+                ..
+            }) => (
+                Permissions::MANAGE_MESSAGES | Permissions::MODERATE_MEMBERS,
+                "the Manage Messages and Timeout Members permissions",
+            ),
+            _ => (
+                Permissions::MANAGE_MESSAGES,
+                "the Manage Messages permission",
+            ),
+        }
+    }
+
+    /// Records what was done on the embed the button was attached to.
+    fn record(self, embed: EmbedBuilder, moderator: &User) -> EmbedBuilder {
+        match self {
+            ComponentRequest::Moderation(request) => embed.field(
+                EmbedFieldBuilder::new("Action taken", request.outcome(moderator.id)).build(),
+            ),
+            ComponentRequest::Review(request) => {
+                embed.footer(EmbedFooterBuilder::new(request.outcome(moderator)).build())
+            }
+        }
+    }
+}
+
+/// Builds the row of moderation buttons attached to a message log.
+pub(crate) fn moderation_buttons(
+    user_id: Id<UserMarker>,
+    message_id: Id<MessageMarker>,
+) -> Component {
+    let button = |button, label: &str, style| {
+        Component::Button(Button {
+            custom_id: Some(
+                ModerationRequest {
+                    button,
+                    user_id,
+                    message_id,
+                }
+                .to_custom_id(),
+            ),
+            disabled: false,
+            emoji: None,
+            label: Some(label.to_owned()),
+            style,
+            url: None,
+        })
+    };
+
+    Component::ActionRow(ActionRow {
+        components: vec![
+            button(ModerationButton::Ban, "Ban", ButtonStyle::Danger),
+            button(
+                ModerationButton::Timeout,
+                "Timeout 1h",
+                ButtonStyle::Primary,
+            ),
+            button(ModerationButton::Pardon, "Pardon", ButtonStyle::Secondary),
+        ],
+    })
+}
+
+/// Builds the row of buttons attached to a message flagged for review.
+pub(crate) fn review_buttons(
+    channel_id: Id<ChannelMarker>,
+    message_id: Id<MessageMarker>,
+) -> Component {
+    let button = |button, label: &str, style| {
+        Component::Button(Button {
+            custom_id: Some(
+                ReviewRequest {
+                    button,
+                    channel_id,
+                    message_id,
+                }
+                .to_custom_id(),
+            ),
+            disabled: false,
+            emoji: None,
+            label: Some(label.to_owned()),
+            style,
+            url: None,
+        })
+    };
+
+    Component::ActionRow(ActionRow {
+        components: vec![
+            button(ReviewButton::Approve, "Approve", ButtonStyle::Success),
+            button(ReviewButton::Remove, "Remove", ButtonStyle::Danger),
+        ],
+    })
+}
+
+async fn respond_ephemeral(
+    interaction_http: &InteractionClient<'_>,
+    interaction: &Interaction,
+    content: &str,
+) -> Result<()> {
+    interaction_http
+        .create_response(
+            interaction.id,
+            &interaction.token,
+            &InteractionResponse {
+                kind: InteractionResponseType::ChannelMessageWithSource,
+                data: Some(
+                    InteractionResponseDataBuilder::new()
+                        .flags(MessageFlags::EPHEMERAL)
+                        .content(content.to_owned())
+                        .build(),
+                ),
+            },
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip(state))]
+pub(crate) async fn handle_component(
+    state: crate::State,
+    interaction: &Interaction,
+    data: &MessageComponentInteractionData,
+) -> Result<()> {
+    let request = match ComponentRequest::from_custom_id(&data.custom_id) {
+        Some(request) => request,
+        None => {
+            tracing::trace!("Received unhandleable interaction: unknown component.");
+            return Ok(());
+        }
+    };
+
+    let (guild_id, moderator) = match (interaction.guild_id, interaction.author()) {
+        (Some(guild_id), Some(moderator)) => (guild_id, moderator),
+        _ => {
+            tracing::trace!("No guild or author for this component interaction");
+            return Ok(());
+        }
+    };
+
+    let application_id = match *state.application_id.read().await {
+        Some(application_id) => application_id,
+        None => {
+            tracing::trace!("No application ID yet");
+            return Ok(());
+        }
+    };
+
+    let interaction_http = state.http.interaction(application_id);
+
+    let (required_permissions, permission_names) = request.required_permissions();
+    let permitted = interaction
+        .member
+        .as_ref()
+        .and_then(|member| member.permissions)
+        .map_or(false, |permissions| {
+            permissions.contains(required_permissions)
+        });
+    if !permitted {
+        return respond_ephemeral(
+            &interaction_http,
+            interaction,
+            &format!("You need {} to do that.", permission_names),
+        )
+        .await;
+    }
+
+    let action = request.action(guild_id, moderator.id);
+    let deferred = action.is_some();
+    if let Some(action) = action {
+        if action.requires_armed() && !state.armed.load(Ordering::Relaxed) {
+            return respond_ephemeral(
+                &interaction_http,
+                interaction,
+                "Chrysanthemum is disarmed, so no action was taken.",
+            )
+            .await;
+        }
+
+        // Retrying the action can take longer than Discord waits for a
+        // response, so acknowledge the button first.
+        interaction_http
+            .create_response(
+                interaction.id,
+                &interaction.token,
+                &InteractionResponse {
+                    kind: InteractionResponseType::DeferredUpdateMessage,
+                    data: None,
+                },
+            )
+            .await?;
+
+        if let Err(err) = action
+            .execute_with_retries(&crate::action_context(&state))
+            .await
+        {
+            tracing::warn!(?action, ?err, "Error executing action from log button");
+            interaction_http
+                .create_followup(&interaction.token)
+                .flags(MessageFlags::EPHEMERAL)
+                .content(&format!("Couldn't take that action: {}", err))?
+                .await?;
+            return Ok(());
+        }
+    }
+
+    // Record what was done on the log itself, and take the buttons away so
+    // that nobody acts on it twice.
+    let embeds = interaction
+        .message
+        .as_ref()
+        .map(|message| {
+            message
+                .embeds
+                .iter()
+                .cloned()
+                .map(|embed| request.record(EmbedBuilder::from(embed), moderator).build())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    if deferred {
+        interaction_http
+            .update_response(&interaction.token)
+            .embeds(Some(&embeds))?
+            .components(Some(&[]))?
+            .await?;
+    } else {
+        interaction_http
+            .create_response(
+                interaction.id,
+                &interaction.token,
+                &InteractionResponse {
+                    kind: InteractionResponseType::UpdateMessage,
+                    data: Some(
+                        InteractionResponseDataBuilder::new()
+                            .embeds(embeds)
+                            .components(Vec::new())
+                            .build(),
+                    ),
+                },
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use twilight_model::{guild::Permissions, id::Id};
+
+    use super::{
+        ComponentRequest, ModerationButton, ModerationRequest, ReviewButton, ReviewRequest,
+    };
+
+    #[test]
+    fn custom_id_round_trip() {
+        for button in [
+            ModerationButton::Ban,
+            ModerationButton::Timeout,
+            ModerationButton::Pardon,
+        ] {
+            let request = ModerationRequest {
+                button,
+                user_id: Id::new(123),
+                message_id: Id::new(456),
+            };
+
+            assert_eq!(
+                ModerationRequest::from_custom_id(&request.to_custom_id()),
+                Some(request)
+            );
+        }
+    }
+
+    #[test]
+    fn ban_and_timeout_need_moderation_permissions() {
+        let required = |button| {
+            ComponentRequest::Moderation(ModerationRequest {
+                button,
+                user_id: Id::new(123),
+                message_id: Id::new(456),
+            })
+            .required_permissions()
+            .0
+        };
+
+        assert_eq!(
+            required(ModerationButton::Ban),
+            Permissions::MANAGE_MESSAGES | Permissions::BAN_MEMBERS
+        );
+        assert_eq!(
+            required(ModerationButton::Timeout),
+            Permissions::MANAGE_MESSAGES | Permissions::MODERATE_MEMBERS
+        );
+        assert_eq!(
+            required(ModerationButton::Pardon),
+            Permissions::MANAGE_MESSAGES
+        );
+        // Someone who can only manage messages can't ban.
+        assert!(!Permissions::MANAGE_MESSAGES.contains(required(ModerationButton::Ban)));
+    }
+
+    #[test]
+    fn review_custom_id_round_trip() {
+        for button in [ReviewButton::Approve, ReviewButton::Remove] {
+            let request = ReviewRequest {
+                button,
+                channel_id: Id::new(123),
+                message_id: Id::new(456),
+            };
+
+            assert_eq!(
+                ReviewRequest::from_custom_id(&request.to_custom_id()),
+                Some(request)
+            );
+            assert_eq!(
+                ModerationRequest::from_custom_id(&request.to_custom_id()),
+                None
+            );
+        }
+
+        assert_eq!(
+            ReviewRequest::from_custom_id("chrysanthemum:ban:123:456"),
+            None
+        );
+        assert_eq!(
+            ReviewRequest::from_custom_id("chrysanthemum-review:approve:123"),
+            None
+        );
+    }
+
+    #[test]
+    fn reject_malformed_custom_ids() {
+        assert_eq!(ModerationRequest::from_custom_id(""), None);
+        assert_eq!(ModerationRequest::from_custom_id("other:ban:123:456"), None);
+        assert_eq!(
+            ModerationRequest::from_custom_id("chrysanthemum:kick:123:456"),
+            None
+        );
+        assert_eq!(
+            ModerationRequest::from_custom_id("chrysanthemum:ban:0:456"),
+            None
+        );
+        assert_eq!(
+            ModerationRequest::from_custom_id("chrysanthemum:ban:123"),
+            None
+        );
+        assert_eq!(
+            ModerationRequest::from_custom_id("chrysanthemum:ban:123:456:789"),
+            None
+        );
+    }
+}
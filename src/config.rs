@@ -1,6 +1,7 @@
 use std::{
     borrow::Cow,
-    collections::HashMap,
+    collections::{hash_map::Entry, HashMap},
+    num::NonZeroUsize,
     path::{Path, PathBuf},
 };
 
@@ -8,12 +9,14 @@ use eyre::{Context, Result};
 use serde::Deserialize;
 
 use twilight_model::id::{
-    marker::{ChannelMarker, EmojiMarker, GuildMarker, RoleMarker, StickerMarker},
+    marker::{ChannelMarker, EmojiMarker, GuildMarker, RoleMarker, StickerMarker, UserMarker},
     Id,
 };
 
 use regex::{Regex, RegexBuilder, RegexSet};
 
+use crate::unknown_keys::{self, UnknownKey};
+
 fn deserialize_regex_pattern<'de, D>(de: D) -> Result<String, D::Error>
 where
     D: serde::Deserializer<'de>,
@@ -70,6 +73,46 @@ where
     }
 }
 
+fn deserialize_optional_word_regex<'de, D>(de: D) -> Result<Option<Regex>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    deserialize_word_regex(de).map(Some)
+}
+
+#[derive(Deserialize)]
+struct WordRegex(#[serde(deserialize_with = "deserialize_word_regex")] Regex);
+
+fn deserialize_word_tiers<'de, D>(de: D) -> Result<Vec<WordTier>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct TiersVisitor;
+    impl<'de> serde::de::Visitor<'de> for TiersVisitor {
+        type Value = Vec<WordTier>;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("map of tier names to word lists")
+        }
+
+        fn visit_map<V>(self, mut map: V) -> Result<Vec<WordTier>, V::Error>
+        where
+            V: serde::de::MapAccess<'de>,
+        {
+            // Tiers are kept in the order they're written in, since that's the
+            // order they're checked in.
+            let mut tiers = Vec::new();
+            while let Some((name, WordRegex(words))) = map.next_entry::<String, WordRegex>()? {
+                tiers.push(WordTier { name, words });
+            }
+
+            Ok(tiers)
+        }
+    }
+
+    de.deserialize_map(TiersVisitor)
+}
+
 fn deserialize_substring_regex<'de, D>(de: D) -> Result<Regex, D::Error>
 where
     D: serde::Deserializer<'de>,
@@ -92,37 +135,151 @@ where
     }
 }
 
-#[derive(Deserialize, Debug)]
-#[serde(tag = "action", rename_all = "snake_case")]
+#[derive(Deserialize, Debug, Clone)]
+pub struct EscalationThreshold {
+    /// How many warnings a user needs for these actions to be taken. Only the
+    /// highest threshold the user has reached applies.
+    pub warnings: usize,
+    pub actions: Vec<MessageFilterAction>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OffenseTier {
+    /// How many recent offenses against the filter, counting the current one,
+    /// a user needs for these actions to be taken. Only the highest tier the
+    /// user has reached applies.
+    pub offenses: usize,
+    pub actions: Vec<MessageFilterAction>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "action", rename_all = "snake_case", deny_unknown_fields)]
 pub enum MessageFilterAction {
     /// Delete the offending piece of content.
-    Delete,
+    Delete {
+        /// If set, messages are left up for this many seconds before they're
+        /// deleted.
+        delay_seconds: Option<u64>,
+    },
     /// Send a message to a channel.
     SendMessage {
         channel_id: Id<ChannelMarker>,
         content: String,
         requires_armed: bool,
     },
+    /// Reply to the offending piece of content in its channel.
+    Reply {
+        content: String,
+        /// If set, the reply is deleted after this many seconds.
+        delete_after_seconds: Option<u64>,
+        #[serde(default)]
+        requires_armed: bool,
+    },
+    /// Send a direct message to the user who sent the offending piece of
+    /// content.
+    SendDm {
+        content: String,
+        requires_armed: bool,
+    },
     /// Ban the user who sent the offending piece of content.
     Ban {
         // Reason used in the ban's audit log.
         reason: String,
         // The period over which to remove the banned user's messages, in seconds.
         delete_message_seconds: u32,
+        /// If set, the ban is lifted after this many seconds.
+        duration_seconds: Option<u64>,
     },
     /// Kick the user who sent the offending piece of content.
-    Kick {
-        reason: String,
-    },
+    Kick { reason: String },
     /// Timeout the user who sent the offending piece of content.
     Timeout {
         reason: String,
         /// How long to mute the user for, in seconds.
         duration: i64,
     },
+    /// Timeout the user who sent the offending piece of content for longer
+    /// each time they offend again.
+    EscalatingTimeout {
+        reason: String,
+        /// How long to mute the user for on each offense, in seconds. Offenses
+        /// after the last one use the last duration.
+        durations: Vec<u64>,
+        /// How long each offense counts towards the next timeout, in seconds.
+        window_seconds: u64,
+    },
+    /// POST details about the filtered content to a URL as JSON.
+    Webhook {
+        url: String,
+        /// If set, the body is signed with HMAC-SHA256 using this secret.
+        secret: Option<String>,
+    },
+    /// Take away all of the user's roles and give them just the quarantine
+    /// role.
+    Quarantine {
+        quarantine_role: Id<RoleMarker>,
+        /// Whether `/chrysanthemum-unquarantine` gives the user's roles back.
+        #[serde(default)]
+        restore_on_unquarantine: bool,
+    },
+    /// Set the slowmode of the channel the offending piece of content was
+    /// posted in.
+    SetSlowmode {
+        seconds: u16,
+        /// If set, the channel's slowmode goes back to what it was after this
+        /// many seconds.
+        revert_after: Option<u64>,
+    },
+    /// Publish the offending message to the channels following its
+    /// announcement channel. Meant for filters scoped with `include_roles`
+    /// rather than for deny-list rules. A message that matches such a filter
+    /// is still treated as filtered, so the filter should come after every
+    /// other filter in its channels.
+    Crosspost,
+    /// Give a role to the user who sent the offending piece of content.
+    AddRole {
+        role_id: Id<RoleMarker>,
+        reason: String,
+    },
+    /// Take a role away from the user who sent the offending piece of content.
+    RemoveRole {
+        role_id: Id<RoleMarker>,
+        reason: String,
+    },
+    /// Issue a warning to the user who sent the offending piece of content.
+    Warn {
+        /// How long the warning counts towards `escalate` actions, in seconds.
+        expiry_seconds: u64,
+    },
+    /// Take different actions depending on how many unexpired warnings the
+    /// user who sent the offending piece of content has.
+    Escalate {
+        thresholds: Vec<EscalationThreshold>,
+    },
+    /// Take different actions depending on how many times the user who sent
+    /// the offending piece of content has matched this filter recently. Each
+    /// match counts as an offense, whether or not the user was warned for it.
+    EscalateOffenses {
+        /// How long each offense counts towards the next tier, in seconds.
+        window_seconds: u64,
+        tiers: Vec<OffenseTier>,
+    },
     SendLog {
         channel_id: Id<ChannelMarker>,
+        /// The color of the log embed, as an RGB integer. Defaults to a color
+        /// based on the most severe action the filter takes.
+        #[serde(default)]
+        color: Option<u32>,
+        /// Text to show at the bottom of the log embed.
+        #[serde(default)]
+        footer: Option<String>,
     },
+    /// Post the offending piece of content to a channel for moderators to
+    /// approve or remove, instead of removing it automatically.
+    FlagForReview { channel_id: Id<ChannelMarker> },
+    /// Take the actions of one of the guild's action templates. These are
+    /// replaced with the template's actions when the configuration is loaded.
+    Template { name: String },
 }
 
 #[derive(Deserialize, Debug)]
@@ -133,32 +290,151 @@ pub enum FilterMode {
     DenyList,
 }
 
-#[derive(Deserialize, Debug, Default)]
+#[derive(Deserialize, Debug, Default, Clone)]
 pub struct Scoping {
     /// Which channels to exclude.
     pub exclude_channels: Option<Vec<Id<ChannelMarker>>>,
     /// Which channels to include.
     pub include_channels: Option<Vec<Id<ChannelMarker>>>,
+    /// Whether messages and reactions in threads are also matched against
+    /// `include_channels` and `exclude_channels` by the thread's parent
+    /// channel.
+    #[serde(default)]
+    pub include_parent_channels: bool,
     /// Which roles to exclude.
     pub exclude_roles: Option<Vec<Id<RoleMarker>>>,
+    /// Which roles to include. If set, only members with at least one of
+    /// these roles are included.
+    pub include_roles: Option<Vec<Id<RoleMarker>>>,
+}
+
+/// A list of regexes that can be matched all at once, while keeping each regex
+/// around so that the text it matched can be found.
+#[derive(Debug)]
+pub struct RegexList {
+    pub set: RegexSet,
+    pub regexes: Vec<Regex>,
+    /// Human-readable descriptions of each regex, used in filter reasons
+    /// instead of the pattern itself.
+    pub descriptions: Vec<Option<String>>,
+}
+
+impl RegexList {
+    #[cfg(test)]
+    pub fn new<I, S>(patterns: I) -> Result<Self, regex::Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self::with_descriptions(patterns.into_iter().map(|pattern| (pattern, None)))
+    }
+
+    pub fn with_descriptions<I, S>(patterns: I) -> Result<Self, regex::Error>
+    where
+        I: IntoIterator<Item = (S, Option<String>)>,
+        S: AsRef<str>,
+    {
+        let (regexes, descriptions): (Vec<_>, Vec<_>) = patterns
+            .into_iter()
+            .map(|(pattern, description)| Ok((Regex::new(pattern.as_ref())?, description)))
+            .collect::<Result<Vec<_>, regex::Error>>()?
+            .into_iter()
+            .unzip();
+        let set = RegexSet::new(regexes.iter().map(Regex::as_str))?;
+        Ok(Self {
+            set,
+            regexes,
+            descriptions,
+        })
+    }
+}
+
+/// A regex in a `regex` rule, which can either be a bare pattern or a pattern
+/// with a description.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RegexListEntry {
+    Pattern(String),
+    Described {
+        pattern: String,
+        description: String,
+    },
+}
+
+impl<'de> Deserialize<'de> for RegexList {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let entries = Vec::<RegexListEntry>::deserialize(de)?;
+        RegexList::with_descriptions(entries.into_iter().map(|entry| match entry {
+            RegexListEntry::Pattern(pattern) => (pattern, None),
+            RegexListEntry::Described {
+                pattern,
+                description,
+            } => (pattern, Some(description)),
+        }))
+        .map_err(|err| serde::de::Error::custom(format!("unable to construct regex: {}", err)))
+    }
+}
+
+/// A named group of words within a `words` rule, so that one rule can treat
+/// some words more severely than others.
+#[derive(Debug)]
+pub struct WordTier {
+    pub name: String,
+    pub words: Regex,
 }
 
 #[derive(Deserialize, Debug)]
-#[serde(tag = "type", rename_all = "snake_case")]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
 pub enum MessageFilterRule {
     Words {
         // Note: In the config format, this is an array of strings, not one
         // regex pattern.
-        #[serde(deserialize_with = "deserialize_word_regex")]
-        words: Regex,
+        #[serde(default, deserialize_with = "deserialize_optional_word_regex")]
+        words: Option<Regex>,
+        /// Additional word lists, keyed by tier name. Tiers are checked in
+        /// order, after `words`.
+        #[serde(default, deserialize_with = "deserialize_word_tiers")]
+        tiers: Vec<WordTier>,
+        /// If set, messages fail unless they contain at least one of the words.
+        #[serde(default)]
+        require_match: bool,
+        /// Overrides the reason given when a message fails this rule.
+        reason: Option<String>,
+        /// Whether to also match against the text with leetspeak undone. This
+        /// is set from the guild's `normalize_leetspeak` when it's loaded.
+        #[serde(skip)]
+        leetspeak: bool,
+        /// Whether to also match against the text with runs of spaced-out
+        /// characters, like `f r e e`, collapsed.
+        #[serde(default)]
+        collapse_spacing: bool,
     },
     Substring {
         #[serde(deserialize_with = "deserialize_substring_regex")]
         substrings: Regex,
+        /// Whether to also match against the text with leetspeak undone. This
+        /// is set from the guild's `normalize_leetspeak` when it's loaded.
+        #[serde(skip)]
+        leetspeak: bool,
+        /// Whether to also match against the text with runs of spaced-out
+        /// characters, like `f r e e`, collapsed.
+        #[serde(default)]
+        collapse_spacing: bool,
     },
     Regex {
-        #[serde(with = "serde_regex")]
-        regexes: RegexSet,
+        regexes: RegexList,
+        /// If set, messages fail unless they match at least one of the regexes.
+        #[serde(default)]
+        require_match: bool,
+        /// Overrides the reason given when a message fails this rule.
+        reason: Option<String>,
+        /// Whether to also match against the text with runs of spaced-out
+        /// characters, like `f r e e`, collapsed.
+        #[serde(default)]
+        collapse_spacing: bool,
     },
     Zalgo,
     MimeType {
@@ -169,18 +445,162 @@ pub enum MessageFilterRule {
         /// either ignore it, or reject it out of an abundance of caution.
         allow_unknown: bool,
     },
+    /// Filter attachments whose MIME type doesn't match what their file
+    /// extension implies. Extensions Chrysanthemum doesn't know about are
+    /// ignored.
+    MimeMismatch {
+        /// What to do with attachments that don't have a MIME type. See the
+        /// same field on `MimeType`.
+        allow_unknown: bool,
+    },
+    AttachmentName {
+        mode: FilterMode,
+        /// File extensions to check attachments against. These are compared
+        /// case-insensitively, and a leading `.` is optional.
+        extensions: Vec<String>,
+    },
+    /// Filter attachments larger than this many bytes.
+    AttachmentSize {
+        max_bytes: u64,
+    },
     Invite {
         mode: FilterMode,
+        #[serde(default)]
         invites: Vec<String>,
+        /// Whether to look up which guild each invite points to, and filter
+        /// invites to any of `guilds`. Lookups fail open.
+        #[serde(default)]
+        resolve: bool,
+        #[serde(default)]
+        guilds: Vec<Id<GuildMarker>>,
+    },
+    /// Filter links to files uploaded to Discord's CDN by file extension, in
+    /// the same way as `AttachmentName`.
+    CdnLinks {
+        mode: FilterMode,
+        extensions: Vec<String>,
+        /// If set, only links to files uploaded in other guilds are checked.
+        #[serde(default)]
+        other_guilds_only: bool,
+    },
+    /// Filter invites to chat platforms other than Discord. Each platform must
+    /// be enabled individually.
+    ExternalInvites {
+        #[serde(default)]
+        telegram: bool,
+        #[serde(default)]
+        whatsapp: bool,
+        #[serde(default)]
+        guilded: bool,
+        #[serde(default)]
+        revolt: bool,
+        #[serde(default)]
+        matrix: bool,
+    },
+    /// Filter messages that an external classification service scores above a
+    /// threshold. Fails open if the service can't be reached in time.
+    ExternalScore {
+        /// The URL to POST message content to.
+        endpoint: String,
+        /// Messages scoring strictly above this are filtered.
+        threshold: f32,
+        /// How long to wait for the service to respond.
+        timeout_ms: u64,
+    },
+    /// Filter messages from accounts or members that are too new.
+    AccountAge {
+        min_account_age_seconds: Option<u64>,
+        min_membership_age_seconds: Option<u64>,
+    },
+    /// Filter replies to other messages, if `forbid` is set.
+    Reply {
+        forbid: bool,
+    },
+    /// Filter messages that repeat the author's previous message in the same
+    /// channel, if it was sent less than `window_seconds` earlier.
+    NoRepost {
+        window_seconds: u64,
+    },
+    /// Apply text rules to the text of a message's embeds, rather than to its
+    /// content.
+    Embeds {
+        rules: Vec<MessageFilterRule>,
+    },
+    /// Filter messages that fail every one of the nested rules, for content
+    /// that's only a problem in combination.
+    All {
+        rules: Vec<MessageFilterRule>,
+    },
+    /// Filter messages that pass the nested rule, for content that's required
+    /// rather than denied.
+    Not {
+        rule: Box<MessageFilterRule>,
+    },
+    /// Filter messages that consist almost entirely of emoji.
+    EmojiOnly {
+        /// Messages with emoji fail unless they have at least this many
+        /// letters and numbers.
+        min_text_chars: usize,
+    },
+    /// Filter messages with more than this many emoji, Unicode and custom.
+    EmojiCount {
+        max: usize,
+    },
+    /// Filter cryptocurrency wallet addresses. Each chain must be enabled
+    /// individually.
+    CryptoAddresses {
+        #[serde(default)]
+        bitcoin: bool,
+        #[serde(default)]
+        ethereum: bool,
+        #[serde(default)]
+        solana: bool,
+        #[serde(default)]
+        monero: bool,
+    },
+    /// Filter long runs of base64 or hex, which can be used to smuggle links
+    /// and other content past the other filters.
+    EncodedBlob {
+        /// How many characters long a run has to be to count.
+        min_length: usize,
+        /// How random a run has to look to count, in bits of Shannon entropy
+        /// per character. Defaults to [`DEFAULT_ENCODED_BLOB_MIN_ENTROPY`].
+        min_entropy: Option<f32>,
+    },
+    /// Filter messages by the language they're written in, for channels meant
+    /// for a particular language. Messages whose language can't be detected
+    /// confidently are let through.
+    Language {
+        mode: FilterMode,
+        /// ISO 639-1 language codes, such as `en`.
+        languages: Vec<String>,
+    },
+    /// Filter contact information. Each kind must be enabled individually,
+    /// since phone numbers in particular are prone to false positives.
+    ContactInfo {
+        #[serde(default)]
+        emails: bool,
+        #[serde(default)]
+        phone_numbers: bool,
     },
     Link {
         mode: FilterMode,
         domains: Vec<String>,
+        /// Links whose path and query match any of these are denied, even on
+        /// allowed domains.
+        #[serde(default, with = "serde_regex")]
+        path_patterns: Vec<Regex>,
     },
     StickerId {
         mode: FilterMode,
         stickers: Vec<Id<StickerMarker>>,
     },
+    /// Filter stickers that don't belong to the guild the message was sent in.
+    StickerSource,
+    /// Filter messages with more than this many stickers.
+    StickerCount {
+        max: usize,
+    },
     StickerName {
         // Note: In the config format, this is an array of strings, not one
         // regex pattern.
@@ -195,12 +615,27 @@ pub enum MessageFilterRule {
     },
 }
 
-#[derive(Deserialize, Debug, Default)]
+#[derive(Deserialize, Debug, Default, Clone)]
 pub struct SpamFilter {
     /// How many emoji in a given interval constitute spam.
     pub emoji: Option<u8>,
     /// How many duplicates in a given interval constitute spam.
     pub duplicates: Option<u8>,
+    /// If set, messages count as duplicates when they're at least this
+    /// similar, from 0 to 1, instead of only when they're identical.
+    pub duplicate_similarity: Option<f32>,
+    /// Whether to compare messages after normalizing them when looking for
+    /// duplicates, so that messages differing only by case, whitespace, or
+    /// confusable characters count as duplicates.
+    #[serde(default = "default_normalize_duplicates")]
+    pub normalize_duplicates: bool,
+    /// How many near-duplicates in a given interval constitute spam. This is
+    /// counted separately from `duplicates`, so that exact duplicates can have
+    /// a stricter threshold.
+    pub near_duplicates: Option<u8>,
+    /// How similar, from 0 to 1, messages must be to count as near-duplicates.
+    /// Defaults to [`DEFAULT_NEAR_DUPLICATE_SIMILARITY`].
+    pub near_duplicate_similarity: Option<f32>,
     /// How many links in a given interval constitute spam.
     pub links: Option<u8>,
     /// How many attachments in a given interval constitute spam.
@@ -209,12 +644,100 @@ pub struct SpamFilter {
     pub spoilers: Option<u8>,
     /// How many mentions in a given interval constitute spam.
     pub mentions: Option<u8>,
+    /// How many different users mentioned in a given interval constitute spam.
+    /// Unlike `mentions`, mentioning the same user repeatedly only counts once.
+    pub unique_mentions: Option<u8>,
+    /// How many stickers in a given interval constitute spam.
+    pub stickers: Option<u8>,
+    /// How many custom emoji in a given interval constitute spam.
+    pub custom_emoji: Option<u8>,
+    /// How many characters in a given interval constitute spam.
+    pub characters: Option<u16>,
+    /// How many line breaks in a given interval constitute spam.
+    pub newlines: Option<u8>,
     /// How long, in seconds, to consider messages for spam.
     pub interval: u16,
+    /// If set, messages only count towards spam in the channel they were sent
+    /// in, rather than across the whole guild.
+    #[serde(default)]
+    pub per_channel: bool,
     /// What actions to take when a message is considered spam.
     pub actions: Option<Vec<MessageFilterAction>>,
+    /// If set, these actions are taken when a message comes close to a spam
+    /// threshold, so that users are warned before being punished. Each user
+    /// is warned at most once per `interval`.
+    pub warn_actions: Option<Vec<MessageFilterAction>>,
+    /// How close to a threshold, as a fraction of it, messages have to come
+    /// for `warn_actions` to be taken. Defaults to
+    /// [`DEFAULT_SPAM_WARN_THRESHOLD_FRACTION`].
+    pub warn_threshold_fraction: Option<f32>,
     /// Scoping rules to apply to the spam filter.
     pub scoping: Option<Scoping>,
+    /// Detection of many users posting the same message, which per-user spam
+    /// thresholds can't catch.
+    pub raid: Option<RaidFilter>,
+}
+
+fn default_normalize_duplicates() -> bool {
+    true
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FloodFilter {
+    /// How many messages a channel can receive within `interval` before it's
+    /// considered flooded, no matter who sends them.
+    pub messages: u16,
+    /// How long, in seconds, to count messages for.
+    pub interval: u64,
+    /// What to set a flooded channel's slowmode to, in seconds.
+    pub slowmode_seconds: u16,
+    /// How long after the flood ends, in seconds, to set the channel's
+    /// slowmode back to what it was.
+    pub revert_after: u64,
+    /// Whether to send a notification when a flood starts.
+    #[serde(default = "default_flood_notify")]
+    pub notify: bool,
+    /// Scoping rules to apply to flood detection.
+    pub scoping: Option<Scoping>,
+}
+
+fn default_flood_notify() -> bool {
+    true
+}
+
+/// Discord's longest slowmode, six hours.
+const MAX_SLOWMODE_SECONDS: u16 = 6 * 60 * 60;
+
+#[derive(Deserialize, Debug)]
+pub struct ReactionSpamFilter {
+    /// How many reactions a user can add within `interval` before they're
+    /// considered spam.
+    pub reactions_per_interval: u16,
+    /// How long, in seconds, to consider reactions for spam.
+    pub interval: u64,
+    /// What actions to take when a reaction is considered spam.
+    pub actions: Option<Vec<MessageFilterAction>>,
+    /// Scoping rules to apply to the reaction spam filter.
+    pub scoping: Option<Scoping>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RaidFilter {
+    /// How many different users can post the same message within
+    /// `window_seconds` before it's considered a raid.
+    pub identical_messages: u16,
+    pub window_seconds: u64,
+    /// Messages shorter than this many characters are never considered part
+    /// of a raid, so that greetings and the like don't set it off.
+    #[serde(default)]
+    pub min_characters: usize,
+    /// If set, the copies of the message posted before the raid was detected
+    /// are deleted too.
+    #[serde(default)]
+    pub delete_earlier: bool,
+    /// What actions to take on messages that are part of a raid. Defaults to
+    /// the spam filter's actions.
+    pub actions: Option<Vec<MessageFilterAction>>,
 }
 
 #[derive(Deserialize, Debug, Default)]
@@ -226,10 +749,17 @@ pub struct MessageFilter {
     pub scoping: Option<Scoping>,
     /// What actions to take when a message matches a filter.
     pub actions: Option<Vec<MessageFilterAction>>,
+    /// Actions to take instead of `actions` when a message matches words in a
+    /// particular tier of a `words` rule.
+    pub actions_by_tier: Option<HashMap<String, Vec<MessageFilterAction>>>,
+    /// If set, messages that match this filter are exempt from all filters
+    /// after it, and no actions are taken.
+    #[serde(default)]
+    pub exempt: bool,
 }
 
 #[derive(Deserialize, Debug)]
-#[serde(tag = "type", rename_all = "snake_case")]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
 pub enum ReactionFilterRule {
     /// Filter default emoji.
     Default {
@@ -248,6 +778,12 @@ pub enum ReactionFilterRule {
         #[serde(deserialize_with = "deserialize_substring_regex")]
         names: Regex,
     },
+    /// Filter reactions once more than `max` distinct users have reacted to a
+    /// message with the same emoji.
+    Count { max: usize },
+    /// Filter animated custom emoji, unless `allow` is set. Default emoji and
+    /// static custom emoji always pass.
+    Animated { allow: bool },
 }
 
 #[derive(Deserialize, Debug)]
@@ -272,7 +808,7 @@ pub struct Notifications {
 }
 
 #[derive(Deserialize, Debug)]
-#[serde(tag = "type")]
+#[serde(tag = "type", deny_unknown_fields)]
 pub enum UsernameFilterRule {
     Substring {
         // Note: In the config format, this is an array of strings, not one
@@ -308,15 +844,74 @@ pub struct GuildConfig {
     pub slash_commands: Option<SlashCommands>,
     pub default_scoping: Option<Scoping>,
     pub default_actions: Option<Vec<MessageFilterAction>>,
+    /// Named lists of actions, which `template` actions can refer to.
+    #[serde(default)]
+    pub action_templates: HashMap<String, Vec<MessageFilterAction>>,
     pub messages: Option<Vec<MessageFilter>>,
     pub reactions: Option<Vec<ReactionFilter>>,
     pub spam: Option<SpamFilter>,
+    pub reaction_spam: Option<ReactionSpamFilter>,
+    pub flood: Option<FloodFilter>,
     pub usernames: Option<UsernameFilter>,
     /// Whether to include bots. This is used for integration tests, where two
     /// bots interact with each other. This should not be set in most production
     /// environments. Chrysanthemum will always ignore itself.
     #[serde(default)]
     pub include_bots: bool,
+    /// Whether to filter messages sent by webhooks, which are otherwise
+    /// ignored. This is independent of `include_bots`.
+    #[serde(default)]
+    pub filter_webhooks: bool,
+    /// Bots whose messages are never filtered, even if `include_bots` is set.
+    pub bot_allowlist: Option<Vec<Id<UserMarker>>>,
+    /// Members with any of these roles bypass message filtering entirely.
+    pub trusted_roles: Option<Vec<Id<RoleMarker>>>,
+    /// Whether members with trusted roles also bypass spam filtering.
+    #[serde(default = "default_trusted_roles_skip_spam")]
+    pub trusted_roles_skip_spam: bool,
+    /// Whether `words` and `substring` rules also match messages with common
+    /// leetspeak substitutions, like `h3ll0`, undone.
+    #[serde(default)]
+    pub normalize_leetspeak: bool,
+    /// Files whose message filters, reaction filters, action templates, and
+    /// spam filter are merged beneath this guild's own, relative to this file.
+    #[serde(default)]
+    pub include: Vec<PathBuf>,
+    /// Which included file each merged part came from.
+    #[serde(skip)]
+    pub(crate) include_sources: IncludeSources,
+    /// Keys in this file, and the files it includes, that don't match any
+    /// field, described as validation errors.
+    #[serde(skip)]
+    pub(crate) unknown_keys: Vec<String>,
+}
+
+/// Which included file the parts of a guild's configuration that didn't come
+/// from its own file came from, so that errors in them can say where to look.
+#[derive(Debug, Default)]
+pub(crate) struct IncludeSources {
+    /// Message filters, by name.
+    messages: HashMap<String, PathBuf>,
+    /// Reaction filters, by name.
+    reactions: HashMap<String, PathBuf>,
+    /// Action templates, by name.
+    action_templates: HashMap<String, PathBuf>,
+    spam: Option<PathBuf>,
+    /// Every file the guild's configuration includes, directly or not.
+    pub(crate) files: Vec<PathBuf>,
+}
+
+/// Notes which included file `errors` came from, if they came from one.
+fn attribute_errors(errors: &mut [String], source: Option<&PathBuf>) {
+    if let Some(source) = source {
+        for error in errors {
+            error.push_str(&format!(" (included from {:?})", source));
+        }
+    }
+}
+
+fn default_trusted_roles_skip_spam() -> bool {
+    true
 }
 
 #[derive(Deserialize, Debug)]
@@ -333,6 +928,22 @@ pub struct SentryConfig {
     pub sample_rate: Option<f32>,
 }
 
+#[derive(Deserialize, Debug)]
+pub struct HealthConfig {
+    /// The address to serve health checks on, like `0.0.0.0:8080`.
+    pub bind: String,
+    /// How long the gateway can go without sending anything before
+    /// Chrysanthemum is no longer considered ready.
+    #[serde(default = "default_stale_after_seconds")]
+    pub stale_after_seconds: u64,
+}
+
+fn default_stale_after_seconds() -> u64 {
+    // Discord sends heartbeat acknowledgements about every 41 seconds, so
+    // this allows for a couple of them to be missed.
+    120
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Config {
     pub guild_config_dir: PathBuf,
@@ -341,6 +952,213 @@ pub struct Config {
     pub sentry: Option<SentryConfig>,
     pub reload_interval: Option<u64>,
     pub armed_by_default: bool,
+    /// If set, no actions are taken at all, whether or not Chrysanthemum is
+    /// armed; `send_log` actions are still sent, marked as a dry run, so that
+    /// filters can be tried out on a guild before enabling them.
+    #[serde(default)]
+    pub dry_run_globally: bool,
+    /// Where to keep state that should survive restarts, like warnings. If
+    /// not set, this state is only kept in memory.
+    pub state_dir: Option<PathBuf>,
+    /// If set, serve `/healthz` and `/readyz` endpoints for health checks.
+    pub http_health: Option<HealthConfig>,
+    /// Extra confusables to merge over the built-in table, in the same format
+    /// as Unicode's `confusables.txt`.
+    pub confusables_file: Option<PathBuf>,
+    /// If set, at most this many messages and reactions are filtered at once,
+    /// and any that arrive while that many are being filtered are dropped
+    /// unfiltered. If not set, there's no limit.
+    pub max_concurrent_filters: Option<NonZeroUsize>,
+    /// If set, guild configurations are reloaded as soon as their files
+    /// change, instead of every `reload_interval` seconds.
+    #[serde(default)]
+    pub watch_guild_configs: bool,
+    /// A spam filter for guilds whose configurations don't have their own.
+    /// A guild's own `spam` replaces this entirely.
+    pub default_spam: Option<SpamFilter>,
+}
+
+/// The longest timeout Discord allows: 28 days.
+pub(crate) const MAX_TIMEOUT_SECONDS: i64 = 28 * 24 * 60 * 60;
+
+/// How similar messages must be to count as near-duplicates for spam, if the
+/// spam config doesn't say.
+pub(crate) const DEFAULT_NEAR_DUPLICATE_SIMILARITY: f32 = 0.8;
+
+/// The default minimum entropy of an `encoded_blob`. Base64 and hex encoding
+/// random or compressed data comes out well above this, while long words and
+/// repetitive strings like `abc123abc123` fall below it.
+pub(crate) const DEFAULT_ENCODED_BLOB_MIN_ENTROPY: f32 = 3.0;
+
+/// No run of base64 characters can have more entropy than this, since there
+/// are only 65 characters in the alphabet, counting padding.
+const MAX_ENCODED_BLOB_ENTROPY: f32 = 6.0;
+
+/// How close to a spam threshold messages have to come for the spam filter's
+/// warn actions to be taken, if the spam config doesn't say.
+pub(crate) const DEFAULT_SPAM_WARN_THRESHOLD_FRACTION: f32 = 0.7;
+
+fn is_escalation(action: &MessageFilterAction) -> bool {
+    matches!(
+        action,
+        MessageFilterAction::Escalate { .. } | MessageFilterAction::EscalateOffenses { .. }
+    )
+}
+
+fn validate_actions(actions: &[MessageFilterAction], context: &str, errors: &mut Vec<String>) {
+    let offense_escalations = actions
+        .iter()
+        .filter(|action| matches!(action, MessageFilterAction::EscalateOffenses { .. }))
+        .count();
+    if offense_escalations > 1 {
+        errors.push(format!(
+            "in {}, there are {} escalate_offenses actions; each one counts every match as an offense, so there can only be one",
+            context, offense_escalations
+        ));
+    }
+
+    for (index, action) in actions.iter().enumerate() {
+        match action {
+            MessageFilterAction::Timeout { duration, .. } if *duration > MAX_TIMEOUT_SECONDS => {
+                errors.push(format!(
+                    "in {}, action {} has a duration of {} seconds, longer than Discord's maximum timeout of 28 days ({} seconds)",
+                    context, index, duration, MAX_TIMEOUT_SECONDS
+                ));
+            }
+            MessageFilterAction::SendLog {
+                color: Some(color), ..
+            } if *color > 0xFF_FF_FF => {
+                errors.push(format!(
+                    "in {}, action {} has color {:#x}, which isn't a valid RGB color",
+                    context, index, color
+                ));
+            }
+            MessageFilterAction::SendLog {
+                footer: Some(footer),
+                ..
+            } if footer.is_empty() => {
+                errors.push(format!(
+                    "in {}, action {} has an empty footer",
+                    context, index
+                ));
+            }
+            MessageFilterAction::SetSlowmode { seconds, .. } if *seconds > MAX_SLOWMODE_SECONDS => {
+                errors.push(format!(
+                    "in {}, action {} has a slowmode of {} seconds, longer than Discord's maximum slowmode of 6 hours ({} seconds)",
+                    context, index, seconds, MAX_SLOWMODE_SECONDS
+                ));
+            }
+            MessageFilterAction::Webhook { url, .. } => {
+                if let Err(err) = reqwest::Url::parse(url) {
+                    errors.push(format!(
+                        "in {}, action {} has webhook URL `{}`, which is not a valid URL: {}",
+                        context, index, url, err
+                    ));
+                }
+            }
+            MessageFilterAction::Warn { expiry_seconds: 0 } => {
+                errors.push(format!(
+                    "in {}, action {} is a warning that expires immediately",
+                    context, index
+                ));
+            }
+            MessageFilterAction::EscalatingTimeout {
+                durations,
+                window_seconds,
+                ..
+            } => {
+                if durations.is_empty() {
+                    errors.push(format!("in {}, action {} has no durations", context, index));
+                }
+
+                if durations.contains(&0) {
+                    errors.push(format!(
+                        "in {}, action {} has a duration of 0",
+                        context, index
+                    ));
+                }
+
+                if durations
+                    .iter()
+                    .any(|duration| *duration > MAX_TIMEOUT_SECONDS as u64)
+                {
+                    errors.push(format!(
+                        "in {}, action {} has a duration longer than Discord's maximum timeout of 28 days ({} seconds)",
+                        context, index, MAX_TIMEOUT_SECONDS
+                    ));
+                }
+
+                if *window_seconds == 0 {
+                    errors.push(format!(
+                        "in {}, action {} has a window of 0 seconds; offenses would never count",
+                        context, index
+                    ));
+                }
+            }
+            MessageFilterAction::Escalate { thresholds } => {
+                if thresholds.is_empty() {
+                    errors.push(format!(
+                        "in {}, action {} has no thresholds",
+                        context, index
+                    ));
+                }
+
+                for threshold in thresholds {
+                    let threshold_context = format!(
+                        "{}, action {}, threshold {}",
+                        context, index, threshold.warnings
+                    );
+
+                    if threshold.actions.is_empty() {
+                        errors.push(format!("in {}, actions is empty", threshold_context));
+                    }
+
+                    if threshold.actions.iter().any(is_escalation) {
+                        errors.push(format!(
+                            "in {}, escalate actions can't be nested",
+                            threshold_context
+                        ));
+                    }
+
+                    validate_actions(&threshold.actions, &threshold_context, errors);
+                }
+            }
+            MessageFilterAction::EscalateOffenses {
+                window_seconds,
+                tiers,
+            } => {
+                if *window_seconds == 0 {
+                    errors.push(format!(
+                        "in {}, action {} has a window of 0 seconds; offenses would never count",
+                        context, index
+                    ));
+                }
+
+                if tiers.is_empty() {
+                    errors.push(format!("in {}, action {} has no tiers", context, index));
+                }
+
+                for tier in tiers {
+                    let tier_context =
+                        format!("{}, action {}, tier {}", context, index, tier.offenses);
+
+                    if tier.actions.is_empty() {
+                        errors.push(format!("in {}, actions is empty", tier_context));
+                    }
+
+                    if tier.actions.iter().any(is_escalation) {
+                        errors.push(format!(
+                            "in {}, escalate actions can't be nested",
+                            tier_context
+                        ));
+                    }
+
+                    validate_actions(&tier.actions, &tier_context, errors);
+                }
+            }
+            _ => {}
+        }
+    }
 }
 
 fn validate_scoping(scoping: &Scoping, context: &str, errors: &mut Vec<String>) {
@@ -362,12 +1180,36 @@ fn validate_scoping(scoping: &Scoping, context: &str, errors: &mut Vec<String>)
         ));
     }
 
+    if scoping.include_parent_channels
+        && scoping.include_channels.is_none()
+        && scoping.exclude_channels.is_none()
+    {
+        errors.push(format!(
+            "in {}, scoping rule sets include_parent_channels without include_channels or exclude_channels, so it has no effect.",
+            context
+        ));
+    }
+
     if scoping.exclude_roles.is_some() && scoping.exclude_roles.as_ref().unwrap().is_empty() {
         errors.push(format!(
             "in {}, scoping rule specifies an empty exclude_roles; omit the key instead.",
             context
         ));
     }
+
+    if scoping.exclude_roles.is_some() && scoping.include_roles.is_some() {
+        errors.push(format!(
+            "in {}, scoping rule specifies both exclude_roles and include_roles. Specify only one.",
+            context
+        ));
+    }
+
+    if scoping.include_roles.is_some() && scoping.include_roles.as_ref().unwrap().is_empty() {
+        errors.push(format!(
+            "in {}, scoping rule specifies an empty include_roles; omit the key instead.",
+            context
+        ));
+    }
 }
 
 fn validate_message_rule(
@@ -376,7 +1218,7 @@ fn validate_message_rule(
     errors: &mut Vec<String>,
 ) {
     match message_rule {
-        MessageFilterRule::Substring { substrings } => {
+        MessageFilterRule::Substring { substrings, .. } => {
             if substrings.is_match("") {
                 errors.push(format!(
                     "in {}, substrings contains an empty string; this would match all messages",
@@ -384,19 +1226,35 @@ fn validate_message_rule(
                 ));
             }
         }
-        MessageFilterRule::Words { words } => {
+        MessageFilterRule::Words { words, tiers, .. } => {
+            if words.is_none() && tiers.is_empty() {
+                errors.push(format!(
+                    "in {}, neither words nor tiers are specified",
+                    context
+                ));
+            }
+
             // HACK: The empty string doesn't work here, because of the structure
             // of the deserialized `words` regex. We use the letter `a`, since the
             // regex crate provides no better way to do this...
-            if words.is_match("a") {
+            if words.as_ref().map_or(false, |words| words.is_match("a")) {
                 errors.push(format!(
                     "in {}, words contains an empty string; this would match all messages",
                     context
                 ));
             }
+
+            for tier in tiers {
+                if tier.words.is_match("a") {
+                    errors.push(format!(
+                        "in {}, tier {} contains an empty string; this would match all messages",
+                        context, tier.name
+                    ));
+                }
+            }
         }
-        MessageFilterRule::Regex { regexes } => {
-            let matches = regexes.matches("").into_iter();
+        MessageFilterRule::Regex { regexes, .. } => {
+            let matches = regexes.set.matches("").into_iter();
             for (index, _) in matches.enumerate() {
                 errors.push(format!(
                     "in {}, regex {} matches an empty string; this would match all messages",
@@ -404,57 +1262,620 @@ fn validate_message_rule(
                 ));
             }
         }
-        _ => {}
-    }
-}
-
-pub fn validate_guild_config(guild: &GuildConfig) -> Result<(), Vec<String>> {
-    let mut errors = Vec::new();
-
-    if let Some(scoping) = &guild.default_scoping {
-        validate_scoping(scoping, "default scoping", &mut errors);
-    }
-
-    let mut has_default_actions = false;
-    if let Some(actions) = &guild.default_actions {
-        if actions.is_empty() {
-            errors.push("default_actions is specified but is empty.".to_string());
-        } else {
-            has_default_actions = true;
-        }
-    }
+        MessageFilterRule::ExternalScore {
+            endpoint,
+            timeout_ms,
+            ..
+        } => {
+            if let Err(err) = reqwest::Url::parse(endpoint) {
+                errors.push(format!(
+                    "in {}, external_score endpoint `{}` is not a valid URL: {}",
+                    context, endpoint, err
+                ));
+            }
 
-    if let Some(notifications) = &guild.notifications {
-        if let Some(roles) = &notifications.ping_roles {
-            if roles.is_empty() {
-                errors.push(
-                    "notification settings, ping_roles is specified but is empty; omit the key."
-                        .to_string(),
-                );
+            if *timeout_ms == 0 {
+                errors.push(format!(
+                    "in {}, external_score timeout_ms is 0; this would never filter any messages",
+                    context
+                ));
             }
         }
-    }
-
-    if let Some(spam) = &guild.spam {
-        if let Some(scoping) = spam.scoping.as_ref() {
-            validate_scoping(scoping, "spam scoping", &mut errors);
+        MessageFilterRule::AccountAge {
+            min_account_age_seconds: None,
+            min_membership_age_seconds: None,
+        } => {
+            errors.push(format!(
+                "in {}, account_age specifies no minimum ages; this would match no messages",
+                context
+            ));
         }
+        MessageFilterRule::Embeds { rules } => {
+            if rules.is_empty() {
+                errors.push(format!("in {}, embeds has no rules", context));
+            }
 
-        if let Some(actions) = &spam.actions {
-            if actions.is_empty() {
-                errors.push("in spam config, actions is specified but is empty.".to_string());
+            for (index, rule) in rules.iter().enumerate() {
+                validate_message_rule(rule, &format!("{}, embed rule {}", context, index), errors);
             }
-        } else if !has_default_actions {
-            errors.push("in spam config, no actions are specified and there are no default actions for this guild.".to_string());
         }
+        MessageFilterRule::All { rules } => {
+            if rules.is_empty() {
+                errors.push(format!("in {}, all has no rules", context));
+            }
 
-        if spam.emoji.is_none()
-            && spam.attachments.is_none()
-            && spam.duplicates.is_none()
-            && spam.links.is_none()
-            && spam.spoilers.is_none()
-        {
-            errors.push("in spam config, no spam thresholds are specified. Spam filtering will have no effects.".to_string());
+            for (index, rule) in rules.iter().enumerate() {
+                validate_message_rule(rule, &format!("{}, nested rule {}", context, index), errors);
+            }
+        }
+        MessageFilterRule::Not { rule } => {
+            validate_message_rule(rule, &format!("{}, negated rule", context), errors);
+        }
+        MessageFilterRule::ExternalInvites {
+            telegram,
+            whatsapp,
+            guilded,
+            revolt,
+            matrix,
+        } => {
+            if !(*telegram || *whatsapp || *guilded || *revolt || *matrix) {
+                errors.push(format!(
+                    "in {}, external_invites has no platforms enabled; this would match no messages",
+                    context
+                ));
+            }
+        }
+        MessageFilterRule::Invite {
+            resolve, guilds, ..
+        } => {
+            if *resolve && guilds.is_empty() {
+                errors.push(format!(
+                    "in {}, invite has resolve set but no guilds; resolving invites would do nothing",
+                    context
+                ));
+            }
+
+            if !*resolve && !guilds.is_empty() {
+                errors.push(format!(
+                    "in {}, invite has guilds but not resolve; guilds are only checked when resolve is set",
+                    context
+                ));
+            }
+        }
+        MessageFilterRule::Link { path_patterns, .. } => {
+            for (index, pattern) in path_patterns.iter().enumerate() {
+                if pattern.is_match("") {
+                    errors.push(format!(
+                        "in {}, path pattern {} matches an empty path; this would match all links",
+                        context, index
+                    ));
+                }
+            }
+        }
+        MessageFilterRule::NoRepost { window_seconds } => {
+            if *window_seconds == 0 {
+                errors.push(format!(
+                    "in {}, no_repost window_seconds is 0; this would match no messages",
+                    context
+                ));
+            }
+        }
+        MessageFilterRule::AttachmentSize { max_bytes } => {
+            if *max_bytes == 0 {
+                errors.push(format!(
+                    "in {}, attachment_size max_bytes is 0; this would match every attachment",
+                    context
+                ));
+            }
+        }
+        MessageFilterRule::EmojiOnly { min_text_chars } => {
+            if *min_text_chars == 0 {
+                errors.push(format!(
+                    "in {}, emoji_only min_text_chars is 0; this would match no messages",
+                    context
+                ));
+            }
+        }
+        MessageFilterRule::CryptoAddresses {
+            bitcoin,
+            ethereum,
+            solana,
+            monero,
+        } => {
+            if !(*bitcoin || *ethereum || *solana || *monero) {
+                errors.push(format!(
+                    "in {}, crypto_addresses has no chains enabled; this would match no messages",
+                    context
+                ));
+            }
+        }
+        MessageFilterRule::EncodedBlob {
+            min_length,
+            min_entropy,
+        } => {
+            if *min_length == 0 {
+                errors.push(format!(
+                    "in {}, encoded_blob min_length is 0; this would match every message",
+                    context
+                ));
+            }
+
+            if let Some(min_entropy) = min_entropy {
+                if !(*min_entropy > 0.0 && *min_entropy <= MAX_ENCODED_BLOB_ENTROPY) {
+                    errors.push(format!(
+                        "in {}, encoded_blob min_entropy is {}, but must be greater than 0 and at most {}",
+                        context, min_entropy, MAX_ENCODED_BLOB_ENTROPY
+                    ));
+                }
+            }
+        }
+        MessageFilterRule::Language { languages, .. } => {
+            if languages.is_empty() {
+                errors.push(format!("in {}, language has no languages", context));
+            }
+
+            for code in languages {
+                if !crate::language::is_supported(code) {
+                    errors.push(format!(
+                        "in {}, language `{}` is not a supported ISO 639-1 language code",
+                        context, code
+                    ));
+                }
+            }
+        }
+        MessageFilterRule::ContactInfo {
+            emails,
+            phone_numbers,
+        } => {
+            if !(*emails || *phone_numbers) {
+                errors.push(format!(
+                    "in {}, contact_info has nothing enabled; this would match no messages",
+                    context
+                ));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Replaces `template` actions in `actions` with the actions of the templates
+/// they name. `stack` holds the templates currently being expanded, to catch
+/// templates that include themselves.
+fn expand_action_templates(
+    actions: Vec<MessageFilterAction>,
+    templates: &HashMap<String, Vec<MessageFilterAction>>,
+    stack: &mut Vec<String>,
+    context: &str,
+    errors: &mut Vec<String>,
+) -> Vec<MessageFilterAction> {
+    let mut expanded = Vec::with_capacity(actions.len());
+
+    for action in actions {
+        match action {
+            MessageFilterAction::Template { name } => {
+                if stack.contains(&name) {
+                    errors.push(format!(
+                        "in {}, action template {} includes itself ({} -> {})",
+                        context,
+                        name,
+                        stack.join(" -> "),
+                        name
+                    ));
+                    continue;
+                }
+
+                match templates.get(&name) {
+                    Some(template) => {
+                        stack.push(name);
+                        expanded.extend(expand_action_templates(
+                            template.clone(),
+                            templates,
+                            stack,
+                            context,
+                            errors,
+                        ));
+                        stack.pop();
+                    }
+                    None => errors.push(format!(
+                        "in {}, there is no action template named {}",
+                        context, name
+                    )),
+                }
+            }
+            MessageFilterAction::Escalate { thresholds } => {
+                let thresholds = thresholds
+                    .into_iter()
+                    .map(|threshold| EscalationThreshold {
+                        warnings: threshold.warnings,
+                        actions: expand_action_templates(
+                            threshold.actions,
+                            templates,
+                            stack,
+                            context,
+                            errors,
+                        ),
+                    })
+                    .collect();
+                expanded.push(MessageFilterAction::Escalate { thresholds });
+            }
+            MessageFilterAction::EscalateOffenses {
+                window_seconds,
+                tiers,
+            } => {
+                let tiers = tiers
+                    .into_iter()
+                    .map(|tier| OffenseTier {
+                        offenses: tier.offenses,
+                        actions: expand_action_templates(
+                            tier.actions,
+                            templates,
+                            stack,
+                            context,
+                            errors,
+                        ),
+                    })
+                    .collect();
+                expanded.push(MessageFilterAction::EscalateOffenses {
+                    window_seconds,
+                    tiers,
+                });
+            }
+            action => expanded.push(action),
+        }
+    }
+
+    expanded
+}
+
+/// Replaces every `template` action in a guild's configuration with the
+/// actions of the template it names, so that nothing past loading has to know
+/// about templates.
+fn resolve_action_templates(guild: &mut GuildConfig) -> Result<(), Vec<String>> {
+    let templates = std::mem::take(&mut guild.action_templates);
+    let mut errors = Vec::new();
+
+    // Check the templates on their own first, so that a broken template is
+    // reported once rather than everywhere it's used.
+    let mut names: Vec<_> = templates.keys().collect();
+    names.sort();
+    for name in names {
+        let first_error = errors.len();
+        expand_action_templates(
+            templates[name].clone(),
+            &templates,
+            &mut vec![name.clone()],
+            &format!("action template {}", name),
+            &mut errors,
+        );
+        attribute_errors(
+            &mut errors[first_error..],
+            guild.include_sources.action_templates.get(name),
+        );
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let expand =
+        |actions: &mut Vec<MessageFilterAction>, context: &str, errors: &mut Vec<String>| {
+            *actions = expand_action_templates(
+                std::mem::take(actions),
+                &templates,
+                &mut Vec::new(),
+                context,
+                errors,
+            );
+        };
+
+    if let Some(actions) = &mut guild.default_actions {
+        expand(actions, "default actions", &mut errors);
+    }
+
+    for filter in guild.messages.iter_mut().flatten() {
+        let first_error = errors.len();
+        let context = format!("message filter {}", filter.name);
+        if let Some(actions) = &mut filter.actions {
+            expand(actions, &context, &mut errors);
+        }
+
+        for actions in filter
+            .actions_by_tier
+            .iter_mut()
+            .flat_map(|tiers| tiers.values_mut())
+        {
+            expand(actions, &context, &mut errors);
+        }
+
+        attribute_errors(
+            &mut errors[first_error..],
+            guild.include_sources.messages.get(&filter.name),
+        );
+    }
+
+    for filter in guild.reactions.iter_mut().flatten() {
+        let first_error = errors.len();
+        if let Some(actions) = &mut filter.actions {
+            expand(
+                actions,
+                &format!("reaction filter {}", filter.name),
+                &mut errors,
+            );
+        }
+
+        attribute_errors(
+            &mut errors[first_error..],
+            guild.include_sources.reactions.get(&filter.name),
+        );
+    }
+
+    let first_error = errors.len();
+    if let Some(actions) = guild.spam.as_mut().and_then(|spam| spam.actions.as_mut()) {
+        expand(actions, "spam config", &mut errors);
+    }
+
+    if let Some(actions) = guild
+        .spam
+        .as_mut()
+        .and_then(|spam| spam.warn_actions.as_mut())
+    {
+        expand(actions, "spam warn actions", &mut errors);
+    }
+
+    if let Some(actions) = guild
+        .spam
+        .as_mut()
+        .and_then(|spam| spam.raid.as_mut())
+        .and_then(|raid| raid.actions.as_mut())
+    {
+        expand(actions, "raid config", &mut errors);
+    }
+    attribute_errors(
+        &mut errors[first_error..],
+        guild.include_sources.spam.as_ref(),
+    );
+
+    if let Some(actions) = guild
+        .reaction_spam
+        .as_mut()
+        .and_then(|reaction_spam| reaction_spam.actions.as_mut())
+    {
+        expand(actions, "reaction spam config", &mut errors);
+    }
+
+    guild.action_templates = templates;
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn validate_spam(spam: &SpamFilter, has_default_actions: bool, errors: &mut Vec<String>) {
+    if let Some(scoping) = spam.scoping.as_ref() {
+        validate_scoping(scoping, "spam scoping", errors);
+    }
+
+    if let Some(actions) = &spam.actions {
+        if actions.is_empty() {
+            errors.push("in spam config, actions is specified but is empty.".to_string());
+        }
+
+        validate_actions(actions, "spam config", errors);
+    } else if !has_default_actions {
+        errors.push("in spam config, no actions are specified and there are no default actions for this guild.".to_string());
+    }
+
+    for (name, similarity) in [
+        ("duplicate_similarity", spam.duplicate_similarity),
+        ("near_duplicate_similarity", spam.near_duplicate_similarity),
+    ] {
+        if let Some(similarity) = similarity {
+            if !(0.0..=1.0).contains(&similarity) {
+                errors.push(format!(
+                    "in spam config, {} is {}, but must be between 0 and 1.",
+                    name, similarity
+                ));
+            }
+        }
+    }
+
+    if let Some(actions) = &spam.warn_actions {
+        if actions.is_empty() {
+            errors.push("in spam config, warn_actions is specified but is empty.".to_string());
+        }
+
+        validate_actions(actions, "spam warn actions", errors);
+    }
+
+    if let Some(fraction) = spam.warn_threshold_fraction {
+        if !(fraction > 0.0 && fraction < 1.0) {
+            errors.push(format!(
+                "in spam config, warn_threshold_fraction is {}, but must be greater than 0 and less than 1.",
+                fraction
+            ));
+        }
+
+        if spam.warn_actions.is_none() {
+            errors.push(
+                "in spam config, warn_threshold_fraction is specified but there are no warn_actions."
+                    .to_string(),
+            );
+        }
+    }
+
+    if spam.emoji.is_none()
+        && spam.attachments.is_none()
+        && spam.duplicates.is_none()
+        && spam.near_duplicates.is_none()
+        && spam.links.is_none()
+        && spam.spoilers.is_none()
+        && spam.mentions.is_none()
+        && spam.unique_mentions.is_none()
+        && spam.stickers.is_none()
+        && spam.custom_emoji.is_none()
+        && spam.characters.is_none()
+        && spam.newlines.is_none()
+        && spam.raid.is_none()
+    {
+        errors.push("in spam config, no spam thresholds are specified. Spam filtering will have no effects.".to_string());
+    }
+
+    if let Some(raid) = &spam.raid {
+        if raid.identical_messages == 0 {
+            errors.push(
+                "in raid config, identical_messages is 0; every message would be considered part of a raid."
+                    .to_string(),
+            );
+        }
+
+        if raid.window_seconds == 0 {
+            errors.push(
+                "in raid config, window_seconds is 0; no raids would be detected.".to_string(),
+            );
+        }
+
+        if let Some(actions) = &raid.actions {
+            if actions.is_empty() {
+                errors.push("in raid config, actions is specified but is empty.".to_string());
+            }
+
+            validate_actions(actions, "raid config", errors);
+        }
+    }
+}
+
+/// Validates the parts of the main configuration that guilds share.
+pub fn validate_config(config: &Config) -> Result<(), Vec<String>> {
+    let mut errors = Vec::new();
+
+    if let Some(spam) = &config.default_spam {
+        // Guilds can supply actions for the default spam filter with their
+        // default actions, so missing actions are checked for each guild.
+        validate_spam(spam, true, &mut errors);
+    }
+
+    if !errors.is_empty() {
+        Err(errors
+            .into_iter()
+            .map(|error| format!("default_spam: {}", error))
+            .collect())
+    } else {
+        Ok(())
+    }
+}
+
+pub fn validate_guild_config(guild: &GuildConfig) -> Result<(), Vec<String>> {
+    let mut errors = guild.unknown_keys.clone();
+
+    if let Some(scoping) = &guild.default_scoping {
+        validate_scoping(scoping, "default scoping", &mut errors);
+    }
+
+    let mut has_default_actions = false;
+    if let Some(actions) = &guild.default_actions {
+        if actions.is_empty() {
+            errors.push("default_actions is specified but is empty.".to_string());
+        } else {
+            has_default_actions = true;
+        }
+
+        validate_actions(actions, "default actions", &mut errors);
+    }
+
+    if let Some(roles) = &guild.trusted_roles {
+        if roles.is_empty() {
+            errors.push("trusted_roles is specified but is empty; omit the key.".to_string());
+        }
+    }
+
+    if let Some(bots) = &guild.bot_allowlist {
+        if bots.is_empty() {
+            errors.push("bot_allowlist is specified but is empty; omit the key.".to_string());
+        }
+    }
+
+    if let Some(notifications) = &guild.notifications {
+        if let Some(roles) = &notifications.ping_roles {
+            if roles.is_empty() {
+                errors.push(
+                    "notification settings, ping_roles is specified but is empty; omit the key."
+                        .to_string(),
+                );
+            }
+        }
+    }
+
+    if let Some(spam) = &guild.spam {
+        let first_error = errors.len();
+        validate_spam(spam, has_default_actions, &mut errors);
+        attribute_errors(
+            &mut errors[first_error..],
+            guild.include_sources.spam.as_ref(),
+        );
+    }
+
+    if let Some(reaction_spam) = &guild.reaction_spam {
+        if let Some(scoping) = reaction_spam.scoping.as_ref() {
+            validate_scoping(scoping, "reaction spam scoping", &mut errors);
+        }
+
+        if let Some(actions) = &reaction_spam.actions {
+            if actions.is_empty() {
+                errors.push(
+                    "in reaction spam config, actions is specified but is empty.".to_string(),
+                );
+            }
+
+            validate_actions(actions, "reaction spam config", &mut errors);
+        } else if !has_default_actions {
+            errors.push("in reaction spam config, no actions are specified and there are no default actions for this guild.".to_string());
+        }
+
+        if reaction_spam.reactions_per_interval == 0 {
+            errors.push(
+                "in reaction spam config, reactions_per_interval is 0; every reaction would be considered spam."
+                    .to_string(),
+            );
+        }
+
+        if reaction_spam.interval == 0 {
+            errors.push(
+                "in reaction spam config, interval is 0; no reaction spam would be detected."
+                    .to_string(),
+            );
+        }
+    }
+
+    if let Some(flood) = &guild.flood {
+        if let Some(scoping) = flood.scoping.as_ref() {
+            validate_scoping(scoping, "flood scoping", &mut errors);
+        }
+
+        if flood.messages == 0 {
+            errors.push(
+                "in flood config, messages is 0; every channel would always be flooded."
+                    .to_string(),
+            );
+        }
+
+        if flood.interval == 0 {
+            errors.push("in flood config, interval is 0; no floods would be detected.".to_string());
+        }
+
+        if flood.slowmode_seconds == 0 || flood.slowmode_seconds > MAX_SLOWMODE_SECONDS {
+            errors.push(format!(
+                "in flood config, slowmode_seconds is {}, but must be between 1 and {}.",
+                flood.slowmode_seconds, MAX_SLOWMODE_SECONDS
+            ));
+        }
+
+        if flood.notify && guild.notifications.is_none() {
+            errors.push(
+                "in flood config, notify is true but there is no notifications config for this guild."
+                    .to_string(),
+            );
         }
     }
 
@@ -474,11 +1895,18 @@ pub fn validate_guild_config(guild: &GuildConfig) -> Result<(), Vec<String>> {
         }
 
         for (i, filter) in messages.iter().enumerate() {
+            let first_error = errors.len();
             match &filter.actions {
+                Some(_) if filter.exempt => {
+                    errors.push(format!("message filter {} is an exempt filter but specifies actions; exempt filters never take actions", i));
+                }
+                None if filter.exempt => {}
                 Some(actions) => {
                     if actions.is_empty() {
                         errors.push(format!("message filter {} has an empty actions array; omit the key to use default actions", i));
                     }
+
+                    validate_actions(actions, &format!("message filter {}", i), &mut errors);
                 }
                 None => {
                     if !has_default_actions {
@@ -487,6 +1915,32 @@ pub fn validate_guild_config(guild: &GuildConfig) -> Result<(), Vec<String>> {
                 }
             }
 
+            if let Some(actions_by_tier) = &filter.actions_by_tier {
+                if filter.exempt {
+                    errors.push(format!("message filter {} is an exempt filter but specifies actions_by_tier; exempt filters never take actions", i));
+                }
+
+                for (tier_name, actions) in actions_by_tier {
+                    let tier_exists = filter.rules.iter().any(|rule| {
+                        matches!(rule, MessageFilterRule::Words { tiers, .. } if tiers.iter().any(|tier| &tier.name == tier_name))
+                    });
+
+                    if !tier_exists {
+                        errors.push(format!("message filter {} specifies actions for tier {}, but none of its words rules have that tier", i, tier_name));
+                    }
+
+                    if actions.is_empty() {
+                        errors.push(format!("message filter {} has an empty actions array for tier {}; omit the tier to use the filter's actions", i, tier_name));
+                    }
+
+                    validate_actions(
+                        actions,
+                        &format!("message filter {}, tier {}", i, tier_name),
+                        &mut errors,
+                    );
+                }
+            }
+
             if let Some(scoping) = &filter.scoping {
                 validate_scoping(scoping, &format!("message filter {}", i), &mut errors);
             }
@@ -502,6 +1956,11 @@ pub fn validate_guild_config(guild: &GuildConfig) -> Result<(), Vec<String>> {
                     );
                 }
             }
+
+            attribute_errors(
+                &mut errors[first_error..],
+                guild.include_sources.messages.get(&filter.name),
+            );
         }
     }
 
@@ -514,11 +1973,14 @@ pub fn validate_guild_config(guild: &GuildConfig) -> Result<(), Vec<String>> {
         }
 
         for (i, filter) in reactions.iter().enumerate() {
+            let first_error = errors.len();
             match &filter.actions {
                 Some(actions) => {
                     if actions.is_empty() {
                         errors.push(format!("reaction filter {} has an empty actions array; omit the key to use default actions", i));
                     }
+
+                    validate_actions(actions, &format!("reaction filter {}", i), &mut errors);
                 }
                 None => {
                     if !has_default_actions {
@@ -534,6 +1996,11 @@ pub fn validate_guild_config(guild: &GuildConfig) -> Result<(), Vec<String>> {
             if filter.rules.is_empty() {
                 errors.push(format!("reaction filter {} has no rules", i));
             }
+
+            attribute_errors(
+                &mut errors[first_error..],
+                guild.include_sources.reactions.get(&filter.name),
+            );
         }
     }
 
@@ -550,92 +2017,729 @@ pub enum LoadConfigError {
     Io(#[from] std::io::Error),
     #[error("Deserialization error: {0:?}")]
     Deserialize(#[from] serde_yaml::Error),
+    #[error("Deserialization error: {0:?}")]
+    DeserializeJson(#[from] serde_json::Error),
+    #[error("Deserialization error: {0:?}")]
+    DeserializeToml(#[from] toml::de::Error),
+    #[error("Unsupported configuration format for {0:?}; guild configurations must end in .yml, .yaml, .json or .toml")]
+    UnsupportedFormat(PathBuf),
     #[error("Configuration validation error: {0:?}")]
     Validate(Vec<String>),
 }
 
-pub fn load_config(config_root: &Path, guild_id: Id<GuildMarker>) -> Result<GuildConfig> {
-    let mut config_path = config_root.join(guild_id.to_string());
-    config_path.set_extension("yml");
+/// The formats guild configuration files can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ConfigFormat {
+    Yaml,
+    Json,
+    Toml,
+}
+
+/// The extensions a guild's configuration file is looked up with, in order of
+/// priority.
+const GUILD_CONFIG_EXTENSIONS: [(&str, ConfigFormat); 4] = [
+    ("yml", ConfigFormat::Yaml),
+    ("yaml", ConfigFormat::Yaml),
+    ("json", ConfigFormat::Json),
+    ("toml", ConfigFormat::Toml),
+];
+
+impl ConfigFormat {
+    /// The format of a configuration file, based on its extension.
+    pub(crate) fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        GUILD_CONFIG_EXTENSIONS
+            .iter()
+            .find(|(candidate, _)| extension.eq_ignore_ascii_case(candidate))
+            .map(|(_, format)| *format)
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            ConfigFormat::Yaml => "YAML",
+            ConfigFormat::Json => "JSON",
+            ConfigFormat::Toml => "TOML",
+        }
+    }
+
+    /// Deserializes a guild configuration, along with the keys in it that
+    /// don't match any field.
+    fn deserialize(
+        self,
+        config_string: &str,
+    ) -> Result<(GuildConfig, Vec<UnknownKey>), LoadConfigError> {
+        let fields = guild_config_fields();
+        match self {
+            ConfigFormat::Yaml => Ok(unknown_keys::deserialize(
+                serde_yaml::Deserializer::from_str(config_string),
+                &fields,
+            )?),
+            ConfigFormat::Json => {
+                let mut de = serde_json::Deserializer::from_str(config_string);
+                let config = unknown_keys::deserialize(&mut de, &fields)?;
+                de.end()?;
+                Ok(config)
+            }
+            ConfigFormat::Toml => Ok(unknown_keys::deserialize(
+                &mut toml::Deserializer::new(config_string),
+                &fields,
+            )?),
+        }
+    }
+}
+
+/// The fields of every struct in a guild configuration, which unknown keys are
+/// checked against for typos. Rules and actions aren't included, since their
+/// unknown fields are already rejected while deserializing.
+fn guild_config_fields() -> Vec<&'static str> {
+    [
+        unknown_keys::struct_fields::<GuildConfig>(),
+        unknown_keys::struct_fields::<Notifications>(),
+        unknown_keys::struct_fields::<SlashCommands>(),
+        unknown_keys::struct_fields::<Scoping>(),
+        unknown_keys::struct_fields::<MessageFilter>(),
+        unknown_keys::struct_fields::<ReactionFilter>(),
+        unknown_keys::struct_fields::<SpamFilter>(),
+        unknown_keys::struct_fields::<RaidFilter>(),
+        unknown_keys::struct_fields::<ReactionSpamFilter>(),
+        unknown_keys::struct_fields::<FloodFilter>(),
+        unknown_keys::struct_fields::<UsernameFilter>(),
+    ]
+    .concat()
+}
+
+/// Where a guild's configuration file is kept. This is the first file named
+/// after the guild with one of [`GUILD_CONFIG_EXTENSIONS`] that exists, or the
+/// `.yml` file if none do.
+pub(crate) fn guild_config_path(config_root: &Path, guild_id: Id<GuildMarker>) -> PathBuf {
+    let config_path = config_root.join(guild_id.to_string());
+    GUILD_CONFIG_EXTENSIONS
+        .iter()
+        .map(|(extension, _)| config_path.with_extension(extension))
+        .find(|candidate| candidate.is_file())
+        .unwrap_or_else(|| config_path.with_extension(GUILD_CONFIG_EXTENSIONS[0].0))
+}
+
+/// Reads, deserializes and validates the guild configuration at `path`,
+/// picking the format based on its extension. Guilds without their own spam
+/// filter get `default_spam`.
+fn load_config_file(path: &Path, default_spam: Option<&SpamFilter>) -> Result<GuildConfig> {
+    let format = ConfigFormat::from_path(path)
+        .ok_or_else(|| LoadConfigError::UnsupportedFormat(path.to_owned()))?;
+    let mut config = read_config_file(path, format)?;
+    let canonical_path = path
+        .canonicalize()
+        .wrap_err(format!("Unable to read {:?}", path))?;
+    merge_includes(&mut config, path, &mut vec![canonical_path])?;
+
+    if config.spam.is_none() {
+        config.spam = default_spam.cloned();
+    }
+
+    apply_normalize_leetspeak(&mut config);
+
+    match resolve_action_templates(&mut config).and_then(|()| validate_guild_config(&config)) {
+        Ok(()) => Ok(config),
+        Err(errs) => {
+            let err: eyre::Report = LoadConfigError::Validate(errs).into();
+            Err(err.wrap_err(format!("Unable to validate {:?} ({})", path, format.name())))
+        }
+    }
+}
+
+/// Tells the `words` and `substring` rules in a guild's message filters
+/// whether to undo leetspeak, which is configured for the whole guild.
+fn apply_normalize_leetspeak(guild: &mut GuildConfig) {
+    fn apply(rule: &mut MessageFilterRule, normalize: bool) {
+        match rule {
+            MessageFilterRule::Words { leetspeak, .. }
+            | MessageFilterRule::Substring { leetspeak, .. } => *leetspeak = normalize,
+            MessageFilterRule::All { rules } | MessageFilterRule::Embeds { rules } => {
+                for rule in rules {
+                    apply(rule, normalize);
+                }
+            }
+            MessageFilterRule::Not { rule } => apply(rule, normalize),
+            _ => {}
+        }
+    }
+
+    for filter in guild.messages.iter_mut().flatten() {
+        for rule in &mut filter.rules {
+            apply(rule, guild.normalize_leetspeak);
+        }
+    }
+}
+
+/// Whether `name` can be used as an environment variable in a configuration.
+fn is_env_var_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Replaces `${NAME}` in `text` with the value `lookup` gives for `NAME`, and
+/// `$$` with a literal `$`. Any other `$` is left alone, so that action
+/// placeholders like `$USER_ID` keep working. Returns an error for each
+/// variable that isn't set.
+pub(crate) fn interpolate_env_vars<F>(text: &str, lookup: F) -> Result<String, Vec<String>>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut interpolated = String::with_capacity(text.len());
+    let mut errors = Vec::new();
+    let mut rest = text;
+
+    while let Some(index) = rest.find('$') {
+        interpolated.push_str(&rest[..index]);
+        rest = &rest[index..];
+
+        if let Some(after) = rest.strip_prefix("$$") {
+            interpolated.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            match after
+                .find('}')
+                .map(|end| (&after[..end], &after[end + 1..]))
+            {
+                Some((name, after)) if is_env_var_name(name) => {
+                    match lookup(name) {
+                        Some(value) => interpolated.push_str(&value),
+                        None => {
+                            let error = format!("environment variable {} is not set", name);
+                            if !errors.contains(&error) {
+                                errors.push(error);
+                            }
+                        }
+                    }
+
+                    rest = after;
+                }
+                _ => {
+                    let reference: String = rest.chars().take(20).collect();
+                    errors.push(format!(
+                        "`{}` is not a valid environment variable reference; write `$${{` for a literal `${{`",
+                        reference
+                    ));
+                    interpolated.push_str("${");
+                    rest = after;
+                }
+            }
+        } else {
+            interpolated.push('$');
+            rest = &rest[1..];
+        }
+    }
+
+    interpolated.push_str(rest);
+    if errors.is_empty() {
+        Ok(interpolated)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Interpolates environment variables into a configuration file's contents.
+/// See [`interpolate_env_vars`].
+pub fn interpolate_env(text: &str) -> Result<String, LoadConfigError> {
+    interpolate_env_vars(text, |name| std::env::var(name).ok()).map_err(LoadConfigError::Validate)
+}
+
+/// Reads and deserializes a guild configuration, without validating it.
+/// Environment variables are interpolated first.
+fn read_config_file(path: &Path, format: ConfigFormat) -> Result<GuildConfig> {
+    let config_string =
+        std::fs::read_to_string(path).wrap_err(format!("Unable to read {:?}", path))?;
+    let config_string = interpolate_env(&config_string).wrap_err(format!(
+        "Unable to interpolate environment variables into {:?}",
+        path
+    ))?;
+    let (mut config, unknown_keys) = format.deserialize(&config_string).wrap_err(format!(
+        "Unable to deserialize {:?} as {}",
+        path,
+        format.name()
+    ))?;
+    config.unknown_keys = unknown_keys.iter().map(ToString::to_string).collect();
+    Ok(config)
+}
+
+/// Merges the files `config` includes beneath it, recursively. `path` is where
+/// `config` was read from, and `including` is the canonical paths of the files
+/// that are being included at the moment, starting with the guild's own file,
+/// so that cycles can be rejected.
+///
+/// Message filters, reaction filters, and action templates are added unless
+/// `config` already has one with the same name, and lists are concatenated in
+/// include order after `config`'s own. The spam filter is only used if `config`
+/// doesn't have one.
+fn merge_includes(
+    config: &mut GuildConfig,
+    path: &Path,
+    including: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+
+    for include in std::mem::take(&mut config.include) {
+        let include_path = base.join(&include);
+        let format = ConfigFormat::from_path(&include_path)
+            .ok_or_else(|| LoadConfigError::UnsupportedFormat(include_path.clone()))?;
+        let canonical_path = include_path.canonicalize().wrap_err(format!(
+            "Unable to read {:?}, included from {:?}",
+            include_path, path
+        ))?;
+
+        if including.contains(&canonical_path) {
+            let mut cycle: Vec<_> = including
+                .iter()
+                .skip_while(|included| **included != canonical_path)
+                .map(|included| format!("{:?}", included))
+                .collect();
+            cycle.push(format!("{:?}", canonical_path));
+            return Err(eyre::eyre!("Include cycle: {}", cycle.join(" -> ")));
+        }
+
+        let mut fragment = read_config_file(&include_path, format)?;
+        attribute_errors(&mut fragment.unknown_keys, Some(&include_path));
+        including.push(canonical_path);
+        merge_includes(&mut fragment, &include_path, including)?;
+        including.pop();
+
+        config.unknown_keys.append(&mut fragment.unknown_keys);
+        let sources = &mut config.include_sources;
+        let mut fragment_sources = fragment.include_sources;
+        for filter in fragment.messages.into_iter().flatten() {
+            let messages = config.messages.get_or_insert_with(Vec::new);
+            if messages.iter().all(|existing| existing.name != filter.name) {
+                let source = fragment_sources
+                    .messages
+                    .remove(&filter.name)
+                    .unwrap_or_else(|| include_path.clone());
+                sources.messages.insert(filter.name.clone(), source);
+                messages.push(filter);
+            }
+        }
+
+        for filter in fragment.reactions.into_iter().flatten() {
+            let reactions = config.reactions.get_or_insert_with(Vec::new);
+            if reactions
+                .iter()
+                .all(|existing| existing.name != filter.name)
+            {
+                let source = fragment_sources
+                    .reactions
+                    .remove(&filter.name)
+                    .unwrap_or_else(|| include_path.clone());
+                sources.reactions.insert(filter.name.clone(), source);
+                reactions.push(filter);
+            }
+        }
+
+        for (name, actions) in fragment.action_templates {
+            if let Entry::Vacant(entry) = config.action_templates.entry(name) {
+                let source = fragment_sources
+                    .action_templates
+                    .remove(entry.key())
+                    .unwrap_or_else(|| include_path.clone());
+                sources.action_templates.insert(entry.key().clone(), source);
+                entry.insert(actions);
+            }
+        }
+
+        if config.spam.is_none() && fragment.spam.is_some() {
+            config.spam = fragment.spam;
+            sources.spam = Some(
+                fragment_sources
+                    .spam
+                    .unwrap_or_else(|| include_path.clone()),
+            );
+        }
+
+        sources.files.push(include_path);
+        sources.files.append(&mut fragment_sources.files);
+    }
+
+    Ok(())
+}
+
+pub fn load_config(
+    config_root: &Path,
+    guild_id: Id<GuildMarker>,
+    default_spam: Option<&SpamFilter>,
+) -> Result<GuildConfig> {
+    load_config_file(&guild_config_path(config_root, guild_id), default_spam)
+}
+
+/// A guild whose configuration failed to load, and why.
+pub type GuildConfigError = (Id<GuildMarker>, eyre::Report);
+
+/// Loads each guild's configuration independently, so that one invalid
+/// configuration doesn't prevent the others from loading. The errors for
+/// guilds that failed to load are returned alongside the configurations that
+/// loaded successfully.
+pub fn load_guild_configs_independently(
+    config_root: &Path,
+    guild_ids: &[Id<GuildMarker>],
+    default_spam: Option<&SpamFilter>,
+) -> (HashMap<Id<GuildMarker>, GuildConfig>, Vec<GuildConfigError>) {
+    let mut configs = HashMap::new();
+    let mut errors = Vec::new();
+
+    for guild_id in guild_ids {
+        let guild_id = *guild_id;
+
+        let result = load_config(config_root, guild_id, default_spam).wrap_err(format!(
+            "Unable to load configuration for guild {}",
+            guild_id
+        ));
+
+        match result {
+            Ok(guild_config) => {
+                configs.insert(guild_id, guild_config);
+            }
+            Err(err) => errors.push((guild_id, err)),
+        }
+    }
+
+    (configs, errors)
+}
+
+/// A configuration file that failed to load, and why.
+pub type ConfigFileError = (PathBuf, eyre::Report);
+
+/// The outcome of loading every configuration file in a directory.
+#[derive(Debug, Default)]
+pub struct ConfigReport {
+    /// How many files were loaded.
+    pub files: usize,
+    /// The files that failed to load, in order of their paths.
+    pub errors: Vec<ConfigFileError>,
+}
+
+impl ConfigReport {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Splits an error loading a configuration file into the lines it's reported
+/// as: one per validation error, or the whole chain of causes otherwise.
+fn config_error_lines(err: &eyre::Report) -> Vec<String> {
+    match err.downcast_ref::<LoadConfigError>() {
+        Some(LoadConfigError::Validate(errs)) => errs.clone(),
+        _ => vec![err
+            .chain()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(": ")],
+    }
+}
+
+impl std::fmt::Display for ConfigReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (path, err) in &self.errors {
+            writeln!(f, "{}:", path.display())?;
+            for line in config_error_lines(err) {
+                writeln!(f, "  - {}", line)?;
+            }
+        }
+
+        write!(
+            f,
+            "{} {}, {} with errors",
+            self.files,
+            if self.files == 1 { "file" } else { "files" },
+            self.errors.len()
+        )
+    }
+}
+
+/// Loads every configuration file in `config_root`, carrying on past files
+/// that fail so that they can all be reported at once. Only failing to read
+/// `config_root` itself is an error.
+pub fn load_all_guild_configs(config_root: &Path) -> Result<ConfigReport> {
+    let mut report = ConfigReport::default();
+    let mut paths = Vec::new();
+
+    for entry in std::fs::read_dir(config_root)? {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                report.errors.push((config_root.to_owned(), err.into()));
+                continue;
+            }
+        };
+
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_file() => paths.push(entry.path()),
+            Ok(_) => {}
+            Err(err) => report.errors.push((entry.path(), err.into())),
+        }
+    }
+
+    paths.sort();
+    report.files = paths.len();
+    for path in paths {
+        if let Err(err) = load_config_file(&path, None) {
+            report.errors.push((path, err));
+        }
+    }
+
+    report.errors.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deserialize_word_regex() {
+        let json = r#"
+        {
+            "type": "words",
+            "words": ["a", "b", "a(b)"]
+        }
+        "#;
+
+        let rule: MessageFilterRule =
+            serde_yaml::from_str(json).expect("couldn't deserialize MessageFilterRule");
+
+        if let MessageFilterRule::Words { words, .. } = rule {
+            assert_eq!(words.unwrap().to_string(), "\\b(a|b|a\\(b\\))\\b");
+        } else {
+            panic!("deserialized wrong filter");
+        }
+    }
+
+    #[test]
+    fn deserialize_word_tiers() {
+        let yml = r#"
+        type: words
+        tiers:
+          severe: [c]
+          mild: [a, b]
+        "#;
+
+        let rule: MessageFilterRule =
+            serde_yaml::from_str(yml).expect("couldn't deserialize MessageFilterRule");
+
+        if let MessageFilterRule::Words { words, tiers, .. } = rule {
+            assert!(words.is_none());
+            assert_eq!(
+                tiers
+                    .iter()
+                    .map(|tier| (tier.name.as_str(), tier.words.to_string()))
+                    .collect::<Vec<_>>(),
+                vec![
+                    ("severe", "\\b(c)\\b".to_string()),
+                    ("mild", "\\b(a|b)\\b".to_string())
+                ]
+            );
+        } else {
+            panic!("deserialized wrong filter");
+        }
+    }
+
+    #[test]
+    fn deserialize_regex_descriptions() {
+        let yml = r#"
+        type: regex
+        regexes:
+          - a+
+          - pattern: b+
+            description: lots of b
+        "#;
+
+        let rule: MessageFilterRule =
+            serde_yaml::from_str(yml).expect("couldn't deserialize MessageFilterRule");
+
+        if let MessageFilterRule::Regex { regexes, .. } = rule {
+            assert_eq!(
+                regexes
+                    .regexes
+                    .iter()
+                    .map(|regex| regex.as_str())
+                    .collect::<Vec<_>>(),
+                vec!["a+", "b+"]
+            );
+            assert_eq!(
+                regexes.descriptions,
+                vec![None, Some("lots of b".to_string())]
+            );
+        } else {
+            panic!("deserialized wrong filter");
+        }
+    }
+
+    #[test]
+    fn validate_encoded_blob() {
+        let yml = r#"
+        type: encoded_blob
+        min_length: 0
+        min_entropy: 7
+        "#;
+
+        let rule: MessageFilterRule =
+            serde_yaml::from_str(yml).expect("couldn't deserialize MessageFilterRule");
+        let mut errors = vec![];
+        super::validate_message_rule(&rule, "rule", &mut errors);
+        assert_eq!(
+            errors,
+            vec![
+                "in rule, encoded_blob min_length is 0; this would match every message",
+                "in rule, encoded_blob min_entropy is 7, but must be greater than 0 and at most 6",
+            ]
+        );
+    }
+
+    #[test]
+    fn apply_normalize_leetspeak() {
+        let yml = r#"
+        normalize_leetspeak: true
+        messages:
+          - name: first
+            rules:
+              - type: words
+                words: [bad]
+              - type: not
+                rule:
+                  type: substring
+                  substrings: [good]
+        "#;
 
-    let config_string = std::fs::read_to_string(&config_path)
-        .wrap_err(format!("Unable to read {:?}", config_path))?;
-    let config_yaml = serde_yaml::from_str(&config_string)?;
+        let mut guild: GuildConfig =
+            serde_yaml::from_str(yml).expect("couldn't deserialize GuildConfig");
+        super::apply_normalize_leetspeak(&mut guild);
 
-    match validate_guild_config(&config_yaml) {
-        Ok(()) => Ok(config_yaml),
-        Err(errs) => Err(LoadConfigError::Validate(errs).into()),
+        let rules = &guild.messages.as_ref().unwrap()[0].rules;
+        assert!(matches!(
+            rules[0],
+            MessageFilterRule::Words {
+                leetspeak: true,
+                ..
+            }
+        ));
+        assert!(matches!(
+            &rules[1],
+            MessageFilterRule::Not { rule } if matches!(**rule, MessageFilterRule::Substring { leetspeak: true, .. })
+        ));
     }
-}
-
-pub fn load_guild_configs(
-    config_root: &Path,
-    guild_ids: &[Id<GuildMarker>],
-) -> Result<HashMap<Id<GuildMarker>, GuildConfig>, (Id<GuildMarker>, eyre::Report)> {
-    let mut configs = HashMap::new();
 
-    for guild_id in guild_ids {
-        let guild_id = *guild_id;
-
-        let guild_config = load_config(config_root, guild_id)
-            .wrap_err(format!(
-                "Unable to load configuration for guild {}",
-                guild_id
-            ))
-            .map_err(|e| (guild_id, e))?;
-        configs.insert(guild_id, guild_config);
+    fn test_env(name: &str) -> Option<String> {
+        match name {
+            "LOG_CHANNEL" => Some("1234".to_owned()),
+            "BAD_WORD" => Some("a$b".to_owned()),
+            "PREFIX" => Some("nitro".to_owned()),
+            _ => None,
+        }
     }
 
-    Ok(configs)
-}
+    #[test]
+    fn interpolate_env_into_ids() {
+        let yml = r#"
+        default_actions:
+          - action: send_log
+            channel_id: "${LOG_CHANNEL}"
+          - action: send_message
+            channel_id: "${LOG_CHANNEL}"
+            content: "$USER_ID owes $$5"
+            requires_armed: false
+        "#;
 
-pub fn load_all_guild_configs(config_root: &Path) -> Result<()> {
-    for entry in std::fs::read_dir(config_root)? {
-        let entry = entry?;
-        if entry.file_type()?.is_file() {
-            let path = entry.path();
-            let config_string =
-                std::fs::read_to_string(&path).wrap_err(format!("Unable to read {:?}", path))?;
-            let config_yaml = serde_yaml::from_str(&config_string)
-                .wrap_err(format!("Unable to deserialize {:?}", path))?;
-
-            match validate_guild_config(&config_yaml) {
-                Ok(()) => {}
-                Err(errs) => {
-                    let err = LoadConfigError::Validate(errs);
-                    let err: eyre::Report = err.into();
-                    return Err(err.wrap_err(format!("Unable to validate {:?}", path)));
-                }
+        let yml = interpolate_env_vars(yml, test_env).expect("couldn't interpolate");
+        let guild: GuildConfig =
+            serde_yaml::from_str(&yml).expect("couldn't deserialize GuildConfig");
+
+        let actions = guild.default_actions.unwrap();
+        assert!(matches!(
+            actions[0],
+            MessageFilterAction::SendLog { channel_id, .. } if channel_id == Id::new(1234)
+        ));
+        match &actions[1] {
+            MessageFilterAction::SendMessage {
+                channel_id,
+                content,
+                ..
+            } => {
+                assert_eq!(*channel_id, Id::new(1234));
+                // Action placeholders aren't environment variables.
+                assert_eq!(content, "$USER_ID owes $5");
             }
+            action => panic!("expected send_message, got {:?}", action),
         }
     }
 
-    Ok(())
-}
-
-#[cfg(test)]
-mod test {
-    use super::*;
-
     #[test]
-    fn deserialize_word_regex() {
-        let json = r#"
-        {
-            "type": "words",
-            "words": ["a", "b", "a(b)"]
-        }
+    fn interpolate_env_into_regexes() {
+        let yml = r#"
+        messages:
+          - name: words
+            rules:
+              - type: words
+                words: ["${BAD_WORD}", "US$$"]
+              - type: regex
+                regexes: ["^${PREFIX}$$"]
         "#;
 
-        let rule: MessageFilterRule =
-            serde_yaml::from_str(&json).expect("couldn't deserialize MessageFilterRule");
+        let yml = interpolate_env_vars(yml, test_env).expect("couldn't interpolate");
+        let guild: GuildConfig =
+            serde_yaml::from_str(&yml).expect("couldn't deserialize GuildConfig");
 
-        if let MessageFilterRule::Words { words } = rule {
-            assert_eq!(words.to_string(), "\\b(a|b|a\\(b\\))\\b");
-        } else {
-            assert!(false, "deserialized wrong filter");
+        let rules = &guild.messages.as_ref().unwrap()[0].rules;
+        // Word lists are escaped, so `$` from a variable or an escape is a
+        // literal dollar sign.
+        match &rules[0] {
+            MessageFilterRule::Words { words, .. } => {
+                assert_eq!(words.as_ref().unwrap().as_str(), "\\b(a\\$b|US\\$)\\b")
+            }
+            rule => panic!("expected words, got {:?}", rule),
+        }
+        // In regexes, an escaped `$` is an anchor.
+        match &rules[1] {
+            MessageFilterRule::Regex { regexes, .. } => {
+                assert!(regexes.find("nitro").is_some());
+                assert!(regexes.find("free nitro").is_none());
+                assert!(regexes.find("nitro please").is_none());
+            }
+            rule => panic!("expected regex, got {:?}", rule),
         }
     }
 
+    #[test]
+    fn report_unset_env_vars() {
+        assert_eq!(
+            interpolate_env_vars("a: ${MISSING}\nb: ${MISSING}\nc: ${ALSO_MISSING}", test_env),
+            Err(vec![
+                "environment variable MISSING is not set".to_owned(),
+                "environment variable ALSO_MISSING is not set".to_owned(),
+            ])
+        );
+        assert_eq!(
+            interpolate_env_vars("a: ${not valid}", test_env),
+            Err(vec![
+                "`${not valid}` is not a valid environment variable reference; write `$${` for a literal `${`".to_owned()
+            ])
+        );
+        assert_eq!(
+            interpolate_env_vars("costs $$5, ${", test_env),
+            Err(vec![
+                "`${` is not a valid environment variable reference; write `$${` for a literal `${`".to_owned()
+            ])
+        );
+    }
+
+    #[test]
+    fn validate_no_repost() {
+        let rule: MessageFilterRule = serde_yaml::from_str("type: no_repost\nwindow_seconds: 0")
+            .expect("couldn't deserialize MessageFilterRule");
+        let mut errors = vec![];
+        super::validate_message_rule(&rule, "rule", &mut errors);
+        assert_eq!(
+            errors,
+            vec!["in rule, no_repost window_seconds is 0; this would match no messages"]
+        );
+    }
+
     #[test]
     fn validate_catches_empty_regex() {
         let yml = r#"
@@ -644,7 +2748,7 @@ mod test {
         "#;
 
         let rule: MessageFilterRule =
-            serde_yaml::from_str(&yml).expect("couldn't deserialize MessageFilterRule");
+            serde_yaml::from_str(yml).expect("couldn't deserialize MessageFilterRule");
         let mut errors = vec![];
         super::validate_message_rule(&rule, "rule", &mut errors);
         assert_eq!(
@@ -658,7 +2762,7 @@ mod test {
         "#;
 
         let rule: MessageFilterRule =
-            serde_yaml::from_str(&yml).expect("couldn't deserialize MessageFilterRule");
+            serde_yaml::from_str(yml).expect("couldn't deserialize MessageFilterRule");
         let mut errors = vec![];
         super::validate_message_rule(&rule, "rule", &mut errors);
         assert_eq!(
@@ -672,7 +2776,7 @@ mod test {
         "#;
 
         let rule: MessageFilterRule =
-            serde_yaml::from_str(&yml).expect("couldn't deserialize MessageFilterRule");
+            serde_yaml::from_str(yml).expect("couldn't deserialize MessageFilterRule");
         let mut errors = vec![];
         super::validate_message_rule(&rule, "rule", &mut errors);
         assert_eq!(
@@ -680,4 +2784,778 @@ mod test {
             vec!["in rule, regex 0 matches an empty string; this would match all messages"]
         );
     }
+
+    #[test]
+    fn reject_zero_role_id() {
+        let yml = r#"
+        action: add_role
+        role_id: "1"
+        reason: $FILTER_REASON
+        "#;
+
+        let action: MessageFilterAction =
+            serde_yaml::from_str(yml).expect("couldn't deserialize MessageFilterAction");
+        assert!(matches!(
+            action,
+            MessageFilterAction::AddRole { role_id, .. } if role_id == Id::new(1)
+        ));
+
+        let yml = r#"
+        action: remove_role
+        role_id: "0"
+        reason: $FILTER_REASON
+        "#;
+
+        assert!(serde_yaml::from_str::<MessageFilterAction>(yml).is_err());
+    }
+
+    #[test]
+    fn deserialize_delayed_delete() {
+        let action: MessageFilterAction =
+            serde_yaml::from_str("action: delete").expect("couldn't deserialize delete action");
+        assert!(matches!(
+            action,
+            MessageFilterAction::Delete {
+                delay_seconds: None
+            }
+        ));
+
+        let yml = r#"
+        action: delete
+        delay_seconds: 30
+        "#;
+
+        let action: MessageFilterAction =
+            serde_yaml::from_str(yml).expect("couldn't deserialize delete action");
+        assert!(matches!(
+            action,
+            MessageFilterAction::Delete {
+                delay_seconds: Some(30)
+            }
+        ));
+    }
+
+    #[test]
+    fn validate_escalate_actions() {
+        let yml = r#"
+        - action: warn
+          expiry_seconds: 0
+        - action: escalate
+          thresholds:
+            - warnings: 3
+              actions:
+                - action: escalate
+                  thresholds: []
+            - warnings: 5
+              actions: []
+        "#;
+
+        let actions: Vec<MessageFilterAction> =
+            serde_yaml::from_str(yml).expect("couldn't deserialize actions");
+        let mut errors = vec![];
+        super::validate_actions(&actions, "filter", &mut errors);
+        assert_eq!(
+            errors,
+            vec![
+                "in filter, action 0 is a warning that expires immediately",
+                "in filter, action 1, threshold 3, escalate actions can't be nested",
+                "in filter, action 1, threshold 3, action 0 has no thresholds",
+                "in filter, action 1, threshold 5, actions is empty",
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_escalate_offenses_actions() {
+        let yml = r#"
+        - action: escalate_offenses
+          window_seconds: 0
+          tiers:
+            - offenses: 1
+              actions:
+                - action: escalate_offenses
+                  window_seconds: 60
+                  tiers: []
+            - offenses: 3
+              actions: []
+        - action: escalate_offenses
+          window_seconds: 86400
+          tiers:
+            - offenses: 2
+              actions:
+                - action: warn
+                  expiry_seconds: 86400
+        "#;
+
+        let actions: Vec<MessageFilterAction> =
+            serde_yaml::from_str(yml).expect("couldn't deserialize actions");
+        let mut errors = vec![];
+        super::validate_actions(&actions, "filter", &mut errors);
+        assert_eq!(
+            errors,
+            vec![
+                "in filter, there are 2 escalate_offenses actions; each one counts every match as an offense, so there can only be one",
+                "in filter, action 0 has a window of 0 seconds; offenses would never count",
+                "in filter, action 0, tier 1, escalate actions can't be nested",
+                "in filter, action 0, tier 1, action 0 has no tiers",
+                "in filter, action 0, tier 3, actions is empty",
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_timeout_durations() {
+        let yml = r#"
+        - action: timeout
+          reason: spam
+          duration: 2592000
+        - action: timeout
+          reason: spam
+          duration: 2419200
+        - action: escalating_timeout
+          reason: spam
+          durations: [600, 2592000]
+          window_seconds: 86400
+        "#;
+
+        let actions: Vec<MessageFilterAction> =
+            serde_yaml::from_str(yml).expect("couldn't deserialize actions");
+        let mut errors = vec![];
+        super::validate_actions(&actions, "filter", &mut errors);
+        assert_eq!(
+            errors,
+            vec![
+                "in filter, action 0 has a duration of 2592000 seconds, longer than Discord's maximum timeout of 28 days (2419200 seconds)",
+                "in filter, action 2 has a duration longer than Discord's maximum timeout of 28 days (2419200 seconds)",
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_slowmode_seconds() {
+        let yml = r#"
+        - action: set_slowmode
+          seconds: 21600
+        - action: set_slowmode
+          seconds: 21601
+          revert_after: 600
+        "#;
+
+        let actions: Vec<MessageFilterAction> =
+            serde_yaml::from_str(yml).expect("couldn't deserialize actions");
+        let mut errors = vec![];
+        super::validate_actions(&actions, "filter", &mut errors);
+        assert_eq!(
+            errors,
+            vec![
+                "in filter, action 1 has a slowmode of 21601 seconds, longer than Discord's maximum slowmode of 6 hours (21600 seconds)",
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_webhook_urls() {
+        let yml = r#"
+        - action: webhook
+          url: https://example.com/hooks/chrysanthemum
+        - action: webhook
+          url: example.com/hooks/chrysanthemum
+        "#;
+
+        let actions: Vec<MessageFilterAction> =
+            serde_yaml::from_str(yml).expect("couldn't deserialize actions");
+        let mut errors = vec![];
+        super::validate_actions(&actions, "filter", &mut errors);
+        assert_eq!(
+            errors,
+            vec![
+                "in filter, action 1 has webhook URL `example.com/hooks/chrysanthemum`, which is not a valid URL: relative URL without a base",
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_reaction_spam() {
+        let yml = r#"
+        reaction_spam:
+          reactions_per_interval: 0
+          interval: 0
+        "#;
+
+        let guild: GuildConfig =
+            serde_yaml::from_str(yml).expect("couldn't deserialize GuildConfig");
+        assert_eq!(
+            super::validate_guild_config(&guild),
+            Err(vec![
+                "in reaction spam config, no actions are specified and there are no default actions for this guild.".to_owned(),
+                "in reaction spam config, reactions_per_interval is 0; every reaction would be considered spam.".to_owned(),
+                "in reaction spam config, interval is 0; no reaction spam would be detected.".to_owned(),
+            ])
+        );
+    }
+
+    #[test]
+    fn validate_flood() {
+        let yml = r#"
+        flood:
+          messages: 0
+          interval: 0
+          slowmode_seconds: 30000
+          revert_after: 300
+        "#;
+
+        let guild: GuildConfig =
+            serde_yaml::from_str(yml).expect("couldn't deserialize GuildConfig");
+        assert!(guild.flood.as_ref().unwrap().notify);
+        assert_eq!(
+            super::validate_guild_config(&guild),
+            Err(vec![
+                "in flood config, messages is 0; every channel would always be flooded.".to_owned(),
+                "in flood config, interval is 0; no floods would be detected.".to_owned(),
+                "in flood config, slowmode_seconds is 30000, but must be between 1 and 21600.".to_owned(),
+                "in flood config, notify is true but there is no notifications config for this guild.".to_owned(),
+            ])
+        );
+    }
+
+    #[test]
+    fn resolve_action_templates() {
+        let yml = r#"
+        action_templates:
+          standard:
+            - action: delete
+            - action: template
+              name: log
+          log:
+            - action: send_log
+              channel_id: "1"
+        messages:
+          - name: first
+            rules:
+              - type: zalgo
+            actions:
+              - action: template
+                name: standard
+              - action: escalate
+                thresholds:
+                  - warnings: 2
+                    actions:
+                      - action: template
+                        name: log
+        "#;
+
+        let mut guild: GuildConfig =
+            serde_yaml::from_str(yml).expect("couldn't deserialize GuildConfig");
+        super::resolve_action_templates(&mut guild).expect("couldn't resolve templates");
+
+        let actions = guild.messages.as_ref().unwrap()[0]
+            .actions
+            .as_ref()
+            .unwrap();
+        assert_eq!(actions.len(), 3);
+        assert!(matches!(actions[0], MessageFilterAction::Delete { .. }));
+        assert!(matches!(actions[1], MessageFilterAction::SendLog { .. }));
+        match &actions[2] {
+            MessageFilterAction::Escalate { thresholds } => assert!(matches!(
+                thresholds[0].actions[..],
+                [MessageFilterAction::SendLog { .. }]
+            )),
+            action => panic!("expected escalate, got {:?}", action),
+        }
+        assert_eq!(guild.action_templates.len(), 2);
+    }
+
+    #[test]
+    fn reject_broken_action_templates() {
+        let yml = r#"
+        action_templates:
+          a:
+            - action: template
+              name: b
+          b:
+            - action: template
+              name: a
+        "#;
+
+        let mut guild: GuildConfig =
+            serde_yaml::from_str(yml).expect("couldn't deserialize GuildConfig");
+        assert_eq!(
+            super::resolve_action_templates(&mut guild),
+            Err(vec![
+                "in action template a, action template a includes itself (a -> b -> a)".to_owned(),
+                "in action template b, action template b includes itself (b -> a -> b)".to_owned(),
+            ])
+        );
+
+        let yml = r#"
+        default_actions:
+          - action: template
+            name: missing
+        "#;
+
+        let mut guild: GuildConfig =
+            serde_yaml::from_str(yml).expect("couldn't deserialize GuildConfig");
+        assert_eq!(
+            super::resolve_action_templates(&mut guild),
+            Err(vec![
+                "in default actions, there is no action template named missing".to_owned()
+            ])
+        );
+    }
+
+    #[test]
+    fn deserialize_trusted_roles() {
+        let yml = r#"
+        trusted_roles: ["1", "2"]
+        "#;
+
+        let guild: GuildConfig =
+            serde_yaml::from_str(yml).expect("couldn't deserialize GuildConfig");
+        assert_eq!(guild.trusted_roles, Some(vec![Id::new(1), Id::new(2)]));
+        assert!(guild.trusted_roles_skip_spam);
+        assert!(guild.is_trusted(&[Id::new(3), Id::new(2)]));
+        assert!(!guild.is_trusted(&[Id::new(3)]));
+
+        let yml = r#"
+        trusted_roles_skip_spam: false
+        "#;
+
+        let guild: GuildConfig =
+            serde_yaml::from_str(yml).expect("couldn't deserialize GuildConfig");
+        assert!(!guild.trusted_roles_skip_spam);
+        assert!(!guild.is_trusted(&[Id::new(1)]));
+    }
+
+    #[test]
+    fn deserialize_filter_webhooks() {
+        let guild: GuildConfig =
+            serde_yaml::from_str("include_bots: true").expect("couldn't deserialize GuildConfig");
+        assert!(!guild.filter_webhooks);
+        assert!(guild.filters_author(true, false));
+        assert!(!guild.filters_author(true, true));
+
+        let guild: GuildConfig = serde_yaml::from_str("filter_webhooks: true")
+            .expect("couldn't deserialize GuildConfig");
+        assert!(guild.filters_author(false, false));
+        assert!(!guild.filters_author(true, false));
+        assert!(guild.filters_author(true, true));
+    }
+
+    #[test]
+    fn deserialize_bot_allowlist() {
+        let yml = r#"
+        include_bots: true
+        bot_allowlist: ["1"]
+        "#;
+
+        let guild: GuildConfig =
+            serde_yaml::from_str(yml).expect("couldn't deserialize GuildConfig");
+        assert!(guild.is_allowlisted_bot(Id::new(1)));
+        assert!(!guild.is_allowlisted_bot(Id::new(2)));
+
+        let guild: GuildConfig =
+            serde_yaml::from_str("bot_allowlist: []").expect("couldn't deserialize GuildConfig");
+        assert_eq!(
+            super::validate_guild_config(&guild),
+            Err(vec![
+                "bot_allowlist is specified but is empty; omit the key.".to_owned()
+            ])
+        );
+    }
+
+    #[test]
+    fn load_guild_configs_independently_keeps_valid_configs() {
+        let config_root =
+            std::env::temp_dir().join(format!("chrysanthemum-load-test-{}", std::process::id()));
+        std::fs::create_dir_all(&config_root).unwrap();
+        std::fs::write(config_root.join("1.yml"), "include_bots: true").unwrap();
+        std::fs::write(config_root.join("2.yml"), "include_bots: [").unwrap();
+
+        let (configs, errors) = load_guild_configs_independently(
+            &config_root,
+            &[Id::new(1), Id::new(2), Id::new(3)],
+            None,
+        );
+        std::fs::remove_dir_all(&config_root).unwrap();
+
+        assert_eq!(configs.len(), 1);
+        assert!(configs[&Id::new(1)].include_bots);
+        assert_eq!(
+            errors
+                .iter()
+                .map(|(guild_id, _)| *guild_id)
+                .collect::<Vec<_>>(),
+            vec![Id::new(2), Id::new(3)]
+        );
+    }
+
+    const YAML_FIXTURE: &str = r#"
+include_bots: true
+messages:
+  - name: Invites
+    rules:
+      - type: invite
+        mode: deny
+    actions:
+      - action: delete
+"#;
+
+    const JSON_FIXTURE: &str = r#"{
+    "include_bots": true,
+    "messages": [
+        {
+            "name": "Invites",
+            "rules": [{ "type": "invite", "mode": "deny" }],
+            "actions": [{ "action": "delete" }]
+        }
+    ]
+}"#;
+
+    const TOML_FIXTURE: &str = r#"
+include_bots = true
+
+[[messages]]
+name = "Invites"
+rules = [{ type = "invite", mode = "deny" }]
+actions = [{ action = "delete" }]
+"#;
+
+    fn fixture_root(name: &str) -> PathBuf {
+        let config_root = std::env::temp_dir().join(format!(
+            "chrysanthemum-{}-test-{}",
+            name,
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&config_root).unwrap();
+        config_root
+    }
+
+    #[test]
+    fn load_guild_configs_in_each_format() {
+        let config_root = fixture_root("formats");
+        std::fs::write(config_root.join("1.yml"), YAML_FIXTURE).unwrap();
+        std::fs::write(config_root.join("2.yaml"), YAML_FIXTURE).unwrap();
+        std::fs::write(config_root.join("3.json"), JSON_FIXTURE).unwrap();
+        std::fs::write(config_root.join("4.toml"), TOML_FIXTURE).unwrap();
+
+        let (configs, errors) = load_guild_configs_independently(
+            &config_root,
+            &[Id::new(1), Id::new(2), Id::new(3), Id::new(4)],
+            None,
+        );
+        let all_valid = load_all_guild_configs(&config_root).unwrap();
+        std::fs::remove_dir_all(&config_root).unwrap();
+
+        assert!(errors.is_empty());
+        assert!(all_valid.is_ok());
+        assert_eq!(all_valid.files, 4);
+        for guild_id in 1..=4 {
+            let config = &configs[&Id::new(guild_id)];
+            assert!(config.include_bots);
+            assert_eq!(config.messages.as_ref().unwrap()[0].name, "Invites");
+        }
+    }
+
+    #[test]
+    fn apply_default_spam() {
+        let config_root = fixture_root("default-spam");
+        std::fs::write(config_root.join("1.yml"), YAML_FIXTURE).unwrap();
+        std::fs::write(
+            config_root.join("2.yml"),
+            "spam:\n  interval: 30\n  links: 3\n  actions:\n    - action: delete",
+        )
+        .unwrap();
+
+        let default_spam: SpamFilter =
+            serde_yaml::from_str("interval: 10\nemoji: 5\nactions:\n  - action: delete")
+                .expect("couldn't deserialize SpamFilter");
+        let (configs, errors) = load_guild_configs_independently(
+            &config_root,
+            &[Id::new(1), Id::new(2)],
+            Some(&default_spam),
+        );
+        std::fs::remove_dir_all(&config_root).unwrap();
+
+        assert!(errors.is_empty());
+        let default = configs[&Id::new(1)].spam.as_ref().unwrap();
+        assert_eq!(default.interval, 10);
+        assert_eq!(default.emoji, Some(5));
+        // A guild's own spam filter replaces the default entirely.
+        let own = configs[&Id::new(2)].spam.as_ref().unwrap();
+        assert_eq!(own.interval, 30);
+        assert_eq!(own.links, Some(3));
+        assert_eq!(own.emoji, None);
+    }
+
+    #[test]
+    fn validate_default_spam() {
+        let yml = r#"
+        guild_config_dir: guilds
+        active_guilds: []
+        armed_by_default: false
+        default_spam:
+          interval: 10
+          duplicate_similarity: 2
+        "#;
+
+        let config: Config = serde_yaml::from_str(yml).expect("couldn't deserialize Config");
+        assert_eq!(
+            validate_config(&config),
+            Err(vec![
+                "default_spam: in spam config, duplicate_similarity is 2, but must be between 0 and 1.".to_owned(),
+                "default_spam: in spam config, no spam thresholds are specified. Spam filtering will have no effects.".to_owned(),
+            ])
+        );
+    }
+
+    #[test]
+    fn merge_included_configs() {
+        let config_root = fixture_root("include");
+        std::fs::create_dir_all(config_root.join("shared")).unwrap();
+        std::fs::write(
+            config_root.join("shared/base.yml"),
+            r#"
+include:
+  - nested.json
+action_templates:
+  standard:
+    - action: delete
+messages:
+  - name: Zalgo
+    rules:
+      - type: zalgo
+    actions:
+      - action: template
+        name: standard
+  - name: Invites
+    rules:
+      - type: invite
+        mode: deny
+spam:
+  interval: 10
+  emoji: 5
+"#,
+        )
+        .unwrap();
+        std::fs::write(config_root.join("shared/nested.json"), JSON_FIXTURE).unwrap();
+        std::fs::write(
+            config_root.join("1.yml"),
+            r#"
+include:
+  - shared/base.yml
+default_actions:
+  - action: delete
+messages:
+  - name: Invites
+    rules:
+      - type: invite
+        mode: allow
+        invites: [chrysanthemum]
+"#,
+        )
+        .unwrap();
+
+        let config = load_config(&config_root, Id::new(1), None);
+        std::fs::remove_dir_all(&config_root).unwrap();
+        let config = config.expect("couldn't load guild config");
+
+        let messages = config.messages.as_ref().unwrap();
+        assert_eq!(
+            messages
+                .iter()
+                .map(|filter| filter.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Invites", "Zalgo"]
+        );
+        // The guild's own filter wins over the included one.
+        assert!(matches!(
+            messages[0].rules[0],
+            MessageFilterRule::Invite {
+                mode: FilterMode::AllowList,
+                ..
+            }
+        ));
+        assert!(matches!(
+            messages[1].actions.as_deref(),
+            Some([MessageFilterAction::Delete { .. }])
+        ));
+        assert_eq!(config.spam.as_ref().unwrap().emoji, Some(5));
+        assert_eq!(
+            config.include_sources.files,
+            vec![
+                config_root.join("shared/base.yml"),
+                config_root.join("shared/nested.json")
+            ]
+        );
+    }
+
+    #[test]
+    fn reject_include_cycles() {
+        let config_root = fixture_root("include-cycle");
+        std::fs::write(config_root.join("1.yml"), "include: [a.yml]").unwrap();
+        std::fs::write(config_root.join("a.yml"), "include: [b.yml]").unwrap();
+        std::fs::write(config_root.join("b.yml"), "include: [a.yml]").unwrap();
+
+        let a = config_root.join("a.yml").canonicalize().unwrap();
+        let b = config_root.join("b.yml").canonicalize().unwrap();
+        let err = load_config(&config_root, Id::new(1), None).unwrap_err();
+        std::fs::remove_dir_all(&config_root).unwrap();
+
+        assert_eq!(
+            err.to_string(),
+            format!("Include cycle: {:?} -> {:?} -> {:?}", a, b, a)
+        );
+    }
+
+    #[test]
+    fn report_errors_with_included_file() {
+        let config_root = fixture_root("include-errors");
+        std::fs::write(
+            config_root.join("shared.yml"),
+            "messages:\n  - name: Zalgo\n    rules:\n      - type: zalgo",
+        )
+        .unwrap();
+        std::fs::write(config_root.join("1.yml"), "include: [shared.yml]").unwrap();
+
+        let err = load_config(&config_root, Id::new(1), None).unwrap_err();
+        std::fs::remove_dir_all(&config_root).unwrap();
+
+        match err.downcast_ref::<LoadConfigError>() {
+            Some(LoadConfigError::Validate(errors)) => assert_eq!(
+                errors,
+                &vec![format!(
+                    "message filter 0 does not specify actions, but this guild has no default actions. (included from {:?})",
+                    config_root.join("shared.yml")
+                )]
+            ),
+            _ => panic!("expected validation errors, got {:?}", err),
+        }
+    }
+
+    #[test]
+    fn reject_unknown_keys() {
+        let config_root = fixture_root("unknown-keys");
+        std::fs::write(
+            config_root.join("shared.json"),
+            r#"{ "reactions": [], "spma": { "interval": 10 } }"#,
+        )
+        .unwrap();
+        std::fs::write(
+            config_root.join("1.yml"),
+            "include: [shared.json]\nincude_bots: true\nmessages:\n  - name: Invites\n    rules:\n      - type: invite\n        mode: deny\n    scopings:\n      include_channels: [1]\n    actions:\n      - action: delete",
+        )
+        .unwrap();
+        std::fs::write(
+            config_root.join("2.yml"),
+            "messages:\n  - name: Invites\n    rules:\n      - type: invite\n        mode: deny\n        allowed: []\n    actions:\n      - action: delete",
+        )
+        .unwrap();
+
+        let included_err = load_config(&config_root, Id::new(1), None).unwrap_err();
+        let rule_err = load_config(&config_root, Id::new(2), None).unwrap_err();
+        std::fs::remove_dir_all(&config_root).unwrap();
+
+        match included_err.downcast_ref::<LoadConfigError>() {
+            Some(LoadConfigError::Validate(errors)) => assert_eq!(
+                errors,
+                &vec![
+                    "unknown key `incude_bots`; did you mean `include_bots`?".to_owned(),
+                    "unknown key `messages[0].scopings`; did you mean `scoping`?".to_owned(),
+                    format!(
+                        "unknown key `spma`; did you mean `spam`? (included from {:?})",
+                        config_root.join("shared.json")
+                    ),
+                ]
+            ),
+            _ => panic!("expected validation errors, got {:?}", included_err),
+        }
+
+        // Rules and actions pick their fields based on their type, so unknown
+        // fields in them are rejected while deserializing instead.
+        assert!(format!("{:?}", rule_err).contains("unknown field `allowed`"));
+    }
+
+    #[test]
+    fn guild_config_extension_priority() {
+        let config_root = fixture_root("priority");
+        std::fs::write(config_root.join("1.toml"), TOML_FIXTURE).unwrap();
+        let toml_path = guild_config_path(&config_root, Id::new(1));
+        std::fs::write(config_root.join("1.json"), JSON_FIXTURE).unwrap();
+        let json_path = guild_config_path(&config_root, Id::new(1));
+        std::fs::write(config_root.join("1.yaml"), YAML_FIXTURE).unwrap();
+        let yaml_path = guild_config_path(&config_root, Id::new(1));
+        std::fs::write(config_root.join("1.yml"), YAML_FIXTURE).unwrap();
+        let yml_path = guild_config_path(&config_root, Id::new(1));
+        let missing_path = guild_config_path(&config_root, Id::new(2));
+        std::fs::remove_dir_all(&config_root).unwrap();
+
+        assert_eq!(toml_path, config_root.join("1.toml"));
+        assert_eq!(json_path, config_root.join("1.json"));
+        assert_eq!(yaml_path, config_root.join("1.yaml"));
+        assert_eq!(yml_path, config_root.join("1.yml"));
+        assert_eq!(missing_path, config_root.join("2.yml"));
+    }
+
+    #[test]
+    fn report_failing_config_format() {
+        let config_root = fixture_root("bad-json");
+        std::fs::write(config_root.join("1.json"), "{\"include_bots\": [").unwrap();
+        let (_, json_err) = load_all_guild_configs(&config_root)
+            .unwrap()
+            .errors
+            .remove(0);
+        std::fs::remove_dir_all(&config_root).unwrap();
+
+        let config_root = fixture_root("bad-extension");
+        std::fs::write(config_root.join("1.ini"), "include_bots = true").unwrap();
+        let (_, ini_err) = load_all_guild_configs(&config_root)
+            .unwrap()
+            .errors
+            .remove(0);
+        std::fs::remove_dir_all(&config_root).unwrap();
+
+        assert!(json_err.to_string().ends_with("as JSON"));
+        assert!(matches!(
+            ini_err.downcast_ref::<LoadConfigError>(),
+            Some(LoadConfigError::UnsupportedFormat(_))
+        ));
+    }
+
+    #[test]
+    fn report_every_failing_config() {
+        let config_root = fixture_root("report");
+        std::fs::write(config_root.join("1.yml"), YAML_FIXTURE).unwrap();
+        std::fs::write(config_root.join("2.json"), "{\"include_bots\": [").unwrap();
+        std::fs::write(
+            config_root.join("3.yml"),
+            "default_actions: []\ntrusted_roles: []",
+        )
+        .unwrap();
+        std::fs::write(config_root.join("4.ini"), "include_bots = true").unwrap();
+        std::fs::write(config_root.join("5.json"), JSON_FIXTURE).unwrap();
+        std::fs::create_dir(config_root.join("shared")).unwrap();
+        let report = load_all_guild_configs(&config_root).unwrap();
+        std::fs::remove_dir_all(&config_root).unwrap();
+
+        assert!(!report.is_ok());
+        assert_eq!(report.files, 5);
+        let failed: Vec<_> = report.errors.iter().map(|(path, _)| path).collect();
+        assert_eq!(
+            failed,
+            vec![
+                &config_root.join("2.json"),
+                &config_root.join("3.yml"),
+                &config_root.join("4.ini"),
+            ]
+        );
+
+        // Validation errors are listed individually under their file.
+        let validation_errors = config_error_lines(&report.errors[1].1);
+        assert_eq!(validation_errors.len(), 2);
+        let text = report.to_string();
+        for error in &validation_errors {
+            assert!(text.contains(&format!("  - {}\n", error)));
+        }
+        assert!(text.ends_with("5 files, 3 with errors"));
+    }
 }
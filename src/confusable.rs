@@ -1,43 +1,96 @@
-use std::{borrow::Cow, collections::HashMap};
+use std::{borrow::Cow, collections::HashMap, path::Path};
 
+use eyre::{eyre, Result, WrapErr};
 use once_cell::sync::OnceCell;
 
 static CONFUSABLE_MAP: OnceCell<HashMap<char, String>> = OnceCell::new();
 
-fn confusables() -> &'static HashMap<char, String> {
-    CONFUSABLE_MAP.get_or_init(|| {
-        let confusable_str = include_str!("confusable_data.txt");
-        let mut map = HashMap::new();
+fn parse_code_point(code_point: &str) -> Option<char> {
+    u32::from_str_radix(code_point, 16)
+        .ok()
+        .and_then(char::from_u32)
+}
 
-        for line in confusable_str.lines() {
-            if line.starts_with('#') {
-                continue;
-            }
+/// Parses confusable data in the format of Unicode's `confusables.txt`: one
+/// mapping per line, with the confusable code point and the code points it
+/// maps to in hex, separated by a `;`. Anything after a `#` is a comment.
+fn parse_confusables(data: &str) -> Result<HashMap<char, String>, String> {
+    let mut map = HashMap::new();
 
-            if !line.contains(';') {
-                continue;
-            }
+    for (index, line) in data.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        if line.trim().is_empty() {
+            continue;
+        }
 
-            let parts: Vec<_> = line.split(';').collect();
+        let mut parts = line.split(';');
+        let from = parts.next().unwrap_or_default().trim();
+        let to = match parts.next() {
+            Some(to) => to.trim(),
+            None => return Err(format!("line {}: expected `;` after `{}`", index + 1, from)),
+        };
+
+        let from = parse_code_point(from)
+            .ok_or_else(|| format!("line {}: `{}` is not a hex code point", index + 1, from))?;
+
+        let mut to_buffer = String::new();
+        for part in to.split_whitespace() {
+            let part = parse_code_point(part)
+                .ok_or_else(|| format!("line {}: `{}` is not a hex code point", index + 1, part))?;
+            to_buffer.push(part);
+        }
 
-            let from = parts[0].trim();
-            let to = parts[1].trim();
+        if to_buffer.is_empty() {
+            return Err(format!(
+                "line {}: no replacement given for `{}`",
+                index + 1,
+                from
+            ));
+        }
 
-            let from = u32::from_str_radix(from, 16).unwrap();
-            let from = char::from_u32(from).unwrap();
+        map.insert(from, to_buffer);
+    }
 
-            let mut to_buffer = String::new();
-            for part in to.split(' ') {
-                let part = u32::from_str_radix(part, 16).unwrap();
-                let part = char::from_u32(part).unwrap();
-                to_buffer.push(part);
-            }
+    Ok(map)
+}
 
-            map.insert(from, to_buffer);
-        }
+fn builtin_confusables() -> HashMap<char, String> {
+    parse_confusables(include_str!("confusable_data.txt"))
+        .expect("built-in confusable data is malformed")
+}
 
-        map
-    })
+fn confusables() -> &'static HashMap<char, String> {
+    CONFUSABLE_MAP.get_or_init(builtin_confusables)
+}
+
+/// Merges the confusables in `path` over the built-in ones, for lookalikes
+/// that the built-in table doesn't know about. This has to be done before any
+/// text is skeletonized.
+pub(crate) fn load_custom_confusables(path: &Path) -> Result<()> {
+    let data = std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("couldn't read confusables file {}", path.display()))?;
+    let custom = parse_confusables(&data)
+        .map_err(|err| eyre!("invalid confusables file {}: {}", path.display(), err))?;
+
+    let mut map = builtin_confusables();
+    map.extend(custom);
+    CONFUSABLE_MAP
+        .set(map)
+        .map_err(|_| eyre!("confusables were already loaded"))
+}
+
+/// Whether a character is invisible when rendered, like zero-width spaces and
+/// bidirectional control characters. These can be inserted between letters to
+/// break up words without changing how they look, so they're removed entirely.
+fn is_invisible(char: char) -> bool {
+    matches!(
+        char,
+        '\u{200B}'..='\u{200F}'
+            | '\u{202A}'..='\u{202E}'
+            | '\u{2066}'..='\u{2069}'
+            | '\u{061C}'
+            | '\u{FEFF}'
+    )
 }
 
 #[tracing::instrument]
@@ -47,7 +100,7 @@ pub fn skeletonize(str: &str) -> Cow<str> {
 
     for (index, char) in str.char_indices() {
         if matches!(result, Cow::Borrowed(_)) {
-            if !confusables.contains_key(&char) {
+            if !confusables.contains_key(&char) && !is_invisible(char) {
                 // Don't need to make any changes: this character isn't confusable.
                 continue;
             } else {
@@ -58,7 +111,10 @@ pub fn skeletonize(str: &str) -> Cow<str> {
             }
         }
 
-        if let Some(to) = confusables.get(&char) {
+        if is_invisible(char) {
+            // Make sure we've copied the string, even if there's nothing to add.
+            result.to_mut();
+        } else if let Some(to) = confusables.get(&char) {
             result.to_mut().push_str(to);
         } else {
             // This branch will only be executed if we've already copied the string, in which case
@@ -80,6 +136,42 @@ mod test {
         assert_eq!(skeletonize("paɣρɑl"), "paypal");
     }
 
+    #[test]
+    fn strip_invisible_characters() {
+        assert_eq!(skeletonize("b\u{200B}a\u{200D}d"), "bad");
+        assert_eq!(skeletonize("\u{FEFF}\u{202E}ρɑɣρɑl\u{2069}"), "paypal");
+        assert_eq!(skeletonize("\u{200B}"), "");
+    }
+
+    #[test]
+    fn parse_custom_confusables() {
+        let map = parse_confusables("# comment\n\n0031 ; 0069 # 1 → i\n00DF ;\t0073 0073 ;\tMA\n")
+            .unwrap();
+        assert_eq!(map.get(&'1').map(String::as_str), Some("i"));
+        assert_eq!(map.get(&'ß').map(String::as_str), Some("ss"));
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn reject_malformed_confusables() {
+        assert_eq!(
+            parse_confusables("0031 0069"),
+            Err("line 1: expected `;` after `0031 0069`".to_owned())
+        );
+        assert_eq!(
+            parse_confusables("\nzz ; 0069"),
+            Err("line 2: `zz` is not a hex code point".to_owned())
+        );
+        assert_eq!(
+            parse_confusables("0031 ; D800"),
+            Err("line 1: `D800` is not a hex code point".to_owned())
+        );
+        assert_eq!(
+            parse_confusables("0031 ;"),
+            Err("line 1: no replacement given for `1`".to_owned())
+        );
+    }
+
     #[test]
     fn dont_copy_if_no_confusables() {
         assert_eq!(skeletonize("paypal"), Cow::Borrowed("paypal"));
@@ -0,0 +1,421 @@
+use crate::config::{GuildConfig, MessageFilterAction, SpamFilter};
+
+/// Embed descriptions can be 4096 characters long; leave room for the note
+/// about changes that were left off.
+const CHANGES_LENGTH: usize = 3900;
+
+/// The parts of a guild's configuration that moderators are told about when a
+/// reload changes them. Rules and actions are compared by their debug
+/// representations, which show regexes by their patterns.
+#[derive(Debug, PartialEq)]
+pub(crate) struct GuildConfigSummary {
+    messages: Vec<FilterSummary>,
+    reactions: Vec<FilterSummary>,
+    spam: Option<SpamSummary>,
+}
+
+#[derive(Debug, PartialEq)]
+struct FilterSummary {
+    name: String,
+    rules: Vec<String>,
+    actions: Vec<ActionSummary>,
+}
+
+#[derive(Debug, PartialEq)]
+struct ActionSummary {
+    /// The tier of a `words` rule the action is taken for, if it's only taken
+    /// for one.
+    tier: Option<String>,
+    action: String,
+}
+
+#[derive(Debug, PartialEq)]
+struct SpamSummary {
+    /// Each threshold and setting that affects what counts as spam, with its
+    /// value, or `none` if it's unset.
+    thresholds: Vec<(&'static str, String)>,
+    actions: Vec<ActionSummary>,
+}
+
+fn debug_strings<T: std::fmt::Debug>(items: &[T]) -> Vec<String> {
+    items.iter().map(|item| format!("{:?}", item)).collect()
+}
+
+fn action_summaries(actions: &[MessageFilterAction], tier: Option<&str>) -> Vec<ActionSummary> {
+    actions
+        .iter()
+        .map(|action| ActionSummary {
+            tier: tier.map(ToOwned::to_owned),
+            action: format!("{:?}", action),
+        })
+        .collect()
+}
+
+fn threshold<T: std::fmt::Display>(value: Option<T>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => "none".to_owned(),
+    }
+}
+
+impl SpamSummary {
+    fn new(spam: &SpamFilter) -> Self {
+        Self {
+            thresholds: vec![
+                ("interval", spam.interval.to_string()),
+                ("emoji", threshold(spam.emoji)),
+                ("custom_emoji", threshold(spam.custom_emoji)),
+                ("duplicates", threshold(spam.duplicates)),
+                ("duplicate_similarity", threshold(spam.duplicate_similarity)),
+                (
+                    "normalize_duplicates",
+                    spam.normalize_duplicates.to_string(),
+                ),
+                ("near_duplicates", threshold(spam.near_duplicates)),
+                (
+                    "near_duplicate_similarity",
+                    threshold(spam.near_duplicate_similarity),
+                ),
+                ("links", threshold(spam.links)),
+                ("attachments", threshold(spam.attachments)),
+                ("spoilers", threshold(spam.spoilers)),
+                ("mentions", threshold(spam.mentions)),
+                ("unique_mentions", threshold(spam.unique_mentions)),
+                ("stickers", threshold(spam.stickers)),
+                ("characters", threshold(spam.characters)),
+                ("newlines", threshold(spam.newlines)),
+                ("per_channel", spam.per_channel.to_string()),
+                (
+                    "warn_threshold_fraction",
+                    threshold(spam.warn_threshold_fraction),
+                ),
+            ],
+            actions: action_summaries(spam.actions.as_deref().unwrap_or(&[]), None),
+        }
+    }
+}
+
+/// Lists the names of actions, like `delete` for
+/// `Delete { delay_seconds: None }`.
+fn action_names(actions: &[ActionSummary]) -> String {
+    if actions.is_empty() {
+        return "the default actions".to_owned();
+    }
+
+    let names: Vec<String> = actions
+        .iter()
+        .map(|summary| {
+            let variant = summary
+                .action
+                .split(|c: char| c == ' ' || c == '{')
+                .next()
+                .unwrap_or(&summary.action);
+
+            let mut name = String::new();
+            for (index, c) in variant.chars().enumerate() {
+                if c.is_uppercase() && index > 0 {
+                    name.push('_');
+                }
+                name.extend(c.to_lowercase());
+            }
+
+            match &summary.tier {
+                Some(tier) => format!("`{}` (for tier `{}`)", name, tier),
+                None => format!("`{}`", name),
+            }
+        })
+        .collect();
+
+    names.join(", ")
+}
+
+fn rule_count(rules: usize) -> String {
+    match rules {
+        1 => "1 rule".to_owned(),
+        rules => format!("{} rules", rules),
+    }
+}
+
+/// Describes how the filters of one kind changed, as lines like
+/// ``Added message filter `Invites` ``.
+fn filter_changes(kind: &str, old: &[FilterSummary], new: &[FilterSummary]) -> Vec<String> {
+    let mut changes = Vec::new();
+    let mut added: Vec<_> = new
+        .iter()
+        .filter(|filter| old.iter().all(|old| old.name != filter.name))
+        .collect();
+
+    for old_filter in old {
+        let new_filter = match new.iter().find(|new| new.name == old_filter.name) {
+            Some(new_filter) => new_filter,
+            None => {
+                // A filter that was removed while another with the same rules
+                // and actions was added has just been renamed.
+                let renamed = added.iter().position(|added| {
+                    added.rules == old_filter.rules && added.actions == old_filter.actions
+                });
+                match renamed {
+                    Some(index) => changes.push(format!(
+                        "Renamed {} filter `{}` to `{}`",
+                        kind,
+                        old_filter.name,
+                        added.remove(index).name
+                    )),
+                    None => changes.push(format!("Removed {} filter `{}`", kind, old_filter.name)),
+                }
+                continue;
+            }
+        };
+
+        if new_filter.rules.len() != old_filter.rules.len() {
+            changes.push(format!(
+                "{} filter `{}` now has {}, instead of {}",
+                capitalize(kind),
+                new_filter.name,
+                rule_count(new_filter.rules.len()),
+                old_filter.rules.len()
+            ));
+        } else if new_filter.rules != old_filter.rules {
+            changes.push(format!(
+                "Changed the rules of {} filter `{}`",
+                kind, new_filter.name
+            ));
+        }
+
+        if new_filter.actions != old_filter.actions {
+            changes.push(format!(
+                "Changed the actions of {} filter `{}` to {}",
+                kind,
+                new_filter.name,
+                action_names(&new_filter.actions)
+            ));
+        }
+    }
+
+    for filter in added {
+        changes.push(format!(
+            "Added {} filter `{}` with {} and {}",
+            kind,
+            filter.name,
+            rule_count(filter.rules.len()),
+            action_names(&filter.actions)
+        ));
+    }
+
+    changes
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+impl GuildConfigSummary {
+    pub(crate) fn new(config: &GuildConfig) -> Self {
+        let messages = config
+            .messages
+            .iter()
+            .flatten()
+            .map(|filter| {
+                let mut actions = action_summaries(filter.actions.as_deref().unwrap_or(&[]), None);
+                // Tiers are kept in a map, so they're sorted to compare them.
+                let mut tiers: Vec<_> = filter.actions_by_tier.iter().flatten().collect();
+                tiers.sort_by(|(a, _), (b, _)| a.cmp(b));
+                for (tier, tier_actions) in tiers {
+                    actions.extend(action_summaries(tier_actions, Some(tier)));
+                }
+
+                FilterSummary {
+                    name: filter.name.clone(),
+                    rules: debug_strings(&filter.rules),
+                    actions,
+                }
+            })
+            .collect();
+        let reactions = config
+            .reactions
+            .iter()
+            .flatten()
+            .map(|filter| FilterSummary {
+                name: filter.name.clone(),
+                rules: debug_strings(&filter.rules),
+                actions: action_summaries(filter.actions.as_deref().unwrap_or(&[]), None),
+            })
+            .collect();
+
+        Self {
+            messages,
+            reactions,
+            spam: config.spam.as_ref().map(SpamSummary::new),
+        }
+    }
+
+    /// Describes what changed between `old` and this summary, one line per
+    /// change. This is empty if nothing changed.
+    pub(crate) fn changes_from(&self, old: &Self) -> Vec<String> {
+        if self == old {
+            return Vec::new();
+        }
+
+        let mut changes = filter_changes("message", &old.messages, &self.messages);
+        changes.extend(filter_changes("reaction", &old.reactions, &self.reactions));
+
+        match (&old.spam, &self.spam) {
+            (None, Some(_)) => changes.push("Added the spam filter".to_owned()),
+            (Some(_), None) => changes.push("Removed the spam filter".to_owned()),
+            (Some(old_spam), Some(new_spam)) => {
+                for ((name, old_value), (_, new_value)) in
+                    old_spam.thresholds.iter().zip(&new_spam.thresholds)
+                {
+                    if old_value != new_value {
+                        changes.push(format!(
+                            "Changed spam `{}` from {} to {}",
+                            name, old_value, new_value
+                        ));
+                    }
+                }
+
+                if old_spam.actions != new_spam.actions {
+                    changes.push(format!(
+                        "Changed the spam filter's actions to {}",
+                        action_names(&new_spam.actions)
+                    ));
+                }
+            }
+            (None, None) => {}
+        }
+
+        changes
+    }
+}
+
+/// Gets the body of the notification sent when a reload changes a guild's
+/// configuration, or `None` if there's nothing to notify about: either nothing
+/// changed, or the guild had no configuration to compare against.
+pub(crate) fn reload_notification(
+    old: Option<&GuildConfigSummary>,
+    new: &GuildConfigSummary,
+) -> Option<String> {
+    let changes = new.changes_from(old?);
+    if changes.is_empty() {
+        return None;
+    }
+
+    let mut body = String::new();
+    for (index, change) in changes.iter().enumerate() {
+        let line = format!("- {}\n", change);
+        if body.chars().count() + line.chars().count() > CHANGES_LENGTH {
+            body.push_str(&format!("...and {} more", changes.len() - index));
+            break;
+        }
+
+        body.push_str(&line);
+    }
+
+    Some(body.trim_end().to_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::{reload_notification, GuildConfigSummary};
+    use crate::config::GuildConfig;
+
+    const CONFIG: &str = r#"
+messages:
+  - name: Invites
+    rules:
+      - type: invite
+        mode: deny
+    actions:
+      - action: delete
+  - name: Words
+    rules:
+      - type: words
+        words: [bad]
+    actions:
+      - action: delete
+reactions:
+  - name: Emoji
+    rules:
+      - type: default
+        mode: deny
+        emoji: ["🍆"]
+    actions:
+      - action: delete
+spam:
+  interval: 30
+  links: 3
+  actions:
+    - action: delete
+"#;
+
+    fn summary(yaml: &str) -> GuildConfigSummary {
+        let config: GuildConfig = serde_yaml::from_str(yaml).expect("couldn't deserialize config");
+        GuildConfigSummary::new(&config)
+    }
+
+    #[test]
+    fn no_change_no_notification() {
+        let old = summary(CONFIG);
+        let new = summary(CONFIG);
+
+        assert_eq!(new, old);
+        assert_eq!(reload_notification(Some(&old), &new), None);
+        // Guilds that didn't have a configuration before aren't notified
+        // either.
+        assert_eq!(reload_notification(None, &new), None);
+    }
+
+    #[test]
+    fn describe_changes() {
+        let old = summary(CONFIG);
+        let new = summary(
+            &CONFIG
+                .replace("name: Invites", "name: Server invites")
+                .replace("words: [bad]", "words: [worse]")
+                .replace(
+                    "  - name: Emoji\n    rules:\n      - type: default\n        mode: deny\n        emoji: [\"🍆\"]\n    actions:\n      - action: delete\n",
+                    "  - name: Emoji\n    rules: []\n    actions:\n      - action: delete\n      - action: send_log\n        channel_id: \"1\"\n",
+                )
+                .replace("links: 3", "links: 5"),
+        );
+
+        assert_eq!(
+            new.changes_from(&old),
+            vec![
+                "Renamed message filter `Invites` to `Server invites`",
+                "Changed the rules of message filter `Words`",
+                "Reaction filter `Emoji` now has 0 rules, instead of 1",
+                "Changed the actions of reaction filter `Emoji` to `delete`, `send_log`",
+                "Changed spam `links` from 3 to 5",
+            ]
+        );
+        assert_eq!(
+            reload_notification(Some(&old), &new)
+                .unwrap()
+                .lines()
+                .count(),
+            5
+        );
+
+        let removed = summary("messages: []");
+        assert_eq!(
+            removed.changes_from(&old),
+            vec![
+                "Removed message filter `Invites`",
+                "Removed message filter `Words`",
+                "Removed reaction filter `Emoji`",
+                "Removed the spam filter",
+            ]
+        );
+        assert_eq!(
+            old.changes_from(&removed)[0],
+            "Added message filter `Invites` with 1 rule and `delete`"
+        );
+    }
+}
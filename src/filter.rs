@@ -1,18 +1,23 @@
-use std::collections::{HashMap, VecDeque};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::convert::TryInto;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use twilight_model::channel::message::ReactionType;
+use futures::future::{BoxFuture, FutureExt};
+
+use twilight_model::channel::message::{Embed, ReactionType};
 use twilight_model::id::{
-    marker::{ChannelMarker, RoleMarker, UserMarker},
+    marker::{ChannelMarker, GuildMarker, RoleMarker, UserMarker},
     Id,
 };
 
 use once_cell::sync::OnceCell;
 use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 
-use crate::{config, MessageInfo};
+use crate::{config, MessageInfo, ReactionInfo};
 
 macro_rules! static_regex {
     ($name:ident = $init:expr) => {
@@ -35,11 +40,19 @@ static_regex!(
         .unwrap()
 );
 static_regex!(
-    link_regex = RegexBuilder::new(r"https?://([^/\s]+)")
+    link_regex = RegexBuilder::new(r"https?://([^/?#\s]+)([^#\s]*)")
         .case_insensitive(true)
         .build()
         .unwrap()
 );
+static_regex!(
+    cdn_link_regex = RegexBuilder::new(
+        r"https?://(cdn\.discordapp\.com|media\.discordapp\.net)/attachments/(\d+)/\d+/([^\s?#]+)"
+    )
+    .case_insensitive(true)
+    .build()
+    .unwrap()
+);
 static_regex!(spoiler_regex = Regex::new(r"\|\|[^\|]*\|\|").unwrap());
 static_regex!(
     emoji_regex =
@@ -47,9 +60,214 @@ static_regex!(
 );
 static_regex!(custom_emoji_regex = Regex::new(r"<a?:([^:]+):(\d+)>").unwrap());
 static_regex!(mention_regex = Regex::new(r"<@[!&]?\d+>").unwrap());
+static_regex!(
+    telegram_invite_regex = RegexBuilder::new(r"\b(?:t|telegram)\.(?:me|dog)/[\w+-]+")
+        .case_insensitive(true)
+        .build()
+        .unwrap()
+);
+static_regex!(
+    whatsapp_invite_regex = RegexBuilder::new(r"\bchat\.whatsapp\.com/(?:invite/)?[\w-]+")
+        .case_insensitive(true)
+        .build()
+        .unwrap()
+);
+static_regex!(
+    guilded_invite_regex = RegexBuilder::new(r"\bguilded\.gg/(?:i/)?[\w-]+")
+        .case_insensitive(true)
+        .build()
+        .unwrap()
+);
+static_regex!(
+    revolt_invite_regex = RegexBuilder::new(r"\b(?:rvlt\.gg|app\.revolt\.chat/invite)/[\w-]+")
+        .case_insensitive(true)
+        .build()
+        .unwrap()
+);
+static_regex!(
+    matrix_invite_regex = RegexBuilder::new(r"\bmatrix\.to/#/[#!@+]\S+")
+        .case_insensitive(true)
+        .build()
+        .unwrap()
+);
+static_regex!(
+    bitcoin_address_regex =
+        Regex::new(r"\b(?:[13][1-9A-HJ-NP-Za-km-z]{25,34}|bc1[ac-hj-np-z02-9]{39,59})\b").unwrap()
+);
+static_regex!(ethereum_address_regex = Regex::new(r"\b0x[0-9a-fA-F]{40}\b").unwrap());
+static_regex!(solana_address_regex = Regex::new(r"\b[1-9A-HJ-NP-Za-km-z]{32,44}\b").unwrap());
+static_regex!(monero_address_regex = Regex::new(r"\b[48][1-9A-HJ-NP-Za-km-z]{94}\b").unwrap());
+static_regex!(encoded_blob_regex = Regex::new(r"[A-Za-z0-9+/_-]+={0,2}").unwrap());
+static_regex!(
+    email_regex = RegexBuilder::new(
+        r"[a-z0-9._%+-]+(?:@|\s*[(\[]at[)\]]\s*)[a-z0-9-]+(?:(?:\.|\s*[(\[]dot[)\]]\s*)[a-z0-9-]+)*(?:\.|\s*[(\[]dot[)\]]\s*)[a-z]{2,}\b"
+    )
+    .case_insensitive(true)
+    .build()
+    .unwrap()
+);
+static_regex!(
+    phone_number_regex = Regex::new(
+        r"\+\d{10,14}\b|(?:\+\d{1,3}[ .-]?)?(?:\(\d{1,4}\)[ .-]?|\b\d{1,4}[ .-])(?:\d{2,4}[ .-]){0,3}\d{3,8}\b"
+    )
+    .unwrap()
+);
 
 pub type FilterResult = Result<(), String>;
 
+/// Why a message failed a filter, along with the tier of the words that it
+/// matched, if the rule that failed has tiers.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct FilterFailure<'f> {
+    pub(crate) reason: String,
+    pub(crate) tier: Option<&'f str>,
+}
+
+/// Fails only if every result failed, combining all of their reasons.
+fn all_rules_fail<I>(results: I) -> FilterResult
+where
+    I: Iterator<Item = FilterResult>,
+{
+    let mut reasons = Vec::new();
+    for result in results {
+        match result {
+            Ok(()) => return Ok(()),
+            Err(reason) => reasons.push(reason),
+        }
+    }
+
+    Err(reasons.join(" and "))
+}
+
+/// Inverts the result of a `not` rule's nested rule.
+fn negate(result: FilterResult) -> FilterResult {
+    match result {
+        Ok(()) => Err("does not satisfy required rule".to_owned()),
+        Err(_) => Ok(()),
+    }
+}
+
+static_regex!(leetspeak_token_regex = Regex::new(r"[\p{L}\d@$]+").unwrap());
+
+/// Undoes common leetspeak substitutions in `text`, reading `1` as `one`,
+/// since it's used for both `i` and `l`. Only words that contain a letter are
+/// changed, so that plain numbers and prices are left alone.
+fn undo_leetspeak(text: &str, one: char) -> Cow<'_, str> {
+    leetspeak_token_regex().replace_all(text, |captures: &regex::Captures| {
+        let token = captures.get(0).unwrap().as_str();
+        if !token.chars().any(char::is_alphabetic) {
+            return token.to_owned();
+        }
+
+        token
+            .chars()
+            .map(|c| match c {
+                '4' | '@' => 'a',
+                '3' => 'e',
+                '1' => one,
+                '0' => 'o',
+                '$' => 's',
+                c => c,
+            })
+            .collect()
+    })
+}
+
+// Runs of at least five single letters or digits separated by spaces or
+// punctuation, like `f r e e` or `n.i.t.r.o`. Shorter runs are left alone so
+// that spaced-out acronyms like `U S A` aren't collapsed.
+static_regex!(
+    spaced_characters_regex =
+        Regex::new(r"\b(?:[\p{L}\p{N}][\s\p{P}]+){4,}[\p{L}\p{N}]\b").unwrap()
+);
+static_regex!(spacing_separator_regex = Regex::new(r"[\s\p{P}]+").unwrap());
+
+/// Collapses runs of spaced-out characters in `text`, so that
+/// `f r e e   n i t r o` becomes `free nitro`. Gaps wider than the narrowest
+/// gap in a run are read as breaks between words.
+fn collapse_spacing(text: &str) -> Cow<'_, str> {
+    spaced_characters_regex().replace_all(text, |captures: &regex::Captures| {
+        let run = captures.get(0).unwrap().as_str();
+        let gaps: Vec<usize> = spacing_separator_regex()
+            .find_iter(run)
+            .map(|gap| gap.as_str().chars().count())
+            .collect();
+        let narrowest = gaps.iter().copied().min().unwrap_or(0);
+
+        let mut collapsed = String::new();
+        for (index, character) in spacing_separator_regex().split(run).enumerate() {
+            if index > 0 && gaps[index - 1] > narrowest {
+                collapsed.push(' ');
+            }
+
+            collapsed.push_str(character);
+        }
+
+        collapsed
+    })
+}
+
+/// The forms of `text` that text rules match against: its skeleton, the text
+/// itself, if `collapse` is set, the text with spaced-out characters collapsed,
+/// and, if `leetspeak` is set, each of those with leetspeak undone.
+fn text_forms(text: &str, leetspeak: bool, collapse: bool) -> Vec<Cow<'_, str>> {
+    fn push_form(forms: &mut Vec<Cow<'_, str>>, form: String) {
+        if !forms.iter().any(|existing| *existing == form) {
+            forms.push(Cow::Owned(form));
+        }
+    }
+
+    let mut forms = vec![crate::confusable::skeletonize(text), Cow::Borrowed(text)];
+
+    if collapse {
+        if let Cow::Owned(collapsed) = collapse_spacing(text) {
+            push_form(&mut forms, collapsed);
+        }
+    }
+
+    if leetspeak {
+        // Leetspeak is undone in everything but the skeleton.
+        for index in 1..forms.len() {
+            for one in IntoIterator::into_iter(['i', 'l']) {
+                if let Cow::Owned(undone) = undo_leetspeak(&forms[index], one) {
+                    push_form(&mut forms, undone);
+                }
+            }
+        }
+    }
+
+    forms
+}
+
+/// Finds the first word in `text` from `words` or any of `tiers`, returning the
+/// word along with the name of the tier it came from.
+fn match_words<'r>(
+    words: Option<&'r Regex>,
+    tiers: &'r [config::WordTier],
+    text: &str,
+    leetspeak: bool,
+    collapse_spacing: bool,
+) -> Option<(Option<&'r str>, String)> {
+    let forms = text_forms(text, leetspeak, collapse_spacing);
+
+    tracing::trace!(%text, ?forms, ?words, ?tiers, "Performing word text filtration");
+
+    words
+        .map(|words| (None, words))
+        .into_iter()
+        .chain(
+            tiers
+                .iter()
+                .map(|tier| (Some(tier.name.as_str()), &tier.words)),
+        )
+        .find_map(|(tier, words)| {
+            forms
+                .iter()
+                .find_map(|form| words.captures(form))
+                .map(|captures| (tier, captures.get(1).unwrap().as_str().to_owned()))
+        })
+}
+
 fn filter_values<T, V, I>(
     mode: &config::FilterMode,
     context: &str,
@@ -76,26 +294,356 @@ where
     result.unwrap_or(Ok(()))
 }
 
+/// Gets the patterns for the chat platforms that are enabled, along with the
+/// name of each platform.
+fn external_invite_regexes(
+    telegram: bool,
+    whatsapp: bool,
+    guilded: bool,
+    revolt: bool,
+    matrix: bool,
+) -> impl Iterator<Item = (&'static str, &'static Regex)> {
+    IntoIterator::into_iter([
+        (telegram, "Telegram", telegram_invite_regex()),
+        (whatsapp, "WhatsApp", whatsapp_invite_regex()),
+        (guilded, "Guilded", guilded_invite_regex()),
+        (revolt, "Revolt", revolt_invite_regex()),
+        (matrix, "Matrix", matrix_invite_regex()),
+    ])
+    .filter(|(enabled, _, _)| *enabled)
+    .map(|(_, platform, regex)| (platform, regex))
+}
+
+/// Gets the patterns for the cryptocurrency chains that are enabled, along with
+/// the name of each chain. More specific patterns come first, since Solana's
+/// address format overlaps with Bitcoin's.
+fn crypto_address_regexes(
+    bitcoin: bool,
+    ethereum: bool,
+    solana: bool,
+    monero: bool,
+) -> impl Iterator<Item = (&'static str, &'static Regex)> {
+    IntoIterator::into_iter([
+        (bitcoin, "Bitcoin", bitcoin_address_regex()),
+        (ethereum, "Ethereum", ethereum_address_regex()),
+        (monero, "Monero", monero_address_regex()),
+        (solana, "Solana", solana_address_regex()),
+    ])
+    .filter(|(enabled, _, _)| *enabled)
+    .map(|(_, chain, regex)| (chain, regex))
+}
+
+fn is_plausible_crypto_address(address: &str) -> bool {
+    if address.starts_with("0x") || address.starts_with("bc1") {
+        return true;
+    }
+
+    // Base58 addresses are effectively random, so they'll almost always mix
+    // uppercase letters, lowercase letters, and digits. Requiring all three
+    // keeps lowercase hex (like git hashes) and plain numbers (like Discord
+    // snowflakes) from being mistaken for addresses.
+    address.chars().any(|c| c.is_ascii_uppercase())
+        && address.chars().any(|c| c.is_ascii_lowercase())
+        && address.chars().any(|c| c.is_ascii_digit())
+}
+
+/// The Shannon entropy of `text`, in bits per character.
+fn shannon_entropy(text: &str) -> f32 {
+    let mut counts = HashMap::new();
+    for c in text.chars() {
+        *counts.entry(c).or_insert(0u32) += 1;
+    }
+
+    let length = text.chars().count() as f32;
+    counts
+        .values()
+        .map(|&count| {
+            let p = count as f32 / length;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Whether `text` has a run of base64 or hex characters that's at least
+/// `min_length` characters long and at least `min_entropy` bits per character
+/// random. Links, custom emoji, and mentions are skipped, since they're made
+/// of the same characters.
+fn contains_encoded_blob(text: &str, min_length: usize, min_entropy: f32) -> bool {
+    let text = link_regex().replace_all(text, " ");
+    let text = custom_emoji_regex().replace_all(&text, " ");
+    let text = mention_regex().replace_all(&text, " ");
+
+    encoded_blob_regex()
+        .find_iter(&text)
+        .map(|m| m.as_str())
+        .any(|run| {
+            // Encoded data mixes letters and digits, which long words and
+            // numeric IDs don't.
+            run.len() >= min_length
+                && run.chars().any(|c| c.is_ascii_alphabetic())
+                && run.chars().any(|c| c.is_ascii_digit())
+                && shannon_entropy(run) >= min_entropy
+        })
+}
+
+/// Gets the patterns for the kinds of contact information that are enabled,
+/// along with the name of each kind and a check for whether a match in some
+/// text is plausible.
+fn contact_info_regexes(
+    emails: bool,
+    phone_numbers: bool,
+) -> impl Iterator<
+    Item = (
+        &'static str,
+        &'static Regex,
+        fn(&str, &regex::Match) -> bool,
+    ),
+> {
+    IntoIterator::into_iter([
+        (
+            emails,
+            "email address",
+            email_regex(),
+            (|_, _| true) as fn(&str, &regex::Match) -> bool,
+        ),
+        (
+            phone_numbers,
+            "phone number",
+            phone_number_regex(),
+            is_plausible_phone_number,
+        ),
+    ])
+    .filter(|(enabled, _, _, _)| *enabled)
+    .map(|(_, kind, regex, is_plausible)| (kind, regex, is_plausible))
+}
+
+fn is_plausible_phone_number(text: &str, number: &regex::Match) -> bool {
+    // Seven-digit local numbers look too much like any other number to be
+    // worth matching.
+    let digits = number.as_str().chars().filter(char::is_ascii_digit).count();
+    if !(10..=15).contains(&digits) {
+        return false;
+    }
+
+    // Dotted numbers are far more often IP addresses, dates, and version
+    // numbers than phone numbers, none of which end in four digits.
+    if number.as_str().contains('.') && number.as_str().rsplit('.').next().map(str::len) != Some(4)
+    {
+        return false;
+    }
+
+    // Numbers that are part of something bigger, like a date followed by a
+    // time or a path, aren't phone numbers either.
+    let before = text[..number.start()].chars().next_back();
+    let mut after = text[number.end()..].chars();
+    !matches!(before, Some('.' | ':' | '/' | '-' | '_'))
+        && match after.next() {
+            Some(':' | '/' | '-' | '_' | '@') => false,
+            Some('.') => !after.next().map_or(false, |c| c.is_ascii_digit()),
+            _ => true,
+        }
+}
+
+/// Gets the result of a rule that matches patterns against text, given the
+/// failure reason for the pattern that matched (if any). Rules that require a
+/// match fail when nothing matched, and pass otherwise.
+fn pattern_rule_result(
+    matched: Option<String>,
+    require_match: bool,
+    custom_reason: Option<&str>,
+    unmatched_reason: &str,
+) -> FilterResult {
+    let reason = match (matched, require_match) {
+        (Some(reason), false) => reason,
+        (None, true) => unmatched_reason.to_owned(),
+        _ => return Ok(()),
+    };
+
+    Err(custom_reason.map(str::to_owned).unwrap_or(reason))
+}
+
+/// Joins all of the text in a message's embeds, so that text rules can be
+/// applied to it.
+fn embed_text(embeds: &[Embed]) -> String {
+    let mut parts = Vec::new();
+    for embed in embeds {
+        parts.extend(embed.author.as_ref().map(|author| author.name.as_str()));
+        parts.extend(embed.title.as_deref());
+        parts.extend(embed.description.as_deref());
+        for field in &embed.fields {
+            parts.push(field.name.as_str());
+            parts.push(field.value.as_str());
+        }
+        parts.extend(embed.footer.as_ref().map(|footer| footer.text.as_str()));
+    }
+
+    parts.join("\n")
+}
+
+/// Formats a size in bytes for filter reasons, in the largest unit that keeps
+/// it above 1, with at most one decimal place.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    let rounded = (size * 10.0).round() / 10.0;
+    if rounded.fract() == 0.0 {
+        format!("{}{}", rounded, UNITS[unit])
+    } else {
+        format!("{:.1}{}", rounded, UNITS[unit])
+    }
+}
+
+/// Gets the extension of an attachment's filename. Only the final component is
+/// considered, so `archive.tar.gz` has an extension of `gz`. Files without an
+/// extension have an empty one.
+pub(crate) fn attachment_extension(filename: &str) -> &str {
+    filename
+        .rsplit_once('.')
+        .map(|(_, extension)| extension)
+        .unwrap_or("")
+}
+
+/// Gets the MIME types that a file with a given (lowercase) extension can be
+/// expected to have, if the extension is known.
+fn expected_mime_types(extension: &str) -> Option<&'static [&'static str]> {
+    let types: &[&str] = match extension {
+        "png" => &["image/png"],
+        "jpg" | "jpeg" => &["image/jpeg"],
+        "gif" => &["image/gif"],
+        "webp" => &["image/webp"],
+        "bmp" => &["image/bmp"],
+        "svg" => &["image/svg+xml"],
+        "mp4" => &["video/mp4"],
+        "webm" => &["video/webm"],
+        "mov" => &["video/quicktime"],
+        "mp3" => &["audio/mpeg"],
+        "ogg" => &["audio/ogg", "video/ogg", "application/ogg"],
+        "wav" => &["audio/wav", "audio/x-wav", "audio/wave"],
+        "txt" => &["text/plain"],
+        "pdf" => &["application/pdf"],
+        "zip" => &["application/zip", "application/x-zip-compressed"],
+        "exe" => &[
+            "application/x-msdownload",
+            "application/vnd.microsoft.portable-executable",
+        ],
+        _ => return None,
+    };
+
+    Some(types)
+}
+
+/// Checks a filename's extension against a list of extensions. On failure,
+/// returns a description of what was wrong with the extension.
+fn filter_extension(
+    mode: &config::FilterMode,
+    extensions: &[String],
+    filename: &str,
+) -> FilterResult {
+    let extension = attachment_extension(filename).to_lowercase();
+    let listed = extensions
+        .iter()
+        .any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(&extension));
+
+    match mode {
+        config::FilterMode::AllowList if extension.is_empty() => {
+            Err("without an extension".to_owned())
+        }
+        config::FilterMode::AllowList if !listed => {
+            Err(format!("with unallowed extension {}", extension))
+        }
+        config::FilterMode::DenyList if listed => {
+            Err(format!("with denied extension {}", extension))
+        }
+        _ => Ok(()),
+    }
+}
+
+impl config::RegexList {
+    /// Finds the first regex that matches `text`, returning it along with its
+    /// description, if it has one, and the text it matched.
+    pub(crate) fn find<'t>(&self, text: &'t str) -> Option<(&Regex, Option<&str>, &'t str)> {
+        let index = self.set.matches(text).into_iter().next()?;
+        let regex = &self.regexes[index];
+        let description = self.descriptions[index].as_deref();
+        regex.find(text).map(|m| (regex, description, m.as_str()))
+    }
+}
+
+impl config::GuildConfig {
+    /// Whether a member with the given roles bypasses message filtering.
+    pub(crate) fn is_trusted(&self, roles: &[Id<RoleMarker>]) -> bool {
+        match &self.trusted_roles {
+            Some(trusted_roles) => roles.iter().any(|role| trusted_roles.contains(role)),
+            None => false,
+        }
+    }
+
+    /// Whether messages from this kind of author are filtered at all. Webhooks
+    /// are only filtered if `filter_webhooks` is set, and other bots only if
+    /// `include_bots` is.
+    pub(crate) fn filters_author(&self, is_bot: bool, is_webhook: bool) -> bool {
+        if is_webhook {
+            self.filter_webhooks
+        } else {
+            !is_bot || self.include_bots
+        }
+    }
+
+    /// The longest `window_seconds` of any `no_repost` rule in this guild's
+    /// message filters, or `None` if there are no `no_repost` rules, in which
+    /// case messages don't need to be remembered for them.
+    pub(crate) fn no_repost_window(&self) -> Option<u64> {
+        self.messages
+            .iter()
+            .flatten()
+            .flat_map(|filter| filter.rules.iter())
+            .filter_map(config::MessageFilterRule::no_repost_window)
+            .max()
+    }
+
+    /// Whether a bot's messages bypass message filtering even when bots are
+    /// included.
+    pub(crate) fn is_allowlisted_bot(&self, author_id: Id<UserMarker>) -> bool {
+        match &self.bot_allowlist {
+            Some(bot_allowlist) => bot_allowlist.contains(&author_id),
+            None => false,
+        }
+    }
+}
+
 impl config::Scoping {
-    pub fn is_included(&self, channel: Id<ChannelMarker>, author_roles: &[Id<RoleMarker>]) -> bool {
-        if self.include_channels.is_some()
-            && self
-                .include_channels
-                .as_ref()
-                .unwrap()
+    /// Whether a message or reaction in `channel`, by a member with
+    /// `author_roles`, is covered by this scoping. If `channel` is a thread,
+    /// `parent_channel` is the channel the thread is in, and it's matched too
+    /// when `include_parent_channels` is set.
+    pub fn is_included(
+        &self,
+        channel: Id<ChannelMarker>,
+        parent_channel: Option<Id<ChannelMarker>>,
+        author_roles: &[Id<RoleMarker>],
+    ) -> bool {
+        let parent_channel = parent_channel.filter(|_| self.include_parent_channels);
+        let matches_channel = |channels: &Vec<Id<ChannelMarker>>| {
+            channels
                 .iter()
-                .all(|c| *c != channel)
+                .any(|c| *c == channel || Some(*c) == parent_channel)
+        };
+
+        if self.include_channels.is_some()
+            && !matches_channel(self.include_channels.as_ref().unwrap())
         {
             return false;
         }
 
         if self.exclude_channels.is_some()
-            && self
-                .exclude_channels
-                .as_ref()
-                .unwrap()
-                .iter()
-                .any(|c| *c == channel)
+            && matches_channel(self.exclude_channels.as_ref().unwrap())
         {
             return false;
         }
@@ -108,17 +656,164 @@ impl config::Scoping {
             }
         }
 
+        if self.include_roles.is_some()
+            && !self
+                .include_roles
+                .as_ref()
+                .unwrap()
+                .iter()
+                .any(|r| author_roles.contains(r))
+        {
+            return false;
+        }
+
         true
     }
 }
 
+#[derive(Serialize)]
+struct ExternalScoreRequest<'a> {
+    content: &'a str,
+}
+
+#[derive(Deserialize)]
+struct ExternalScoreResponse {
+    score: f32,
+}
+
+async fn fetch_external_score(
+    web_client: &reqwest::Client,
+    endpoint: &str,
+    timeout_ms: u64,
+    content: &str,
+) -> reqwest::Result<f32> {
+    let response: ExternalScoreResponse = web_client
+        .post(endpoint)
+        .timeout(Duration::from_millis(timeout_ms))
+        .json(&ExternalScoreRequest { content })
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    Ok(response.score)
+}
+
+const DISCORD_API_BASE: &str = "https://discord.com/api/v10/";
+const INVITE_RESOLVE_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long to remember which guild an invite points to. Invites can't be
+/// pointed at a different guild, but they can expire and have their code
+/// reused.
+const INVITE_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Deserialize)]
+struct InviteResponse {
+    guild: Option<InviteGuild>,
+}
+
+#[derive(Deserialize)]
+struct InviteGuild {
+    id: Id<GuildMarker>,
+}
+
+type InviteCache = HashMap<String, (Option<Id<GuildMarker>>, std::time::Instant)>;
+
+fn invite_cache() -> &'static Mutex<InviteCache> {
+    static CACHE: OnceCell<Mutex<InviteCache>> = OnceCell::new();
+    CACHE.get_or_init(Default::default)
+}
+
+/// Looks up which guild an invite points to. Unknown invites point to no
+/// guild.
+async fn fetch_invite_guild(
+    web_client: &reqwest::Client,
+    api_base: &str,
+    code: &str,
+) -> reqwest::Result<Option<Id<GuildMarker>>> {
+    let response = web_client
+        .get(format!("{}invites/{}", api_base, code))
+        .timeout(INVITE_RESOLVE_TIMEOUT)
+        .send()
+        .await?;
+
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+
+    let invite: InviteResponse = response.error_for_status()?.json().await?;
+    Ok(invite.guild.map(|guild| guild.id))
+}
+
+/// Like [`fetch_invite_guild`], but remembers the guilds of invites that have
+/// already been looked up. Invites that can't be looked up are treated as
+/// pointing to no guild, and aren't remembered.
+async fn resolve_invite(
+    web_client: &reqwest::Client,
+    api_base: &str,
+    code: &str,
+) -> Option<Id<GuildMarker>> {
+    let now = std::time::Instant::now();
+    if let Some((guild_id, resolved_at)) = invite_cache().lock().unwrap().get(code) {
+        if now.duration_since(*resolved_at) < INVITE_CACHE_TTL {
+            return *guild_id;
+        }
+    }
+
+    match fetch_invite_guild(web_client, api_base, code).await {
+        Ok(guild_id) => {
+            let mut cache = invite_cache().lock().unwrap();
+            cache.retain(|_, (_, resolved_at)| now.duration_since(*resolved_at) < INVITE_CACHE_TTL);
+            cache.insert(code.to_owned(), (guild_id, now));
+            guild_id
+        }
+        Err(err) => {
+            // Fail open: Discord being unavailable shouldn't stop invites
+            // from being posted.
+            tracing::warn!(?err, %code, "Unable to resolve invite");
+            None
+        }
+    }
+}
+
+/// Checks whether any invite in `text` points to one of `guilds`.
+async fn filter_invite_guilds(
+    text: &str,
+    guilds: &[Id<GuildMarker>],
+    web_client: &reqwest::Client,
+    api_base: &str,
+) -> FilterResult {
+    for capture in invite_regex().captures_iter(text) {
+        let code = capture.get(1).unwrap().as_str();
+        if let Some(guild_id) = resolve_invite(web_client, api_base, code).await {
+            if guilds.contains(&guild_id) {
+                return Err(format!(
+                    "contains invite `{}` to denied guild {}",
+                    code, guild_id
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
 impl config::MessageFilter {
-    pub(crate) fn filter_message(&self, message: &MessageInfo<'_>) -> FilterResult {
-        self.rules
-            .iter()
-            .map(|f| f.filter_message(message))
-            .find(|r| r.is_err())
-            .unwrap_or(Ok(()))
+    pub(crate) async fn filter_message(
+        &self,
+        message: &MessageInfo<'_>,
+        web_client: &reqwest::Client,
+    ) -> Result<(), FilterFailure<'_>> {
+        for rule in &self.rules {
+            if let Err(reason) = rule.filter_message_async(message, web_client).await {
+                return Err(FilterFailure {
+                    reason,
+                    tier: rule.matched_tier(message.content),
+                });
+            }
+        }
+
+        Ok(())
     }
 
     pub fn filter_text(&self, text: &str) -> FilterResult {
@@ -128,67 +823,104 @@ impl config::MessageFilter {
             .find(|r| r.is_err())
             .unwrap_or(Ok(()))
     }
+
+    /// Redacts anything this filter's rules consider sensitive from `text`.
+    pub(crate) fn redact<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        self.rules
+            .iter()
+            .fold(Cow::Borrowed(text), |text, rule| match rule.redact(&text) {
+                Cow::Borrowed(_) => text,
+                Cow::Owned(redacted) => Cow::Owned(redacted),
+            })
+    }
 }
 
 impl config::MessageFilterRule {
     pub fn filter_text(&self, text: &str) -> FilterResult {
         match self {
-            config::MessageFilterRule::Words { words } => {
-                let skeleton = crate::confusable::skeletonize(text);
-
-                tracing::trace!(%text, %skeleton, ?words, "Performing word text filtration");
-
-                if let Some(captures) = words.captures(&skeleton) {
-                    Err(format!(
-                        "contains word `{}`",
-                        captures.get(1).unwrap().as_str()
-                    ))
-                } else if let Some(captures) = words.captures(text) {
-                    Err(format!(
-                        "contains word `{}`",
-                        captures.get(1).unwrap().as_str()
-                    ))
-                } else {
-                    Ok(())
-                }
+            config::MessageFilterRule::Words {
+                words,
+                tiers,
+                require_match,
+                reason,
+                leetspeak,
+                collapse_spacing,
+            } => {
+                let matched =
+                    match_words(words.as_ref(), tiers, text, *leetspeak, *collapse_spacing)
+                        .map(|(_, word)| format!("contains word `{}`", word));
+
+                pattern_rule_result(
+                    matched,
+                    *require_match,
+                    reason.as_deref(),
+                    "does not contain any required words",
+                )
             }
-            config::MessageFilterRule::Substring { substrings } => {
-                let skeleton = crate::confusable::skeletonize(text);
+            config::MessageFilterRule::Substring {
+                substrings,
+                leetspeak,
+                collapse_spacing,
+            } => {
+                let forms = text_forms(text, *leetspeak, *collapse_spacing);
 
-                tracing::trace!(%text, %skeleton, ?substrings, "Performing substring text filtration");
+                tracing::trace!(%text, ?forms, ?substrings, "Performing substring text filtration");
 
-                if let Some(captures) = substrings.captures(&skeleton) {
-                    Err(format!(
-                        "contains substring `{}`",
-                        captures.get(0).unwrap().as_str()
-                    ))
-                } else if let Some(captures) = substrings.captures(text) {
-                    Err(format!(
+                match forms.iter().find_map(|form| substrings.captures(form)) {
+                    Some(captures) => Err(format!(
                         "contains substring `{}`",
                         captures.get(0).unwrap().as_str()
-                    ))
-                } else {
-                    Ok(())
+                    )),
+                    None => Ok(()),
                 }
             }
-            config::MessageFilterRule::Regex { regexes } => {
-                let skeleton = crate::confusable::skeletonize(text);
-
-                tracing::trace!(%text, %skeleton, ?regexes, "Performing regex text filtration");
-
-                let raw_match = regexes.matches(text).into_iter().next();
-                let skeleton_match = regexes.matches(&skeleton).into_iter().next();
-
-                if let Some(pattern_index) = raw_match.or(skeleton_match) {
-                    let pattern = regexes.patterns().iter().nth(pattern_index);
+            config::MessageFilterRule::Regex {
+                regexes,
+                require_match,
+                reason,
+                collapse_spacing,
+            } => {
+                let forms = text_forms(text, false, *collapse_spacing);
+
+                tracing::trace!(%text, ?forms, ?regexes, "Performing regex text filtration");
+
+                // The text itself is checked before its skeleton, so that
+                // what's reported as matching is what was actually sent.
+                let matched = regexes
+                    .find(&forms[1])
+                    .or_else(|| regexes.find(&forms[0]))
+                    .or_else(|| forms[2..].iter().find_map(|form| regexes.find(form)))
+                    .map(|(regex, description, matched)| match description {
+                        Some(description) => {
+                            format!("matches `{}` on text `{}`", description, matched)
+                        }
+                        None => format!("matches regex `{}` on text `{}`", regex, matched),
+                    });
+
+                pattern_rule_result(
+                    matched,
+                    *require_match,
+                    reason.as_deref(),
+                    "does not match any required regexes",
+                )
+            }
+            config::MessageFilterRule::Language { mode, languages } => {
+                let language = match crate::language::detect(text) {
+                    Some(language) => language,
+                    None => return Ok(()),
+                };
 
-                    debug_assert!(matches!(pattern, Some(_)));
-                    if let Some(pattern) = pattern {
-                        return Err(format!("matches regex `{}`", pattern));
-                    }
+                let listed = languages
+                    .iter()
+                    .any(|code| code.eq_ignore_ascii_case(language.code));
+                match (mode, listed) {
+                    (config::FilterMode::AllowList, false)
+                    | (config::FilterMode::DenyList, true) => Err(format!(
+                        "detected language {} is not allowed here",
+                        language.name
+                    )),
+                    _ => Ok(()),
                 }
-
-                Ok(())
             }
             config::MessageFilterRule::Zalgo => {
                 let zalgo_regex = zalgo_regex();
@@ -198,14 +930,18 @@ impl config::MessageFilterRule {
                     Ok(())
                 }
             }
-            config::MessageFilterRule::Invite { mode, invites } => {
+            config::MessageFilterRule::Invite { mode, invites, .. } => {
                 let invite_regex = invite_regex();
                 let mut invite_ids = invite_regex
                     .captures_iter(text)
                     .map(|c| c.get(1).unwrap().as_str());
                 filter_values(mode, "invite", &mut invite_ids, invites)
             }
-            config::MessageFilterRule::Link { mode, domains } => {
+            config::MessageFilterRule::Link {
+                mode,
+                domains,
+                path_patterns,
+            } => {
                 let link_regex = link_regex();
                 let mut link_domains = link_regex
                     .captures_iter(text)
@@ -222,29 +958,326 @@ impl config::MessageFilterRule {
                         .find(|v| domains.iter().any(|f| f == v || v == &format!("www.{}", f)))
                         .map(|v| Err(format!("contains denied domain `{}`", v))),
                 };
+                result.unwrap_or(Ok(()))?;
 
-                result.unwrap_or(Ok(()))
-            }
-            config::MessageFilterRule::EmojiName { names } => {
-                for capture in custom_emoji_regex().captures_iter(text) {
-                    let name = capture.get(1).unwrap().as_str();
-                    let substring_match = names.captures(name);
-                    if let Some(substring_match) = substring_match {
+                for capture in link_regex.captures_iter(text) {
+                    let domain = capture.get(1).unwrap().as_str();
+                    let path = capture.get(2).unwrap().as_str();
+                    if let Some(pattern) = path_patterns.iter().find(|p| p.is_match(path)) {
                         return Err(format!(
-                            "contains emoji with denied name substring `{}`",
-                            substring_match.get(0).unwrap().as_str()
+                            "contains link to `{}{}` matching denied path pattern `{}`",
+                            domain,
+                            path,
+                            pattern.as_str()
                         ));
                     }
                 }
 
                 Ok(())
             }
-            _ => Ok(()),
-        }
+            config::MessageFilterRule::EncodedBlob {
+                min_length,
+                min_entropy,
+            } => {
+                let min_entropy = min_entropy.unwrap_or(config::DEFAULT_ENCODED_BLOB_MIN_ENTROPY);
+                if contains_encoded_blob(text, *min_length, min_entropy) {
+                    Err("contains a suspicious encoded blob".to_owned())
+                } else {
+                    Ok(())
+                }
+            }
+            config::MessageFilterRule::CryptoAddresses {
+                bitcoin,
+                ethereum,
+                solana,
+                monero,
+            } => {
+                for (chain, regex) in crypto_address_regexes(*bitcoin, *ethereum, *solana, *monero)
+                {
+                    if regex
+                        .find_iter(text)
+                        .any(|m| is_plausible_crypto_address(m.as_str()))
+                    {
+                        return Err(format!("contains {} address", chain));
+                    }
+                }
+
+                Ok(())
+            }
+            config::MessageFilterRule::ContactInfo {
+                emails,
+                phone_numbers,
+            } => {
+                let skeleton = crate::confusable::skeletonize(text);
+
+                tracing::trace!(%text, %skeleton, "Performing contact info text filtration");
+
+                for (kind, regex, is_plausible) in contact_info_regexes(*emails, *phone_numbers) {
+                    let matched = [text, &skeleton]
+                        .iter()
+                        .any(|text| regex.find_iter(text).any(|m| is_plausible(text, &m)));
+
+                    if matched {
+                        return Err(format!("contains {}", kind));
+                    }
+                }
+
+                Ok(())
+            }
+            config::MessageFilterRule::ExternalInvites {
+                telegram,
+                whatsapp,
+                guilded,
+                revolt,
+                matrix,
+            } => {
+                for (platform, regex) in
+                    external_invite_regexes(*telegram, *whatsapp, *guilded, *revolt, *matrix)
+                {
+                    if let Some(invite) = regex.find(text) {
+                        return Err(format!(
+                            "contains {} invite `{}`",
+                            platform,
+                            invite.as_str()
+                        ));
+                    }
+                }
+
+                Ok(())
+            }
+            config::MessageFilterRule::EmojiOnly { min_text_chars } => {
+                if !emoji_regex().is_match(text) && !custom_emoji_regex().is_match(text) {
+                    return Ok(());
+                }
+
+                let without_custom_emoji = custom_emoji_regex().replace_all(text, "");
+                let without_emoji = emoji_regex().replace_all(&without_custom_emoji, "");
+
+                // This leaves out whitespace and punctuation, as well as any
+                // joiners and modifiers left over from removing the emoji.
+                let text_chars = without_emoji
+                    .chars()
+                    .filter(|c| c.is_alphanumeric())
+                    .count();
+                if text_chars < *min_text_chars {
+                    Err("message is emoji-only".to_owned())
+                } else {
+                    Ok(())
+                }
+            }
+            config::MessageFilterRule::All { rules } => {
+                all_rules_fail(rules.iter().map(|rule| rule.filter_text(text)))
+            }
+            config::MessageFilterRule::Not { rule } => negate(rule.filter_text(text)),
+            config::MessageFilterRule::EmojiCount { max } => {
+                let count = emoji_regex().find_iter(text).count()
+                    + custom_emoji_regex().find_iter(text).count();
+                if count > *max {
+                    Err(format!(
+                        "contains {} emoji, more than the maximum of {}",
+                        count, max
+                    ))
+                } else {
+                    Ok(())
+                }
+            }
+            config::MessageFilterRule::EmojiName { names } => {
+                for capture in custom_emoji_regex().captures_iter(text) {
+                    let name = capture.get(1).unwrap().as_str();
+                    let substring_match = names.captures(name);
+                    if let Some(substring_match) = substring_match {
+                        return Err(format!(
+                            "contains emoji with denied name substring `{}`",
+                            substring_match.get(0).unwrap().as_str()
+                        ));
+                    }
+                }
+
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Like [`filter_message`](Self::filter_message), but also evaluates rules
+    /// that need to make network requests. These rules always pass in the
+    /// synchronous paths.
+    ///
+    /// Rules can be nested arbitrarily deep, so this returns a boxed future
+    /// rather than being an `async fn`.
+    pub(crate) fn filter_message_async<'a>(
+        &'a self,
+        message: &'a MessageInfo<'_>,
+        web_client: &'a reqwest::Client,
+    ) -> BoxFuture<'a, FilterResult> {
+        async move {
+            match self {
+                config::MessageFilterRule::Invite {
+                    resolve: true,
+                    guilds,
+                    ..
+                } => {
+                    self.filter_message(message)?;
+                    filter_invite_guilds(message.content, guilds, web_client, DISCORD_API_BASE)
+                        .await
+                }
+                config::MessageFilterRule::ExternalScore {
+                    endpoint,
+                    threshold,
+                    timeout_ms,
+                } => {
+                    if message.content.is_empty() {
+                        return Ok(());
+                    }
+
+                    match fetch_external_score(web_client, endpoint, *timeout_ms, message.content)
+                        .await
+                    {
+                        Ok(score) if score > *threshold => Err(format!(
+                            "scored {:.2} on external classifier (threshold {:.2})",
+                            score, threshold
+                        )),
+                        Ok(_) => Ok(()),
+                        Err(err) => {
+                            // Fail open: an unavailable classifier shouldn't
+                            // cause every message to be filtered.
+                            tracing::warn!(?err, %endpoint, "Unable to get external score for message");
+                            Ok(())
+                        }
+                    }
+                }
+                config::MessageFilterRule::All { rules } => {
+                    let mut reasons = Vec::with_capacity(rules.len());
+                    for rule in rules {
+                        match rule.filter_message_async(message, web_client).await {
+                            Ok(()) => return Ok(()),
+                            Err(reason) => reasons.push(reason),
+                        }
+                    }
+
+                    Err(reasons.join(" and "))
+                }
+                config::MessageFilterRule::Not { rule } => {
+                    negate(rule.filter_message_async(message, web_client).await)
+                }
+                _ => self.filter_message(message),
+            }
+        }
+        .boxed()
+    }
+
+    /// The longest `window_seconds` of any `no_repost` rule in this rule,
+    /// including nested rules.
+    fn no_repost_window(&self) -> Option<u64> {
+        match self {
+            config::MessageFilterRule::NoRepost { window_seconds } => Some(*window_seconds),
+            config::MessageFilterRule::All { rules } => rules
+                .iter()
+                .filter_map(config::MessageFilterRule::no_repost_window)
+                .max(),
+            config::MessageFilterRule::Not { rule } => rule.no_repost_window(),
+            _ => None,
+        }
+    }
+
+    /// Gets the tier of the words in `text` that this rule matched, if it's a
+    /// `words` rule with tiers.
+    pub(crate) fn matched_tier(&self, text: &str) -> Option<&str> {
+        match self {
+            config::MessageFilterRule::Words {
+                words,
+                tiers,
+                require_match: false,
+                leetspeak,
+                collapse_spacing,
+                ..
+            } if !tiers.is_empty() => {
+                match_words(words.as_ref(), tiers, text, *leetspeak, *collapse_spacing)
+                    .and_then(|(tier, _)| tier)
+            }
+            _ => None,
+        }
+    }
+
+    /// Redacts anything this rule considers sensitive from `text`, so that it
+    /// isn't repeated in logs or messages.
+    pub(crate) fn redact<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        match self {
+            config::MessageFilterRule::CryptoAddresses {
+                bitcoin,
+                ethereum,
+                solana,
+                monero,
+            } => crypto_address_regexes(*bitcoin, *ethereum, *solana, *monero).fold(
+                Cow::Borrowed(text),
+                |text, (_, regex)| {
+                    let redacted = regex.replace_all(&text, |captures: &regex::Captures| {
+                        let address = captures.get(0).unwrap().as_str();
+                        if is_plausible_crypto_address(address) {
+                            "[redacted]".to_owned()
+                        } else {
+                            address.to_owned()
+                        }
+                    });
+
+                    match redacted {
+                        Cow::Borrowed(_) => text,
+                        Cow::Owned(redacted) => Cow::Owned(redacted),
+                    }
+                },
+            ),
+            config::MessageFilterRule::ContactInfo {
+                emails,
+                phone_numbers,
+            } => contact_info_regexes(*emails, *phone_numbers).fold(
+                Cow::Borrowed(text),
+                |text, (_, regex, is_plausible)| {
+                    let redacted = regex.replace_all(&text, |captures: &regex::Captures| {
+                        let contact = captures.get(0).unwrap();
+                        if is_plausible(&text, &contact) {
+                            "[redacted]".to_owned()
+                        } else {
+                            contact.as_str().to_owned()
+                        }
+                    });
+
+                    match redacted {
+                        Cow::Borrowed(_) => text,
+                        Cow::Owned(redacted) => Cow::Owned(redacted),
+                    }
+                },
+            ),
+            config::MessageFilterRule::All { rules } => {
+                rules
+                    .iter()
+                    .fold(Cow::Borrowed(text), |text, rule| match rule.redact(&text) {
+                        Cow::Borrowed(_) => text,
+                        Cow::Owned(redacted) => Cow::Owned(redacted),
+                    })
+            }
+            _ => Cow::Borrowed(text),
+        }
     }
 
     pub(crate) fn filter_message(&self, message: &MessageInfo<'_>) -> FilterResult {
         match self {
+            config::MessageFilterRule::All { rules } => {
+                all_rules_fail(rules.iter().map(|rule| rule.filter_message(message)))
+            }
+            config::MessageFilterRule::Not { rule } => negate(rule.filter_message(message)),
+            config::MessageFilterRule::Embeds { rules } => {
+                if message.embeds.is_empty() {
+                    return Ok(());
+                }
+
+                let text = embed_text(message.embeds);
+                for rule in rules {
+                    rule.filter_text(&text)
+                        .map_err(|reason| format!("{} (in embed)", reason))?;
+                }
+
+                Ok(())
+            }
             config::MessageFilterRule::MimeType {
                 mode,
                 types,
@@ -260,12 +1293,188 @@ impl config::MessageFilterRule {
                     .filter_map(|a| a.content_type.as_deref());
                 filter_values(mode, "content type", &mut attachment_types, types)
             }
+            config::MessageFilterRule::MimeMismatch { allow_unknown } => {
+                for attachment in message.attachments {
+                    let extension = attachment_extension(&attachment.filename).to_lowercase();
+                    let expected_types = match expected_mime_types(&extension) {
+                        Some(types) => types,
+                        None => continue,
+                    };
+
+                    let content_type = match attachment.content_type.as_deref() {
+                        Some(content_type) => content_type,
+                        None if *allow_unknown => continue,
+                        None => return Err("unknown content type for attachment".to_owned()),
+                    };
+
+                    // Ignore parameters, like `; charset=utf-8`.
+                    let essence = content_type.split(';').next().unwrap_or("").trim();
+                    if !expected_types
+                        .iter()
+                        .any(|expected| expected.eq_ignore_ascii_case(essence))
+                    {
+                        return Err(format!(
+                            "contains attachment with extension {} but content type {}",
+                            extension, content_type
+                        ));
+                    }
+                }
+
+                Ok(())
+            }
+            config::MessageFilterRule::AttachmentName { mode, extensions } => {
+                for attachment in message.attachments {
+                    filter_extension(mode, extensions, &attachment.filename)
+                        .map_err(|reason| format!("contains attachment {}", reason))?;
+                }
+
+                Ok(())
+            }
+            config::MessageFilterRule::AttachmentSize { max_bytes } => {
+                for attachment in message.attachments {
+                    if attachment.size > *max_bytes {
+                        return Err(format!(
+                            "attachment '{}' exceeds size limit ({} > {})",
+                            attachment.filename,
+                            format_size(attachment.size),
+                            format_size(*max_bytes)
+                        ));
+                    }
+                }
+
+                Ok(())
+            }
+            config::MessageFilterRule::CdnLinks {
+                mode,
+                extensions,
+                other_guilds_only,
+            } => {
+                for capture in cdn_link_regex().captures_iter(message.content) {
+                    let host = capture.get(1).unwrap().as_str();
+                    let channel_id = capture.get(2).unwrap().as_str();
+                    let filename = capture.get(3).unwrap().as_str();
+
+                    if *other_guilds_only {
+                        // If we don't know the guild's channels, we can't tell
+                        // where the link points. Let it through.
+                        let from_this_guild = match message.guild_channels {
+                            Some(guild_channels) => guild_channels
+                                .iter()
+                                .any(|channel| channel.to_string() == channel_id),
+                            None => true,
+                        };
+
+                        if from_this_guild {
+                            continue;
+                        }
+                    }
+
+                    filter_extension(mode, extensions, filename).map_err(|reason| {
+                        format!(
+                            "contains link to attachment `{}` {} from {}",
+                            filename, reason, host
+                        )
+                    })?;
+                }
+
+                Ok(())
+            }
+            config::MessageFilterRule::AccountAge {
+                min_account_age_seconds,
+                min_membership_age_seconds,
+            } => {
+                let now = message.timestamp.as_secs();
+
+                if let Some(min_age) = min_account_age_seconds {
+                    let age = now - message.account_created_at.as_secs();
+                    if age < *min_age as i64 {
+                        return Err(format!(
+                            "account is {} seconds old, below the minimum of {} seconds",
+                            age, min_age
+                        ));
+                    }
+                }
+
+                if let (Some(min_age), Some(joined_at)) =
+                    (min_membership_age_seconds, message.joined_at)
+                {
+                    let age = now - joined_at.as_secs();
+                    if age < *min_age as i64 {
+                        return Err(format!(
+                            "member joined {} seconds ago, below the minimum of {} seconds",
+                            age, min_age
+                        ));
+                    }
+                }
+
+                Ok(())
+            }
+            config::MessageFilterRule::Reply { forbid } => {
+                let replied_to = message.reference.and_then(|reference| reference.message_id);
+                match replied_to {
+                    Some(message_id) if *forbid => {
+                        Err(format!("replies to message {}", message_id))
+                    }
+                    _ => Ok(()),
+                }
+            }
+            config::MessageFilterRule::NoRepost { window_seconds } => {
+                let last_message = match message.last_message {
+                    Some(last_message) => last_message,
+                    None => return Ok(()),
+                };
+
+                let sent_at: u64 = message.timestamp.as_micros().try_into().unwrap_or(0);
+                let within_window = sent_at.saturating_sub(last_message.sent_at)
+                    <= window_seconds.saturating_mul(1_000_000);
+                if within_window
+                    && !last_message.content.is_empty()
+                    && last_message.content == normalize_for_similarity(message.content)
+                {
+                    Err(format!(
+                        "repeats message {} sent in this channel less than {} seconds earlier",
+                        last_message.id, window_seconds
+                    ))
+                } else {
+                    Ok(())
+                }
+            }
             config::MessageFilterRule::StickerId { mode, stickers } => filter_values(
                 mode,
                 "sticker",
                 &mut message.stickers.iter().map(|s| s.id),
                 stickers,
             ),
+            config::MessageFilterRule::StickerSource => {
+                // If we haven't been able to fetch the guild's stickers, we
+                // can't tell where a sticker came from. Let it through.
+                if let Some(guild_stickers) = message.guild_stickers {
+                    let foreign_sticker = message
+                        .stickers
+                        .iter()
+                        .find(|s| !guild_stickers.contains(&s.id));
+
+                    if let Some(sticker) = foreign_sticker {
+                        return Err(format!(
+                            "contains sticker `{}` ({}) from another server",
+                            sticker.name, sticker.id
+                        ));
+                    }
+                }
+
+                Ok(())
+            }
+            config::MessageFilterRule::StickerCount { max } => {
+                if message.stickers.len() > *max {
+                    Err(format!(
+                        "contains {} stickers, more than the maximum of {}",
+                        message.stickers.len(),
+                        max
+                    ))
+                } else {
+                    Ok(())
+                }
+            }
             config::MessageFilterRule::StickerName { stickers } => {
                 for sticker in message.stickers.iter() {
                     let substring_match = stickers.captures_iter(&sticker.name).next();
@@ -285,7 +1494,7 @@ impl config::MessageFilterRule {
 }
 
 impl config::ReactionFilter {
-    pub fn filter_reaction(&self, reaction: &ReactionType) -> FilterResult {
+    pub(crate) fn filter_reaction(&self, reaction: &ReactionInfo) -> FilterResult {
         self.rules
             .iter()
             .map(|f| f.filter_reaction(reaction))
@@ -295,7 +1504,9 @@ impl config::ReactionFilter {
 }
 
 impl config::ReactionFilterRule {
-    pub fn filter_reaction(&self, reaction: &ReactionType) -> FilterResult {
+    pub(crate) fn filter_reaction(&self, reaction_info: &ReactionInfo) -> FilterResult {
+        let reaction = &reaction_info.reaction;
+
         match self {
             config::ReactionFilterRule::Default {
                 emoji: filtered_emoji,
@@ -361,6 +1572,22 @@ impl config::ReactionFilterRule {
                     Ok(())
                 }
             }
+            config::ReactionFilterRule::Animated { allow } => match reaction {
+                ReactionType::Custom {
+                    animated: true, id, ..
+                } if !allow => Err(format!("reacted with animated emoji `{}`", id)),
+                _ => Ok(()),
+            },
+            config::ReactionFilterRule::Count { max } => {
+                if reaction_info.reaction_count > *max {
+                    Err(format!(
+                        "reaction count {} exceeds maximum of {}",
+                        reaction_info.reaction_count, max
+                    ))
+                } else {
+                    Ok(())
+                }
+            }
         }
     }
 }
@@ -368,12 +1595,23 @@ impl config::ReactionFilterRule {
 #[derive(Debug)]
 pub struct SpamRecord {
     content: String,
+    /// `content`, normalized with [`normalize_for_similarity`] to compare
+    /// against other messages.
+    normalized_content: String,
     emoji: u8,
     links: u8,
     attachments: u8,
     spoilers: u8,
     mentions: u8,
+    mentioned_users: HashSet<Id<UserMarker>>,
+    stickers: u8,
+    custom_emoji: u8,
+    characters: u16,
+    newlines: u8,
     sent_at: i64,
+    /// Whether this message got its author warned for coming close to a
+    /// threshold.
+    warned: bool,
 }
 
 impl SpamRecord {
@@ -382,11 +1620,13 @@ impl SpamRecord {
         let emoji = emoji_regex().find_iter(message.content).count();
         let links = link_regex().find_iter(message.content).count();
         let mentions = mention_regex().find_iter(message.content).count();
+        let custom_emoji = custom_emoji_regex().find_iter(message.content).count();
 
         SpamRecord {
             // Unfortunately, this clone is necessary, because `message` will be
             // dropped while we still need this.
             content: message.content.to_string(),
+            normalized_content: normalize_for_similarity(message.content),
             emoji: emoji as u8,
             links: links as u8,
             // `as` cast is safe for our purposes. If the message has more than
@@ -394,138 +1634,544 @@ impl SpamRecord {
             attachments: message.attachments.len() as u8,
             spoilers: spoilers as u8,
             mentions: mentions as u8,
+            mentioned_users: message.mentioned_users.iter().copied().collect(),
+            stickers: message.stickers.len() as u8,
+            custom_emoji: custom_emoji as u8,
+            characters: message.content.chars().count().min(u16::MAX as usize) as u16,
+            newlines: message.content.matches('\n').count().min(u8::MAX as usize) as u8,
             sent_at: message.timestamp.as_micros(),
+            warned: false,
         }
     }
 }
 
-pub type SpamHistory = HashMap<Id<UserMarker>, Arc<Mutex<VecDeque<SpamRecord>>>>;
+/// Whose messages a spam history holds: a user's across the whole guild, or
+/// only those in one channel if the spam filter is set to `per_channel`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpamHistoryKey {
+    User(Id<UserMarker>),
+    UserInChannel(Id<UserMarker>, Id<ChannelMarker>),
+}
 
-fn exceeds_spam_thresholds(
+impl SpamHistoryKey {
+    fn for_message(message: &MessageInfo<'_>, config: &config::SpamFilter) -> Self {
+        if config.per_channel {
+            SpamHistoryKey::UserInChannel(message.author_id, message.channel_id)
+        } else {
+            SpamHistoryKey::User(message.author_id)
+        }
+    }
+}
+
+/// Each history has its own async lock, so that checking one user's messages
+/// only waits on other messages from the same user, and doesn't block the
+/// executor thread while it does.
+pub type SpamHistory = HashMap<SpamHistoryKey, Arc<tokio::sync::Mutex<VecDeque<SpamRecord>>>>;
+
+/// Gets how similar two strings are, from 0 (nothing in common) to 1
+/// (identical), based on the Levenshtein distance between them. Gives up and
+/// returns `None` as soon as they're known to be less similar than
+/// `min_similarity`, so that long messages that are nothing alike aren't
+/// compared in full.
+fn similarity(a: &str, b: &str, min_similarity: f32) -> Option<f32> {
+    if a == b {
+        return Some(1.0);
+    }
+
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+
+    // The most edits the strings can be apart while still being similar
+    // enough. This is rounded up, so that floating point error can only make
+    // a comparison take longer, never give up too early.
+    let max_distance = ((1.0 - min_similarity.clamp(0.0, 1.0)) * longest as f32).ceil() as usize;
+    if a.len().abs_diff(b.len()) > max_distance {
+        return None;
+    }
+
+    // Only the previous row of the distance matrix is needed at any point, and
+    // only the cells within `max_distance` of its diagonal, since every path
+    // through the others is too long. Cells outside of that band are left at
+    // `too_far`.
+    let too_far = max_distance + 1;
+    let mut previous: Vec<usize> = (0..=b.len()).map(|j| j.min(too_far)).collect();
+    let mut current = vec![too_far; b.len() + 1];
+    for (i, a_char) in a.iter().enumerate() {
+        let row = i + 1;
+        let start = row.saturating_sub(max_distance);
+        let end = (row + max_distance).min(b.len());
+        if start == 0 {
+            current[0] = row;
+        } else {
+            current[start - 1] = too_far;
+        }
+
+        let mut row_min = current[start.saturating_sub(1)];
+        for j in start.max(1)..=end {
+            let substitution = previous[j - 1] + (*a_char != b[j - 1]) as usize;
+            current[j] = substitution
+                .min(previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(too_far);
+            row_min = row_min.min(current[j]);
+        }
+
+        // The distance can only grow from the closest cell in this row.
+        if row_min > max_distance {
+            return None;
+        }
+
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    let distance = previous[b.len()];
+    if distance > max_distance {
+        return None;
+    }
+
+    Some(1.0 - distance as f32 / longest as f32)
+}
+
+/// Maps fullwidth forms of ASCII characters, such as `ｆ`, to the characters
+/// themselves. These aren't considered confusable, but are an easy way to make
+/// a message look different to a computer and the same to a person.
+fn fold_fullwidth(c: char) -> char {
+    match c {
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+        '\u{3000}' => ' ',
+        _ => c,
+    }
+}
+
+/// Normalizes message content for comparison, so that messages that only
+/// differ by case, whitespace, invisible characters, or confusable and
+/// fullwidth characters are considered the same.
+pub(crate) fn normalize_for_similarity(content: &str) -> String {
+    // Case is folded before finding the skeleton too, as some uppercase
+    // letters are confusable with different lowercase ones (`I` and `l`).
+    let folded: String = content
+        .chars()
+        .map(fold_fullwidth)
+        .collect::<String>()
+        .to_lowercase();
+    crate::confusable::skeletonize(&folded)
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The totals of a spam history, including the message being checked.
+#[derive(Debug)]
+struct SpamTotals {
+    emoji: u8,
+    links: u8,
+    attachments: u8,
+    spoilers: u8,
+    mentions: u8,
+    unique_mentions: usize,
+    stickers: u8,
+    custom_emoji: u8,
+    characters: u32,
+    newlines: u8,
+    duplicates: u8,
+    near_duplicates: usize,
+}
+
+/// Totals up a message's spam record and the spam history before it.
+fn spam_totals(
     history: &VecDeque<SpamRecord>,
     current_record: &SpamRecord,
     config: &config::SpamFilter,
-) -> FilterResult {
-    let (emoji_sum, link_sum, attachment_sum, spoiler_sum, mention_sum, matching_duplicates) =
-        history
+) -> SpamTotals {
+    let is_exact_duplicate = |record: &SpamRecord| {
+        if config.normalize_duplicates {
+            record.normalized_content == current_record.normalized_content
+        } else {
+            record.content == current_record.content
+        }
+    };
+
+    // Each record is compared with the current one at most once, however many
+    // thresholds use the comparison, and only as closely as the least similar
+    // of them needs.
+    let near_duplicate_similarity = config
+        .near_duplicate_similarity
+        .unwrap_or(config::DEFAULT_NEAR_DUPLICATE_SIMILARITY);
+    let min_similarity = match (config.duplicate_similarity, config.near_duplicates) {
+        (Some(duplicate_similarity), Some(_)) => {
+            Some(duplicate_similarity.min(near_duplicate_similarity))
+        }
+        (Some(duplicate_similarity), None) => Some(duplicate_similarity),
+        (None, Some(_)) => Some(near_duplicate_similarity),
+        (None, None) => None,
+    };
+    let similarities: Vec<Option<f32>> = history
+        .iter()
+        .map(|record| {
+            min_similarity.and_then(|min_similarity| {
+                similarity(
+                    &record.normalized_content,
+                    &current_record.normalized_content,
+                    min_similarity,
+                )
+            })
+        })
+        .collect();
+    let is_duplicate =
+        |record: &SpamRecord, similarity: Option<f32>| match config.duplicate_similarity {
+            Some(threshold) => similarity.map_or(false, |similarity| similarity >= threshold),
+            None => is_exact_duplicate(record),
+        };
+
+    let matching_near_duplicates = match config.near_duplicates {
+        Some(_) => history
             .iter()
-            // Start with a value of 1 for matching_duplicates because the current spam record
-            // is always a duplicate of itself.
-            .fold(
+            .zip(&similarities)
+            .filter(|(record, similarity)| {
+                // Exact duplicates are always near-duplicates, even if
+                // they're only identical before normalization.
+                is_exact_duplicate(record)
+                    || similarity
+                        .map_or(false, |similarity| similarity >= near_duplicate_similarity)
+            })
+            .count()
+            .saturating_add(1),
+        None => 0,
+    };
+
+    let (
+        emoji_sum,
+        link_sum,
+        attachment_sum,
+        spoiler_sum,
+        mention_sum,
+        sticker_sum,
+        custom_emoji_sum,
+        character_sum,
+        newline_sum,
+        matching_duplicates,
+    ) = history
+        .iter()
+        .zip(&similarities)
+        // Start with a value of 1 for matching_duplicates because the current spam record
+        // is always a duplicate of itself.
+        .fold(
+            (
+                current_record.emoji,
+                current_record.links,
+                current_record.attachments,
+                current_record.spoilers,
+                current_record.mentions,
+                current_record.stickers,
+                current_record.custom_emoji,
+                // A handful of long messages is enough to overflow a u16.
+                u32::from(current_record.characters),
+                current_record.newlines,
+                1u8,
+            ),
+            |(
+                total_emoji,
+                total_links,
+                total_attachments,
+                total_spoilers,
+                total_mentions,
+                total_stickers,
+                total_custom_emoji,
+                total_characters,
+                total_newlines,
+                total_duplicates,
+            ),
+             (record, &similarity)| {
                 (
-                    current_record.emoji,
-                    current_record.links,
-                    current_record.attachments,
-                    current_record.spoilers,
-                    current_record.mentions,
-                    1u8,
-                ),
-                |(
-                    total_emoji,
-                    total_links,
-                    total_attachments,
-                    total_spoilers,
-                    total_mentions,
-                    total_duplicates,
-                ),
-                 record| {
-                    (
-                        total_emoji.saturating_add(record.emoji),
-                        total_links.saturating_add(record.links),
-                        total_attachments.saturating_add(record.attachments),
-                        total_spoilers.saturating_add(record.spoilers),
-                        total_mentions.saturating_add(record.mentions),
-                        total_duplicates
-                            .saturating_add((record.content == current_record.content) as u8),
-                    )
-                },
-            );
+                    total_emoji.saturating_add(record.emoji),
+                    total_links.saturating_add(record.links),
+                    total_attachments.saturating_add(record.attachments),
+                    total_spoilers.saturating_add(record.spoilers),
+                    total_mentions.saturating_add(record.mentions),
+                    total_stickers.saturating_add(record.stickers),
+                    total_custom_emoji.saturating_add(record.custom_emoji),
+                    total_characters.saturating_add(u32::from(record.characters)),
+                    total_newlines.saturating_add(record.newlines),
+                    total_duplicates.saturating_add(is_duplicate(record, similarity) as u8),
+                )
+            },
+        );
+
+    let unique_mention_count = if config.unique_mentions.is_some() {
+        history
+            .iter()
+            .flat_map(|record| record.mentioned_users.iter())
+            .chain(current_record.mentioned_users.iter())
+            .collect::<HashSet<_>>()
+            .len()
+    } else {
+        0
+    };
 
     tracing::trace!(
-        "Spam summary: {} emoji, {} links, {} attachments, {} spoilers, {} mentions, {} duplicates",
+        "Spam summary: {} emoji, {} links, {} attachments, {} spoilers, {} mentions, {} unique mentions, {} stickers, {} custom emoji, {} characters, {} newlines, {} duplicates",
         emoji_sum,
         link_sum,
         attachment_sum,
         spoiler_sum,
         mention_sum,
+        unique_mention_count,
+        sticker_sum,
+        custom_emoji_sum,
+        character_sum,
+        newline_sum,
         matching_duplicates
     );
 
-    if config.emoji.is_some() && emoji_sum > config.emoji.unwrap() && current_record.emoji > 0 {
-        Err("sent too many emoji".to_owned())
-    } else if config.links.is_some() && link_sum > config.links.unwrap() && current_record.links > 0
-    {
-        Err("sent too many links".to_owned())
-    } else if config.attachments.is_some()
-        && attachment_sum > config.attachments.unwrap()
-        && current_record.attachments > 0
-    {
-        Err("sent too many attachments".to_owned())
-    } else if config.spoilers.is_some()
-        && spoiler_sum > config.spoilers.unwrap()
-        && current_record.spoilers > 0
-    {
-        Err("sent too many spoilers".to_owned())
-    } else if config.mentions.is_some()
-        && mention_sum > config.mentions.unwrap()
-        && current_record.mentions > 0
-    {
-        Err("sent too many mentions".to_owned())
-    } else if config.duplicates.is_some() && matching_duplicates > config.duplicates.unwrap() {
-        Err("sent too many duplicate messages".to_owned())
-    } else {
-        Ok(())
+    SpamTotals {
+        emoji: emoji_sum,
+        links: link_sum,
+        attachments: attachment_sum,
+        spoilers: spoiler_sum,
+        mentions: mention_sum,
+        unique_mentions: unique_mention_count,
+        stickers: sticker_sum,
+        custom_emoji: custom_emoji_sum,
+        characters: character_sum,
+        newlines: newline_sum,
+        duplicates: matching_duplicates,
+        near_duplicates: matching_near_duplicates,
     }
 }
 
-pub(crate) async fn check_spam_record(
-    message: &MessageInfo<'_>,
-    config: &config::SpamFilter,
-    spam_history: Arc<RwLock<SpamHistory>>,
-    now: u64,
-) -> FilterResult {
-    let new_spam_record = SpamRecord::from_message(message);
-    let author_spam_history = {
-        let read_history = spam_history.read().await;
-        // This is tricky: We need to release the read lock, acquire a write lock, and
-        // then insert the new history entry into the map.
-        if !read_history.contains_key(&message.author_id) {
-            drop(read_history);
-
-            let new_history = Arc::new(Mutex::new(VecDeque::new()));
-            let mut write_history = spam_history.write().await;
-            write_history.insert(message.author_id, new_history.clone());
-            new_history
+/// Whether `total` is over `fraction` of `threshold`, if there is one.
+/// `baseline` is the least the total can be for a message that counts towards
+/// the threshold at all. A fraction that rounds down to no more than that would
+/// be passed by every such message, so it's never considered passed; the full
+/// threshold always applies as is.
+fn over_threshold(total: u32, threshold: Option<u32>, fraction: f32, baseline: u32) -> bool {
+    threshold.map_or(false, |threshold| {
+        if fraction >= 1.0 {
+            return total > threshold;
+        }
+
+        let scaled = (threshold as f32 * fraction).floor() as u32;
+        scaled > baseline && total > scaled
+    })
+}
+
+impl SpamTotals {
+    /// Gets the reason for the first threshold that these totals are over
+    /// `fraction` of. Thresholds for things the current record doesn't
+    /// contain are skipped, so that, for instance, a message without emoji
+    /// isn't filtered for the emoji its author sent earlier.
+    fn exceeded_threshold(
+        &self,
+        current_record: &SpamRecord,
+        config: &config::SpamFilter,
+        fraction: f32,
+    ) -> Option<&'static str> {
+        // Every message is a duplicate and near-duplicate of itself, so those
+        // totals start at 1 rather than 0.
+        let over = |total: u32, threshold: Option<u32>, present: bool| {
+            present && over_threshold(total, threshold, fraction, 0)
+        };
+        let over_self =
+            |total: u32, threshold: Option<u32>| over_threshold(total, threshold, fraction, 1);
+
+        if over(
+            self.emoji.into(),
+            config.emoji.map(u32::from),
+            current_record.emoji > 0,
+        ) {
+            Some("sent too many emoji")
+        } else if over(
+            self.links.into(),
+            config.links.map(u32::from),
+            current_record.links > 0,
+        ) {
+            Some("sent too many links")
+        } else if over(
+            self.attachments.into(),
+            config.attachments.map(u32::from),
+            current_record.attachments > 0,
+        ) {
+            Some("sent too many attachments")
+        } else if over(
+            self.spoilers.into(),
+            config.spoilers.map(u32::from),
+            current_record.spoilers > 0,
+        ) {
+            Some("sent too many spoilers")
+        } else if over(
+            self.mentions.into(),
+            config.mentions.map(u32::from),
+            current_record.mentions > 0,
+        ) {
+            Some("sent too many mentions")
+        } else if over(
+            self.unique_mentions as u32,
+            config.unique_mentions.map(u32::from),
+            !current_record.mentioned_users.is_empty(),
+        ) {
+            Some("mentioned too many distinct users")
+        } else if over(
+            self.stickers.into(),
+            config.stickers.map(u32::from),
+            current_record.stickers > 0,
+        ) {
+            Some("sent too many stickers")
+        } else if over(
+            self.custom_emoji.into(),
+            config.custom_emoji.map(u32::from),
+            current_record.custom_emoji > 0,
+        ) {
+            Some("sent too many custom emoji")
+        } else if over(
+            self.characters,
+            config.characters.map(u32::from),
+            current_record.characters > 0,
+        ) {
+            Some("sent too much text")
+        } else if over(
+            self.newlines.into(),
+            config.newlines.map(u32::from),
+            current_record.newlines > 0,
+        ) {
+            Some("sent too many line breaks")
+        } else if over_self(self.duplicates.into(), config.duplicates.map(u32::from)) {
+            Some("sent too many duplicate messages")
+        } else if over_self(
+            self.near_duplicates as u32,
+            config.near_duplicates.map(u32::from),
+        ) {
+            Some("sent too many similar messages")
         } else {
-            read_history.get(&message.author_id).unwrap().clone()
+            None
         }
-    };
+    }
+}
+
+/// The outcome of checking a message against the spam filter.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum SpamCheck {
+    Clean,
+    /// The message came close to a threshold, so its author should be warned.
+    Warn(String),
+    /// The message exceeded a threshold.
+    Exceeded(String),
+}
+
+fn exceeds_spam_thresholds(
+    history: &VecDeque<SpamRecord>,
+    current_record: &SpamRecord,
+    config: &config::SpamFilter,
+) -> SpamCheck {
+    let totals = spam_totals(history, current_record, config);
+    if let Some(reason) = totals.exceeded_threshold(current_record, config, 1.0) {
+        return SpamCheck::Exceeded(reason.to_owned());
+    }
 
-    let mut spam_history = author_spam_history.lock().unwrap();
+    // Users are only warned once per interval, so that they aren't warned
+    // again for every message they send until their history expires.
+    if config.warn_actions.is_some() && !history.iter().any(|record| record.warned) {
+        let fraction = config
+            .warn_threshold_fraction
+            .unwrap_or(config::DEFAULT_SPAM_WARN_THRESHOLD_FRACTION);
+        if let Some(reason) = totals.exceeded_threshold(current_record, config, fraction) {
+            return SpamCheck::Warn(format!("nearly {}", reason));
+        }
+    }
+
+    SpamCheck::Clean
+}
 
+/// Removes the records from a spam history that were sent more than
+/// `interval` seconds before `now`, returning how many were removed.
+fn clear_expired_records(history: &mut VecDeque<SpamRecord>, interval: u16, now: u64) -> usize {
     let mut cleared_count = 0;
-    while let Some(front) = spam_history.front() {
+    while let Some(front) = history.front() {
         if now.saturating_sub(
             front
                 .sent_at
                 .try_into()
                 .expect("Couldn't convert i64 to u64"),
-        ) > (config.interval as u64) * 1_000_000
+        ) > (interval as u64) * 1_000_000
         {
-            spam_history.pop_front();
+            history.pop_front();
             cleared_count += 1;
         } else {
             break;
         }
     }
 
-    tracing::trace!(
-        "Cleared {} spam records for user {}",
-        cleared_count,
-        message.author_id
-    );
+    cleared_count
+}
+
+/// Removes expired records from every spam history, rather than waiting for
+/// each user to send another message, and then removes the histories that are
+/// left empty. `interval` should be the longest spam interval of any guild, so
+/// that no records that are still needed are removed. Returns how many
+/// histories were removed.
+pub(crate) async fn prune_spam_history(
+    spam_history: &RwLock<SpamHistory>,
+    interval: u16,
+    now: u64,
+) -> usize {
+    // Snapshot the histories so that the map isn't locked while each one is
+    // being cleared.
+    let histories: Vec<_> = spam_history
+        .read()
+        .await
+        .iter()
+        .map(|(key, history)| (*key, history.clone()))
+        .collect();
+
+    let mut emptied = HashSet::new();
+    for (key, history) in histories {
+        let mut history = history.lock().await;
+        clear_expired_records(&mut history, interval, now);
+        if history.is_empty() {
+            emptied.insert(key);
+        }
+    }
+
+    let mut spam_history = spam_history.write().await;
+    let before = spam_history.len();
+    spam_history.retain(|key, history| {
+        // A history that's in use might be about to get a new record, so it
+        // has to be kept. Nothing can start using one while the map is locked.
+        !emptied.contains(key)
+            || Arc::strong_count(history) > 1
+            || history
+                .try_lock()
+                .map_or(true, |history| !history.is_empty())
+    });
+
+    before - spam_history.len()
+}
+
+pub(crate) async fn check_spam_record(
+    message: &MessageInfo<'_>,
+    config: &config::SpamFilter,
+    spam_history: Arc<RwLock<SpamHistory>>,
+    now: u64,
+) -> SpamCheck {
+    let mut new_spam_record = SpamRecord::from_message(message);
+    let key = SpamHistoryKey::for_message(message, config);
+    let existing_history = spam_history.read().await.get(&key).cloned();
+    let author_spam_history = match existing_history {
+        Some(history) => history,
+        // Another message from the same user might have created the history
+        // between releasing the read lock and acquiring the write lock, so
+        // only insert one if there still isn't one.
+        None => spam_history.write().await.entry(key).or_default().clone(),
+    };
+
+    let mut spam_history = author_spam_history.lock().await;
+    let cleared_count = clear_expired_records(&mut spam_history, config.interval, now);
+
+    tracing::trace!("Cleared {} spam records for {:?}", cleared_count, key);
 
     let result = exceeds_spam_thresholds(&spam_history, &new_spam_record, config);
+    new_spam_record.warned = matches!(result, SpamCheck::Warn(_));
     spam_history.push_back(new_spam_record);
     result
 }
@@ -538,7 +2184,7 @@ mod test {
 
         use crate::config::Scoping;
 
-        const EMPTY_ROLES: &'static [Id<RoleMarker>] = &[];
+        const EMPTY_ROLES: &[Id<RoleMarker>] = &[];
 
         #[test]
         fn include_channels() {
@@ -546,10 +2192,12 @@ mod test {
                 exclude_channels: None,
                 exclude_roles: None,
                 include_channels: Some(vec![Id::new(1)]),
+                include_roles: None,
+                include_parent_channels: false,
             };
 
-            assert_eq!(scoping.is_included(Id::new(2), EMPTY_ROLES), false);
-            assert_eq!(scoping.is_included(Id::new(1), EMPTY_ROLES), true);
+            assert_eq!(scoping.is_included(Id::new(2), None, EMPTY_ROLES), false);
+            assert_eq!(scoping.is_included(Id::new(1), None, EMPTY_ROLES), true);
         }
 
         #[test]
@@ -558,10 +2206,54 @@ mod test {
                 include_channels: None,
                 exclude_roles: None,
                 exclude_channels: Some(vec![Id::new(1)]),
+                include_roles: None,
+                include_parent_channels: false,
+            };
+
+            assert_eq!(scoping.is_included(Id::new(2), None, EMPTY_ROLES), true);
+            assert_eq!(scoping.is_included(Id::new(1), None, EMPTY_ROLES), false);
+        }
+
+        #[test]
+        fn include_parent_channels() {
+            let mut scoping = Scoping {
+                include_channels: Some(vec![Id::new(1)]),
+                exclude_roles: None,
+                exclude_channels: None,
+                include_roles: None,
+                include_parent_channels: false,
             };
 
-            assert_eq!(scoping.is_included(Id::new(2), EMPTY_ROLES), true);
-            assert_eq!(scoping.is_included(Id::new(1), EMPTY_ROLES), false);
+            // Threads have their own IDs, so they aren't matched by their
+            // parent channel unless include_parent_channels is set.
+            assert_eq!(
+                scoping.is_included(Id::new(3), Some(Id::new(1)), EMPTY_ROLES),
+                false
+            );
+            scoping.include_parent_channels = true;
+            assert_eq!(
+                scoping.is_included(Id::new(3), Some(Id::new(1)), EMPTY_ROLES),
+                true
+            );
+            assert_eq!(
+                scoping.is_included(Id::new(3), Some(Id::new(2)), EMPTY_ROLES),
+                false
+            );
+            assert_eq!(scoping.is_included(Id::new(1), None, EMPTY_ROLES), true);
+
+            let scoping = Scoping {
+                include_channels: None,
+                exclude_channels: Some(vec![Id::new(1)]),
+                ..scoping
+            };
+            assert_eq!(
+                scoping.is_included(Id::new(3), Some(Id::new(1)), EMPTY_ROLES),
+                false
+            );
+            assert_eq!(
+                scoping.is_included(Id::new(3), Some(Id::new(2)), EMPTY_ROLES),
+                true
+            );
         }
 
         #[test]
@@ -570,11 +2262,32 @@ mod test {
                 include_channels: None,
                 exclude_roles: Some(vec![Id::new(1)]),
                 exclude_channels: None,
+                include_roles: None,
+                include_parent_channels: false,
+            };
+
+            assert_eq!(scoping.is_included(Id::new(1), None, EMPTY_ROLES), true);
+            assert_eq!(scoping.is_included(Id::new(1), None, &[Id::new(1)]), false);
+            assert_eq!(scoping.is_included(Id::new(1), None, &[Id::new(2)]), true);
+        }
+
+        #[test]
+        fn include_roles() {
+            let scoping = Scoping {
+                include_channels: None,
+                exclude_roles: None,
+                exclude_channels: None,
+                include_roles: Some(vec![Id::new(1)]),
+                include_parent_channels: false,
             };
 
-            assert_eq!(scoping.is_included(Id::new(1), EMPTY_ROLES), true);
-            assert_eq!(scoping.is_included(Id::new(1), &[Id::new(1)]), false);
-            assert_eq!(scoping.is_included(Id::new(1), &[Id::new(2)]), true);
+            assert_eq!(scoping.is_included(Id::new(1), None, EMPTY_ROLES), false);
+            assert_eq!(scoping.is_included(Id::new(1), None, &[Id::new(1)]), true);
+            assert_eq!(scoping.is_included(Id::new(1), None, &[Id::new(2)]), false);
+            assert_eq!(
+                scoping.is_included(Id::new(1), None, &[Id::new(2), Id::new(1)]),
+                true
+            );
         }
 
         #[test]
@@ -583,33 +2296,44 @@ mod test {
                 include_channels: Some(vec![Id::new(1)]),
                 exclude_channels: None,
                 exclude_roles: Some(vec![Id::new(1)]),
+                include_roles: None,
+                include_parent_channels: false,
             };
 
-            assert_eq!(scoping.is_included(Id::new(1), EMPTY_ROLES), true);
-            assert_eq!(scoping.is_included(Id::new(2), EMPTY_ROLES), false);
-            assert_eq!(scoping.is_included(Id::new(1), &[Id::new(1)]), false);
-            assert_eq!(scoping.is_included(Id::new(2), &[Id::new(1)]), false);
-            assert_eq!(scoping.is_included(Id::new(1), &[Id::new(2)]), true);
-            assert_eq!(scoping.is_included(Id::new(2), &[Id::new(2)]), false);
+            assert_eq!(scoping.is_included(Id::new(1), None, EMPTY_ROLES), true);
+            assert_eq!(scoping.is_included(Id::new(2), None, EMPTY_ROLES), false);
+            assert_eq!(scoping.is_included(Id::new(1), None, &[Id::new(1)]), false);
+            assert_eq!(scoping.is_included(Id::new(2), None, &[Id::new(1)]), false);
+            assert_eq!(scoping.is_included(Id::new(1), None, &[Id::new(2)]), true);
+            assert_eq!(scoping.is_included(Id::new(2), None, &[Id::new(2)]), false);
         }
     }
 
     mod messages {
         use pretty_assertions::assert_eq;
 
-        use regex::{Regex, RegexSet};
+        use regex::Regex;
         use twilight_model::{
-            channel::{message::sticker::MessageSticker, Attachment},
+            channel::{message::sticker::MessageSticker, message::MessageReference, Attachment},
             id::Id,
         };
 
-        use crate::config::{FilterMode, MessageFilterRule};
-        use crate::model::test::{message, BAD_CONTENT, GOOD_CONTENT};
+        use twilight_model::util::Timestamp;
+        use twilight_util::builder::embed::{EmbedBuilder, EmbedFieldBuilder};
+
+        use crate::config::{FilterMode, MessageFilterRule, RegexList, WordTier};
+        use crate::model::test::{attachment, message, message_at_time, BAD_CONTENT, GOOD_CONTENT};
+        use crate::repost::LastMessage;
 
         #[test]
         fn filter_words() {
             let rule = MessageFilterRule::Words {
-                words: Regex::new("\\b(bad|asdf)\\b").unwrap(),
+                words: Some(Regex::new("\\b(bad|asdf)\\b").unwrap()),
+                tiers: vec![],
+                require_match: false,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
             };
 
             assert_eq!(rule.filter_message(&message(GOOD_CONTENT)), Ok(()));
@@ -623,6 +2347,8 @@ mod test {
         fn filter_substrings() {
             let rule = MessageFilterRule::Substring {
                 substrings: Regex::new("(bad|asdf)").unwrap(),
+                leetspeak: false,
+                collapse_spacing: false,
             };
 
             assert_eq!(rule.filter_message(&message(GOOD_CONTENT)), Ok(()));
@@ -635,13 +2361,264 @@ mod test {
         #[test]
         fn filter_regex() {
             let rule = MessageFilterRule::Regex {
-                regexes: RegexSet::new(&["sd"]).unwrap(),
+                regexes: RegexList::new(["sd"]).unwrap(),
+                require_match: false,
+                reason: None,
+                collapse_spacing: false,
+            };
+
+            assert_eq!(rule.filter_message(&message(GOOD_CONTENT)), Ok(()));
+            assert_eq!(
+                rule.filter_message(&message(BAD_CONTENT)),
+                Err("matches regex `sd` on text `sd`".to_owned())
+            );
+
+            let rule = MessageFilterRule::Regex {
+                regexes: RegexList::new(["nothing", "a[0-9]+b"]).unwrap(),
+                require_match: false,
+                reason: None,
+                collapse_spacing: false,
+            };
+
+            assert_eq!(
+                rule.filter_message(&message("see xa123b")),
+                Err("matches regex `a[0-9]+b` on text `a123b`".to_owned())
+            );
+
+            let rule = MessageFilterRule::Regex {
+                regexes: RegexList::with_descriptions([(
+                    "a[0-9]+b",
+                    Some("numbered ab".to_owned()),
+                )])
+                .unwrap(),
+                require_match: false,
+                reason: None,
+                collapse_spacing: false,
+            };
+
+            assert_eq!(
+                rule.filter_message(&message("see xa123b")),
+                Err("matches `numbered ab` on text `a123b`".to_owned())
+            );
+        }
+
+        #[test]
+        fn filter_words_with_invisible_characters() {
+            let rule = MessageFilterRule::Words {
+                words: Some(Regex::new("\\b(bad|asdf)\\b").unwrap()),
+                tiers: vec![],
+                require_match: false,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
+            };
+
+            assert_eq!(
+                rule.filter_message(&message("b\u{200B}a\u{200B}d message")),
+                Err("contains word `bad`".to_owned())
+            );
+            assert_eq!(
+                rule.filter_message(&message("a\u{200C}s\u{FEFF}d\u{200E}f")),
+                Err("contains word `asdf`".to_owned())
+            );
+        }
+
+        #[test]
+        fn filter_word_tiers() {
+            let rule = MessageFilterRule::Words {
+                words: None,
+                tiers: vec![
+                    WordTier {
+                        name: "severe".to_owned(),
+                        words: Regex::new("\\b(evilserver)\\b").unwrap(),
+                    },
+                    WordTier {
+                        name: "mild".to_owned(),
+                        words: Regex::new("\\b(bad)\\b").unwrap(),
+                    },
+                ],
+                require_match: false,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
+            };
+
+            assert_eq!(rule.filter_message(&message(GOOD_CONTENT)), Ok(()));
+            assert_eq!(rule.matched_tier(GOOD_CONTENT), None);
+            // Earlier tiers take precedence, regardless of where the words are
+            // in the message.
+            assert_eq!(
+                rule.filter_message(&message(BAD_CONTENT)),
+                Err("contains word `evilserver`".to_owned())
+            );
+            assert_eq!(rule.matched_tier(BAD_CONTENT), Some("severe"));
+            assert_eq!(rule.matched_tier("bad"), Some("mild"));
+        }
+
+        #[test]
+        fn filter_all() {
+            let rule = MessageFilterRule::All {
+                rules: vec![
+                    MessageFilterRule::Words {
+                        words: Some(Regex::new("\\b(bad)\\b").unwrap()),
+                        tiers: vec![],
+                        require_match: false,
+                        reason: None,
+                        leetspeak: false,
+                        collapse_spacing: false,
+                    },
+                    MessageFilterRule::Link {
+                        mode: FilterMode::DenyList,
+                        domains: vec!["example.com".to_owned()],
+                        path_patterns: vec![],
+                    },
+                ],
+            };
+
+            assert_eq!(rule.filter_message(&message("bad message")), Ok(()));
+            assert_eq!(
+                rule.filter_message(&message("https://example.com/")),
+                Ok(())
+            );
+            assert_eq!(
+                rule.filter_message(&message(BAD_CONTENT)),
+                Err("contains word `bad` and contains denied domain `example.com`".to_owned())
+            );
+        }
+
+        #[test]
+        fn filter_not() {
+            let disclaimer = MessageFilterRule::Not {
+                rule: Box::new(MessageFilterRule::Regex {
+                    regexes: RegexList::new(["(?i)not legal advice"]).unwrap(),
+                    require_match: false,
+                    reason: None,
+                    collapse_spacing: false,
+                }),
+            };
+
+            assert_eq!(
+                disclaimer.filter_message(&message("This is not legal advice.")),
+                Ok(())
+            );
+            assert_eq!(
+                disclaimer.filter_message(&message("You should sue.")),
+                Err("does not satisfy required rule".to_owned())
+            );
+
+            // Only messages with links need the disclaimer.
+            let rule = MessageFilterRule::All {
+                rules: vec![
+                    MessageFilterRule::Link {
+                        mode: FilterMode::DenyList,
+                        domains: vec!["example.com".to_owned()],
+                        path_patterns: vec![],
+                    },
+                    disclaimer,
+                ],
+            };
+
+            assert_eq!(rule.filter_message(&message("You should sue.")), Ok(()));
+            assert_eq!(
+                rule.filter_message(&message("https://example.com/ - this is not legal advice")),
+                Ok(())
+            );
+            assert_eq!(
+                rule.filter_message(&message("https://example.com/")),
+                Err(
+                    "contains denied domain `example.com` and does not satisfy required rule"
+                        .to_owned()
+                )
+            );
+        }
+
+        #[test]
+        fn filter_regex_require_match() {
+            let rule = MessageFilterRule::Regex {
+                regexes: RegexList::new(["(?i)^name: .+", "(?i)^pronouns: .+"]).unwrap(),
+                require_match: true,
+                reason: Some("does not follow the introduction template".to_owned()),
+                collapse_spacing: false,
+            };
+
+            assert_eq!(
+                rule.filter_message(&message("Name: Amaranthine\nPronouns: they/them")),
+                Ok(())
+            );
+            assert_eq!(
+                rule.filter_message(&message("hi everyone")),
+                Err("does not follow the introduction template".to_owned())
+            );
+        }
+
+        #[test]
+        fn filter_words_require_match() {
+            let rule = MessageFilterRule::Words {
+                words: Some(Regex::new("\\b(hello|hi)\\b").unwrap()),
+                tiers: vec![],
+                require_match: true,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
+            };
+
+            assert_eq!(rule.filter_message(&message("hi everyone")), Ok(()));
+            assert_eq!(
+                rule.filter_message(&message(GOOD_CONTENT)),
+                Err("does not contain any required words".to_owned())
+            );
+        }
+
+        #[test]
+        fn filter_regex_custom_reason() {
+            let rule = MessageFilterRule::Regex {
+                regexes: RegexList::new(["sd"]).unwrap(),
+                require_match: false,
+                reason: Some("looks suspicious".to_owned()),
+                collapse_spacing: false,
             };
 
             assert_eq!(rule.filter_message(&message(GOOD_CONTENT)), Ok(()));
             assert_eq!(
                 rule.filter_message(&message(BAD_CONTENT)),
-                Err("matches regex `sd`".to_owned())
+                Err("looks suspicious".to_owned())
+            );
+        }
+
+        #[test]
+        fn filter_emoji_only() {
+            let rule = MessageFilterRule::EmojiOnly { min_text_chars: 3 };
+
+            assert_eq!(rule.filter_message(&message(GOOD_CONTENT)), Ok(()));
+            assert_eq!(rule.filter_message(&message("")), Ok(()));
+            assert_eq!(rule.filter_message(&message("lol 💀💀💀")), Ok(()));
+            assert_eq!(
+                rule.filter_message(&message("💀💀💀")),
+                Err("message is emoji-only".to_owned())
+            );
+            assert_eq!(
+                rule.filter_message(&message("<:skull:123> <a:dance:456>!!")),
+                Err("message is emoji-only".to_owned())
+            );
+            assert_eq!(
+                rule.filter_message(&message("👍🏽 ok")),
+                Err("message is emoji-only".to_owned())
+            );
+            assert_eq!(
+                rule.filter_message(&message("👨‍👩‍👧 ❤️")),
+                Err("message is emoji-only".to_owned())
+            );
+        }
+
+        #[test]
+        fn filter_emoji_count() {
+            let rule = MessageFilterRule::EmojiCount { max: 2 };
+
+            assert_eq!(rule.filter_message(&message(GOOD_CONTENT)), Ok(()));
+            assert_eq!(rule.filter_message(&message("💀 <:skull:123>")), Ok(()));
+            assert_eq!(
+                rule.filter_message(&message("💀💀 <a:dance:456>")),
+                Err("contains 3 emoji, more than the maximum of 2".to_owned())
             );
         }
 
@@ -784,11 +2761,122 @@ mod test {
             );
         }
 
+        #[test]
+        fn filter_mime_mismatch() {
+            let rule = MessageFilterRule::MimeMismatch {
+                allow_unknown: false,
+            };
+
+            let mut ok_message = message(GOOD_CONTENT);
+            let ok_attachments = [
+                attachment("cat.PNG", Some("image/png")),
+                attachment("notes.txt", Some("text/plain; charset=utf-8")),
+                attachment("data.bin", Some("application/octet-stream")),
+            ];
+            ok_message.attachments = &ok_attachments;
+
+            let mut mismatched_message = message(GOOD_CONTENT);
+            let mismatched_attachments = [
+                attachment("cat.jpg", Some("image/jpeg")),
+                attachment("cat.png", Some("application/x-msdownload")),
+            ];
+            mismatched_message.attachments = &mismatched_attachments;
+
+            let mut unknown_message = message(GOOD_CONTENT);
+            let unknown_attachments = [attachment("cat.png", None)];
+            unknown_message.attachments = &unknown_attachments;
+
+            assert_eq!(rule.filter_message(&ok_message), Ok(()));
+            assert_eq!(
+                rule.filter_message(&mismatched_message),
+                Err(
+                    "contains attachment with extension png but content type application/x-msdownload"
+                        .to_owned()
+                )
+            );
+            assert_eq!(
+                rule.filter_message(&unknown_message),
+                Err("unknown content type for attachment".to_owned())
+            );
+
+            let rule = MessageFilterRule::MimeMismatch {
+                allow_unknown: true,
+            };
+            assert_eq!(rule.filter_message(&unknown_message), Ok(()));
+        }
+
+        #[test]
+        fn filter_attachment_name_deny() {
+            let rule = MessageFilterRule::AttachmentName {
+                mode: FilterMode::DenyList,
+                extensions: vec!["exe".to_owned(), ".scr".to_owned()],
+            };
+
+            let mut ok_message = message(GOOD_CONTENT);
+            let ok_attachments = [attachment("cat.png", None)];
+            ok_message.attachments = &ok_attachments;
+
+            let mut wrong_message = message(GOOD_CONTENT);
+            let wrong_attachments = [
+                attachment("cat.png", Some("image/png")),
+                attachment("totally_a_cat.EXE", Some("image/png")),
+            ];
+            wrong_message.attachments = &wrong_attachments;
+
+            let mut dotted_message = message(GOOD_CONTENT);
+            let dotted_attachments = [attachment("screensaver.scr", None)];
+            dotted_message.attachments = &dotted_attachments;
+
+            assert_eq!(rule.filter_message(&ok_message), Ok(()));
+            assert_eq!(
+                rule.filter_message(&wrong_message),
+                Err("contains attachment with denied extension exe".to_owned())
+            );
+            assert_eq!(
+                rule.filter_message(&dotted_message),
+                Err("contains attachment with denied extension scr".to_owned())
+            );
+        }
+
+        #[test]
+        fn filter_attachment_name_allow() {
+            let rule = MessageFilterRule::AttachmentName {
+                mode: FilterMode::AllowList,
+                extensions: vec!["gz".to_owned(), "png".to_owned()],
+            };
+
+            let mut ok_message = message(GOOD_CONTENT);
+            let ok_attachments = [
+                attachment("source.tar.gz", None),
+                attachment("CAT.PNG", None),
+            ];
+            ok_message.attachments = &ok_attachments;
+
+            let mut wrong_message = message(GOOD_CONTENT);
+            let wrong_attachments = [attachment("source.gz.tar", None)];
+            wrong_message.attachments = &wrong_attachments;
+
+            let mut no_extension_message = message(GOOD_CONTENT);
+            let no_extension_attachments = [attachment("README", None)];
+            no_extension_message.attachments = &no_extension_attachments;
+
+            assert_eq!(rule.filter_message(&ok_message), Ok(()));
+            assert_eq!(
+                rule.filter_message(&wrong_message),
+                Err("contains attachment with unallowed extension tar".to_owned())
+            );
+            assert_eq!(
+                rule.filter_message(&no_extension_message),
+                Err("contains attachment without an extension".to_owned())
+            );
+        }
+
         #[test]
         fn filter_domain_deny() {
             let rule = MessageFilterRule::Link {
                 mode: FilterMode::DenyList,
                 domains: vec!["example.com".to_owned()],
+                path_patterns: vec![],
             };
 
             assert_eq!(rule.filter_message(&message(GOOD_CONTENT)), Ok(()));
@@ -798,11 +2886,37 @@ mod test {
             );
         }
 
+        #[test]
+        fn filter_link_paths() {
+            let rule = MessageFilterRule::Link {
+                mode: FilterMode::AllowList,
+                domains: vec!["steamcommunity.com".to_owned()],
+                path_patterns: vec![Regex::new("^/gift/").unwrap()],
+            };
+
+            assert_eq!(
+                rule.filter_message(&message(
+                    "my profile https://steamcommunity.com/id/someone?tab=games"
+                )),
+                Ok(())
+            );
+            assert_eq!(
+                rule.filter_message(&message(
+                    "free nitro https://steamcommunity.com/gift/claim?code=1#top"
+                )),
+                Err(
+                    "contains link to `steamcommunity.com/gift/claim?code=1` matching denied path pattern `^/gift/`"
+                        .to_owned()
+                )
+            );
+        }
+
         #[test]
         fn filter_domain_allow() {
             let rule = MessageFilterRule::Link {
                 mode: FilterMode::AllowList,
                 domains: vec!["discord.gg".to_owned()],
+                path_patterns: vec![],
             };
 
             assert_eq!(rule.filter_message(&message(GOOD_CONTENT)), Ok(()));
@@ -817,6 +2931,8 @@ mod test {
             let rule = MessageFilterRule::Invite {
                 mode: FilterMode::DenyList,
                 invites: vec!["evilserver".to_owned()],
+                resolve: false,
+                guilds: vec![],
             };
 
             assert_eq!(rule.filter_message(&message(GOOD_CONTENT)), Ok(()));
@@ -831,6 +2947,8 @@ mod test {
             let rule = MessageFilterRule::Invite {
                 mode: FilterMode::AllowList,
                 invites: vec!["roblox".to_owned()],
+                resolve: false,
+                guilds: vec![],
             };
 
             assert_eq!(rule.filter_message(&message(GOOD_CONTENT)), Ok(()));
@@ -869,6 +2987,75 @@ mod test {
             );
         }
 
+        #[test]
+        fn filter_attachment_size() {
+            let rule = MessageFilterRule::AttachmentSize {
+                max_bytes: 25 * 1024 * 1024,
+            };
+
+            let attachment = |filename: &str, size| Attachment {
+                content_type: None,
+                ephemeral: false,
+                filename: filename.to_owned(),
+                description: None,
+                height: None,
+                id: Id::new(1),
+                proxy_url: "doesn't_matter".to_owned(),
+                size,
+                url: "doesn't_matter".to_owned(),
+                width: None,
+            };
+
+            let mut good_message = message(GOOD_CONTENT);
+            let good_attachments = [attachment("image.png", 25 * 1024 * 1024)];
+            good_message.attachments = &good_attachments;
+
+            let mut bad_message = message(GOOD_CONTENT);
+            let bad_attachments = [
+                attachment("image.png", 1024),
+                attachment("movie.mkv", 80 * 1024 * 1024),
+            ];
+            bad_message.attachments = &bad_attachments;
+
+            assert_eq!(rule.filter_message(&good_message), Ok(()));
+            assert_eq!(
+                rule.filter_message(&bad_message),
+                Err("attachment 'movie.mkv' exceeds size limit (80MB > 25MB)".to_owned())
+            );
+        }
+
+        #[test]
+        fn format_sizes() {
+            assert_eq!(crate::filter::format_size(0), "0B");
+            assert_eq!(crate::filter::format_size(1536), "1.5KB");
+            assert_eq!(crate::filter::format_size(25 * 1024 * 1024), "25MB");
+        }
+
+        #[test]
+        fn filter_sticker_count() {
+            let rule = MessageFilterRule::StickerCount { max: 1 };
+
+            let sticker = MessageSticker {
+                format_type: twilight_model::channel::message::sticker::StickerFormatType::Apng,
+                id: Id::new(1),
+                name: "sticker".to_owned(),
+            };
+
+            let mut good_message = message(GOOD_CONTENT);
+            let good_stickers = [sticker.clone()];
+            good_message.stickers = &good_stickers;
+
+            let mut bad_message = message(GOOD_CONTENT);
+            let bad_stickers = [sticker.clone(), sticker];
+            bad_message.stickers = &bad_stickers;
+
+            assert_eq!(rule.filter_message(&good_message), Ok(()));
+            assert_eq!(
+                rule.filter_message(&bad_message),
+                Err("contains 2 stickers, more than the maximum of 1".to_owned())
+            );
+        }
+
         #[test]
         fn filter_sticker_id_allow() {
             let rule = MessageFilterRule::StickerId {
@@ -900,75 +3087,899 @@ mod test {
         }
 
         #[test]
-        fn filter_sticker_id_deny() {
-            let rule = MessageFilterRule::StickerId {
-                mode: FilterMode::DenyList,
-                stickers: vec![Id::new(2)],
+        fn filter_sticker_id_deny() {
+            let rule = MessageFilterRule::StickerId {
+                mode: FilterMode::DenyList,
+                stickers: vec![Id::new(2)],
+            };
+
+            let mut good_message = message(GOOD_CONTENT);
+            let good_stickers = [MessageSticker {
+                format_type: twilight_model::channel::message::sticker::StickerFormatType::Apng,
+                id: Id::new(1),
+                name: "goodsticker".to_owned(),
+            }];
+            good_message.stickers = &good_stickers;
+
+            let mut bad_message = message(BAD_CONTENT);
+            let bad_stickers = [MessageSticker {
+                format_type: twilight_model::channel::message::sticker::StickerFormatType::Apng,
+                id: Id::new(2),
+                name: "badsticker".to_owned(),
+            }];
+            bad_message.stickers = &bad_stickers;
+
+            assert_eq!(rule.filter_message(&good_message), Ok(()));
+            assert_eq!(
+                rule.filter_message(&bad_message),
+                Err("contains denied sticker `2`".to_owned())
+            );
+        }
+
+        #[test]
+        fn filter_cdn_links() {
+            let rule = MessageFilterRule::CdnLinks {
+                mode: FilterMode::DenyList,
+                extensions: vec!["exe".to_owned()],
+                other_guilds_only: false,
+            };
+
+            assert_eq!(rule.filter_message(&message(GOOD_CONTENT)), Ok(()));
+            assert_eq!(
+                rule.filter_message(&message(
+                    "https://cdn.discordapp.com/attachments/10/20/cat.png?ex=1"
+                )),
+                Ok(())
+            );
+            assert_eq!(
+                rule.filter_message(&message(
+                    "get it here https://media.discordapp.net/attachments/10/20/Setup.EXE"
+                )),
+                Err("contains link to attachment `Setup.EXE` with denied extension exe from media.discordapp.net".to_owned())
+            );
+        }
+
+        #[test]
+        fn filter_cdn_links_other_guilds_only() {
+            let rule = MessageFilterRule::CdnLinks {
+                mode: FilterMode::DenyList,
+                extensions: vec!["exe".to_owned()],
+                other_guilds_only: true,
+            };
+            let guild_channels = [Id::new(10)];
+
+            let mut local_message =
+                message("https://cdn.discordapp.com/attachments/10/20/setup.exe");
+            local_message.guild_channels = Some(&guild_channels);
+            let mut foreign_message =
+                message("https://cdn.discordapp.com/attachments/11/20/setup.exe");
+            foreign_message.guild_channels = Some(&guild_channels);
+            let unknown_message = message("https://cdn.discordapp.com/attachments/11/20/setup.exe");
+
+            assert_eq!(rule.filter_message(&local_message), Ok(()));
+            assert_eq!(
+                rule.filter_message(&foreign_message),
+                Err("contains link to attachment `setup.exe` with denied extension exe from cdn.discordapp.com".to_owned())
+            );
+            assert_eq!(rule.filter_message(&unknown_message), Ok(()));
+        }
+
+        #[test]
+        fn filter_account_age() {
+            let rule = MessageFilterRule::AccountAge {
+                min_account_age_seconds: Some(60),
+                min_membership_age_seconds: Some(30),
+            };
+
+            // The test message is sent at 100 seconds.
+            let mut old_message = message(GOOD_CONTENT);
+            old_message.account_created_at = Timestamp::from_secs(10).unwrap();
+            old_message.joined_at = Some(Timestamp::from_secs(50).unwrap());
+
+            let mut new_account_message = message(GOOD_CONTENT);
+            new_account_message.account_created_at = Timestamp::from_secs(90).unwrap();
+
+            let mut new_member_message = message(GOOD_CONTENT);
+            new_member_message.joined_at = Some(Timestamp::from_secs(80).unwrap());
+
+            let mut non_member_message = message(GOOD_CONTENT);
+            non_member_message.joined_at = None;
+
+            assert_eq!(rule.filter_message(&old_message), Ok(()));
+            assert_eq!(
+                rule.filter_message(&new_account_message),
+                Err("account is 10 seconds old, below the minimum of 60 seconds".to_owned())
+            );
+            assert_eq!(
+                rule.filter_message(&new_member_message),
+                Err("member joined 20 seconds ago, below the minimum of 30 seconds".to_owned())
+            );
+            assert_eq!(rule.filter_message(&non_member_message), Ok(()));
+        }
+
+        #[test]
+        fn filter_language() {
+            let rule = MessageFilterRule::Language {
+                mode: FilterMode::AllowList,
+                languages: vec!["en".to_owned(), "UK".to_owned()],
+            };
+
+            let english = "Does anyone know when the next update is coming out? I can't find it.";
+            let russian = "Кто-нибудь знает, когда выйдет следующее обновление? Я не могу найти.";
+            assert_eq!(rule.filter_text(english), Ok(()));
+            assert_eq!(
+                rule.filter_text(russian),
+                Err("detected language Russian is not allowed here".to_owned())
+            );
+            // Too short to tell.
+            assert_eq!(rule.filter_text("привет"), Ok(()));
+
+            let rule = MessageFilterRule::Language {
+                mode: FilterMode::DenyList,
+                languages: vec!["ru".to_owned()],
+            };
+            assert_eq!(rule.filter_text(english), Ok(()));
+            assert_eq!(
+                rule.filter_text(russian),
+                Err("detected language Russian is not allowed here".to_owned())
+            );
+        }
+
+        #[test]
+        fn filter_encoded_blob() {
+            let rule = MessageFilterRule::EncodedBlob {
+                min_length: 24,
+                min_entropy: None,
+            };
+
+            for text in [
+                "decode this: VmlzaXQgZnJlZS1uaXRyby5leGFtcGxlL2NsYWltIHRvIGdldCB5b3VyIGdpZnQ=",
+                "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08",
+            ] {
+                assert_eq!(
+                    rule.filter_text(text),
+                    Err("contains a suspicious encoded blob".to_owned()),
+                    "{}",
+                    text
+                );
+            }
+
+            for text in [
+                "Pneumonoultramicroscopicsilicovolcanoconiosis is a long word",
+                "my user ID is 123456789012345678901234",
+                "abc123abc123abc123abc123abc123",
+                "https://cdn.discordapp.com/attachments/123456789012345678/876543210987654321/image.png",
+                "<@123456789012345678> <a:party_parrot_dance_fast:876543210987654321>",
+                "VmlzaXQgZnJlZS1uaXRyby5",
+            ] {
+                assert_eq!(rule.filter_text(text), Ok(()), "{}", text);
+            }
+
+            // Raising the entropy threshold lets hex through, but not base64.
+            let rule = MessageFilterRule::EncodedBlob {
+                min_length: 24,
+                min_entropy: Some(4.5),
+            };
+            assert_eq!(
+                rule.filter_text(
+                    "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+                ),
+                Ok(())
+            );
+            assert_eq!(
+                rule.filter_text(
+                    "VmlzaXQgZnJlZS1uaXRyby5leGFtcGxlL2NsYWltIHRvIGdldCB5b3VyIGdpZnQ="
+                ),
+                Err("contains a suspicious encoded blob".to_owned())
+            );
+        }
+
+        #[test]
+        fn filter_no_repost() {
+            let rule = MessageFilterRule::NoRepost { window_seconds: 60 };
+            let last_message = LastMessage {
+                id: Id::new(5),
+                content: "hello there".to_owned(),
+                sent_at: 50_000_000,
+            };
+
+            // The test message is sent at 100 seconds.
+            let mut repost = message("Hello  there");
+            repost.last_message = Some(&last_message);
+
+            let mut different = message(GOOD_CONTENT);
+            different.last_message = Some(&last_message);
+
+            let mut late_repost = message_at_time("hello there", 200);
+            late_repost.last_message = Some(&last_message);
+
+            assert_eq!(
+                rule.filter_message(&repost),
+                Err(
+                    "repeats message 5 sent in this channel less than 60 seconds earlier"
+                        .to_owned()
+                )
+            );
+            assert_eq!(rule.filter_message(&message("hello there")), Ok(()));
+            assert_eq!(rule.filter_message(&different), Ok(()));
+            assert_eq!(rule.filter_message(&late_repost), Ok(()));
+        }
+
+        #[test]
+        fn filter_reply() {
+            let rule = MessageFilterRule::Reply { forbid: true };
+            let reference = MessageReference {
+                channel_id: Some(crate::model::test::CHANNEL_ID),
+                guild_id: Some(crate::model::test::GUILD_ID),
+                message_id: Some(Id::new(5)),
+                fail_if_not_exists: None,
+            };
+
+            let mut reply = message(GOOD_CONTENT);
+            reply.reference = Some(&reference);
+
+            assert_eq!(rule.filter_message(&message(GOOD_CONTENT)), Ok(()));
+            assert_eq!(
+                rule.filter_message(&reply),
+                Err("replies to message 5".to_owned())
+            );
+            assert_eq!(
+                MessageFilterRule::Reply { forbid: false }.filter_message(&reply),
+                Ok(())
+            );
+        }
+
+        #[test]
+        fn filter_sticker_source() {
+            let rule = MessageFilterRule::StickerSource;
+            let guild_stickers = [Id::new(1)];
+
+            let mut good_message = message(GOOD_CONTENT);
+            let good_stickers = [MessageSticker {
+                format_type: twilight_model::channel::message::sticker::StickerFormatType::Apng,
+                id: Id::new(1),
+                name: "goodsticker".to_owned(),
+            }];
+            good_message.stickers = &good_stickers;
+            good_message.guild_stickers = Some(&guild_stickers);
+
+            let mut foreign_message = message(BAD_CONTENT);
+            let foreign_stickers = [MessageSticker {
+                format_type: twilight_model::channel::message::sticker::StickerFormatType::Apng,
+                id: Id::new(2),
+                name: "foreignsticker".to_owned(),
+            }];
+            foreign_message.stickers = &foreign_stickers;
+            foreign_message.guild_stickers = Some(&guild_stickers);
+
+            let mut unfetched_message = message(BAD_CONTENT);
+            unfetched_message.stickers = &foreign_stickers;
+
+            assert_eq!(rule.filter_message(&good_message), Ok(()));
+            assert_eq!(
+                rule.filter_message(&foreign_message),
+                Err("contains sticker `foreignsticker` (2) from another server".to_owned())
+            );
+            assert_eq!(rule.filter_message(&unfetched_message), Ok(()));
+        }
+
+        #[test]
+        fn filter_external_invites() {
+            let rule = MessageFilterRule::ExternalInvites {
+                telegram: true,
+                whatsapp: true,
+                guilded: true,
+                revolt: true,
+                matrix: true,
+            };
+
+            assert_eq!(rule.filter_message(&message(GOOD_CONTENT)), Ok(()));
+            assert_eq!(
+                rule.filter_message(&message("join https://t.me/+AbCdEf123 for free nitro")),
+                Err("contains Telegram invite `t.me/+AbCdEf123`".to_owned())
+            );
+            assert_eq!(
+                rule.filter_message(&message("chat.whatsapp.com/KxYz987")),
+                Err("contains WhatsApp invite `chat.whatsapp.com/KxYz987`".to_owned())
+            );
+            assert_eq!(
+                rule.filter_message(&message("https://www.guilded.gg/i/2XyZabcd")),
+                Err("contains Guilded invite `guilded.gg/i/2XyZabcd`".to_owned())
+            );
+            assert_eq!(
+                rule.filter_message(&message("rvlt.gg/Testers")),
+                Err("contains Revolt invite `rvlt.gg/Testers`".to_owned())
+            );
+            assert_eq!(
+                rule.filter_message(&message("https://matrix.to/#/#room:example.org")),
+                Err("contains Matrix invite `matrix.to/#/#room:example.org`".to_owned())
+            );
+            // Similar-looking hosts aren't matched.
+            assert_eq!(
+                rule.filter_message(&message("https://chat.me/abc and https://art.me/abc")),
+                Ok(())
+            );
+        }
+
+        #[test]
+        fn filter_external_invites_only_enabled_platforms() {
+            let rule = MessageFilterRule::ExternalInvites {
+                telegram: false,
+                whatsapp: true,
+                guilded: false,
+                revolt: false,
+                matrix: false,
+            };
+
+            assert_eq!(rule.filter_message(&message("t.me/somechannel")), Ok(()));
+        }
+
+        #[test]
+        fn filter_crypto_addresses() {
+            let rule = MessageFilterRule::CryptoAddresses {
+                bitcoin: true,
+                ethereum: true,
+                solana: true,
+                monero: true,
+            };
+
+            assert_eq!(
+                rule.filter_message(&message(
+                    "send btc to 1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2 pls"
+                )),
+                Err("contains Bitcoin address".to_owned())
+            );
+            assert_eq!(
+                rule.filter_message(&message("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq")),
+                Err("contains Bitcoin address".to_owned())
+            );
+            assert_eq!(
+                rule.filter_message(&message("eth: 0x71C7656EC7ab88b098defB751B7401B5f6d8976F")),
+                Err("contains Ethereum address".to_owned())
+            );
+            assert_eq!(
+                rule.filter_message(&message("sol: 7EcDhSYGxXyscszYEp35KHN8vvw3svAuLKTzXwCFLtV")),
+                Err("contains Solana address".to_owned())
+            );
+            assert_eq!(
+                rule.filter_message(&message(
+                    "xmr: 4AdUndXHHZ6cfufTMvppY6JwXNouMBzSkbLYfpAV5Usx3skxNgYeYTRj5UzqtReoS44qo9mtmXCqY45DJ852K5Jv2684Rge"
+                )),
+                Err("contains Monero address".to_owned())
+            );
+        }
+
+        #[test]
+        fn filter_crypto_addresses_only_enabled_chains() {
+            let rule = MessageFilterRule::CryptoAddresses {
+                bitcoin: false,
+                ethereum: true,
+                solana: false,
+                monero: false,
+            };
+
+            assert_eq!(
+                rule.filter_message(&message("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2")),
+                Ok(())
+            );
+        }
+
+        #[test]
+        fn filter_crypto_addresses_ignores_ids_and_hashes() {
+            let rule = MessageFilterRule::CryptoAddresses {
+                bitcoin: true,
+                ethereum: true,
+                solana: true,
+                monero: true,
+            };
+
+            // Discord snowflakes.
+            assert_eq!(
+                rule.filter_message(&message("<@165243529931128832> in 1043681520359927848")),
+                Ok(())
+            );
+            // Git commit hashes, full and abbreviated.
+            assert_eq!(
+                rule.filter_message(&message(
+                    "fixed in 3c5b2a9f1e7d4c8b6a0f9e2d1c3b5a7f9e1d2c4b, see 3c5b2a9"
+                )),
+                Ok(())
+            );
+            // Hex strings that aren't prefixed like Ethereum addresses.
+            assert_eq!(
+                rule.filter_message(&message(
+                    "sha256 e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+                )),
+                Ok(())
+            );
+        }
+
+        #[test]
+        fn redact_crypto_addresses() {
+            let rule = MessageFilterRule::CryptoAddresses {
+                bitcoin: true,
+                ethereum: true,
+                solana: false,
+                monero: false,
+            };
+
+            assert_eq!(
+                rule.redact(
+                    "pay 1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2 or 0x71C7656EC7ab88b098defB751B7401B5f6d8976F"
+                ),
+                "pay [redacted] or [redacted]"
+            );
+            assert_eq!(
+                rule.redact("commit 3c5b2a9f1e7d4c8b6a0f9e2d1c3b5a7f9e1d2c4b"),
+                "commit 3c5b2a9f1e7d4c8b6a0f9e2d1c3b5a7f9e1d2c4b"
+            );
+        }
+
+        #[test]
+        fn filter_contact_info() {
+            let rule = MessageFilterRule::ContactInfo {
+                emails: true,
+                phone_numbers: true,
+            };
+
+            for content in [
+                "dm me at john.doe@gmail.com",
+                "john (at) gmail [dot] com",
+                "john@g\u{200B}m\u{0430}il.com",
+            ] {
+                assert_eq!(
+                    rule.filter_message(&message(content)),
+                    Err("contains email address".to_owned()),
+                    "{}",
+                    content
+                );
+            }
+
+            for content in [
+                "call 555-123-4567",
+                "call (555) 123-4567 now",
+                "555.123.4567.",
+                "+1 555 123 4567",
+                "+44 20 7946 0958",
+                "+15551234567",
+            ] {
+                assert_eq!(
+                    rule.filter_message(&message(content)),
+                    Err("contains phone number".to_owned()),
+                    "{}",
+                    content
+                );
+            }
+        }
+
+        #[test]
+        fn filter_contact_info_only_enabled_kinds() {
+            let rule = MessageFilterRule::ContactInfo {
+                emails: false,
+                phone_numbers: true,
+            };
+
+            assert_eq!(rule.filter_message(&message("john@gmail.com")), Ok(()));
+
+            let rule = MessageFilterRule::ContactInfo {
+                emails: true,
+                phone_numbers: false,
+            };
+
+            assert_eq!(rule.filter_message(&message("call 555-123-4567")), Ok(()));
+        }
+
+        #[test]
+        fn filter_contact_info_ignores_numbers() {
+            let rule = MessageFilterRule::ContactInfo {
+                emails: true,
+                phone_numbers: true,
+            };
+
+            for content in [
+                // Version numbers.
+                "updated to v1.2.3, then 10.15.7 and 2023.10.1",
+                "rustc 1.73.0 (cc66ad468 2023-10-03)",
+                // Dates and timestamps.
+                "2023-10-16 12:30:45",
+                "16.10.2023 14:30",
+                "2023-10-16T12:30:45Z",
+                "<t:1697459400:R>",
+                // IP addresses.
+                "connect to 192.168.100.200",
+                // Discord mentions, IDs, and plain numbers.
+                "<@165243529931128832> in 1043681520359927848",
+                "pi is 3.14159265358979",
+                "it costs 1234 or 5678",
+                // Mentions and handles that look a little like emails.
+                "meet me @home.",
+                "@everyone check this",
+            ] {
+                assert_eq!(
+                    rule.filter_message(&message(content)),
+                    Ok(()),
+                    "{}",
+                    content
+                );
+            }
+        }
+
+        #[test]
+        fn redact_contact_info() {
+            let rule = MessageFilterRule::ContactInfo {
+                emails: true,
+                phone_numbers: true,
+            };
+
+            assert_eq!(
+                rule.redact("mail john@gmail.com or call 555-123-4567 after 2023-10-16 12:30"),
+                "mail [redacted] or call [redacted] after 2023-10-16 12:30"
+            );
+        }
+
+        /// Serves a single HTTP response with the given body, returning the URL
+        /// to request.
+        async fn serve_once(body: &'static str) -> String {
+            use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap();
+
+            tokio::spawn(async move {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buffer = [0; 4096];
+                let _ = stream.read(&mut buffer).await;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            });
+
+            format!("http://{}/", address)
+        }
+
+        #[tokio::test]
+        async fn filter_invites_by_guild() {
+            let web_client = reqwest::Client::new();
+
+            let api_base = serve_once(r#"{"code": "rotated", "guild": {"id": "123"}}"#).await;
+            assert_eq!(
+                crate::filter::filter_invite_guilds(
+                    "join discord.gg/rotated",
+                    &[Id::new(123)],
+                    &web_client,
+                    &api_base
+                )
+                .await,
+                Err("contains invite `rotated` to denied guild 123".to_owned())
+            );
+
+            // The server only answers once, so this has to come from the cache.
+            assert_eq!(
+                crate::filter::resolve_invite(&web_client, &api_base, "rotated").await,
+                Some(Id::new(123))
+            );
+
+            let api_base = serve_once(r#"{"code": "friendly", "guild": {"id": "456"}}"#).await;
+            assert_eq!(
+                crate::filter::filter_invite_guilds(
+                    "join discord.gg/friendly",
+                    &[Id::new(123)],
+                    &web_client,
+                    &api_base
+                )
+                .await,
+                Ok(())
+            );
+        }
+
+        #[tokio::test]
+        async fn filter_invites_by_guild_fails_open() {
+            let web_client = reqwest::Client::new();
+
+            let api_base = serve_once("not json").await;
+            assert_eq!(
+                crate::filter::filter_invite_guilds(
+                    "join discord.gg/broken",
+                    &[Id::new(123)],
+                    &web_client,
+                    &api_base
+                )
+                .await,
+                Ok(())
+            );
+        }
+
+        #[tokio::test]
+        async fn filter_external_score() {
+            let web_client = reqwest::Client::new();
+
+            let rule = MessageFilterRule::ExternalScore {
+                endpoint: serve_once(r#"{"score": 0.9}"#).await,
+                threshold: 0.8,
+                timeout_ms: 5_000,
+            };
+            assert_eq!(
+                rule.filter_message_async(&message(BAD_CONTENT), &web_client)
+                    .await,
+                Err("scored 0.90 on external classifier (threshold 0.80)".to_owned())
+            );
+
+            let rule = MessageFilterRule::ExternalScore {
+                endpoint: serve_once(r#"{"score": 0.1}"#).await,
+                threshold: 0.8,
+                timeout_ms: 5_000,
+            };
+            assert_eq!(
+                rule.filter_message_async(&message(GOOD_CONTENT), &web_client)
+                    .await,
+                Ok(())
+            );
+        }
+
+        #[tokio::test]
+        async fn filter_external_score_fails_open() {
+            let web_client = reqwest::Client::new();
+
+            // Nothing is listening on this port once the listener is dropped.
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let unreachable = format!("http://{}/", listener.local_addr().unwrap());
+            drop(listener);
+
+            let rule = MessageFilterRule::ExternalScore {
+                endpoint: unreachable,
+                threshold: 0.8,
+                timeout_ms: 5_000,
+            };
+            assert_eq!(
+                rule.filter_message_async(&message(BAD_CONTENT), &web_client)
+                    .await,
+                Ok(())
+            );
+
+            // This listener accepts connections but never responds.
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let rule = MessageFilterRule::ExternalScore {
+                endpoint: format!("http://{}/", listener.local_addr().unwrap()),
+                threshold: 0.8,
+                timeout_ms: 50,
+            };
+            assert_eq!(
+                rule.filter_message_async(&message(BAD_CONTENT), &web_client)
+                    .await,
+                Ok(())
+            );
+
+            let rule = MessageFilterRule::ExternalScore {
+                endpoint: serve_once("not json").await,
+                threshold: 0.8,
+                timeout_ms: 5_000,
+            };
+            assert_eq!(
+                rule.filter_message_async(&message(BAD_CONTENT), &web_client)
+                    .await,
+                Ok(())
+            );
+        }
+
+        #[test]
+        fn filter_embeds() {
+            let rule = MessageFilterRule::Embeds {
+                rules: vec![MessageFilterRule::Words {
+                    words: Some(Regex::new("\\b(bad|asdf)\\b").unwrap()),
+                    tiers: vec![],
+                    require_match: false,
+                    reason: None,
+                    leetspeak: false,
+                    collapse_spacing: false,
+                }],
+            };
+
+            let good_embeds = [EmbedBuilder::new()
+                .title("Server rules")
+                .description("be nice")
+                .build()];
+            let bad_embeds = [
+                EmbedBuilder::new().title("hello").build(),
+                EmbedBuilder::new()
+                    .title("Free nitro")
+                    .field(EmbedFieldBuilder::new("Claim now", "bad link"))
+                    .build(),
+            ];
+
+            // Embed rules don't look at message content.
+            let mut good_message = message(BAD_CONTENT);
+            good_message.embeds = &good_embeds;
+            let mut bad_message = message(GOOD_CONTENT);
+            bad_message.embeds = &bad_embeds;
+
+            assert_eq!(rule.filter_message(&message(BAD_CONTENT)), Ok(()));
+            assert_eq!(rule.filter_message(&good_message), Ok(()));
+            assert_eq!(
+                rule.filter_message(&bad_message),
+                Err("contains word `bad` (in embed)".to_owned())
+            );
+        }
+
+        #[test]
+        fn filter_words_with_skeletonization() {
+            let rule = MessageFilterRule::Words {
+                words: Some(Regex::new("\\b(bad)\\b").unwrap()),
+                tiers: vec![],
+                require_match: false,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
+            };
+
+            assert_eq!(
+                rule.filter_message(&message("b⍺d message")),
+                Err("contains word `bad`".to_owned())
+            );
+        }
+
+        #[test]
+        fn filter_substrings_with_skeletonization() {
+            let rule = MessageFilterRule::Substring {
+                substrings: Regex::new("(bad)").unwrap(),
+                leetspeak: false,
+                collapse_spacing: false,
+            };
+
+            assert_eq!(
+                rule.filter_message(&message("b⍺dmessage")),
+                Err("contains substring `bad`".to_owned())
+            );
+        }
+
+        #[test]
+        fn filter_words_with_leetspeak() {
+            let mut rule = MessageFilterRule::Words {
+                words: Some(Regex::new("\\b(bad|hello|kill|ass)\\b").unwrap()),
+                tiers: vec![],
+                require_match: false,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
+            };
+
+            assert_eq!(rule.filter_message(&message("b4d message")), Ok(()));
+
+            if let MessageFilterRule::Words { leetspeak, .. } = &mut rule {
+                *leetspeak = true;
+            }
+
+            assert_eq!(
+                rule.filter_message(&message("b4d message")),
+                Err("contains word `bad`".to_owned())
+            );
+            assert_eq!(
+                rule.filter_message(&message("h3ll0 there")),
+                Err("contains word `hello`".to_owned())
+            );
+            // 1 can stand for either i or l.
+            assert_eq!(
+                rule.filter_message(&message("k1ll it")),
+                Err("contains word `kill`".to_owned())
+            );
+            assert_eq!(
+                rule.filter_message(&message("what an @ss")),
+                Err("contains word `ass`".to_owned())
+            );
+            // Numbers on their own aren't words, so they aren't changed.
+            assert_eq!(
+                rule.filter_message(&message("I owe you $4.00 for 840")),
+                Ok(())
+            );
+        }
+
+        #[test]
+        fn filter_substrings_with_leetspeak() {
+            let rule = MessageFilterRule::Substring {
+                substrings: Regex::new("(freenitro)").unwrap(),
+                leetspeak: true,
+                collapse_spacing: false,
+            };
+
+            assert_eq!(
+                rule.filter_message(&message("get fr33n1tr0 here")),
+                Err("contains substring `freenitro`".to_owned())
+            );
+            assert_eq!(rule.filter_message(&message("free 1000 nitro")), Ok(()));
+        }
+
+        #[test]
+        fn filter_words_with_collapsed_spacing() {
+            let mut rule = MessageFilterRule::Words {
+                words: Some(Regex::new("\\b(nitro|free nitro|usa|ass)\\b").unwrap()),
+                tiers: vec![],
+                require_match: false,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
             };
 
-            let mut good_message = message(GOOD_CONTENT);
-            let good_stickers = [MessageSticker {
-                format_type: twilight_model::channel::message::sticker::StickerFormatType::Apng,
-                id: Id::new(1),
-                name: "goodsticker".to_owned(),
-            }];
-            good_message.stickers = &good_stickers;
+            assert_eq!(
+                rule.filter_message(&message("get f r e e   n i t r o now")),
+                Ok(())
+            );
 
-            let mut bad_message = message(BAD_CONTENT);
-            let bad_stickers = [MessageSticker {
-                format_type: twilight_model::channel::message::sticker::StickerFormatType::Apng,
-                id: Id::new(2),
-                name: "badsticker".to_owned(),
-            }];
-            bad_message.stickers = &bad_stickers;
+            if let MessageFilterRule::Words {
+                collapse_spacing, ..
+            } = &mut rule
+            {
+                *collapse_spacing = true;
+            }
 
-            assert_eq!(rule.filter_message(&good_message), Ok(()));
+            // Wider gaps between the characters are read as word breaks.
             assert_eq!(
-                rule.filter_message(&bad_message),
-                Err("contains denied sticker `2`".to_owned())
+                rule.filter_message(&message("get f r e e   n i t r o now")),
+                Err("contains word `free nitro`".to_owned())
+            );
+            assert_eq!(
+                rule.filter_message(&message("claim your n.i.t.r.o")),
+                Err("contains word `nitro`".to_owned())
+            );
+            // Short runs, like spaced-out acronyms, aren't collapsed.
+            assert_eq!(rule.filter_message(&message("made in the U S A")), Ok(()));
+            assert_eq!(
+                rule.filter_message(&message("t h e   a s s e s s m e n t")),
+                Ok(())
+            );
+            assert_eq!(
+                rule.filter_message(&message("options a, b, c or d")),
+                Ok(())
             );
         }
 
         #[test]
-        fn filter_words_with_skeletonization() {
-            let rule = MessageFilterRule::Words {
-                words: Regex::new("\\b(bad)\\b").unwrap(),
+        fn filter_substrings_and_regexes_with_collapsed_spacing() {
+            let substring = MessageFilterRule::Substring {
+                substrings: Regex::new("(freenitro)").unwrap(),
+                leetspeak: true,
+                collapse_spacing: true,
             };
 
             assert_eq!(
-                rule.filter_message(&message("b⍺d message")),
-                Err("contains word `bad`".to_owned())
+                substring.filter_message(&message("f-r-3-3-n-1-t-r-0")),
+                Err("contains substring `freenitro`".to_owned())
             );
-        }
 
-        #[test]
-        fn filter_substrings_with_skeletonization() {
-            let rule = MessageFilterRule::Substring {
-                substrings: Regex::new("(bad)").unwrap(),
+            let regex = MessageFilterRule::Regex {
+                regexes: RegexList::new(["free\\s*nitro"]).unwrap(),
+                require_match: false,
+                reason: None,
+                collapse_spacing: true,
             };
 
             assert_eq!(
-                rule.filter_message(&message("b⍺dmessage")),
-                Err("contains substring `bad`".to_owned())
+                regex.filter_message(&message("f r e e  n i t r o")),
+                Err("matches regex `free\\s*nitro` on text `free nitro`".to_owned())
             );
+            assert_eq!(regex.filter_message(&message("f r e e and nitro")), Ok(()));
         }
 
         #[test]
         fn filter_regex_with_skeletonization() {
             let rule = MessageFilterRule::Regex {
-                regexes: RegexSet::new(&["bad"]).unwrap(),
+                regexes: RegexList::new(["bad"]).unwrap(),
+                require_match: false,
+                reason: None,
+                collapse_spacing: false,
             };
 
             assert_eq!(
                 rule.filter_message(&message("b⍺dmessage")),
-                Err("matches regex `bad`".to_owned())
+                Err("matches regex `bad` on text `bad`".to_owned())
             );
         }
     }
 
     mod spam {
         use std::{
-            collections::{HashMap, VecDeque},
+            collections::{HashMap, HashSet, VecDeque},
             sync::Arc,
         };
 
@@ -979,7 +3990,10 @@ mod test {
 
         use crate::{
             config::SpamFilter,
-            filter::{exceeds_spam_thresholds, SpamRecord},
+            filter::{
+                exceeds_spam_thresholds, normalize_for_similarity, similarity, SpamCheck,
+                SpamHistoryKey, SpamRecord,
+            },
             model::MessageInfo,
         };
 
@@ -994,10 +4008,21 @@ mod test {
                 channel_id: Id::new(1),
                 guild_id: Id::new(1),
                 author_roles: &[],
+                account_created_at: Timestamp::from_secs(0).unwrap(),
+                joined_at: None,
                 content: "test message https://discord.gg/ ||spoiler|| 💟 <@123>",
                 timestamp: Timestamp::from_secs(100).unwrap(),
                 attachments: &[],
                 stickers: &[],
+                embeds: &[],
+                guild_stickers: None,
+                guild_channels: None,
+                reference: None,
+                mentioned_users: &[],
+                previous_content: None,
+                is_webhook: false,
+                last_message: None,
+                parent_channel_id: None,
             };
 
             let attachments = [Attachment {
@@ -1029,23 +4054,43 @@ mod test {
             let config = SpamFilter {
                 emoji: Some(2),
                 duplicates: Some(1),
+                duplicate_similarity: None,
+                near_duplicates: None,
+                near_duplicate_similarity: None,
                 links: Some(2),
                 attachments: Some(2),
                 spoilers: Some(2),
                 mentions: Some(2),
+                unique_mentions: None,
+                stickers: Some(2),
+                custom_emoji: Some(2),
+                characters: Some(100),
+                newlines: Some(2),
                 interval: 30,
+                per_channel: false,
                 actions: None,
                 scoping: None,
+                raid: None,
+                warn_actions: None,
+                warn_threshold_fraction: None,
+                normalize_duplicates: true,
             };
 
             let initial_record = SpamRecord {
                 content: "asdf".to_owned(),
+                normalized_content: normalize_for_similarity("asdf"),
                 spoilers: 1,
                 emoji: 1,
                 links: 1,
                 mentions: 1,
+                mentioned_users: HashSet::new(),
                 attachments: 1,
+                stickers: 1,
+                custom_emoji: 1,
+                characters: 4,
+                newlines: 1,
                 sent_at: 0,
+                warned: false,
             };
 
             history.push_back(initial_record);
@@ -1059,16 +4104,23 @@ mod test {
 
             let succeeding_record = SpamRecord {
                 content: "not asdf".to_owned(),
+                normalized_content: normalize_for_similarity("not asdf"),
                 spoilers: 0,
                 emoji: 0,
                 links: 0,
                 mentions: 0,
+                mentioned_users: HashSet::new(),
                 attachments: 0,
+                stickers: 0,
+                custom_emoji: 0,
+                characters: 0,
+                newlines: 0,
                 sent_at: 10,
+                warned: false,
             };
 
             let result = exceeds_spam_thresholds(&history, &succeeding_record, &config);
-            assert_eq!(result, Ok(()))
+            assert_eq!(result, SpamCheck::Clean)
         }
 
         #[test]
@@ -1077,16 +4129,175 @@ mod test {
 
             let failing_record = SpamRecord {
                 content: "asdf".to_owned(),
+                normalized_content: normalize_for_similarity("asdf"),
+                spoilers: 0,
+                emoji: 0,
+                links: 0,
+                mentions: 0,
+                mentioned_users: HashSet::new(),
+                attachments: 0,
+                stickers: 0,
+                custom_emoji: 0,
+                characters: 0,
+                newlines: 0,
+                sent_at: 10,
+                warned: false,
+            };
+
+            let result = exceeds_spam_thresholds(&history, &failing_record, &config);
+            assert_eq!(
+                result,
+                SpamCheck::Exceeded("sent too many duplicate messages".to_owned())
+            );
+        }
+
+        #[test]
+        fn fuzzy_content_spam_checker() {
+            let (history, mut config) = setup_for_testing();
+            config.duplicate_similarity = Some(0.8);
+
+            let failing_record = SpamRecord {
+                content: "ΑSDF!".to_owned(),
+                normalized_content: normalize_for_similarity("ΑSDF!"),
+                spoilers: 0,
+                emoji: 0,
+                links: 0,
+                mentions: 0,
+                mentioned_users: HashSet::new(),
+                attachments: 0,
+                stickers: 0,
+                custom_emoji: 0,
+                characters: 0,
+                newlines: 0,
+                sent_at: 10,
+                warned: false,
+            };
+
+            let result = exceeds_spam_thresholds(&history, &failing_record, &config);
+            assert_eq!(
+                result,
+                SpamCheck::Exceeded("sent too many duplicate messages".to_owned())
+            );
+
+            let succeeding_record = SpamRecord {
+                content: "not asdf".to_owned(),
+                normalized_content: normalize_for_similarity("not asdf"),
                 spoilers: 0,
                 emoji: 0,
                 links: 0,
                 mentions: 0,
+                mentioned_users: HashSet::new(),
                 attachments: 0,
+                stickers: 0,
+                custom_emoji: 0,
+                characters: 0,
+                newlines: 0,
                 sent_at: 10,
+                warned: false,
             };
 
+            let result = exceeds_spam_thresholds(&history, &succeeding_record, &config);
+            assert_eq!(result, SpamCheck::Clean);
+        }
+
+        #[test]
+        fn near_duplicate_spam_checker() {
+            let (mut history, mut config) = setup_for_testing();
+            config.duplicates = Some(3);
+            config.near_duplicates = Some(2);
+            config.emoji = None;
+
+            history[0] =
+                SpamRecord::from_message(&message_at_time("free nitro at example.com 🎉", 0));
+            history.push_back(SpamRecord::from_message(&message_at_time(
+                "free nitro at example.com 🎁",
+                5,
+            )));
+
+            // Differs only by a trailing emoji and a zero-width space.
+            let failing_record = SpamRecord::from_message(&message_at_time(
+                "free  nitro\u{200B} at example.com 💸",
+                10,
+            ));
             let result = exceeds_spam_thresholds(&history, &failing_record, &config);
-            assert_eq!(result, Err("sent too many duplicate messages".to_owned()));
+            assert_eq!(
+                result,
+                SpamCheck::Exceeded("sent too many similar messages".to_owned())
+            );
+
+            // Exact duplicates use the cheaper equality check, which is
+            // reported first.
+            let duplicate_record =
+                SpamRecord::from_message(&message_at_time("free nitro at example.com 🎉", 10));
+            config.duplicates = Some(1);
+            let result = exceeds_spam_thresholds(&history, &duplicate_record, &config);
+            assert_eq!(
+                result,
+                SpamCheck::Exceeded("sent too many duplicate messages".to_owned())
+            );
+
+            let succeeding_record = SpamRecord::from_message(&message_at_time(
+                "is anyone around to help with my build?",
+                10,
+            ));
+            let result = exceeds_spam_thresholds(&history, &succeeding_record, &config);
+            assert_eq!(result, SpamCheck::Clean);
+        }
+
+        #[test]
+        fn normalized_duplicate_spam_checker() {
+            let (mut history, mut config) = setup_for_testing();
+            history[0] = SpamRecord::from_message(&message_at_time("Free nitro at example.com", 0));
+
+            // Fullwidth, confusable, and zero-width variants of the same text.
+            for (content, sent_at) in [
+                ("ｆｒｅｅ ｎｉｔｒｏ at example.com", 5),
+                ("FREE  NΙTRO\u{200B} at example.com", 10),
+            ] {
+                let record = SpamRecord::from_message(&message_at_time(content, sent_at));
+                let result = exceeds_spam_thresholds(&history, &record, &config);
+                assert_eq!(
+                    result,
+                    SpamCheck::Exceeded("sent too many duplicate messages".to_owned())
+                );
+                assert_eq!(record.content, content);
+            }
+
+            config.normalize_duplicates = false;
+            let record =
+                SpamRecord::from_message(&message_at_time("ｆｒｅｅ ｎｉｔｒｏ at example.com", 5));
+            let result = exceeds_spam_thresholds(&history, &record, &config);
+            assert_eq!(result, SpamCheck::Clean);
+        }
+
+        #[test]
+        fn similarity_ratio() {
+            assert_eq!(similarity("", "", 0.0), Some(1.0));
+            assert_eq!(similarity("asdf", "asdf", 0.0), Some(1.0));
+            assert_eq!(similarity("asdf", "qwer", 0.0), Some(0.0));
+            assert_eq!(similarity("", "asdf", 0.0), Some(0.0));
+            assert_eq!(similarity("kitten", "sitting", 0.0), Some(1.0 - 3.0 / 7.0));
+            assert_eq!(
+                similarity("kitten", "sitting", 1.0 - 3.0 / 7.0),
+                Some(1.0 - 3.0 / 7.0)
+            );
+        }
+
+        #[test]
+        fn give_up_on_dissimilar_strings() {
+            assert_eq!(similarity("asdf", "qwer", 0.5), None);
+            assert_eq!(similarity("kitten", "sitting", 0.8), None);
+            // Too different in length to ever be similar enough.
+            assert_eq!(similarity("a", &"a".repeat(4000), 0.5), None);
+
+            let long = "free nitro ".repeat(350);
+            let edited = format!("{}!", long);
+            assert_eq!(
+                similarity(&long, &edited, 0.9),
+                Some(1.0 - 1.0 / edited.chars().count() as f32)
+            );
+            let unrelated = "a".repeat(long.len());
+            assert_eq!(similarity(&long, &unrelated, 0.9), None);
         }
 
         #[test]
@@ -1095,16 +4306,26 @@ mod test {
 
             let failing_record = SpamRecord {
                 content: "foo".to_owned(),
+                normalized_content: normalize_for_similarity("foo"),
                 spoilers: 0,
                 emoji: 2,
                 links: 0,
                 mentions: 0,
+                mentioned_users: HashSet::new(),
                 attachments: 0,
+                stickers: 0,
+                custom_emoji: 0,
+                characters: 0,
+                newlines: 0,
                 sent_at: 10,
+                warned: false,
             };
 
             let result = exceeds_spam_thresholds(&history, &failing_record, &config);
-            assert_eq!(result, Err("sent too many emoji".to_owned()));
+            assert_eq!(
+                result,
+                SpamCheck::Exceeded("sent too many emoji".to_owned())
+            );
         }
 
         #[test]
@@ -1113,16 +4334,26 @@ mod test {
 
             let failing_record = SpamRecord {
                 content: "foo".to_owned(),
+                normalized_content: normalize_for_similarity("foo"),
                 spoilers: 0,
                 emoji: 0,
                 links: 2,
                 mentions: 0,
+                mentioned_users: HashSet::new(),
                 attachments: 0,
+                stickers: 0,
+                custom_emoji: 0,
+                characters: 0,
+                newlines: 0,
                 sent_at: 10,
+                warned: false,
             };
 
             let result = exceeds_spam_thresholds(&history, &failing_record, &config);
-            assert_eq!(result, Err("sent too many links".to_owned()));
+            assert_eq!(
+                result,
+                SpamCheck::Exceeded("sent too many links".to_owned())
+            );
         }
 
         #[test]
@@ -1131,16 +4362,58 @@ mod test {
 
             let failing_record = SpamRecord {
                 content: "foo".to_owned(),
+                normalized_content: normalize_for_similarity("foo"),
                 spoilers: 0,
                 emoji: 0,
                 links: 0,
                 mentions: 2,
+                mentioned_users: HashSet::new(),
                 attachments: 0,
+                stickers: 0,
+                custom_emoji: 0,
+                characters: 0,
+                newlines: 0,
                 sent_at: 10,
+                warned: false,
+            };
+
+            let result = exceeds_spam_thresholds(&history, &failing_record, &config);
+            assert_eq!(
+                result,
+                SpamCheck::Exceeded("sent too many mentions".to_owned())
+            );
+        }
+
+        #[test]
+        fn unique_mention_spam_checker() {
+            let (mut history, mut config) = setup_for_testing();
+            config.mentions = None;
+            config.duplicates = None;
+            config.unique_mentions = Some(3);
+
+            let mentioning = |users: &[u64], sent_at: i64| SpamRecord {
+                mentioned_users: users.iter().map(|id| Id::new(*id)).collect(),
+                ..SpamRecord::from_message(&message_at_time("", sent_at))
             };
+            history.push_back(mentioning(&[1, 2], 5));
 
+            // Mentioning the same users again doesn't add to the count.
+            let repeated_record = mentioning(&[1, 2, 3], 10);
+            let result = exceeds_spam_thresholds(&history, &repeated_record, &config);
+            assert_eq!(result, SpamCheck::Clean);
+
+            history.push_back(repeated_record);
+            let failing_record = mentioning(&[2, 4], 15);
             let result = exceeds_spam_thresholds(&history, &failing_record, &config);
-            assert_eq!(result, Err("sent too many mentions".to_owned()));
+            assert_eq!(
+                result,
+                SpamCheck::Exceeded("mentioned too many distinct users".to_owned())
+            );
+
+            // Messages without mentions don't trip the threshold themselves.
+            let succeeding_record = mentioning(&[], 15);
+            let result = exceeds_spam_thresholds(&history, &succeeding_record, &config);
+            assert_eq!(result, SpamCheck::Clean);
         }
 
         #[test]
@@ -1149,16 +4422,144 @@ mod test {
 
             let failing_record = SpamRecord {
                 content: "foo".to_owned(),
+                normalized_content: normalize_for_similarity("foo"),
                 spoilers: 0,
                 emoji: 0,
                 links: 0,
                 mentions: 0,
+                mentioned_users: HashSet::new(),
                 attachments: 2,
+                stickers: 0,
+                custom_emoji: 0,
+                characters: 0,
+                newlines: 0,
+                sent_at: 10,
+                warned: false,
+            };
+
+            let result = exceeds_spam_thresholds(&history, &failing_record, &config);
+            assert_eq!(
+                result,
+                SpamCheck::Exceeded("sent too many attachments".to_owned())
+            );
+        }
+
+        #[test]
+        fn sticker_spam_checker() {
+            let (history, config) = setup_for_testing();
+
+            let failing_record = SpamRecord {
+                content: "foo".to_owned(),
+                normalized_content: normalize_for_similarity("foo"),
+                spoilers: 0,
+                emoji: 0,
+                links: 0,
+                mentions: 0,
+                mentioned_users: HashSet::new(),
+                attachments: 0,
+                stickers: 2,
+                custom_emoji: 0,
+                characters: 0,
+                newlines: 0,
+                sent_at: 10,
+                warned: false,
+            };
+
+            let result = exceeds_spam_thresholds(&history, &failing_record, &config);
+            assert_eq!(
+                result,
+                SpamCheck::Exceeded("sent too many stickers".to_owned())
+            );
+        }
+
+        #[test]
+        fn custom_emoji_spam_checker() {
+            let (history, config) = setup_for_testing();
+
+            let failing_record = SpamRecord {
+                content: "foo".to_owned(),
+                normalized_content: normalize_for_similarity("foo"),
+                spoilers: 0,
+                emoji: 0,
+                links: 0,
+                mentions: 0,
+                mentioned_users: HashSet::new(),
+                attachments: 0,
+                stickers: 0,
+                custom_emoji: 2,
+                characters: 0,
+                newlines: 0,
+                sent_at: 10,
+                warned: false,
+            };
+
+            let result = exceeds_spam_thresholds(&history, &failing_record, &config);
+            assert_eq!(
+                result,
+                SpamCheck::Exceeded("sent too many custom emoji".to_owned())
+            );
+        }
+
+        #[test]
+        fn text_spam_checker() {
+            let (history, mut config) = setup_for_testing();
+
+            let failing_record = SpamRecord {
+                content: "foo".to_owned(),
+                normalized_content: normalize_for_similarity("foo"),
+                spoilers: 0,
+                emoji: 0,
+                links: 0,
+                mentions: 0,
+                mentioned_users: HashSet::new(),
+                attachments: 0,
+                stickers: 0,
+                custom_emoji: 0,
+                characters: 97,
+                newlines: 0,
+                sent_at: 10,
+                warned: false,
+            };
+
+            let result = exceeds_spam_thresholds(&history, &failing_record, &config);
+            assert_eq!(result, SpamCheck::Exceeded("sent too much text".to_owned()));
+
+            // Character counts add up to more than fits in a u16.
+            config.characters = Some(u16::MAX);
+            let failing_record = SpamRecord {
+                characters: u16::MAX,
+                ..failing_record
+            };
+            let result = exceeds_spam_thresholds(&history, &failing_record, &config);
+            assert_eq!(result, SpamCheck::Exceeded("sent too much text".to_owned()));
+        }
+
+        #[test]
+        fn newline_spam_checker() {
+            let (history, config) = setup_for_testing();
+
+            let failing_record = SpamRecord {
+                content: "foo".to_owned(),
+                normalized_content: normalize_for_similarity("foo"),
+                spoilers: 0,
+                emoji: 0,
+                links: 0,
+                mentions: 0,
+                mentioned_users: HashSet::new(),
+                attachments: 0,
+                stickers: 0,
+                custom_emoji: 0,
+                characters: 0,
+                newlines: 2,
                 sent_at: 10,
+                warned: false,
             };
 
             let result = exceeds_spam_thresholds(&history, &failing_record, &config);
-            assert_eq!(result, Err("sent too many attachments".to_owned()));
+            assert_eq!(
+                result,
+                SpamCheck::Exceeded("sent too many line breaks".to_owned())
+            );
         }
 
         #[tokio::test]
@@ -1168,13 +4569,26 @@ mod test {
             let config = SpamFilter {
                 emoji: None,
                 duplicates: Some(1),
+                duplicate_similarity: None,
+                near_duplicates: None,
+                near_duplicate_similarity: None,
                 links: None,
                 attachments: None,
                 spoilers: None,
                 mentions: None,
+                unique_mentions: None,
+                stickers: None,
+                custom_emoji: None,
+                characters: None,
+                newlines: None,
                 interval: 30,
+                per_channel: false,
                 actions: None,
                 scoping: None,
+                raid: None,
+                warn_actions: None,
+                warn_threshold_fraction: None,
+                normalize_duplicates: true,
             };
 
             let history = Arc::new(RwLock::new(history));
@@ -1187,7 +4601,7 @@ mod test {
                 10 * 1_000_000,
             )
             .await;
-            assert_eq!(result, Ok(()));
+            assert_eq!(result, SpamCheck::Clean);
 
             let second_message = message_at_time(GOOD_CONTENT, 15);
             let result = super::super::check_spam_record(
@@ -1197,7 +4611,10 @@ mod test {
                 20 * 1_000_000,
             )
             .await;
-            assert_eq!(result, Err("sent too many duplicate messages".to_owned()));
+            assert_eq!(
+                result,
+                SpamCheck::Exceeded("sent too many duplicate messages".to_owned())
+            );
 
             let third_message = message_at_time(GOOD_CONTENT, 45);
             let result = super::super::check_spam_record(
@@ -1207,15 +4624,241 @@ mod test {
                 60 * 1_000_000,
             )
             .await;
-            assert_eq!(result, Ok(()));
+            assert_eq!(result, SpamCheck::Clean);
 
             let read_history = history.read().await;
             let read_history_queue = read_history
-                .get(&crate::model::test::USER_ID)
+                .get(&SpamHistoryKey::User(crate::model::test::USER_ID))
                 .expect("user ID not in spam record?")
                 .lock()
-                .expect("couldn't lock mutex");
+                .await;
             assert_eq!(read_history_queue.len(), 1);
         }
+
+        #[tokio::test]
+        async fn warn_once_per_interval() {
+            let config = SpamFilter {
+                duplicates: Some(4),
+                interval: 30,
+                warn_actions: Some(vec![]),
+                warn_threshold_fraction: Some(0.5),
+                ..Default::default()
+            };
+            let history = Arc::new(RwLock::new(HashMap::new()));
+
+            let expected = [
+                (1, SpamCheck::Clean),
+                (2, SpamCheck::Clean),
+                (
+                    3,
+                    SpamCheck::Warn("nearly sent too many duplicate messages".to_owned()),
+                ),
+                // Still over half the threshold, but already warned.
+                (4, SpamCheck::Clean),
+                (
+                    5,
+                    SpamCheck::Exceeded("sent too many duplicate messages".to_owned()),
+                ),
+                // Once the earlier messages expire, the user can be warned
+                // again.
+                (40, SpamCheck::Clean),
+                (41, SpamCheck::Clean),
+                (
+                    42,
+                    SpamCheck::Warn("nearly sent too many duplicate messages".to_owned()),
+                ),
+                (43, SpamCheck::Clean),
+            ];
+            for (sent_at, expected) in expected {
+                let message = message_at_time(GOOD_CONTENT, sent_at);
+                let result = super::super::check_spam_record(
+                    &message,
+                    &config,
+                    history.clone(),
+                    sent_at as u64 * 1_000_000,
+                )
+                .await;
+                assert_eq!(result, expected, "message sent at {}", sent_at);
+            }
+        }
+
+        #[tokio::test]
+        async fn skip_warnings_for_small_thresholds() {
+            // Half of each of these thresholds doesn't leave room for a
+            // single message before it, so every message that counts towards
+            // one would otherwise be warned about.
+            let duplicates = SpamFilter {
+                duplicates: Some(3),
+                interval: 30,
+                warn_actions: Some(vec![]),
+                warn_threshold_fraction: Some(0.5),
+                ..Default::default()
+            };
+            let links = SpamFilter {
+                duplicates: None,
+                links: Some(1),
+                ..duplicates.clone()
+            };
+
+            for (config, content, reason) in [
+                (duplicates, "hello", "sent too many duplicate messages"),
+                (links, "https://example.com", "sent too many links"),
+            ] {
+                let history = Arc::new(RwLock::new(HashMap::new()));
+                let limit = config.duplicates.or(config.links).unwrap() as i64;
+                for sent_at in 1..=limit + 1 {
+                    let message = message_at_time(content, sent_at);
+                    let result = super::super::check_spam_record(
+                        &message,
+                        &config,
+                        history.clone(),
+                        sent_at as u64 * 1_000_000,
+                    )
+                    .await;
+                    let expected = if sent_at > limit {
+                        SpamCheck::Exceeded(reason.to_owned())
+                    } else {
+                        SpamCheck::Clean
+                    };
+                    assert_eq!(result, expected, "{} sent at {}", content, sent_at);
+                }
+            }
+        }
+
+        #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+        async fn concurrent_checks_for_one_user() {
+            let config = Arc::new(SpamFilter {
+                duplicates: Some(10),
+                interval: 30,
+                ..Default::default()
+            });
+            let history = Arc::new(RwLock::new(HashMap::new()));
+
+            let checks: Vec<_> = (0..50)
+                .map(|i| {
+                    let config = config.clone();
+                    let history = history.clone();
+                    tokio::spawn(async move {
+                        let message = message_at_time(GOOD_CONTENT, 1 + i / 10);
+                        super::super::check_spam_record(&message, &config, history, 10 * 1_000_000)
+                            .await
+                    })
+                })
+                .collect();
+
+            let mut exceeded = 0;
+            for check in checks {
+                if let SpamCheck::Exceeded(_) = check.await.unwrap() {
+                    exceeded += 1;
+                }
+            }
+
+            // Each check sees every record pushed before it, so exactly the
+            // checks after the tenth one exceed the threshold, whatever order
+            // they ran in.
+            assert_eq!(exceeded, 40);
+            let read_history = history.read().await;
+            assert_eq!(read_history.len(), 1);
+            assert_eq!(
+                read_history
+                    .get(&SpamHistoryKey::User(crate::model::test::USER_ID))
+                    .unwrap()
+                    .lock()
+                    .await
+                    .len(),
+                50
+            );
+        }
+
+        #[tokio::test]
+        async fn prune_expired_histories() {
+            let config = SpamFilter {
+                duplicates: Some(5),
+                interval: 30,
+                ..Default::default()
+            };
+            let history = Arc::new(RwLock::new(HashMap::new()));
+
+            let mut old_message = message_at_time(GOOD_CONTENT, 5);
+            old_message.author_id = Id::new(100);
+            let new_message = message_at_time(GOOD_CONTENT, 50);
+            for message in [&old_message, &new_message] {
+                let result = super::super::check_spam_record(
+                    message,
+                    &config,
+                    history.clone(),
+                    55 * 1_000_000,
+                )
+                .await;
+                assert_eq!(result, SpamCheck::Clean);
+            }
+
+            let removed = super::super::prune_spam_history(&history, 30, 60 * 1_000_000).await;
+            assert_eq!(removed, 1);
+
+            let read_history = history.read().await;
+            assert!(!read_history.contains_key(&SpamHistoryKey::User(Id::new(100))));
+            assert_eq!(
+                read_history
+                    .get(&SpamHistoryKey::User(crate::model::test::USER_ID))
+                    .expect("recent history was removed")
+                    .lock()
+                    .await
+                    .len(),
+                1
+            );
+        }
+
+        #[tokio::test]
+        async fn per_channel_history() {
+            let mut config = SpamFilter {
+                duplicates: Some(1),
+                interval: 30,
+                ..Default::default()
+            };
+            let history = Arc::new(RwLock::new(HashMap::new()));
+
+            let first_message = message_at_time(GOOD_CONTENT, 5);
+            let mut second_message = message_at_time(GOOD_CONTENT, 10);
+            second_message.channel_id = Id::new(100);
+
+            // Guild-wide, posting the same thing in two channels is a duplicate.
+            for (message, expected) in [
+                (&first_message, SpamCheck::Clean),
+                (
+                    &second_message,
+                    SpamCheck::Exceeded("sent too many duplicate messages".to_owned()),
+                ),
+            ] {
+                let result = super::super::check_spam_record(
+                    message,
+                    &config,
+                    history.clone(),
+                    15 * 1_000_000,
+                )
+                .await;
+                assert_eq!(result, expected);
+            }
+
+            config.per_channel = true;
+            let history = Arc::new(RwLock::new(HashMap::new()));
+            for message in [&first_message, &second_message] {
+                let result = super::super::check_spam_record(
+                    message,
+                    &config,
+                    history.clone(),
+                    15 * 1_000_000,
+                )
+                .await;
+                assert_eq!(result, SpamCheck::Clean);
+            }
+
+            let read_history = history.read().await;
+            assert!(read_history.contains_key(&SpamHistoryKey::UserInChannel(
+                crate::model::test::USER_ID,
+                Id::new(100)
+            )));
+            assert!(!read_history.contains_key(&SpamHistoryKey::User(crate::model::test::USER_ID)));
+        }
     }
 }
@@ -0,0 +1,188 @@
+use std::collections::{HashMap, VecDeque};
+
+use twilight_model::id::{marker::ChannelMarker, Id};
+
+use crate::config::FloodFilter;
+
+/// What a message did to its channel's flood state.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum FloodCheck {
+    /// The channel isn't being flooded.
+    Calm,
+    /// This message started a flood, and it's the `messages`th message in the
+    /// flood filter's interval.
+    Started { messages: usize },
+    /// The channel was already being flooded, and still is.
+    Continuing,
+}
+
+#[derive(Debug, Default)]
+struct ChannelRate {
+    /// When each recent message was sent, in microseconds.
+    sent: VecDeque<u64>,
+    flooded: bool,
+}
+
+/// How many messages each channel has received recently, so that floods can be
+/// spotted independently of who's sending the messages.
+#[derive(Debug, Default)]
+pub(crate) struct FloodTracker {
+    channels: HashMap<Id<ChannelMarker>, ChannelRate>,
+}
+
+/// Removes the timestamps that are more than `interval_seconds` before `now`.
+fn clear_expired_messages(sent: &mut VecDeque<u64>, interval_seconds: u64, now: u64) {
+    let interval = interval_seconds.saturating_mul(1_000_000);
+    while let Some(&front) = sent.front() {
+        if now.saturating_sub(front) > interval {
+            sent.pop_front();
+        } else {
+            break;
+        }
+    }
+}
+
+impl FloodTracker {
+    /// Records a message sent to a channel at `now`, in microseconds.
+    ///
+    /// A flood starts once the channel has received more than the flood
+    /// filter's `messages` within its interval, but it only ends once the
+    /// channel has calmed down to half that, so that a channel hovering around
+    /// the threshold doesn't start a new flood with every other message.
+    pub(crate) fn record(
+        &mut self,
+        channel_id: Id<ChannelMarker>,
+        config: &FloodFilter,
+        now: u64,
+    ) -> FloodCheck {
+        let rate = self.channels.entry(channel_id).or_default();
+        clear_expired_messages(&mut rate.sent, config.interval, now);
+        rate.sent.push_back(now);
+
+        let messages = rate.sent.len();
+        let threshold = usize::from(config.messages);
+        if rate.flooded {
+            if messages > threshold / 2 {
+                return FloodCheck::Continuing;
+            }
+
+            rate.flooded = false;
+        }
+
+        if messages > threshold {
+            rate.flooded = true;
+            FloodCheck::Started { messages }
+        } else {
+            FloodCheck::Calm
+        }
+    }
+
+    /// Removes messages older than `interval_seconds`, which should be the
+    /// longest flood interval of any guild, and then removes any channels that
+    /// are left without messages. Returns how many channels were removed.
+    pub(crate) fn prune(&mut self, interval_seconds: u64, now: u64) -> usize {
+        let before = self.channels.len();
+        self.channels.retain(|_, rate| {
+            clear_expired_messages(&mut rate.sent, interval_seconds, now);
+            !rate.sent.is_empty()
+        });
+
+        before - self.channels.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use twilight_model::id::Id;
+
+    use super::{FloodCheck, FloodTracker};
+    use crate::{config::FloodFilter, model::test::CHANNEL_ID};
+
+    fn config() -> FloodFilter {
+        FloodFilter {
+            messages: 4,
+            interval: 10,
+            slowmode_seconds: 30,
+            revert_after: 300,
+            notify: true,
+            scoping: None,
+        }
+    }
+
+    #[test]
+    fn detect_floods() {
+        let config = config();
+        let mut tracker = FloodTracker::default();
+
+        for second in 1..=4 {
+            assert_eq!(
+                tracker.record(CHANNEL_ID, &config, second * 1_000_000),
+                FloodCheck::Calm
+            );
+        }
+
+        assert_eq!(
+            tracker.record(CHANNEL_ID, &config, 5_000_000),
+            FloodCheck::Started { messages: 5 }
+        );
+        assert_eq!(
+            tracker.record(CHANNEL_ID, &config, 6_000_000),
+            FloodCheck::Continuing
+        );
+
+        // Other channels are counted separately.
+        assert_eq!(
+            tracker.record(Id::new(100), &config, 6_000_000),
+            FloodCheck::Calm
+        );
+    }
+
+    #[test]
+    fn flood_ends_below_half_threshold() {
+        let config = config();
+        let mut tracker = FloodTracker::default();
+
+        for second in 1..=5 {
+            tracker.record(CHANNEL_ID, &config, second * 1_000_000);
+        }
+
+        // Four messages are still within the interval, which is below the
+        // threshold but above half of it, so the flood continues.
+        assert_eq!(
+            tracker.record(CHANNEL_ID, &config, 13_000_000),
+            FloodCheck::Continuing
+        );
+        // Now only two are, so the flood is over.
+        assert_eq!(
+            tracker.record(CHANNEL_ID, &config, 15_500_000),
+            FloodCheck::Calm
+        );
+
+        for second in 16..=17 {
+            assert_eq!(
+                tracker.record(CHANNEL_ID, &config, second * 1_000_000),
+                FloodCheck::Calm
+            );
+        }
+
+        assert_eq!(
+            tracker.record(CHANNEL_ID, &config, 18_000_000),
+            FloodCheck::Started { messages: 5 }
+        );
+    }
+
+    #[test]
+    fn prune_quiet_channels() {
+        let config = config();
+        let mut tracker = FloodTracker::default();
+
+        tracker.record(CHANNEL_ID, &config, 1_000_000);
+        tracker.record(Id::new(100), &config, 8_000_000);
+
+        assert_eq!(tracker.prune(10, 15_000_000), 1);
+        assert_eq!(tracker.prune(10, 15_000_000), 0);
+        assert_eq!(tracker.prune(10, 30_000_000), 1);
+        assert!(tracker.channels.is_empty());
+    }
+}
@@ -0,0 +1,122 @@
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicI64, Ordering},
+        Arc,
+    },
+};
+
+use eyre::Result;
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+use tokio::sync::RwLock;
+use twilight_gateway::Event;
+use twilight_model::id::{marker::GuildMarker, Id};
+
+use crate::config::{GuildConfig, HealthConfig};
+
+/// Tracks whether the gateway connection looks alive, for the `/readyz`
+/// endpoint.
+#[derive(Debug, Default)]
+pub(crate) struct Health {
+    connected: AtomicBool,
+    /// When the last gateway event was received, as a Unix timestamp in
+    /// seconds.
+    last_event_at: AtomicI64,
+}
+
+impl Health {
+    pub(crate) fn record_event(&self, event: &Event, now: i64) {
+        match event {
+            Event::Ready(_) | Event::Resumed => self.connected.store(true, Ordering::Relaxed),
+            Event::ShardDisconnected(_) => self.connected.store(false, Ordering::Relaxed),
+            _ => {}
+        }
+
+        self.last_event_at.store(now, Ordering::Relaxed);
+    }
+
+    /// Whether the gateway is connected and has sent something, even just a
+    /// heartbeat acknowledgement, within the last `stale_after_seconds`.
+    fn is_ready(&self, now: i64, stale_after_seconds: u64) -> bool {
+        self.connected.load(Ordering::Relaxed)
+            && now - self.last_event_at.load(Ordering::Relaxed) <= stale_after_seconds as i64
+    }
+}
+
+async fn respond(
+    request: Request<Body>,
+    health: Arc<Health>,
+    guild_cfgs: Arc<RwLock<HashMap<Id<GuildMarker>, Arc<GuildConfig>>>>,
+    stale_after_seconds: u64,
+) -> Result<Response<Body>, Infallible> {
+    let status = match request.uri().path() {
+        "/healthz" => StatusCode::OK,
+        "/readyz" => {
+            let now = chrono::Utc::now().timestamp();
+            if health.is_ready(now, stale_after_seconds) && !guild_cfgs.read().await.is_empty() {
+                StatusCode::OK
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+        }
+        _ => StatusCode::NOT_FOUND,
+    };
+
+    let mut response = Response::new(Body::from(status.canonical_reason().unwrap_or_default()));
+    *response.status_mut() = status;
+    Ok(response)
+}
+
+/// Binds the health check server, returning a future that serves it. Binding
+/// happens up front so that a bad address is reported at startup.
+pub(crate) fn bind(
+    config: &HealthConfig,
+    health: Arc<Health>,
+    guild_cfgs: Arc<RwLock<HashMap<Id<GuildMarker>, Arc<GuildConfig>>>>,
+) -> Result<impl std::future::Future<Output = hyper::Result<()>>> {
+    let address: SocketAddr = config.bind.parse()?;
+    let stale_after_seconds = config.stale_after_seconds;
+
+    let make_service = make_service_fn(move |_| {
+        let health = health.clone();
+        let guild_cfgs = guild_cfgs.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                respond(
+                    request,
+                    health.clone(),
+                    guild_cfgs.clone(),
+                    stale_after_seconds,
+                )
+            }))
+        }
+    });
+
+    Ok(Server::try_bind(&address)?.serve(make_service))
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::Ordering;
+
+    use super::Health;
+
+    #[test]
+    fn ready_until_stale() {
+        let health = Health::default();
+        assert!(!health.is_ready(0, 60));
+
+        health.connected.store(true, Ordering::Relaxed);
+        health.last_event_at.store(100, Ordering::Relaxed);
+        assert!(health.is_ready(160, 60));
+        assert!(!health.is_ready(161, 60));
+
+        health.connected.store(false, Ordering::Relaxed);
+        assert!(!health.is_ready(100, 60));
+    }
+}
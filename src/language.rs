@@ -0,0 +1,216 @@
+use once_cell::sync::OnceCell;
+use regex::Regex;
+use whatlang::Lang;
+
+/// Messages with fewer letters than this, once links, code, and the like are
+/// left out, are too short to detect the language of reliably.
+const MIN_LETTERS: usize = 20;
+/// Messages where less than this fraction of the characters are letters are
+/// mostly links, emoji, or code, and are left alone.
+const MIN_PROSE_FRACTION: f32 = 0.5;
+/// How confident the detector has to be in the language it picked, from 0 to
+/// 1.
+const MIN_CONFIDENCE: f64 = 0.4;
+
+/// The languages that can be detected, by ISO 639-1 code.
+static LANGUAGES: &[(&str, Lang)] = &[
+    ("af", Lang::Afr),
+    ("ak", Lang::Aka),
+    ("am", Lang::Amh),
+    ("ar", Lang::Ara),
+    ("az", Lang::Aze),
+    ("be", Lang::Bel),
+    ("bg", Lang::Bul),
+    ("bn", Lang::Ben),
+    ("ca", Lang::Cat),
+    ("cs", Lang::Ces),
+    ("da", Lang::Dan),
+    ("de", Lang::Deu),
+    ("el", Lang::Ell),
+    ("en", Lang::Eng),
+    ("eo", Lang::Epo),
+    ("es", Lang::Spa),
+    ("et", Lang::Est),
+    ("fa", Lang::Pes),
+    ("fi", Lang::Fin),
+    ("fr", Lang::Fra),
+    ("gu", Lang::Guj),
+    ("he", Lang::Heb),
+    ("hi", Lang::Hin),
+    ("hr", Lang::Hrv),
+    ("hu", Lang::Hun),
+    ("hy", Lang::Hye),
+    ("id", Lang::Ind),
+    ("it", Lang::Ita),
+    ("ja", Lang::Jpn),
+    ("jv", Lang::Jav),
+    ("ka", Lang::Kat),
+    ("km", Lang::Khm),
+    ("kn", Lang::Kan),
+    ("ko", Lang::Kor),
+    ("la", Lang::Lat),
+    ("lt", Lang::Lit),
+    ("lv", Lang::Lav),
+    ("mk", Lang::Mkd),
+    ("ml", Lang::Mal),
+    ("mr", Lang::Mar),
+    ("my", Lang::Mya),
+    ("nb", Lang::Nob),
+    ("ne", Lang::Nep),
+    ("nl", Lang::Nld),
+    ("or", Lang::Ori),
+    ("pa", Lang::Pan),
+    ("pl", Lang::Pol),
+    ("pt", Lang::Por),
+    ("ro", Lang::Ron),
+    ("ru", Lang::Rus),
+    ("si", Lang::Sin),
+    ("sk", Lang::Slk),
+    ("sl", Lang::Slv),
+    ("sn", Lang::Sna),
+    ("sr", Lang::Srp),
+    ("sv", Lang::Swe),
+    ("ta", Lang::Tam),
+    ("te", Lang::Tel),
+    ("th", Lang::Tha),
+    ("tk", Lang::Tuk),
+    ("tl", Lang::Tgl),
+    ("tr", Lang::Tur),
+    ("uk", Lang::Ukr),
+    ("ur", Lang::Urd),
+    ("uz", Lang::Uzb),
+    ("vi", Lang::Vie),
+    ("yi", Lang::Yid),
+    ("zh", Lang::Cmn),
+    ("zu", Lang::Zul),
+];
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Language {
+    /// The language's ISO 639-1 code.
+    pub(crate) code: &'static str,
+    pub(crate) name: &'static str,
+}
+
+/// Whether `code` is the ISO 639-1 code of a language that can be detected.
+pub(crate) fn is_supported(code: &str) -> bool {
+    LANGUAGES
+        .iter()
+        .any(|(candidate, _)| candidate.eq_ignore_ascii_case(code))
+}
+
+fn non_prose_regex() -> &'static Regex {
+    static REGEX: OnceCell<Regex> = OnceCell::new();
+    // Code blocks, inline code, links, custom emoji, and mentions.
+    REGEX.get_or_init(|| {
+        Regex::new(r"(?s)```.*?```|`[^`]*`|https?://\S+|<a?:\w+:\d+>|<[@#][!&]?\d+>").unwrap()
+    })
+}
+
+/// Detects what language `text` is written in. Returns `None` for text that's
+/// too short, is mostly links, emoji, or code, or that the detector isn't
+/// confident about.
+pub(crate) fn detect(text: &str) -> Option<Language> {
+    let prose = non_prose_regex().replace_all(text, " ");
+
+    let letters = prose.chars().filter(|c| c.is_alphabetic()).count();
+    let characters = text.chars().filter(|c| !c.is_whitespace()).count();
+    if letters < MIN_LETTERS || (letters as f32) < characters as f32 * MIN_PROSE_FRACTION {
+        return None;
+    }
+
+    let info = whatlang::detect(&prose)?;
+    if info.confidence() < MIN_CONFIDENCE {
+        return None;
+    }
+
+    let (code, lang) = LANGUAGES.iter().find(|(_, lang)| *lang == info.lang())?;
+    Some(Language {
+        code,
+        name: lang.eng_name(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::{detect, is_supported};
+
+    fn detected_code(text: &str) -> Option<&'static str> {
+        detect(text).map(|language| language.code)
+    }
+
+    #[test]
+    fn detect_languages() {
+        assert_eq!(
+            detected_code("Hey, does anyone know what time the event starts tonight?"),
+            Some("en")
+        );
+        assert_eq!(
+            detected_code("Hola, ¿alguien sabe a qué hora empieza el evento esta noche?"),
+            Some("es")
+        );
+        assert_eq!(
+            detected_code("Salut, est-ce que vous savez à quelle heure commence le jeu ?"),
+            Some("fr")
+        );
+        assert_eq!(
+            detected_code("Hallo, weiß jemand, wann das Event heute Abend anfängt? Ich habe es nicht gesehen."),
+            Some("de")
+        );
+        assert_eq!(
+            detected_code(
+                "Привет, кто-нибудь знает, во сколько начинается событие? Я не видел объявления."
+            ),
+            Some("ru")
+        );
+        assert_eq!(
+            detected_code(
+                "Привіт, хтось знає, о котрій годині починається подія? Я не бачив оголошення."
+            ),
+            Some("uk")
+        );
+        assert_eq!(
+            detected_code("こんにちは、今夜のイベントは何時に始まるか知っていますか？"),
+            Some("ja")
+        );
+        assert_eq!(
+            detected_code("大家好，有人知道今晚的活动几点开始吗？我没有看到公告。"),
+            Some("zh")
+        );
+        assert_eq!(
+            detected_code("Γεια σας, ξέρει κανείς τι ώρα ξεκινάει η εκδήλωση απόψε;"),
+            Some("el")
+        );
+    }
+
+    #[test]
+    fn skip_ambiguous_text() {
+        // Too short.
+        assert_eq!(detected_code("lol ok"), None);
+        assert_eq!(detected_code("Привет!"), None);
+        // Mostly links and code.
+        assert_eq!(
+            detected_code("https://example.com/a/very/long/link/to/somewhere ```fn main() { println!(\"hi\"); }```"),
+            None
+        );
+        assert_eq!(
+            detected_code("💀💀💀💀💀💀💀💀💀💀💀💀💀💀💀💀💀💀💀💀💀 lmao"),
+            None
+        );
+        // No common words to tell Latin-script languages apart by.
+        assert_eq!(
+            detected_code("Pneumonoultramicroscopicsilicovolcanoconiosis"),
+            None
+        );
+    }
+
+    #[test]
+    fn supported_language_codes() {
+        assert!(is_supported("en"));
+        assert!(is_supported("RU"));
+        assert!(!is_supported("english"));
+        assert!(!is_supported("xx"));
+    }
+}
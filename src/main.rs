@@ -1,30 +1,34 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use action::{MessageAction, ReactionAction};
+use action::{ActionContext, MessageAction, ReactionAction};
 use chrono::{DateTime, Utc};
 use filter::SpamHistory;
 use influxdb::{InfluxDbWriteable, WriteQuery};
+use message::MessageFilterFailure;
 use reqwest::header::HeaderValue;
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock, Semaphore};
 
 use futures::stream::StreamExt;
 
+use sentry::SentryFutureExt;
+
 use tracing::Instrument;
 
 use twilight_cache_inmemory::{InMemoryCache, ResourceType};
 use twilight_gateway::Event;
 use twilight_gateway::Shard;
+use twilight_http::request::AuditLogReason;
 use twilight_http::Client as HttpClient;
 use twilight_mention::Mention;
-use twilight_model::application::interaction::InteractionData;
+use twilight_model::application::interaction::{InteractionData, InteractionType};
 use twilight_model::channel::Message;
 use twilight_model::gateway::payload::incoming::MessageUpdate;
 use twilight_model::gateway::{GatewayReaction, Intents};
-use twilight_model::id::marker::ApplicationMarker;
+use twilight_model::id::marker::{ApplicationMarker, ChannelMarker, StickerMarker, UserMarker};
 use twilight_model::id::{marker::GuildMarker, Id};
 
 use color_eyre::eyre::Result;
@@ -35,26 +39,75 @@ use twilight_util::builder::embed::{EmbedBuilder, EmbedFieldBuilder};
 
 mod action;
 mod command;
+mod component;
 mod config;
 mod confusable;
+mod diff;
 mod filter;
+mod flood;
+mod health;
+mod language;
 mod message;
 mod model;
+mod quarantine;
+mod raid;
 mod reaction;
+mod repost;
+mod tempban;
+mod unknown_keys;
+mod warnings;
+mod watch;
 
 const DEFAULT_RELOAD_INTERVAL: u64 = 5 * 60;
+/// How long to spend sending offline notifications before giving up and
+/// exiting anyway.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often to check for temporary bans that should be lifted.
+const TEMPBAN_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// How often to clear out spam history that's too old to matter.
+const SPAM_HISTORY_PRUNE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// How long to keep counting reactors for an emoji on a message after its last
+/// reaction, in seconds.
+const REACTION_COUNT_MAX_AGE: u64 = 24 * 60 * 60;
+/// How many dropped events to log about at once while shedding load, so that
+/// a raid doesn't also flood the logs.
+const SHED_EVENT_LOG_INTERVAL: u64 = 100;
+
+type GuildStickers = HashMap<Id<GuildMarker>, Vec<Id<StickerMarker>>>;
 
 #[derive(Clone, Debug)]
 struct State {
     cfg: Arc<Config>,
-    guild_cfgs: Arc<RwLock<HashMap<Id<GuildMarker>, GuildConfig>>>,
+    guild_cfgs: Arc<RwLock<HashMap<Id<GuildMarker>, Arc<GuildConfig>>>>,
+    /// Filters disabled with `/chrysanthemum-toggle-filter`, by name. These
+    /// are re-enabled whenever configurations are reloaded.
+    disabled_filters: Arc<RwLock<HashMap<Id<GuildMarker>, HashSet<String>>>>,
+    guild_stickers: Arc<RwLock<GuildStickers>>,
     http: Arc<HttpClient>,
     application_id: Arc<RwLock<Option<Id<ApplicationMarker>>>>,
     cache: Arc<InMemoryCache>,
     spam_history: Arc<RwLock<SpamHistory>>,
+    raid_index: Arc<Mutex<raid::RaidIndex>>,
+    reaction_counts: Arc<RwLock<reaction::ReactionCounts>>,
+    reaction_spam_history: Arc<Mutex<reaction::ReactionSpamHistory>>,
+    flood_tracker: Arc<Mutex<flood::FloodTracker>>,
+    repost_history: Arc<Mutex<repost::RepostHistory>>,
+    warnings: Arc<warnings::WarningStore>,
+    quarantines: Arc<quarantine::QuarantineStore>,
+    tempbans: Arc<tempban::TempbanStore>,
+    slowmodes: Arc<Mutex<action::Slowmodes>>,
+    pending_deletes: Arc<Mutex<action::PendingDeletes>>,
     influx_client: Arc<Option<influxdb::Client>>,
     influx_report_count: Arc<AtomicUsize>,
+    /// Shared client for requests to services other than Discord.
+    web_client: reqwest::Client,
     armed: Arc<AtomicBool>,
+    /// Limits how many messages and reactions are filtered at once, if
+    /// `max_concurrent_filters` is set.
+    filter_permits: Option<Arc<Semaphore>>,
+    /// How many messages and reactions have been dropped unfiltered because
+    /// `max_concurrent_filters` were already being filtered.
+    shed_events: Arc<AtomicU64>,
 }
 
 #[derive(Debug, InfluxDbWriteable)]
@@ -76,6 +129,12 @@ struct MessageFilterReport {
     channel: String,
 }
 
+#[derive(Debug, InfluxDbWriteable)]
+struct ShedEventReport {
+    time: DateTime<Utc>,
+    shed_events: u64,
+}
+
 #[derive(Debug, InfluxDbWriteable)]
 struct ReactionFilterReport {
     time: DateTime<Utc>,
@@ -125,7 +184,12 @@ fn validate_configs() -> Result<()> {
             .nth(2)
             .expect("Second argument (config path) not passed"),
     );
-    config::load_all_guild_configs(&config_path)?;
+    let report = config::load_all_guild_configs(&config_path)?;
+    println!("{}", report);
+    if !report.is_ok() {
+        std::process::exit(1);
+    }
+
     println!("All guild configs are valid");
     Ok(())
 }
@@ -148,20 +212,18 @@ fn main() -> Result<()> {
         .nth(1)
         .unwrap_or_else(|| "chrysanthemum.cfg.yml".to_owned());
 
-    let cfg_json = std::fs::read_to_string(&config_path).expect("couldn't read config file");
+    let cfg_json = std::fs::read_to_string(config_path).expect("couldn't read config file");
+    let cfg_json = config::interpolate_env(&cfg_json)?;
     let cfg: Config = serde_yaml::from_str(&cfg_json).expect("Couldn't deserialize config");
+    config::validate_config(&cfg).map_err(LoadConfigError::Validate)?;
 
-    let _sentry_guard = cfg.sentry.as_ref().map(|sentry_config| {
-        sentry::init((
-            sentry_config.url.clone(),
-            sentry::ClientOptions {
-                release: sentry::release_name!(),
-                traces_sample_rate: sentry_config.sample_rate.unwrap_or(0.01),
-                debug: cfg!(debug_assertions),
-                ..Default::default()
-            },
-        ))
-    });
+    if let Some(confusables_file) = &cfg.confusables_file {
+        confusable::load_custom_confusables(confusables_file)?;
+    }
+
+    // This has to happen before the runtime is built so that its worker threads
+    // inherit the main hub.
+    let sentry_guard = cfg.sentry.as_ref().map(init_sentry);
 
     let influx_client = if let Some(influx_cfg) = &cfg.influx {
         let mut headers = reqwest::header::HeaderMap::new();
@@ -180,39 +242,107 @@ fn main() -> Result<()> {
         None
     };
 
-    let intents = Intents::GUILD_MESSAGES
+    let intents = Intents::GUILDS
+        | Intents::GUILD_MESSAGES
         | Intents::GUILD_MEMBERS
         | Intents::GUILD_MESSAGE_REACTIONS
         | Intents::MESSAGE_CONTENT;
 
-    tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap().block_on(async {
+    let result = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap().block_on(async {
 
     let (shard, mut events) = Shard::builder(discord_token.clone(), intents).build();
     shard.start().await?;
 
     let http = Arc::new(HttpClient::new(discord_token));
     let cache = InMemoryCache::builder()
-        .resource_types(ResourceType::MESSAGE | ResourceType::MEMBER | ResourceType::USER)
+        .resource_types(
+            ResourceType::MESSAGE
+                | ResourceType::MEMBER
+                | ResourceType::USER
+                | ResourceType::CHANNEL,
+        )
         .build();
 
     let cfg = Arc::new(cfg);
     let spam_history = Arc::new(RwLock::new(filter::SpamHistory::new()));
-    let initial_guild_configs =
-        config::load_guild_configs(&cfg.guild_config_dir, &cfg.active_guilds)
-            .map_err(|(_, e)| e)?;
+    let warnings = match &cfg.state_dir {
+        Some(state_dir) => warnings::WarningStore::load(state_dir)?,
+        None => warnings::WarningStore::in_memory(),
+    };
+    let quarantines = match &cfg.state_dir {
+        Some(state_dir) => quarantine::QuarantineStore::load(state_dir)?,
+        None => quarantine::QuarantineStore::in_memory(),
+    };
+    let tempbans = match &cfg.state_dir {
+        Some(state_dir) => tempban::TempbanStore::load(state_dir)?,
+        None => tempban::TempbanStore::in_memory(),
+    };
+    // One guild's invalid configuration shouldn't stop every other guild from
+    // being filtered, so load each guild's configuration separately.
+    let (initial_guild_configs, failed_guild_configs) =
+        config::load_guild_configs_independently(
+        &cfg.guild_config_dir,
+        &cfg.active_guilds,
+        cfg.default_spam.as_ref(),
+    );
+    for (guild_id, report) in &failed_guild_configs {
+        tracing::error!(%guild_id, ?report, "Unable to load guild configuration; this guild will not be filtered until its configuration is fixed");
+    }
+
+    let filter_permits = cfg
+        .max_concurrent_filters
+        .map(|max| Arc::new(Semaphore::new(max.get())));
 
     let state = State {
         armed: Arc::new(AtomicBool::new(cfg.armed_by_default)),
         http,
         spam_history,
+        raid_index: Arc::new(Mutex::new(raid::RaidIndex::default())),
+        reaction_counts: Arc::new(RwLock::new(reaction::ReactionCounts::default())),
+        reaction_spam_history: Arc::new(Mutex::new(reaction::ReactionSpamHistory::default())),
+        flood_tracker: Arc::new(Mutex::new(flood::FloodTracker::default())),
+        repost_history: Arc::new(Mutex::new(repost::RepostHistory::default())),
+        warnings: Arc::new(warnings),
+        quarantines: Arc::new(quarantines),
+        tempbans: Arc::new(tempbans),
+        slowmodes: Arc::new(Mutex::new(action::Slowmodes::default())),
+        pending_deletes: Arc::new(Mutex::new(action::PendingDeletes::default())),
         cfg,
         cache: Arc::new(cache),
         application_id: Arc::new(RwLock::new(None)),
-        guild_cfgs: Arc::new(RwLock::new(initial_guild_configs)),
+        guild_cfgs: Arc::new(RwLock::new(
+            initial_guild_configs
+                .into_iter()
+                .map(|(guild_id, guild_config)| (guild_id, Arc::new(guild_config)))
+                .collect(),
+        )),
+        disabled_filters: Arc::new(RwLock::new(HashMap::new())),
+        guild_stickers: Arc::new(RwLock::new(HashMap::new())),
         influx_client: Arc::new(influx_client),
         influx_report_count: Arc::new(AtomicUsize::new(0)),
+        web_client: reqwest::Client::new(),
+        filter_permits,
+        shed_events: Arc::new(AtomicU64::new(0)),
     };
 
+    let health = Arc::new(health::Health::default());
+    if let Some(health_config) = &state.cfg.http_health {
+        let server = health::bind(health_config, health.clone(), state.guild_cfgs.clone())?;
+        tracing::info!(bind = %health_config.bind, "Serving health checks");
+        tokio::spawn(async move {
+            if let Err(err) = server.await {
+                tracing::error!(?err, "Health check server failed");
+            }
+        });
+    }
+
+    tokio::spawn(lift_tempbans(state.clone()));
+    tokio::spawn(prune_spam_history(state.clone()));
+
+    if let Err(err) = refresh_guild_stickers(&state).await {
+        tracing::error!(?err, "Error fetching guild stickers");
+    }
+
     tracing::info!("About to enter main event loop; Chrysanthemum is now online.");
 
     for (guild_id, _) in state.guild_cfgs.read().await.iter() {
@@ -228,36 +358,251 @@ fn main() -> Result<()> {
         }
     }
 
+    let mut watcher = if state.cfg.watch_guild_configs {
+        watch::ConfigWatcher::new(
+            &state.cfg.guild_config_dir,
+            &included_config_files(&state).await,
+            watch::WATCH_DEBOUNCE,
+        )
+    } else {
+        None
+    };
+    // Only used while configurations aren't being watched.
     let mut interval = tokio::time::interval(Duration::from_secs(
         state.cfg.reload_interval.unwrap_or(DEFAULT_RELOAD_INTERVAL),
     ));
     interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let shutdown_signal = shutdown_signal();
+    tokio::pin!(shutdown_signal);
     loop {
         tokio::select! {
+            result = &mut shutdown_signal => {
+                result?;
+                tracing::info!("Received shutdown signal; Chrysanthemum is going offline.");
+                break;
+            },
             Some(event) = events.next() => {
+                health.record_event(&event, Utc::now().timestamp());
+                // Edits are filtered after the cache has been updated, so grab
+                // the content from before the edit while it's still there.
+                let previous_content = match &event {
+                    Event::MessageUpdate(update) => state
+                        .cache
+                        .message(update.id)
+                        .map(|message| message.content().to_owned()),
+                    _ => None,
+                };
                 state.cache.update(&event);
-                tokio::spawn(handle_event_wrapper(event, state.clone()).instrument(tracing::debug_span!("Handling event")));
+                spawn_event_handler(event, previous_content, state.clone());
             },
-            _ = interval.tick() => {
-                let result = reload_guild_configs(&state).await;
-                if let Err((guild_id, report)) = result {
-                    tracing::error!(?guild_id, ?report, "Error reloading guild configuration");
-                    send_notification_to_guild(&state, guild_id, "Configuration reload failed", &format!("Failure reason:\n```{:#?}```\nConfiguration changes have **not** been applied.", report)).await?;
+            changes = async { watcher.as_mut()?.changed().await }, if watcher.is_some() => {
+                let errors = match changes {
+                    Some(changes) => {
+                        let included_files = included_config_files(&state).await;
+                        let changed_guilds = changes.guilds(&state.cfg.guild_config_dir, &state.cfg.active_guilds, &included_files);
+                        let errors = if changed_guilds.is_empty() {
+                            Vec::new()
+                        } else {
+                            reload_some_guild_configs(&state, &changed_guilds).await
+                        };
+
+                        // Reloading can change which files are included.
+                        if let Some(watcher) = &mut watcher {
+                            watcher.watch_included(&included_config_files(&state).await);
+                        }
+                        errors
+                    }
+                    None => {
+                        tracing::warn!("Stopped watching guild configurations; falling back to reloading them on an interval");
+                        // The first tick is immediate, so any changes that
+                        // were missed are picked up straight away.
+                        watcher = None;
+                        Vec::new()
+                    }
+                };
+                notify_reload_failures(&state, errors).await;
+            }
+            _ = interval.tick(), if watcher.is_none() => {
+                let errors = reload_guild_configs(&state).await;
+                notify_reload_failures(&state, errors).await;
+            }
+        }
+    }
+
+    shard.shutdown();
+
+    // Influx points are written as each event is handled, so there's nothing
+    // buffered to flush here; Sentry's queue is flushed when its guard is
+    // dropped below.
+    let notify_offline = async {
+        for (guild_id, _) in state.guild_cfgs.read().await.iter() {
+            let result = send_notification_to_guild(
+                &state,
+                *guild_id,
+                "Chrysanthemum offline",
+                "Chrysanthemum is shutting down.",
+            )
+            .await;
+            if let Err(err) = result {
+                tracing::error!(?err, %guild_id, "Error sending down notification");
+            }
+        }
+    };
+
+    if tokio::time::timeout(SHUTDOWN_TIMEOUT, notify_offline).await.is_err() {
+        tracing::warn!("Timed out sending down notifications");
+    }
+
+    Ok(())
+    });
+
+    if let Err(report) = &result {
+        tracing::error!(?report, "Chrysanthemum exited with an error");
+    }
+
+    // Dropping the guard flushes any queued events, including the error above.
+    // This must happen after the runtime has shut down, so that events from
+    // tasks it was still running are captured too.
+    drop(sentry_guard);
+
+    result
+}
+
+/// Resolves once the process is asked to stop, either with Ctrl+C (SIGINT) or,
+/// on Unix, with SIGTERM.
+async fn shutdown_signal() -> Result<()> {
+    #[cfg(unix)]
+    {
+        let mut sigterm =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result?,
+            _ = sigterm.recv() => {},
+        }
+    }
+
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await?;
+
+    Ok(())
+}
+
+fn init_sentry(sentry_config: &SentryConfig) -> sentry::ClientInitGuard {
+    sentry::init((
+        sentry_config.url.clone(),
+        sentry::ClientOptions {
+            release: sentry::release_name!(),
+            traces_sample_rate: sentry_config.sample_rate.unwrap_or(0.01),
+            debug: cfg!(debug_assertions),
+            // Panics are captured by the panic integration, which is part of the
+            // default integrations.
+            ..Default::default()
+        },
+    ))
+}
+
+/// Spawns a task to handle an event, along with a supervisory task that
+/// reports the handler panicking instead of letting it disappear silently.
+fn spawn_event_handler(event: Event, previous_content: Option<String>, state: State) {
+    let event_kind = event.kind();
+
+    // Only filtering is limited; everything else, like commands and guild
+    // updates, is cheap and shouldn't be lost.
+    let permit = match (&state.filter_permits, &event) {
+        (
+            Some(permits),
+            Event::MessageCreate(_) | Event::MessageUpdate(_) | Event::ReactionAdd(_),
+        ) => match permits.clone().try_acquire_owned() {
+            Ok(permit) => Some(permit),
+            Err(_) => {
+                shed_event(&state, event_kind);
+                return;
+            }
+        },
+        _ => None,
+    };
+
+    // Each event gets its own hub, so that anything reported while handling it
+    // (including panics) is tagged with the event kind.
+    let hub = Arc::new(sentry::Hub::new_from_top(sentry::Hub::current()));
+    hub.configure_scope(|scope| scope.set_tag("event", format!("{:?}", event_kind)));
+
+    let span = tracing::debug_span!("Handling event", ?event_kind);
+    let handle = tokio::spawn(
+        async move {
+            handle_event_wrapper(event, previous_content, state).await;
+            drop(permit);
+        }
+        .instrument(span.clone())
+        .bind_hub(hub),
+    );
+
+    tokio::spawn(
+        async move {
+            match handle.await {
+                Ok(()) => {}
+                Err(err) if err.is_panic() => {
+                    let payload = err.into_panic();
+                    tracing::error!(
+                        ?event_kind,
+                        panic = panic_message(payload.as_ref()),
+                        "Panicked while handling event"
+                    );
+                }
+                Err(err) => {
+                    tracing::warn!(?event_kind, ?err, "Event handler was cancelled");
                 }
             }
         }
+        .instrument(span),
+    );
+}
+
+/// Records that an event was dropped because too many were already being
+/// filtered.
+fn shed_event(state: &State, event_kind: twilight_model::gateway::event::EventType) {
+    let shed_events = state.shed_events.fetch_add(1, Ordering::Relaxed) + 1;
+    tracing::trace!(?event_kind, shed_events, "Dropped event unfiltered");
+
+    if shed_events % SHED_EVENT_LOG_INTERVAL == 1 {
+        tracing::warn!(
+            shed_events,
+            max_concurrent_filters = ?state.cfg.max_concurrent_filters,
+            "Shedding load: dropping events unfiltered because too many are already being filtered"
+        );
+
+        let report = ShedEventReport {
+            time: Utc::now(),
+            shed_events,
+        };
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(err) = send_influx_point(&state, &report.into_query("shed_events")).await {
+                tracing::error!("Unable to send Influx report: {:?}", err);
+            }
+        });
     }
-    })
 }
 
-async fn handle_event_wrapper(event: Event, state: State) {
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&'static str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "<non-string panic payload>"
+    }
+}
+
+async fn handle_event_wrapper(event: Event, previous_content: Option<String>, state: State) {
     let start = Instant::now();
-    let result = handle_event(&event, state.clone()).await;
+    let result = handle_event(&event, previous_content.as_deref(), state.clone()).await;
     let end = Instant::now();
     let time = end - start;
 
     if let Err(report) = result {
-        tracing::error!(result = ?report, event = ?event, "Error handling event");
+        tracing::error!(result = ?report, event_kind = ?event.kind(), event = ?event, "Error handling event");
     }
 
     let (guild_id, channel_id, action_kind) = match event {
@@ -299,19 +644,42 @@ async fn handle_event_wrapper(event: Event, state: State) {
 }
 
 #[tracing::instrument(skip(state))]
-async fn handle_event(event: &Event, state: State) -> Result<()> {
+async fn handle_event(event: &Event, previous_content: Option<&str>, state: State) -> Result<()> {
     match event {
         Event::MessageCreate(message) => {
             let message = &message.0;
             filter_message(message, state).await?;
         }
         Event::MessageUpdate(update) => {
-            filter_message_edit(update, &state).await?;
+            filter_message_edit(update, previous_content, &state).await?;
         }
         Event::ReactionAdd(rxn) => {
             let rxn = &rxn.0;
             filter_reaction(rxn, state).await?;
         }
+        Event::ReactionRemove(rxn) => {
+            let rxn = &rxn.0;
+            state
+                .reaction_counts
+                .write()
+                .await
+                .remove(rxn.message_id, &rxn.emoji, rxn.user_id);
+            log_removed_reaction(rxn, state).await?;
+        }
+        Event::ReactionRemoveEmoji(removal) => {
+            state
+                .reaction_counts
+                .write()
+                .await
+                .remove_emoji(removal.message_id, &removal.emoji);
+        }
+        Event::ReactionRemoveAll(removal) => {
+            state
+                .reaction_counts
+                .write()
+                .await
+                .remove_all(removal.message_id);
+        }
         Event::Ready(ready) => {
             {
                 *state.application_id.write().await = Some(ready.application.id);
@@ -331,8 +699,20 @@ async fn handle_event(event: &Event, state: State) -> Result<()> {
         }
         Event::InteractionCreate(interaction) => {
             let interaction = &interaction.0;
-            if let Some(InteractionData::ApplicationCommand(cmd)) = &interaction.data {
-                command::handle_command(state.clone(), interaction, cmd.as_ref()).await?;
+            match &interaction.data {
+                // Autocomplete requests come with the same data as commands.
+                Some(InteractionData::ApplicationCommand(cmd))
+                    if interaction.kind == InteractionType::ApplicationCommandAutocomplete =>
+                {
+                    command::handle_autocomplete(state.clone(), interaction, cmd.as_ref()).await?;
+                }
+                Some(InteractionData::ApplicationCommand(cmd)) => {
+                    command::handle_command(state.clone(), interaction, cmd.as_ref()).await?;
+                }
+                Some(InteractionData::MessageComponent(data)) => {
+                    component::handle_component(state.clone(), interaction, data).await?;
+                }
+                _ => {}
             }
         }
         _ => {}
@@ -341,11 +721,60 @@ async fn handle_event(event: &Event, state: State) -> Result<()> {
     Ok(())
 }
 
+/// Reloads every guild's configuration. Guilds whose configurations load
+/// successfully are updated even if others fail; the guilds that failed keep
+/// their previous configurations, and are returned along with why they failed.
+async fn reload_guild_configs(state: &State) -> Vec<GuildConfigError> {
+    reload_some_guild_configs(state, &state.cfg.active_guilds).await
+}
+
+/// Tells each guild whose configuration failed to reload why, and that it's
+/// still using its previous configuration. Failing to notify a guild is only
+/// logged, so that a broken notification channel can't stop the bot.
+async fn notify_reload_failures(state: &State, errors: Vec<GuildConfigError>) {
+    for (guild_id, report) in errors {
+        tracing::error!(?guild_id, ?report, "Error reloading guild configuration");
+        let result = send_notification_to_guild(
+            state,
+            guild_id,
+            "Configuration reload failed",
+            &format!(
+                "Failure reason:\n```{:#?}```\nConfiguration changes have **not** been applied.",
+                report
+            ),
+        )
+        .await;
+        if let Err(err) = result {
+            tracing::warn!(?err, %guild_id, "Error sending reload failure notification");
+        }
+    }
+}
+
+/// The files each loaded guild configuration includes, so that changes to them
+/// can be watched for too.
+async fn included_config_files(state: &State) -> watch::IncludedFiles {
+    state
+        .guild_cfgs
+        .read()
+        .await
+        .iter()
+        .map(|(guild_id, guild_config)| (*guild_id, guild_config.include_sources.files.clone()))
+        .collect()
+}
+
+/// Like [`reload_guild_configs`], but only reloads the configurations of
+/// `guild_ids`, leaving every other guild's alone.
 #[tracing::instrument(skip(state))]
-async fn reload_guild_configs(state: &State) -> Result<(), (Id<GuildMarker>, eyre::Report)> {
+async fn reload_some_guild_configs(
+    state: &State,
+    guild_ids: &[Id<GuildMarker>],
+) -> Vec<GuildConfigError> {
     tracing::debug!("Reloading guild configurations");
-    let new_guild_configs =
-        crate::config::load_guild_configs(&state.cfg.guild_config_dir, &state.cfg.active_guilds)?;
+    let (mut new_guild_configs, mut errors) = crate::config::load_guild_configs_independently(
+        &state.cfg.guild_config_dir,
+        guild_ids,
+        state.cfg.default_spam.as_ref(),
+    );
     let mut guild_cfgs = state.guild_cfgs.write().await;
     let application_id = *state.application_id.read().await;
 
@@ -354,24 +783,296 @@ async fn reload_guild_configs(state: &State) -> Result<(), (Id<GuildMarker>, eyr
     if let Some(application_id) = application_id {
         let interaction_http = state.http.interaction(application_id);
 
+        let mut failed_guilds = Vec::new();
         for (guild_id, new_guild_config) in &new_guild_configs {
             tracing::trace!(%guild_id, "Updating guild commands");
 
-            command::update_guild_commands(
+            let result = command::update_guild_commands(
                 &interaction_http,
                 *guild_id,
                 new_guild_config.slash_commands.as_ref(),
             )
-            .await
-            .map_err(|e| (*guild_id, e))?;
+            .await;
+            if let Err(err) = result {
+                failed_guilds.push(*guild_id);
+                errors.push((*guild_id, err));
+            }
+        }
+
+        for guild_id in failed_guilds {
+            new_guild_configs.remove(&guild_id);
+        }
+    }
+
+    let mut disabled_filters = state.disabled_filters.write().await;
+    let mut notifications = Vec::new();
+    for (guild_id, new_guild_config) in new_guild_configs {
+        let old_summary = guild_cfgs
+            .get(&guild_id)
+            .map(|old_guild_config| diff::GuildConfigSummary::new(old_guild_config));
+        let new_summary = diff::GuildConfigSummary::new(&new_guild_config);
+        if let Some(changes) = diff::reload_notification(old_summary.as_ref(), &new_summary) {
+            notifications.push((guild_id, changes));
+        }
+
+        disabled_filters.remove(&guild_id);
+        guild_cfgs.insert(guild_id, Arc::new(new_guild_config));
+    }
+    drop(disabled_filters);
+    drop(guild_cfgs);
+
+    // Reloads happen every few minutes, so guilds are only told about ones
+    // that changed something.
+    for (guild_id, changes) in notifications {
+        let result =
+            send_notification_to_guild(state, guild_id, "Configuration reloaded", &changes).await;
+        if let Err(err) = result {
+            tracing::warn!(?err, %guild_id, "Error sending configuration change notification");
+        }
+    }
+
+    if let Err(err) = refresh_guild_stickers(state).await {
+        tracing::error!(?err, "Error fetching guild stickers");
+    }
+
+    errors
+}
+
+/// Fetches the stickers belonging to each configured guild, for use by the
+/// `sticker_source` rule. Discord's built-in stickers are included in every
+/// guild's set, since they don't come from another server.
+#[tracing::instrument(skip(state))]
+async fn refresh_guild_stickers(state: &State) -> Result<()> {
+    let guild_ids: Vec<_> = state.guild_cfgs.read().await.keys().copied().collect();
+
+    let standard_stickers: Vec<_> = state
+        .http
+        .nitro_sticker_packs()
+        .await?
+        .model()
+        .await?
+        .sticker_packs
+        .into_iter()
+        .flat_map(|pack| pack.stickers)
+        .map(|sticker| sticker.id)
+        .collect();
+
+    let mut new_guild_stickers = HashMap::new();
+    for guild_id in guild_ids {
+        match fetch_guild_sticker_ids(&state.http, guild_id).await {
+            Ok(guild_sticker_ids) => {
+                let mut sticker_ids = standard_stickers.clone();
+                sticker_ids.extend(guild_sticker_ids);
+                new_guild_stickers.insert(guild_id, sticker_ids);
+            }
+            Err(err) => {
+                // Keep whatever we fetched last time, if anything.
+                tracing::warn!(?err, %guild_id, "Unable to fetch guild stickers");
+                if let Some(old_stickers) = state.guild_stickers.read().await.get(&guild_id) {
+                    new_guild_stickers.insert(guild_id, old_stickers.clone());
+                }
+            }
         }
     }
 
-    *guild_cfgs = new_guild_configs;
+    *state.guild_stickers.write().await = new_guild_stickers;
 
     Ok(())
 }
 
+async fn fetch_guild_sticker_ids(
+    http: &HttpClient,
+    guild_id: Id<GuildMarker>,
+) -> Result<Vec<Id<StickerMarker>>> {
+    let stickers = http.guild_stickers(guild_id).await?.models().await?;
+    Ok(stickers.into_iter().map(|sticker| sticker.id).collect())
+}
+
+fn action_context(state: &State) -> ActionContext {
+    ActionContext {
+        http: state.http.clone(),
+        warnings: state.warnings.clone(),
+        quarantines: state.quarantines.clone(),
+        cache: state.cache.clone(),
+        web_client: state.web_client.clone(),
+        slowmodes: state.slowmodes.clone(),
+        pending_deletes: state.pending_deletes.clone(),
+        tempbans: state.tempbans.clone(),
+    }
+}
+
+/// Lifts temporary bans as they come due. Bans that came due while
+/// Chrysanthemum was offline are lifted on the first check.
+async fn lift_tempbans(state: State) {
+    let mut interval = tokio::time::interval(TEMPBAN_CHECK_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+
+        for unban in state.tempbans.due(Utc::now().timestamp()).await {
+            // Unbans that fail are left scheduled, so they're tried again on
+            // the next check.
+            if let Err(err) = lift_tempban(&state, &unban).await {
+                tracing::warn!(?unban, ?err, "Error lifting temporary ban; will retry");
+                continue;
+            }
+
+            if let Err(err) = state.tempbans.remove(&unban).await {
+                tracing::error!(?unban, ?err, "Error removing lifted temporary ban");
+            }
+        }
+    }
+}
+
+/// Clears out spam history, raid records, reaction spam history, channel
+/// message rates, and remembered messages that are older than any guild's spam
+/// interval, raid window, reaction spam interval, flood interval, or
+/// `no_repost` window, so that users who stop sending messages, like raid
+/// accounts, don't stay in memory forever.
+async fn prune_spam_history(state: State) {
+    let mut interval = tokio::time::interval(SPAM_HISTORY_PRUNE_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+
+        let (max_interval, max_raid_window) = state
+            .guild_cfgs
+            .read()
+            .await
+            .values()
+            .filter_map(|guild_config| guild_config.spam.as_ref())
+            .fold((0, 0), |(max_interval, max_raid_window), spam_config| {
+                (
+                    max_interval.max(spam_config.interval),
+                    spam_config.raid.as_ref().map_or(max_raid_window, |raid| {
+                        max_raid_window.max(raid.window_seconds)
+                    }),
+                )
+            });
+        let (max_reaction_spam_interval, max_flood_interval) =
+            state.guild_cfgs.read().await.values().fold(
+                (0, 0),
+                |(max_reaction_spam, max_flood), guild_config| {
+                    (
+                        guild_config
+                            .reaction_spam
+                            .as_ref()
+                            .map_or(max_reaction_spam, |reaction_spam| {
+                                max_reaction_spam.max(reaction_spam.interval)
+                            }),
+                        guild_config
+                            .flood
+                            .as_ref()
+                            .map_or(max_flood, |flood| max_flood.max(flood.interval)),
+                    )
+                },
+            );
+        let max_no_repost_window = state
+            .guild_cfgs
+            .read()
+            .await
+            .values()
+            .filter_map(|guild_config| guild_config.no_repost_window())
+            .max()
+            .unwrap_or(0);
+        let now = (Utc::now().timestamp_millis() as u64) * 1000;
+
+        let removed = filter::prune_spam_history(&state.spam_history, max_interval, now).await;
+        tracing::debug!(removed, "Pruned spam history");
+
+        let removed = state.raid_index.lock().await.prune(max_raid_window, now);
+        tracing::debug!(removed, "Pruned raid index");
+
+        let removed = state
+            .reaction_spam_history
+            .lock()
+            .await
+            .prune(max_reaction_spam_interval, now);
+        tracing::debug!(removed, "Pruned reaction spam history");
+
+        let removed = state
+            .flood_tracker
+            .lock()
+            .await
+            .prune(max_flood_interval, now);
+        tracing::debug!(removed, "Pruned flood tracker");
+
+        let removed = state
+            .repost_history
+            .lock()
+            .await
+            .prune(max_no_repost_window, now);
+        tracing::debug!(removed, "Pruned repost history");
+
+        let removed = state
+            .reaction_counts
+            .write()
+            .await
+            .prune(REACTION_COUNT_MAX_AGE, now);
+        tracing::debug!(removed, "Pruned reaction counts");
+    }
+}
+
+async fn lift_tempban(state: &State, unban: &tempban::ScheduledUnban) -> Result<()> {
+    let result = state
+        .http
+        .delete_ban(unban.guild_id, unban.user_id)
+        .reason(&format!(
+            "Temporary ban by filter {} expired",
+            unban.filter_name
+        ))?
+        .await;
+
+    match result {
+        Ok(_) => Ok(()),
+        // Someone already unbanned them by hand.
+        Err(err) if action::not_banned(&err) => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Copies the IDs of a guild's channels out of the cache, so that no reference
+/// into the cache is held while filtering.
+fn cached_guild_channels(
+    cache: &InMemoryCache,
+    guild_id: Id<GuildMarker>,
+) -> Option<Vec<Id<ChannelMarker>>> {
+    cache
+        .guild_channels(guild_id)
+        .map(|channels| channels.iter().copied().collect())
+}
+
+/// Gets the channel a thread is in, so that scoping can match messages in the
+/// thread by its parent channel. Returns `None` for channels that aren't
+/// threads. Threads that aren't cached are fetched.
+async fn thread_parent_channel(
+    state: &State,
+    channel_id: Id<ChannelMarker>,
+) -> Option<Id<ChannelMarker>> {
+    if let Some(channel) = state.cache.channel(channel_id) {
+        return channel.parent_id.filter(|_| channel.kind.is_thread());
+    }
+
+    let channel =
+        async { Ok::<_, eyre::Report>(state.http.channel(channel_id).await?.model().await?) }.await;
+
+    match channel {
+        Ok(channel) => channel.parent_id.filter(|_| channel.kind.is_thread()),
+        Err(err) => {
+            tracing::warn!(?err, %channel_id, "Unable to fetch channel to find its parent");
+            None
+        }
+    }
+}
+
+fn mentioned_user_ids(
+    mentions: &[twilight_model::channel::message::Mention],
+) -> Vec<Id<UserMarker>> {
+    mentions.iter().map(|mention| mention.id).collect()
+}
+
 #[tracing::instrument(skip(state))]
 async fn filter_message_info<'msg>(
     guild_id: Id<GuildMarker>,
@@ -379,74 +1080,148 @@ async fn filter_message_info<'msg>(
     state: &'msg State,
     context: &'static str,
 ) -> Result<()> {
-    let guild_cfgs = state.guild_cfgs.read().await;
-    if let Some(guild_config) = guild_cfgs.get(&guild_id) {
-        if message_info.author_is_bot && !guild_config.include_bots {
-            tracing::trace!(?guild_id, author = %message_info.author_id, "Skipping message filtration because message was sent by a bot and include_bots is false for this guild");
-            return Ok(());
+    match check_message_info(guild_id, message_info, state, context).await {
+        Some(failure) => {
+            act_on_filtered_message(guild_id, message_info.channel_id, failure, state, context)
+                .await
         }
+        None => Ok(()),
+    }
+}
 
-        tracing::trace!(?message_info, "Filtering message");
-
-        if let Some(message_filters) = &guild_config.messages {
-            let now = (Utc::now().timestamp_millis() as u64) * 1000;
-
-            let result = crate::message::filter_and_spam_check_message(
-                guild_config.spam.as_ref(),
-                &message_filters[..],
-                guild_config.default_scoping.as_ref(),
-                guild_config.default_actions.as_deref(),
-                state.spam_history.clone(),
-                message_info,
-                context,
-                now,
-            )
-            .await;
+/// Checks a message against its guild's filters, returning why it was
+/// filtered, if it was. Nothing is done about it yet; see
+/// [`act_on_filtered_message`].
+#[tracing::instrument(skip(state))]
+async fn check_message_info<'msg>(
+    guild_id: Id<GuildMarker>,
+    message_info: &'msg MessageInfo<'_>,
+    state: &'msg State,
+    context: &'static str,
+) -> Option<MessageFilterFailure> {
+    // Filtering can wait on other services, so the guild's configuration is
+    // taken out of the lock rather than holding it up for reloads.
+    let guild_config = state.guild_cfgs.read().await.get(&guild_id)?.clone();
+
+    if !guild_config.filters_author(message_info.author_is_bot, message_info.is_webhook) {
+        if message_info.is_webhook {
+            tracing::trace!(?guild_id, webhook = %message_info.author_id, "Skipping message filtration because message was sent by a webhook and filter_webhooks is false for this guild");
+        } else {
+            tracing::trace!(?guild_id, author = %message_info.author_id, "Skipping message filtration because message was sent by a bot and include_bots is false for this guild");
+        }
 
-            if let Err(failure) = result {
-                tracing::trace!(%message_info.id, %message_info.channel_id, %message_info.author_id, ?failure, "Message filtered");
+        return None;
+    }
 
-                let armed = state.armed.load(Ordering::Relaxed);
-                let mut deleted = false;
+    if message_info.author_is_bot && guild_config.is_allowlisted_bot(message_info.author_id) {
+        tracing::trace!(?guild_id, author = %message_info.author_id, "Skipping message filtration because message was sent by a bot in the guild's bot_allowlist");
+        return None;
+    }
 
-                for action in failure.actions {
-                    tracing::trace!(?action, "Executing action");
+    tracing::trace!(?message_info, "Filtering message");
 
-                    // We only want to execute Delete actions once per message,
-                    // since we'll get a 404 on subsequent requests.
-                    if let MessageAction::Delete { .. } = action {
-                        if deleted {
-                            tracing::trace!(?action, "Skipping duplicate delete action");
-                            continue;
-                        }
+    let message_filters = guild_config.messages.as_ref()?;
+    let trusted = guild_config.is_trusted(message_info.author_roles);
+    if trusted && guild_config.trusted_roles_skip_spam {
+        tracing::trace!(?guild_id, author = %message_info.author_id, "Skipping message filtration because author has a trusted role");
+        return None;
+    }
 
-                        deleted = true;
-                    }
+    // Trusted members that don't skip spam filtering are still spam
+    // checked, but no filters are applied to their messages.
+    let message_filters = if trusted { &[] } else { &message_filters[..] };
 
-                    if action.requires_armed() && !armed {
-                        tracing::trace!(?action, "Skipping execution because we are not armed");
-                        continue;
-                    }
+    let now = (Utc::now().timestamp_millis() as u64) * 1000;
 
-                    if let Err(action_err) = action.execute(&state.http).await {
-                        tracing::warn!(?action, ?action_err, "Error executing action");
-                    }
-                }
+    let last_message = if guild_config.no_repost_window().is_some() {
+        state.repost_history.lock().await.record(message_info)
+    } else {
+        None
+    };
+    let message_info = &MessageInfo {
+        last_message: last_message.as_ref(),
+        ..message_info.clone()
+    };
 
-                tracing::trace!(%message_info.id, %message_info.channel_id, %message_info.author_id, "Filtration completed, all actions executed");
+    let disabled_filters = state.disabled_filters.read().await.get(&guild_id).cloned();
+    let result = crate::message::filter_and_spam_check_message(
+        guild_config.spam.as_ref(),
+        message_filters,
+        disabled_filters.as_ref(),
+        guild_config.default_scoping.as_ref(),
+        guild_config.default_actions.as_deref(),
+        state.spam_history.clone(),
+        state.raid_index.clone(),
+        &state.web_client,
+        message_info,
+        context,
+        now,
+    )
+    .await;
+
+    match result {
+        Ok(()) => None,
+        Err(failure) => {
+            tracing::trace!(%message_info.id, %message_info.channel_id, %message_info.author_id, ?failure, "Message filtered");
+            Some(failure)
+        }
+    }
+}
 
-                let report = MessageFilterReport {
-                    time: Utc::now(),
-                    guild: guild_id.to_string(),
-                    channel: message_info.channel_id.to_string(),
-                };
+/// Takes the actions for a message that was filtered, and reports it to
+/// Influx.
+#[tracing::instrument(skip(state))]
+async fn act_on_filtered_message(
+    guild_id: Id<GuildMarker>,
+    channel_id: Id<ChannelMarker>,
+    failure: MessageFilterFailure,
+    state: &State,
+    context: &'static str,
+) -> Result<()> {
+    let armed = state.armed.load(Ordering::Relaxed);
+    let mut deleted = HashSet::new();
+
+    for action in failure.actions {
+        tracing::trace!(?action, "Executing action");
+
+        // We only want to execute Delete actions once per message,
+        // since we'll get a 404 on subsequent requests.
+        if let MessageAction::Delete { message_id, .. } = &action {
+            if !deleted.insert(*message_id) {
+                tracing::trace!(?action, "Skipping duplicate delete action");
+                continue;
+            }
+        }
 
-                send_influx_point(state, &report.into_query(context)).await?;
-                tracing::trace!(%message_info.id, %message_info.channel_id, %message_info.author_id, "Influx point sent");
+        let action = if state.cfg.dry_run_globally {
+            tracing::info!(?action, "Dry run; would have executed action");
+            match action.into_dry_run() {
+                Some(action) => action,
+                None => continue,
             }
+        } else if action.requires_armed() && !armed {
+            tracing::trace!(?action, "Skipping execution because we are not armed");
+            continue;
+        } else {
+            action
+        };
+
+        if let Err(action_err) = action.execute_with_retries(&action_context(state)).await {
+            tracing::warn!(?action, ?action_err, "Error executing action");
         }
     }
 
+    tracing::trace!(%channel_id, filter = %failure.filter_name, "Filtration completed, all actions executed");
+
+    let report = MessageFilterReport {
+        time: Utc::now(),
+        guild: guild_id.to_string(),
+        channel: channel_id.to_string(),
+    };
+
+    send_influx_point(state, &report.into_query(context)).await?;
+    tracing::trace!(%channel_id, "Influx point sent");
+
     Ok(())
 }
 
@@ -457,8 +1232,12 @@ async fn filter_message(message: &Message, state: State) -> Result<()> {
         None => return Ok(()),
     };
 
-    let member = match message.member.as_ref() {
-        Some(member) => member,
+    // Webhooks aren't members, so their messages never have member
+    // information.
+    let is_webhook = message.webhook_id.is_some();
+    let (author_roles, joined_at) = match message.member.as_ref() {
+        Some(member) => (&member.roles[..], Some(member.joined_at)),
+        None if is_webhook => (&[][..], None),
         None => {
             // For non-bot users, this should always be set.
             if !message.author.bot {
@@ -474,6 +1253,10 @@ async fn filter_message(message: &Message, state: State) -> Result<()> {
     };
 
     let clean_message_content = crate::message::clean_mentions(&message.content, &message.mentions);
+    let mentioned_users = mentioned_user_ids(&message.mentions);
+    let parent_channel_id = thread_parent_channel(&state, message.channel_id).await;
+    let guild_stickers = state.guild_stickers.read().await.get(&guild_id).cloned();
+    let guild_channels = cached_guild_channels(&state.cache, guild_id);
 
     let message_info = MessageInfo {
         id: message.id,
@@ -483,15 +1266,219 @@ async fn filter_message(message: &Message, state: State) -> Result<()> {
         guild_id: message.guild_id.unwrap(),
         timestamp: message.timestamp,
         author_is_bot: message.author.bot,
-        author_roles: &member.roles,
+        is_webhook,
+        author_roles,
+        account_created_at: model::snowflake_timestamp(message.author.id),
+        joined_at,
         content: &clean_message_content,
         attachments: &message.attachments,
         stickers: &message.sticker_items,
+        embeds: &message.embeds,
+        guild_stickers: guild_stickers.as_deref(),
+        guild_channels: guild_channels.as_deref(),
+        previous_content: None,
+        reference: message.reference.as_ref(),
+        mentioned_users: &mentioned_users,
+        last_message: None,
+        parent_channel_id,
     };
 
+    if let Err(err) = check_flood(&message_info, &state).await {
+        tracing::warn!(?err, %message_info.channel_id, "Unable to handle channel flood");
+    }
+
     filter_message_info(guild_id, &message_info, &state, "message create").await
 }
 
+/// Tests an existing message against every filter of its guild without acting
+/// on it, for the message context-menu command. Returns `None` if the guild
+/// wouldn't filter the message at all, such as when its author is a bot and
+/// bots aren't filtered.
+#[tracing::instrument(skip(state))]
+async fn test_existing_message(
+    message: &Message,
+    guild_id: Id<GuildMarker>,
+    state: &State,
+) -> Result<Option<Vec<MessageFilterFailure>>> {
+    let is_webhook = message.webhook_id.is_some();
+    let (author_roles, joined_at) = if is_webhook {
+        (Vec::new(), None)
+    } else {
+        let cached_member = state.cache.member(guild_id, message.author.id);
+        match cached_member.as_ref() {
+            Some(member) => (member.roles().to_owned(), Some(member.joined_at())),
+            None => {
+                let member = state
+                    .http
+                    .guild_member(guild_id, message.author.id)
+                    .await?
+                    .model()
+                    .await?;
+                (member.roles, Some(member.joined_at))
+            }
+        }
+    };
+
+    let clean_message_content = crate::message::clean_mentions(&message.content, &message.mentions);
+    let mentioned_users = mentioned_user_ids(&message.mentions);
+    let parent_channel_id = thread_parent_channel(state, message.channel_id).await;
+    let guild_stickers = state.guild_stickers.read().await.get(&guild_id).cloned();
+    let guild_channels = cached_guild_channels(&state.cache, guild_id);
+
+    let message_info = MessageInfo {
+        id: message.id,
+        author_id: message.author.id,
+        channel_id: message.channel_id,
+        guild_id,
+        timestamp: message.timestamp,
+        author_is_bot: message.author.bot,
+        is_webhook,
+        author_roles: &author_roles,
+        account_created_at: model::snowflake_timestamp(message.author.id),
+        joined_at,
+        content: &clean_message_content,
+        attachments: &message.attachments,
+        stickers: &message.sticker_items,
+        embeds: &message.embeds,
+        guild_stickers: guild_stickers.as_deref(),
+        guild_channels: guild_channels.as_deref(),
+        previous_content: None,
+        reference: message.reference.as_ref(),
+        mentioned_users: &mentioned_users,
+        last_message: None,
+        parent_channel_id,
+    };
+
+    let guild_config = match state.guild_cfgs.read().await.get(&guild_id) {
+        Some(guild_config) => guild_config.clone(),
+        None => return Ok(None),
+    };
+
+    if !guild_config.filters_author(message_info.author_is_bot, message_info.is_webhook)
+        || (message_info.author_is_bot && guild_config.is_allowlisted_bot(message_info.author_id))
+    {
+        return Ok(None);
+    }
+
+    // Trusted members are treated the same way as in `filter_message_info`.
+    let trusted = guild_config.is_trusted(message_info.author_roles);
+    if trusted && guild_config.trusted_roles_skip_spam {
+        return Ok(None);
+    }
+
+    let message_filters = match &guild_config.messages {
+        Some(_) if trusted => &[][..],
+        Some(message_filters) => &message_filters[..],
+        None => return Ok(None),
+    };
+
+    let now = (Utc::now().timestamp_millis() as u64) * 1000;
+    let disabled_filters = state.disabled_filters.read().await.get(&guild_id).cloned();
+    let failures = crate::message::filter_message_every_failure(
+        guild_config.spam.as_ref(),
+        message_filters,
+        disabled_filters.as_ref(),
+        guild_config.default_scoping.as_ref(),
+        guild_config.default_actions.as_deref(),
+        &state.web_client,
+        &message_info,
+        "message test",
+        now,
+    )
+    .await;
+
+    Ok(Some(failures))
+}
+
+/// Counts a new message towards its channel's message rate, and sets the
+/// channel's slowmode if it's being flooded.
+#[tracing::instrument(skip(state))]
+async fn check_flood(message_info: &MessageInfo<'_>, state: &State) -> Result<()> {
+    // Setting the slowmode and sending the notification both wait on Discord,
+    // so the configuration's lock isn't held for them.
+    let guild_config = match state.guild_cfgs.read().await.get(&message_info.guild_id) {
+        Some(guild_config) => guild_config.clone(),
+        None => return Ok(()),
+    };
+
+    let flood_config = match &guild_config.flood {
+        Some(flood_config) => flood_config,
+        None => return Ok(()),
+    };
+
+    if !guild_config.filters_author(message_info.author_is_bot, message_info.is_webhook) {
+        return Ok(());
+    }
+
+    if let Some(scoping) = flood_config
+        .scoping
+        .as_ref()
+        .or(guild_config.default_scoping.as_ref())
+    {
+        if !scoping.is_included(
+            message_info.channel_id,
+            message_info.parent_channel_id,
+            message_info.author_roles,
+        ) {
+            return Ok(());
+        }
+    }
+
+    let now = (Utc::now().timestamp_millis() as u64) * 1000;
+    let check = state
+        .flood_tracker
+        .lock()
+        .await
+        .record(message_info.channel_id, flood_config, now);
+    let messages = match check {
+        flood::FloodCheck::Calm => return Ok(()),
+        flood::FloodCheck::Started { messages } => Some(messages),
+        // Setting the slowmode again pushes the revert back, so that it's
+        // only reverted once the flood is over.
+        flood::FloodCheck::Continuing => None,
+    };
+
+    let action = MessageAction::SetSlowmode {
+        channel_id: message_info.channel_id,
+        seconds: flood_config.slowmode_seconds,
+        revert_after: Some(flood_config.revert_after),
+    };
+    let slowmode_set = if state.cfg.dry_run_globally {
+        tracing::info!(?action, "Dry run; would have set slowmode for a flood");
+        false
+    } else if !state.armed.load(Ordering::Relaxed) {
+        tracing::trace!(
+            ?action,
+            "Not setting slowmode for a flood because we are not armed"
+        );
+        false
+    } else {
+        action.execute_with_retries(&action_context(state)).await?;
+        true
+    };
+
+    let messages = match messages {
+        Some(messages) if flood_config.notify => messages,
+        _ => return Ok(()),
+    };
+
+    let mut body = format!(
+        "<#{}> received {} messages in the last {} seconds.",
+        message_info.channel_id, messages, flood_config.interval
+    );
+    if slowmode_set {
+        body += &format!(
+            " Its slowmode has been set to {} seconds, and will be reverted {} seconds after the flood ends.",
+            flood_config.slowmode_seconds, flood_config.revert_after
+        );
+    } else {
+        body +=
+            " Its slowmode has not been changed, since Chrysanthemum is disarmed or in a dry run.";
+    }
+
+    send_notification_to_guild(state, message_info.guild_id, "Channel flooded", &body).await
+}
+
 #[tracing::instrument(skip(state))]
 async fn filter_reaction(rxn: &GatewayReaction, state: State) -> Result<()> {
     if rxn.guild_id.is_none() {
@@ -508,25 +1495,74 @@ async fn filter_reaction(rxn: &GatewayReaction, state: State) -> Result<()> {
 
     let member = rxn.member.as_ref().unwrap();
 
-    let guild_cfgs = state.guild_cfgs.read().await;
-    if let Some(guild_config) = guild_cfgs.get(&guild_id) {
+    let guild_config = state.guild_cfgs.read().await.get(&guild_id).cloned();
+    if let Some(guild_config) = guild_config {
+        // Bots' reactions still count towards the total, even if they're not
+        // filtered themselves.
+        let reaction_filters = guild_config.reactions.as_deref();
+        let reaction_count = if reaction_filters.map_or(false, reaction::uses_reaction_counts) {
+            state.reaction_counts.write().await.add(
+                rxn.message_id,
+                &rxn.emoji,
+                rxn.user_id,
+                (Utc::now().timestamp_millis() as u64) * 1000,
+            )
+        } else {
+            0
+        };
+
         if member.user.bot && !guild_config.include_bots {
             tracing::trace!("A reaction was added by a bot and include_bots is not set. Ignoring.");
             return Ok(());
         }
 
-        if let Some(reaction_filters) = &guild_config.reactions {
-            let reaction_info = ReactionInfo {
-                author_is_bot: member.user.bot,
-                author_roles: &member.roles,
-                author_id: rxn.user_id,
-                channel_id: rxn.channel_id,
-                message_id: rxn.message_id,
-                // We can assume guild_id exists since the DM intent is disabled
-                guild_id: rxn.guild_id.unwrap(),
-                reaction: rxn.emoji.clone(),
-            };
+        let parent_channel_id = thread_parent_channel(&state, rxn.channel_id).await;
+        let reaction_info = ReactionInfo {
+            author_is_bot: member.user.bot,
+            author_roles: &member.roles,
+            author_id: rxn.user_id,
+            channel_id: rxn.channel_id,
+            message_id: rxn.message_id,
+            // We can assume guild_id exists since the DM intent is disabled
+            guild_id: rxn.guild_id.unwrap(),
+            reaction: rxn.emoji.clone(),
+            reaction_count,
+            parent_channel_id,
+        };
+
+        if let Some(reaction_spam) = &guild_config.reaction_spam {
+            let trusted = guild_config.is_trusted(&member.roles);
+            if trusted && guild_config.trusted_roles_skip_spam {
+                tracing::trace!(?guild_id, author = %rxn.user_id, "Skipping reaction spam filtration because author has a trusted role");
+            } else {
+                let now = (Utc::now().timestamp_millis() as u64) * 1000;
+                let spam_result = crate::reaction::check_reaction_spam(
+                    reaction_spam,
+                    guild_config.default_scoping.as_ref(),
+                    guild_config.default_actions.as_deref(),
+                    &reaction_info,
+                    &mut *state.reaction_spam_history.lock().await,
+                    now,
+                );
 
+                // Spam actions usually delete the reaction already, so the
+                // other filters don't need to run too.
+                if let Err(failure) = spam_result {
+                    execute_reaction_actions(failure.actions, &state).await;
+
+                    let report = ReactionFilterReport {
+                        time: Utc::now(),
+                        guild: guild_id.to_string(),
+                        channel: rxn.channel_id.to_string(),
+                    };
+
+                    send_influx_point(&state, &report.into_query("reaction_spam")).await?;
+                    return Ok(());
+                }
+            }
+        }
+
+        if let Some(reaction_filters) = reaction_filters {
             let filter_result = crate::reaction::filter_reaction(
                 reaction_filters,
                 guild_config.default_scoping.as_ref(),
@@ -535,26 +1571,7 @@ async fn filter_reaction(rxn: &GatewayReaction, state: State) -> Result<()> {
             );
 
             if let Err(failure) = filter_result {
-                let armed = state.armed.load(Ordering::Relaxed);
-                let mut deleted = false;
-
-                for action in failure.actions {
-                    if matches!(action, ReactionAction::Delete { .. }) {
-                        if deleted {
-                            continue;
-                        }
-
-                        deleted = true;
-                    }
-
-                    if action.requires_armed() && !armed {
-                        continue;
-                    }
-
-                    if let Err(action_err) = action.execute(&state.http).await {
-                        tracing::warn!(?action_err, ?action, "Error executing reaction action");
-                    }
-                }
+                execute_reaction_actions(failure.actions, &state).await;
 
                 let report = ReactionFilterReport {
                     time: Utc::now(),
@@ -570,16 +1587,131 @@ async fn filter_reaction(rxn: &GatewayReaction, state: State) -> Result<()> {
     Ok(())
 }
 
+/// Executes the actions for a reaction that failed filtering, respecting dry
+/// runs and whether Chrysanthemum is armed.
+async fn execute_reaction_actions(actions: Vec<ReactionAction>, state: &State) {
+    let armed = state.armed.load(Ordering::Relaxed);
+    let mut deleted = false;
+
+    for action in actions {
+        if matches!(action, ReactionAction::Delete { .. }) {
+            if deleted {
+                continue;
+            }
+
+            deleted = true;
+        }
+
+        let action = if state.cfg.dry_run_globally {
+            tracing::info!(?action, "Dry run; would have executed action");
+            match action.into_dry_run() {
+                Some(action) => action,
+                None => continue,
+            }
+        } else if action.requires_armed() && !armed {
+            continue;
+        } else {
+            action
+        };
+
+        if let Err(action_err) = action.execute_with_retries(&action_context(state)).await {
+            tracing::warn!(?action_err, ?action, "Error executing reaction action");
+        }
+    }
+}
+
+/// Logs the removal of a reaction that would have been deleted. Chrysanthemum
+/// deletes reactions for every user at once, which Discord reports as a
+/// separate event, so a removal reported here was done by someone else -
+/// usually the user, trying to get the reaction past the filters.
 #[tracing::instrument(skip(state))]
-async fn filter_message_edit_http(update: &MessageUpdate, state: &State) -> Result<()> {
+async fn log_removed_reaction(rxn: &GatewayReaction, state: State) -> Result<()> {
+    let guild_id = match rxn.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    // Nothing would have been deleted while disarmed or in a dry run.
+    if !state.armed.load(Ordering::Relaxed) || state.cfg.dry_run_globally {
+        return Ok(());
+    }
+
+    let guild_config = match state.guild_cfgs.read().await.get(&guild_id) {
+        Some(guild_config) => guild_config.clone(),
+        None => return Ok(()),
+    };
+
+    let reaction_filters = match guild_config.reactions.as_deref() {
+        Some(reaction_filters) => reaction_filters,
+        None => return Ok(()),
+    };
+
+    // Removal events don't come with member information.
+    let author_is_bot = match state.cache.user(rxn.user_id) {
+        Some(user) => user.bot,
+        None => {
+            tracing::trace!("A reaction was removed, but its author isn't cached. Ignoring.");
+            return Ok(());
+        }
+    };
+    let author_roles = match state.cache.member(guild_id, rxn.user_id) {
+        Some(member) => member.roles().to_owned(),
+        None => {
+            tracing::trace!("A reaction was removed, but its author isn't cached. Ignoring.");
+            return Ok(());
+        }
+    };
+
+    if author_is_bot && !guild_config.include_bots {
+        return Ok(());
+    }
+
+    let parent_channel_id = thread_parent_channel(&state, rxn.channel_id).await;
+    let reaction_info = ReactionInfo {
+        author_is_bot,
+        author_roles: &author_roles,
+        author_id: rxn.user_id,
+        channel_id: rxn.channel_id,
+        message_id: rxn.message_id,
+        guild_id,
+        reaction: rxn.emoji.clone(),
+        // The reaction no longer counts towards the total.
+        reaction_count: 0,
+        parent_channel_id,
+    };
+
+    let filter_result = crate::reaction::filter_reaction(
+        reaction_filters,
+        guild_config.default_scoping.as_ref(),
+        guild_config.default_actions.as_deref(),
+        &reaction_info,
+    );
+
+    if let Err(failure) = filter_result {
+        for action in crate::reaction::removal_logs(failure.actions) {
+            if let Err(action_err) = action.execute_with_retries(&action_context(&state)).await {
+                tracing::warn!(?action_err, ?action, "Error logging removed reaction");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[tracing::instrument(skip(state))]
+async fn check_message_edit_http(
+    update: &MessageUpdate,
+    previous_content: Option<&str>,
+    state: &State,
+) -> Result<Option<MessageFilterFailure>> {
     let guild_id = match update.guild_id {
         Some(id) => id,
-        None => return Ok(()),
+        None => return Ok(None),
     };
 
     let (author_id, author_is_bot) = match &update.author {
         Some(author) => (author.id, author.bot),
-        None => return Ok(()),
+        None => return Ok(None),
     };
 
     let http_message = state
@@ -589,21 +1721,30 @@ async fn filter_message_edit_http(update: &MessageUpdate, state: &State) -> Resu
         .model()
         .await?;
 
-    let author_roles = {
+    let is_webhook = http_message.webhook_id.is_some();
+    let (author_roles, joined_at) = if is_webhook {
+        (Vec::new(), None)
+    } else {
         let cached_member = state.cache.member(guild_id, author_id);
         match cached_member.as_ref() {
-            Some(member) => member.roles().to_owned(),
-            None => state
-                .http
-                .guild_member(guild_id, author_id)
-                .await?
-                .model()
-                .await?
-                .roles
-                .clone(),
+            Some(member) => (member.roles().to_owned(), Some(member.joined_at())),
+            None => {
+                let member = state
+                    .http
+                    .guild_member(guild_id, author_id)
+                    .await?
+                    .model()
+                    .await?;
+                (member.roles, Some(member.joined_at))
+            }
         }
     };
 
+    let parent_channel_id = thread_parent_channel(state, http_message.channel_id).await;
+    let guild_stickers = state.guild_stickers.read().await.get(&guild_id).cloned();
+    let guild_channels = cached_guild_channels(&state.cache, guild_id);
+    let mentioned_users = mentioned_user_ids(&http_message.mentions);
+
     let message_info = MessageInfo {
         id: http_message.id,
         channel_id: http_message.channel_id,
@@ -611,23 +1752,60 @@ async fn filter_message_edit_http(update: &MessageUpdate, state: &State) -> Resu
         guild_id: http_message.guild_id.unwrap(),
         timestamp: http_message.timestamp,
         author_roles: &author_roles[..],
+        account_created_at: model::snowflake_timestamp(author_id),
+        joined_at,
         content: &http_message.content,
         attachments: &http_message.attachments,
         stickers: &http_message.sticker_items,
+        embeds: &http_message.embeds,
+        guild_stickers: guild_stickers.as_deref(),
+        guild_channels: guild_channels.as_deref(),
         author_id,
         author_is_bot,
+        is_webhook,
+        previous_content: Some(previous_content),
+        reference: http_message.reference.as_ref(),
+        mentioned_users: &mentioned_users,
+        last_message: None,
+        parent_channel_id,
     };
 
-    filter_message_info(guild_id, &message_info, state, "message edit").await
+    Ok(check_message_info(guild_id, &message_info, state, "message edit").await)
 }
 
 #[tracing::instrument(skip(state))]
-async fn filter_message_edit(update: &MessageUpdate, state: &State) -> Result<()> {
+async fn filter_message_edit(
+    update: &MessageUpdate,
+    previous_content: Option<&str>,
+    state: &State,
+) -> Result<()> {
     let guild_id = match update.guild_id {
         Some(id) => id,
         None => return Ok(()),
     };
 
+    match check_message_edit(update, previous_content, state).await? {
+        Some(failure) => {
+            act_on_filtered_message(guild_id, update.channel_id, failure, state, "message edit")
+                .await
+        }
+        None => Ok(()),
+    }
+}
+
+/// Checks an edited message against its guild's filters, using the cache
+/// where possible.
+#[tracing::instrument(skip(state))]
+async fn check_message_edit(
+    update: &MessageUpdate,
+    previous_content: Option<&str>,
+    state: &State,
+) -> Result<Option<MessageFilterFailure>> {
+    let guild_id = match update.guild_id {
+        Some(id) => id,
+        None => return Ok(None),
+    };
+
     let cached_message = state.cache.message(update.id);
 
     match (cached_message, update.content.as_deref()) {
@@ -646,7 +1824,7 @@ async fn filter_message_edit(update: &MessageUpdate, state: &State) -> Result<()
                             // another thread holds a reference to the cached message. Dropping
                             // the cached reference prevents this.
                             drop(message);
-                            return filter_message_edit_http(update, state).await;
+                            return check_message_edit_http(update, previous_content, state).await;
                         }
                     }
                 }
@@ -655,38 +1833,64 @@ async fn filter_message_edit(update: &MessageUpdate, state: &State) -> Result<()
             let timestamp = message.timestamp();
             let attachments = message.attachments().to_owned();
             let sticker_items = message.sticker_items().to_owned();
+            let reference = message.reference().cloned();
+            let is_webhook = message.webhook_id().is_some();
+            let mentioned_users = match update.mentions.as_deref() {
+                Some(mentions) => mentioned_user_ids(mentions),
+                None => message.mentions().to_owned(),
+            };
+            let embeds = match update.embeds.as_ref() {
+                Some(embeds) => embeds.clone(),
+                None => message.embeds().to_owned(),
+            };
 
             // For the same reason as above, we drop the message here.
             drop(message);
 
-            let author_roles = {
+            let (author_roles, joined_at) = if is_webhook {
+                (Vec::new(), None)
+            } else {
                 let cached_member = state.cache.member(guild_id, author_id);
                 match cached_member.as_ref() {
-                    Some(member) => member.roles().to_owned(),
-                    None => return filter_message_edit_http(update, state).await,
+                    Some(member) => (member.roles().to_owned(), Some(member.joined_at())),
+                    None => return check_message_edit_http(update, previous_content, state).await,
                 }
             };
 
             let clean_message_content =
                 crate::message::clean_mentions(content, update.mentions.as_deref().unwrap_or(&[]));
+            let parent_channel_id = thread_parent_channel(state, update.channel_id).await;
+            let guild_stickers = state.guild_stickers.read().await.get(&guild_id).cloned();
+            let guild_channels = cached_guild_channels(&state.cache, guild_id);
 
             let message_info = MessageInfo {
                 id: update.id,
                 author_id,
                 author_is_bot,
+                is_webhook,
                 // We can assume guild_id exists since the DM intent is disabled
                 guild_id: update.guild_id.unwrap(),
                 author_roles: &author_roles[..],
+                account_created_at: model::snowflake_timestamp(author_id),
+                joined_at,
                 content: &clean_message_content,
                 channel_id: update.channel_id,
                 timestamp,
                 attachments: &attachments[..],
                 stickers: &sticker_items[..],
+                embeds: &embeds[..],
+                guild_stickers: guild_stickers.as_deref(),
+                guild_channels: guild_channels.as_deref(),
+                previous_content: Some(previous_content),
+                reference: reference.as_ref(),
+                mentioned_users: &mentioned_users,
+                last_message: None,
+                parent_channel_id,
             };
 
-            filter_message_info(guild_id, &message_info, state, "message edit").await
+            Ok(check_message_info(guild_id, &message_info, state, "message edit").await)
         }
-        _ => filter_message_edit_http(update, state).await,
+        _ => check_message_edit_http(update, previous_content, state).await,
     }
 }
 
@@ -722,3 +1926,162 @@ async fn send_notification_to_guild(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize};
+    use std::sync::Arc;
+
+    use pretty_assertions::assert_eq;
+    use tokio::sync::{Mutex, RwLock};
+    use twilight_cache_inmemory::{InMemoryCache, ResourceType};
+    use twilight_gateway::Event;
+    use twilight_http::Client as HttpClient;
+    use twilight_model::channel::{Channel, Message};
+    use twilight_model::gateway::payload::incoming::{ChannelCreate, MessageCreate, MessageUpdate};
+
+    use crate::action::MessageAction;
+    use crate::config::{Config, GuildConfig};
+    use crate::model::test::{CHANNEL_ID, GUILD_ID, MESSAGE_ID, USER_ID};
+    use crate::State;
+
+    const GUILD_CONFIG: &str = r#"
+messages:
+  - name: Words
+    rules:
+      - type: words
+        words: [bad]
+    actions:
+      - action: send_log
+        channel_id: "1"
+"#;
+
+    /// Builds a state with a cache and guild configuration, but no way to
+    /// reach Discord, so tests must only use what's cached.
+    fn state(cache: InMemoryCache) -> State {
+        let cfg: Config = serde_json::from_value(serde_json::json!({
+            "guild_config_dir": "guild-configs",
+            "active_guilds": [GUILD_ID],
+            "armed_by_default": false,
+        }))
+        .unwrap();
+        let guild_config: GuildConfig = serde_yaml::from_str(GUILD_CONFIG).unwrap();
+
+        State {
+            cfg: Arc::new(cfg),
+            guild_cfgs: Arc::new(RwLock::new(
+                std::iter::once((GUILD_ID, Arc::new(guild_config))).collect(),
+            )),
+            disabled_filters: Arc::new(RwLock::new(HashMap::new())),
+            guild_stickers: Arc::new(RwLock::new(HashMap::new())),
+            http: Arc::new(HttpClient::new(String::new())),
+            application_id: Arc::new(RwLock::new(None)),
+            cache: Arc::new(cache),
+            spam_history: Arc::new(RwLock::new(HashMap::new())),
+            raid_index: Arc::new(Mutex::new(Default::default())),
+            reaction_counts: Arc::new(RwLock::new(Default::default())),
+            reaction_spam_history: Arc::new(Mutex::new(Default::default())),
+            flood_tracker: Arc::new(Mutex::new(Default::default())),
+            repost_history: Arc::new(Mutex::new(Default::default())),
+            warnings: Arc::new(crate::warnings::WarningStore::in_memory()),
+            quarantines: Arc::new(crate::quarantine::QuarantineStore::in_memory()),
+            tempbans: Arc::new(crate::tempban::TempbanStore::in_memory()),
+            slowmodes: Arc::new(Mutex::new(Default::default())),
+            pending_deletes: Arc::new(Mutex::new(Default::default())),
+            influx_client: Arc::new(None),
+            influx_report_count: Arc::new(AtomicUsize::new(0)),
+            web_client: reqwest::Client::new(),
+            armed: Arc::new(AtomicBool::new(false)),
+            filter_permits: None,
+            shed_events: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    fn message(content: &str) -> Message {
+        serde_json::from_value(serde_json::json!({
+            "id": MESSAGE_ID,
+            "channel_id": CHANNEL_ID,
+            "guild_id": GUILD_ID,
+            "author": {
+                "id": USER_ID,
+                "username": "user",
+                "discriminator": "0001",
+                "avatar": null,
+            },
+            "member": {
+                "roles": [],
+                "joined_at": "2022-01-01T00:00:00.000000+00:00",
+                "deaf": false,
+                "mute": false,
+                "flags": 0,
+            },
+            "content": content,
+            "timestamp": "2022-01-01T00:00:00.000000+00:00",
+            "edited_timestamp": null,
+            "tts": false,
+            "mention_everyone": false,
+            "mentions": [],
+            "mention_roles": [],
+            "attachments": [],
+            "embeds": [],
+            "pinned": false,
+            "type": 0,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn log_previous_content_of_cached_edits() {
+        let cache = InMemoryCache::builder()
+            .resource_types(
+                ResourceType::MESSAGE
+                    | ResourceType::MEMBER
+                    | ResourceType::USER
+                    | ResourceType::CHANNEL,
+            )
+            .build();
+        let channel: Channel = serde_json::from_value(serde_json::json!({
+            "id": CHANNEL_ID,
+            "guild_id": GUILD_ID,
+            "type": 0,
+            "name": "general",
+        }))
+        .unwrap();
+        cache.update(&Event::ChannelCreate(Box::new(ChannelCreate(channel))));
+        cache.update(&Event::MessageCreate(Box::new(MessageCreate(message(
+            "good message",
+        )))));
+
+        let update = MessageUpdate {
+            content: Some("bad message".to_owned()),
+            ..serde_json::from_value(serde_json::json!({
+                "id": MESSAGE_ID,
+                "channel_id": CHANNEL_ID,
+                "guild_id": GUILD_ID,
+            }))
+            .unwrap()
+        };
+
+        let state = state(cache);
+        let failure = super::check_message_edit(&update, Some("good message"), &state)
+            .await
+            .unwrap()
+            .expect("the edit should have been filtered");
+
+        match &failure.actions[..] {
+            [MessageAction::SendLog {
+                previous_content,
+                content,
+                context,
+                ..
+            }] => {
+                assert_eq!(previous_content, &Some(Some("good message".to_owned())));
+                assert_eq!(content, "bad message");
+                assert_eq!(*context, "message edit");
+            }
+            actions => panic!("expected a single log, got {:?}", actions),
+        }
+        assert_eq!(failure.filter_name, "Words");
+    }
+}
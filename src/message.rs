@@ -1,22 +1,33 @@
-use std::{borrow::Cow, sync::Arc};
+use std::{borrow::Cow, collections::HashSet, sync::Arc};
 
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
 use twilight_mention::Mention as MentionTrait;
 use twilight_model::channel::message::Mention;
 
 use crate::{
-    action::MessageAction,
+    action::{
+        default_log_color, jump_link, truncate_with_ellipsis, webhook_content_preview,
+        EscalationCount, LoggedAttachment, MessageAction, Tempban, WebhookPayload,
+        AUDIT_LOG_REASON_LENGTH,
+    },
     config::{MessageFilter, MessageFilterAction, Scoping, SpamFilter},
-    filter::{check_spam_record, SpamHistory},
+    filter::{check_spam_record, FilterFailure, SpamCheck, SpamHistory},
     model::MessageInfo,
+    raid::RaidIndex,
 };
 
 const SPAM_FILTER_NAME: &str = "Spam";
+const RAID_FILTER_NAME: &str = "Raid";
 
 #[derive(Debug, PartialEq, Eq)]
 pub(crate) struct MessageFilterFailure {
     pub(crate) actions: Vec<MessageAction>,
     pub(crate) filter_name: String,
+    /// Why the message was filtered, like ``contains word `bad` ``.
+    pub(crate) reason: String,
+    /// Which tier of words the message matched, for filters with tiered word
+    /// lists.
+    pub(crate) tier: Option<String>,
     pub(crate) context: &'static str,
 }
 
@@ -68,38 +79,136 @@ fn format_message_preview(format_string: String, content: &str) -> String {
     }
 }
 
+/// Links to the message. The link stops working once the message is deleted,
+/// so it's only useful in actions that don't need the message to still exist.
+fn message_link(message: &MessageInfo) -> String {
+    jump_link(message.guild_id, message.channel_id, message.id)
+}
+
+/// Gets the audit log reason for an action taken by a filter, so that
+/// moderators can tell which filter it was.
+fn audit_log_reason(filter_name: &str, filter_reason: &str) -> String {
+    truncate_with_ellipsis(
+        &format!("Filter {}: {}", filter_name, filter_reason),
+        AUDIT_LOG_REASON_LENGTH,
+    )
+    .into_owned()
+}
+
+/// Fills in the template variables in an action's content or reason, as
+/// described for `send_message` in the README.
+fn substitute_template(
+    template: &str,
+    message: &MessageInfo,
+    message_content: &str,
+    filter_reason: &str,
+    filter_tier: Option<&str>,
+) -> String {
+    let formatted = template.replace("$USER_ID", &message.author_id.to_string());
+    let formatted = formatted.replace("$FILTER_REASON", filter_reason);
+    let formatted = formatted.replace("$FILTER_TIER", filter_tier.unwrap_or(""));
+    let formatted = formatted.replace("$MESSAGE_LINK", &message_link(message));
+
+    format_message_preview(formatted, message_content)
+}
+
+#[allow(clippy::too_many_arguments)]
 fn map_filter_action_to_action(
     filter_action: &MessageFilterAction,
+    filter_actions: &[MessageFilterAction],
     message: &MessageInfo,
+    message_content: &str,
+    previous_content: Option<Option<&str>>,
     filter_name: &str,
     filter_reason: &str,
+    filter_tier: Option<&str>,
     context: &'static str,
 ) -> MessageAction {
+    let map_nested = |actions: &[MessageFilterAction]| {
+        actions
+            .iter()
+            .map(|action| {
+                map_filter_action_to_action(
+                    action,
+                    actions,
+                    message,
+                    message_content,
+                    previous_content,
+                    filter_name,
+                    filter_reason,
+                    filter_tier,
+                    context,
+                )
+            })
+            .collect()
+    };
+
     match filter_action {
-        MessageFilterAction::Delete => MessageAction::Delete {
+        MessageFilterAction::Delete { delay_seconds } => MessageAction::Delete {
             message_id: message.id,
             channel_id: message.channel_id,
+            delay_seconds: *delay_seconds,
+            reason: Some(audit_log_reason(filter_name, filter_reason)),
         },
         MessageFilterAction::SendLog {
             channel_id: log_channel,
+            color,
+            footer,
         } => MessageAction::SendLog {
             to: *log_channel,
             filter_name: filter_name.to_string(),
             message_channel: message.channel_id,
-            content: message.content.to_string(),
+            message_id: message.id,
+            content: message_content.to_string(),
+            previous_content: previous_content.map(|content| content.map(str::to_owned)),
             filter_reason: filter_reason.to_string(),
             author: message.author_id,
             context,
+            guild_id: message.guild_id,
+            attachments: message
+                .attachments
+                .iter()
+                .map(|attachment| LoggedAttachment {
+                    filename: attachment.filename.clone(),
+                    url: attachment.url.clone(),
+                    proxy_url: attachment.proxy_url.clone(),
+                    is_image: attachment
+                        .content_type
+                        .as_deref()
+                        .map_or(false, |content_type| content_type.starts_with("image/")),
+                })
+                .collect(),
+            stickers: message
+                .stickers
+                .iter()
+                .map(|sticker| sticker.name.clone())
+                .collect(),
+            color: color.unwrap_or_else(|| default_log_color(filter_actions)),
+            footer: footer.clone(),
+            dry_run: false,
+        },
+        MessageFilterAction::FlagForReview { channel_id } => MessageAction::FlagForReview {
+            to: *channel_id,
+            filter_name: filter_name.to_string(),
+            guild_id: message.guild_id,
+            message_channel: message.channel_id,
+            message_id: message.id,
+            content: message_content.to_string(),
+            filter_reason: filter_reason.to_string(),
+            author: message.author_id,
         },
         MessageFilterAction::SendMessage {
             channel_id,
             content,
             requires_armed,
         } => {
-            let formatted_content = content.replace("$USER_ID", &message.author_id.to_string());
-            let formatted_content = formatted_content.replace("$FILTER_REASON", filter_reason);
-
-            let formatted_content = format_message_preview(formatted_content, message.content);
+            let formatted_content = substitute_template(
+                content,
+                message,
+                message_content,
+                filter_reason,
+                filter_tier,
+            );
 
             MessageAction::SendMessage {
                 to: *channel_id,
@@ -107,27 +216,67 @@ fn map_filter_action_to_action(
                 requires_armed: *requires_armed,
             }
         }
+        MessageFilterAction::Reply {
+            content,
+            delete_after_seconds,
+            requires_armed,
+        } => {
+            let formatted_content = substitute_template(
+                content,
+                message,
+                message_content,
+                filter_reason,
+                filter_tier,
+            );
+
+            MessageAction::Reply {
+                channel_id: message.channel_id,
+                message_id: message.id,
+                content: formatted_content,
+                delete_after_seconds: *delete_after_seconds,
+                requires_armed: *requires_armed,
+            }
+        }
+        MessageFilterAction::SendDm {
+            content,
+            requires_armed,
+        } => {
+            let formatted_content = substitute_template(
+                content,
+                message,
+                message_content,
+                filter_reason,
+                filter_tier,
+            );
+
+            MessageAction::SendDm {
+                user_id: message.author_id,
+                content: formatted_content,
+                requires_armed: *requires_armed,
+            }
+        }
         MessageFilterAction::Ban {
             delete_message_seconds,
             reason,
+            duration_seconds,
         } => {
-            let formatted_content = reason.replace("$USER_ID", &message.author_id.to_string());
-            let formatted_content = formatted_content.replace("$FILTER_REASON", filter_reason);
-
-            let formatted_content = format_message_preview(formatted_content, message.content);
+            let formatted_content =
+                substitute_template(reason, message, message_content, filter_reason, filter_tier);
 
             MessageAction::Ban {
                 user_id: message.author_id,
                 guild_id: message.guild_id,
                 delete_message_seconds: *delete_message_seconds,
                 reason: formatted_content,
+                tempban: duration_seconds.map(|duration_seconds| Tempban {
+                    duration_seconds,
+                    filter_name: filter_name.to_owned(),
+                }),
             }
         }
         MessageFilterAction::Kick { reason } => {
-            let formatted_content = reason.replace("$USER_ID", &message.author_id.to_string());
-            let formatted_content = formatted_content.replace("$FILTER_REASON", filter_reason);
-
-            let formatted_content = format_message_preview(formatted_content, message.content);
+            let formatted_content =
+                substitute_template(reason, message, message_content, filter_reason, filter_tier);
 
             MessageAction::Kick {
                 user_id: message.author_id,
@@ -135,11 +284,116 @@ fn map_filter_action_to_action(
                 reason: formatted_content,
             }
         }
-        MessageFilterAction::Timeout { duration, reason } => {
-            let formatted_content = reason.replace("$USER_ID", &message.author_id.to_string());
-            let formatted_content = formatted_content.replace("$FILTER_REASON", filter_reason);
+        MessageFilterAction::AddRole { role_id, reason } => {
+            let formatted_content =
+                substitute_template(reason, message, message_content, filter_reason, filter_tier);
 
-            let formatted_content = format_message_preview(formatted_content, message.content);
+            MessageAction::AddRole {
+                user_id: message.author_id,
+                guild_id: message.guild_id,
+                role_id: *role_id,
+                reason: formatted_content,
+            }
+        }
+        MessageFilterAction::RemoveRole { role_id, reason } => {
+            let formatted_content =
+                substitute_template(reason, message, message_content, filter_reason, filter_tier);
+
+            MessageAction::RemoveRole {
+                user_id: message.author_id,
+                guild_id: message.guild_id,
+                role_id: *role_id,
+                reason: formatted_content,
+            }
+        }
+        MessageFilterAction::EscalatingTimeout {
+            reason,
+            durations,
+            window_seconds,
+        } => {
+            let formatted_content =
+                substitute_template(reason, message, message_content, filter_reason, filter_tier);
+
+            MessageAction::EscalatingTimeout {
+                user_id: message.author_id,
+                guild_id: message.guild_id,
+                reason: formatted_content,
+                durations: durations.clone(),
+                window_seconds: *window_seconds,
+            }
+        }
+        MessageFilterAction::Webhook { url, secret } => MessageAction::Webhook {
+            url: url.clone(),
+            secret: secret.clone(),
+            payload: WebhookPayload {
+                guild_id: message.guild_id,
+                channel_id: message.channel_id,
+                message_id: message.id,
+                author_id: message.author_id,
+                filter_name: filter_name.to_owned(),
+                reason: filter_reason.to_owned(),
+                context,
+                content_preview: Some(webhook_content_preview(message_content)),
+                reaction: None,
+            },
+        },
+        MessageFilterAction::Quarantine {
+            quarantine_role,
+            restore_on_unquarantine,
+        } => MessageAction::Quarantine {
+            user_id: message.author_id,
+            guild_id: message.guild_id,
+            quarantine_role: *quarantine_role,
+            restore_on_unquarantine: *restore_on_unquarantine,
+            reason: filter_reason.to_owned(),
+        },
+        MessageFilterAction::SetSlowmode {
+            seconds,
+            revert_after,
+        } => MessageAction::SetSlowmode {
+            channel_id: message.channel_id,
+            seconds: *seconds,
+            revert_after: *revert_after,
+        },
+        MessageFilterAction::Crosspost => MessageAction::Crosspost {
+            channel_id: message.channel_id,
+            message_id: message.id,
+        },
+        MessageFilterAction::Warn { expiry_seconds } => MessageAction::Warn {
+            user_id: message.author_id,
+            guild_id: message.guild_id,
+            expiry_seconds: *expiry_seconds,
+        },
+        MessageFilterAction::Template { .. } => {
+            unreachable!("action templates are expanded when configurations are loaded")
+        }
+        MessageFilterAction::Escalate { thresholds } => MessageAction::Escalate {
+            user_id: message.author_id,
+            guild_id: message.guild_id,
+            count: EscalationCount::Warnings,
+            thresholds: thresholds
+                .iter()
+                .map(|threshold| (threshold.warnings, map_nested(&threshold.actions)))
+                .collect(),
+        },
+        MessageFilterAction::EscalateOffenses {
+            window_seconds,
+            tiers,
+        } => MessageAction::Escalate {
+            user_id: message.author_id,
+            guild_id: message.guild_id,
+            count: EscalationCount::FilterOffenses {
+                filter_name: filter_name.to_owned(),
+                window_seconds: *window_seconds,
+            },
+            thresholds: tiers
+                .iter()
+                .map(|tier| (tier.offenses, map_nested(&tier.actions)))
+                .collect(),
+        },
+        MessageFilterAction::Timeout { duration, reason } => {
+            let formatted_content =
+                substitute_template(reason, message, message_content, filter_reason, filter_tier);
 
             MessageAction::Timeout {
                 user_id: message.author_id,
@@ -151,40 +405,81 @@ fn map_filter_action_to_action(
     }
 }
 
-#[tracing::instrument(skip(filters, default_scoping, default_actions))]
-fn filter_message(
+#[tracing::instrument(skip(filters, default_scoping, default_actions, web_client))]
+async fn filter_message(
     filters: &[MessageFilter],
+    disabled_filters: Option<&HashSet<String>>,
     default_scoping: Option<&Scoping>,
     default_actions: Option<&[MessageFilterAction]>,
-    message: &MessageInfo,
+    web_client: &reqwest::Client,
+    message: &MessageInfo<'_>,
     context: &'static str,
 ) -> Result<(), MessageFilterFailure> {
     for filter in filters {
+        if disabled_filters.map_or(false, |disabled| disabled.contains(&filter.name)) {
+            tracing::trace!(%filter.name, "Skipping disabled filter");
+            continue;
+        }
+
         if let Some(scoping) = filter.scoping.as_ref().or(default_scoping) {
-            if !scoping.is_included(message.channel_id, message.author_roles) {
+            if !scoping.is_included(
+                message.channel_id,
+                message.parent_channel_id,
+                message.author_roles,
+            ) {
                 continue;
             }
         }
 
-        let result = filter.filter_message(message);
-        if let Err(reason) = result {
-            if let Some(actions) = filter.actions.as_deref().or(default_actions) {
+        let result = filter.filter_message(message, web_client).await;
+        if let Err(FilterFailure { reason, tier }) = result {
+            if filter.exempt {
+                tracing::trace!(%filter.name, %reason, "Message exempted from further filtering");
+                return Ok(());
+            }
+
+            let tier_actions = tier
+                .and_then(|tier| filter.actions_by_tier.as_ref()?.get(tier))
+                .map(Vec::as_slice);
+
+            if let Some(actions) = tier_actions
+                .or(filter.actions.as_deref())
+                .or(default_actions)
+            {
+                let content = filter.redact(message.content);
+                let previous_content = message
+                    .previous_content
+                    .map(|content| content.map(|content| filter.redact(content)));
                 let actions = actions
                     .iter()
                     .map(|a| {
-                        map_filter_action_to_action(a, message, &filter.name, &reason, context)
+                        map_filter_action_to_action(
+                            a,
+                            actions,
+                            message,
+                            &content,
+                            previous_content.as_ref().map(|content| content.as_deref()),
+                            &filter.name,
+                            &reason,
+                            tier,
+                            context,
+                        )
                     })
                     .collect();
 
                 return Err(MessageFilterFailure {
                     filter_name: filter.name.clone(),
+                    reason,
                     actions,
+                    tier: tier.map(str::to_owned),
                     context,
                 });
             } else {
                 return Err(MessageFilterFailure {
                     actions: vec![],
                     filter_name: filter.name.clone(),
+                    reason,
+                    tier: tier.map(str::to_owned),
                     context,
                 });
             }
@@ -194,62 +489,169 @@ fn filter_message(
     Ok(())
 }
 
+/// Builds the failure for a message caught by the spam filter, or by its raid
+/// detection.
+fn spam_failure(
+    actions: &[MessageFilterAction],
+    filter_name: &str,
+    reason: &str,
+    message: &MessageInfo,
+    context: &'static str,
+) -> MessageFilterFailure {
+    let actions = actions
+        .iter()
+        .map(|a| {
+            map_filter_action_to_action(
+                a,
+                actions,
+                message,
+                message.content,
+                message.previous_content,
+                filter_name,
+                reason,
+                None,
+                context,
+            )
+        })
+        .collect();
+
+    MessageFilterFailure {
+        actions,
+        filter_name: filter_name.to_string(),
+        reason: reason.to_string(),
+        context,
+        tier: None,
+    }
+}
+
 // Explicit lifetime is necessary to prevent https://github.com/rust-lang/rust/issues/63033
 // from occurring. We technically want two lifetimes, 'cfg and 'msg, but that also
 // triggers that issue.
-#[tracing::instrument(skip(spam_config, default_scoping, default_actions, spam_history))]
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip(
+    spam_config,
+    default_scoping,
+    default_actions,
+    spam_history,
+    raid_index
+))]
 async fn spam_check_message<'msg>(
     spam_config: &'msg SpamFilter,
     default_scoping: Option<&'msg Scoping>,
     default_actions: Option<&'msg [MessageFilterAction]>,
     spam_history: Arc<RwLock<SpamHistory>>,
+    raid_index: Arc<Mutex<RaidIndex>>,
     message: &'msg MessageInfo<'msg>,
     context: &'static str,
     now: u64,
 ) -> Result<(), MessageFilterFailure> {
     if let Some(scoping) = spam_config.scoping.as_ref().or(default_scoping) {
-        if !scoping.is_included(message.channel_id, message.author_roles) {
+        if !scoping.is_included(
+            message.channel_id,
+            message.parent_channel_id,
+            message.author_roles,
+        ) {
             return Ok(());
         }
     }
 
-    let result = check_spam_record(message, spam_config, spam_history, now).await;
+    let spam_actions = spam_config
+        .actions
+        .as_deref()
+        .or(default_actions)
+        .unwrap_or(&[]);
+
+    let warning = match check_spam_record(message, spam_config, spam_history, now).await {
+        SpamCheck::Clean => None,
+        SpamCheck::Warn(reason) => Some(reason),
+        SpamCheck::Exceeded(reason) => {
+            return Err(spam_failure(
+                spam_actions,
+                SPAM_FILTER_NAME,
+                &reason,
+                message,
+                context,
+            ));
+        }
+    };
 
-    match result {
-        Ok(()) => Ok(()),
-        Err(reason) => {
-            let actions = spam_config
-                .actions
-                .as_deref()
-                .or(default_actions)
-                .unwrap_or(&[])
-                .iter()
-                .map(|a| {
-                    map_filter_action_to_action(a, message, SPAM_FILTER_NAME, &reason, context)
-                })
-                .collect();
-            Err(MessageFilterFailure {
-                actions,
-                filter_name: SPAM_FILTER_NAME.to_string(),
+    if let Some(raid_config) = &spam_config.raid {
+        let detection = raid_index.lock().await.record(message, raid_config, now);
+        if let Some(detection) = detection {
+            let reason = format!(
+                "part of a raid: {} users posted the same message",
+                detection.users
+            );
+            let mut failure = spam_failure(
+                raid_config.actions.as_deref().unwrap_or(spam_actions),
+                RAID_FILTER_NAME,
+                &reason,
+                message,
                 context,
-            })
+            );
+
+            failure
+                .actions
+                .extend(detection.earlier_messages.into_iter().map(|earlier| {
+                    MessageAction::Delete {
+                        message_id: earlier.message_id,
+                        channel_id: earlier.channel_id,
+                        delay_seconds: None,
+                        reason: Some(audit_log_reason(RAID_FILTER_NAME, &reason)),
+                    }
+                }));
+
+            return Err(failure);
         }
     }
+
+    // Raids take priority over warnings, since they're acted on immediately.
+    if let (Some(reason), Some(warn_actions)) = (warning, &spam_config.warn_actions) {
+        return Err(spam_failure(
+            warn_actions,
+            SPAM_FILTER_NAME,
+            &reason,
+            message,
+            context,
+        ));
+    }
+
+    Ok(())
 }
 
 #[allow(clippy::too_many_arguments)]
-#[tracing::instrument(skip(spam_config, filters, default_scoping, default_actions, spam_history))]
+#[tracing::instrument(skip(
+    spam_config,
+    filters,
+    default_scoping,
+    default_actions,
+    spam_history,
+    raid_index,
+    web_client
+))]
 pub(crate) async fn filter_and_spam_check_message<'msg>(
     spam_config: Option<&'msg SpamFilter>,
     filters: &'msg [MessageFilter],
+    disabled_filters: Option<&'msg HashSet<String>>,
     default_scoping: Option<&'msg Scoping>,
     default_actions: Option<&'msg [MessageFilterAction]>,
     spam_history: Arc<RwLock<SpamHistory>>,
+    raid_index: Arc<Mutex<RaidIndex>>,
+    web_client: &'msg reqwest::Client,
     message: &'msg MessageInfo<'msg>,
     context: &'static str,
     now: u64,
 ) -> Result<(), MessageFilterFailure> {
-    let result = filter_message(filters, default_scoping, default_actions, message, context);
+    let result = filter_message(
+        filters,
+        disabled_filters,
+        default_scoping,
+        default_actions,
+        web_client,
+        message,
+        context,
+    )
+    .await;
 
     if let Ok(()) = result {
         if let Some(spam_config) = spam_config {
@@ -258,6 +660,7 @@ pub(crate) async fn filter_and_spam_check_message<'msg>(
                 default_scoping,
                 default_actions,
                 spam_history,
+                raid_index,
                 message,
                 context,
                 now,
@@ -271,36 +674,127 @@ pub(crate) async fn filter_and_spam_check_message<'msg>(
     }
 }
 
+/// Runs a message through the filtering pipeline without acting on it,
+/// collecting every filter it fails instead of stopping at the first. Each
+/// filter is still skipped if it's disabled or scoped away from the message,
+/// and an exempt filter still stops the ones after it. The spam filter is
+/// checked against an empty history, so only limits a single message can
+/// exceed are reported, and nothing is recorded for later messages.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn filter_message_every_failure<'msg>(
+    spam_config: Option<&'msg SpamFilter>,
+    filters: &'msg [MessageFilter],
+    disabled_filters: Option<&'msg HashSet<String>>,
+    default_scoping: Option<&'msg Scoping>,
+    default_actions: Option<&'msg [MessageFilterAction]>,
+    web_client: &'msg reqwest::Client,
+    message: &'msg MessageInfo<'msg>,
+    context: &'static str,
+    now: u64,
+) -> Vec<MessageFilterFailure> {
+    let mut failures = Vec::new();
+    let mut remaining = filters;
+
+    while !remaining.is_empty() {
+        let result = filter_and_spam_check_message(
+            None,
+            remaining,
+            disabled_filters,
+            default_scoping,
+            default_actions,
+            Arc::new(RwLock::new(SpamHistory::default())),
+            Arc::new(Mutex::new(RaidIndex::default())),
+            web_client,
+            message,
+            context,
+            now,
+        )
+        .await;
+
+        let failure = match result {
+            Ok(()) => break,
+            Err(failure) => failure,
+        };
+
+        let index = remaining
+            .iter()
+            .position(|filter| filter.name == failure.filter_name)
+            .unwrap_or(remaining.len() - 1);
+        remaining = &remaining[index + 1..];
+        failures.push(failure);
+    }
+
+    if spam_config.is_some() {
+        let result = filter_and_spam_check_message(
+            spam_config,
+            &[],
+            disabled_filters,
+            default_scoping,
+            default_actions,
+            Arc::new(RwLock::new(SpamHistory::default())),
+            Arc::new(Mutex::new(RaidIndex::default())),
+            web_client,
+            message,
+            context,
+            now,
+        )
+        .await;
+
+        if let Err(failure) = result {
+            failures.push(failure);
+        }
+    }
+
+    failures
+}
+
 #[cfg(test)]
 mod test {
     use std::{collections::HashMap, sync::Arc};
 
     use pretty_assertions::assert_eq;
     use regex::Regex;
-    use tokio::sync::RwLock;
-    use twilight_model::id::Id;
+    use tokio::sync::{Mutex, RwLock};
+    use twilight_model::{
+        channel::{
+            message::sticker::{MessageSticker, StickerFormatType},
+            Attachment,
+        },
+        id::Id,
+    };
 
     use super::MessageFilterFailure;
     use twilight_mention::Mention as MentionTrait;
 
     use crate::{
-        action::MessageAction,
-        config::{MessageFilter, MessageFilterAction, MessageFilterRule, Scoping, SpamFilter},
+        action::{EscalationCount, LoggedAttachment, MessageAction},
+        config::{
+            MessageFilter, MessageFilterAction, MessageFilterRule, OffenseTier, RaidFilter,
+            Scoping, SpamFilter, WordTier,
+        },
+        raid::RaidIndex,
     };
 
-    #[test]
-    fn filter_basic() {
+    #[tokio::test]
+    async fn filter_basic() {
         let filters = vec![MessageFilter {
             name: "first".to_string(),
             rules: vec![MessageFilterRule::Words {
-                words: Regex::new("\\b(bad)\\b").unwrap(),
+                words: Some(Regex::new("\\b(bad)\\b").unwrap()),
+                tiers: vec![],
+                require_match: false,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
             }],
             scoping: Some(Scoping {
                 include_channels: Some(vec![crate::model::test::CHANNEL_ID]),
                 ..Default::default()
             }),
             actions: Some(vec![
-                MessageFilterAction::Delete,
+                MessageFilterAction::Delete {
+                    delay_seconds: None,
+                },
                 MessageFilterAction::SendMessage {
                     channel_id: Id::new(1),
                     content: "$USER_ID\n$FILTER_REASON\n$MESSAGE_PREVIEW".to_string(),
@@ -308,12 +802,25 @@ mod test {
                 },
                 MessageFilterAction::SendLog {
                     channel_id: Id::new(1),
+                    color: None,
+                    footer: None,
                 },
             ]),
+            actions_by_tier: None,
+            exempt: false,
         }];
 
         let message = crate::model::test::message(crate::model::test::BAD_CONTENT);
-        let result = super::filter_message(&filters, None, None, &message, "message create");
+        let result = super::filter_message(
+            &filters,
+            None,
+            None,
+            None,
+            &reqwest::Client::new(),
+            &message,
+            "message create",
+        )
+        .await;
         assert_eq!(
             result,
             Err(MessageFilterFailure {
@@ -323,6 +830,8 @@ mod test {
                     MessageAction::Delete {
                         message_id: crate::model::test::MESSAGE_ID,
                         channel_id: crate::model::test::CHANNEL_ID,
+                        delay_seconds: None,
+                        reason: Some("Filter first: contains word `bad`".to_owned()),
                     },
                     MessageAction::SendMessage {
                         to: Id::new(1),
@@ -340,21 +849,534 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
                         filter_reason: "contains word `bad`".to_owned(),
                         author: crate::model::test::USER_ID,
                         context: "message create",
+                        message_id: crate::model::test::MESSAGE_ID,
+                        guild_id: crate::model::test::GUILD_ID,
+                        attachments: vec![],
+                        stickers: vec![],
+                        previous_content: None,
+                        color: crate::action::LOG_COLOR_LOG,
+                        footer: None,
+                        dry_run: false,
                     }
                 ],
+                tier: None,
+                reason: "contains word `bad`".to_owned(),
             })
         )
     }
 
-    #[test]
-    fn use_default_scoping_if_no_scoping() {
+    #[tokio::test]
+    async fn log_attachments_and_stickers() {
+        let filters = vec![MessageFilter {
+            name: "first".to_string(),
+            rules: vec![MessageFilterRule::Words {
+                words: Some(Regex::new("\\b(bad)\\b").unwrap()),
+                tiers: vec![],
+                require_match: false,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
+            }],
+            scoping: None,
+            actions: Some(vec![MessageFilterAction::SendLog {
+                channel_id: Id::new(1),
+                color: None,
+                footer: None,
+            }]),
+            actions_by_tier: None,
+            exempt: false,
+        }];
+
+        let attachments = [Attachment {
+            content_type: Some("image/png".to_owned()),
+            ephemeral: false,
+            filename: "image.png".to_owned(),
+            description: None,
+            height: None,
+            id: Id::new(1),
+            proxy_url: "https://media.discordapp.net/image.png".to_owned(),
+            size: 1,
+            url: "https://cdn.discordapp.com/image.png".to_owned(),
+            width: None,
+        }];
+        let stickers = [MessageSticker {
+            format_type: StickerFormatType::Png,
+            id: Id::new(1),
+            name: "sticker".to_owned(),
+        }];
+        let mut message = crate::model::test::message(crate::model::test::BAD_CONTENT);
+        message.attachments = &attachments;
+        message.stickers = &stickers;
+
+        let result = super::filter_message(
+            &filters,
+            None,
+            None,
+            None,
+            &reqwest::Client::new(),
+            &message,
+            "message create",
+        )
+        .await;
+        assert_eq!(
+            result.unwrap_err().actions,
+            vec![MessageAction::SendLog {
+                to: Id::new(1),
+                filter_name: "first".to_owned(),
+                message_channel: crate::model::test::CHANNEL_ID,
+                content: crate::model::test::BAD_CONTENT.to_owned(),
+                filter_reason: "contains word `bad`".to_owned(),
+                author: crate::model::test::USER_ID,
+                context: "message create",
+                message_id: crate::model::test::MESSAGE_ID,
+                guild_id: crate::model::test::GUILD_ID,
+                attachments: vec![LoggedAttachment {
+                    filename: "image.png".to_owned(),
+                    url: "https://cdn.discordapp.com/image.png".to_owned(),
+                    proxy_url: "https://media.discordapp.net/image.png".to_owned(),
+                    is_image: true,
+                }],
+                stickers: vec!["sticker".to_owned()],
+                previous_content: None,
+                color: crate::action::LOG_COLOR_LOG,
+                footer: None,
+                dry_run: false,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn log_previous_content_of_edits() {
+        let filters = vec![MessageFilter {
+            name: "first".to_string(),
+            rules: vec![MessageFilterRule::Words {
+                words: Some(Regex::new("\\b(bad)\\b").unwrap()),
+                tiers: vec![],
+                require_match: false,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
+            }],
+            scoping: None,
+            actions: Some(vec![MessageFilterAction::SendLog {
+                channel_id: Id::new(1),
+                color: None,
+                footer: None,
+            }]),
+            actions_by_tier: None,
+            exempt: false,
+        }];
+
+        for previous_content in [Some("good message"), None] {
+            let mut message = crate::model::test::message(crate::model::test::BAD_CONTENT);
+            message.previous_content = Some(previous_content);
+
+            let result = super::filter_message(
+                &filters,
+                None,
+                None,
+                None,
+                &reqwest::Client::new(),
+                &message,
+                "message edit",
+            )
+            .await;
+            assert_eq!(
+                result.unwrap_err().actions,
+                vec![MessageAction::SendLog {
+                    to: Id::new(1),
+                    filter_name: "first".to_owned(),
+                    message_channel: crate::model::test::CHANNEL_ID,
+                    content: crate::model::test::BAD_CONTENT.to_owned(),
+                    previous_content: Some(previous_content.map(str::to_owned)),
+                    filter_reason: "contains word `bad`".to_owned(),
+                    author: crate::model::test::USER_ID,
+                    context: "message edit",
+                    message_id: crate::model::test::MESSAGE_ID,
+                    guild_id: crate::model::test::GUILD_ID,
+                    attachments: vec![],
+                    stickers: vec![],
+                    color: crate::action::LOG_COLOR_LOG,
+                    footer: None,
+                    dry_run: false,
+                }]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn skip_disabled_filters() {
+        let filters = vec![MessageFilter {
+            name: "first".to_string(),
+            rules: vec![MessageFilterRule::Words {
+                words: Some(Regex::new("\\b(bad)\\b").unwrap()),
+                tiers: vec![],
+                require_match: false,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
+            }],
+            scoping: None,
+            actions: Some(vec![MessageFilterAction::Delete {
+                delay_seconds: None,
+            }]),
+            actions_by_tier: None,
+            exempt: false,
+        }];
+
+        let disabled_filters = std::iter::once("first".to_string()).collect();
+        let message = crate::model::test::message("bad message");
+        let result = super::filter_message(
+            &filters,
+            Some(&disabled_filters),
+            None,
+            None,
+            &reqwest::Client::new(),
+            &message,
+            "message create",
+        )
+        .await;
+        assert_eq!(result, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn send_dm_to_author() {
+        let filters = vec![MessageFilter {
+            name: "first".to_string(),
+            rules: vec![MessageFilterRule::Words {
+                words: Some(Regex::new("\\b(bad)\\b").unwrap()),
+                tiers: vec![],
+                require_match: false,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
+            }],
+            scoping: None,
+            actions: Some(vec![MessageFilterAction::SendDm {
+                content: "Your message was removed because it $FILTER_REASON".to_string(),
+                requires_armed: true,
+            }]),
+            actions_by_tier: None,
+            exempt: false,
+        }];
+
+        let message = crate::model::test::message("bad message");
+        let result = super::filter_message(
+            &filters,
+            None,
+            None,
+            None,
+            &reqwest::Client::new(),
+            &message,
+            "message create",
+        )
+        .await;
+        assert_eq!(
+            result,
+            Err(MessageFilterFailure {
+                filter_name: "first".to_owned(),
+                context: "message create",
+                actions: vec![MessageAction::SendDm {
+                    user_id: crate::model::test::USER_ID,
+                    content: "Your message was removed because it contains word `bad`".to_owned(),
+                    requires_armed: true,
+                }],
+                tier: None,
+                reason: "contains word `bad`".to_owned(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn reply_to_message() {
+        let filters = vec![MessageFilter {
+            name: "first".to_string(),
+            rules: vec![MessageFilterRule::Words {
+                words: Some(Regex::new("\\b(bad)\\b").unwrap()),
+                tiers: vec![],
+                require_match: false,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
+            }],
+            scoping: None,
+            actions: Some(vec![MessageFilterAction::Reply {
+                content: "<@$USER_ID>, message removed: $FILTER_REASON".to_string(),
+                delete_after_seconds: Some(10),
+                requires_armed: false,
+            }]),
+            actions_by_tier: None,
+            exempt: false,
+        }];
+
+        let message = crate::model::test::message("bad message");
+        let result = super::filter_message(
+            &filters,
+            None,
+            None,
+            None,
+            &reqwest::Client::new(),
+            &message,
+            "message create",
+        )
+        .await;
+        assert_eq!(
+            result,
+            Err(MessageFilterFailure {
+                filter_name: "first".to_owned(),
+                context: "message create",
+                actions: vec![MessageAction::Reply {
+                    channel_id: crate::model::test::CHANNEL_ID,
+                    message_id: crate::model::test::MESSAGE_ID,
+                    content: "<@3>, message removed: contains word `bad`".to_owned(),
+                    delete_after_seconds: Some(10),
+                    requires_armed: false,
+                }],
+                tier: None,
+                reason: "contains word `bad`".to_owned(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn set_slowmode_in_message_channel() {
+        let filters = vec![MessageFilter {
+            name: "first".to_string(),
+            rules: vec![MessageFilterRule::Words {
+                words: Some(Regex::new("\\b(bad)\\b").unwrap()),
+                tiers: vec![],
+                require_match: false,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
+            }],
+            scoping: None,
+            actions: Some(vec![MessageFilterAction::SetSlowmode {
+                seconds: 30,
+                revert_after: Some(600),
+            }]),
+            actions_by_tier: None,
+            exempt: false,
+        }];
+
+        let message = crate::model::test::message("bad message");
+        let result = super::filter_message(
+            &filters,
+            None,
+            None,
+            None,
+            &reqwest::Client::new(),
+            &message,
+            "message create",
+        )
+        .await;
+        assert_eq!(
+            result,
+            Err(MessageFilterFailure {
+                filter_name: "first".to_owned(),
+                context: "message create",
+                actions: vec![MessageAction::SetSlowmode {
+                    channel_id: crate::model::test::CHANNEL_ID,
+                    seconds: 30,
+                    revert_after: Some(600),
+                }],
+                tier: None,
+                reason: "contains word `bad`".to_owned(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn escalate_offenses_against_filter() {
+        let filters = vec![MessageFilter {
+            name: "first".to_string(),
+            rules: vec![MessageFilterRule::Words {
+                words: Some(Regex::new("\\b(bad)\\b").unwrap()),
+                tiers: vec![],
+                require_match: false,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
+            }],
+            scoping: None,
+            actions: Some(vec![MessageFilterAction::EscalateOffenses {
+                window_seconds: 86400,
+                tiers: vec![
+                    OffenseTier {
+                        offenses: 1,
+                        actions: vec![MessageFilterAction::Warn {
+                            expiry_seconds: 86400,
+                        }],
+                    },
+                    OffenseTier {
+                        offenses: 2,
+                        actions: vec![MessageFilterAction::Timeout {
+                            reason: "repeat offense: $FILTER_REASON".to_owned(),
+                            duration: 600,
+                        }],
+                    },
+                ],
+            }]),
+            actions_by_tier: None,
+            exempt: false,
+        }];
+
+        let message = crate::model::test::message("bad message");
+        let result = super::filter_message(
+            &filters,
+            None,
+            None,
+            None,
+            &reqwest::Client::new(),
+            &message,
+            "message create",
+        )
+        .await;
+        assert_eq!(
+            result,
+            Err(MessageFilterFailure {
+                filter_name: "first".to_owned(),
+                context: "message create",
+                actions: vec![MessageAction::Escalate {
+                    user_id: crate::model::test::USER_ID,
+                    guild_id: crate::model::test::GUILD_ID,
+                    count: EscalationCount::FilterOffenses {
+                        filter_name: "first".to_owned(),
+                        window_seconds: 86400,
+                    },
+                    thresholds: vec![
+                        (
+                            1,
+                            vec![MessageAction::Warn {
+                                user_id: crate::model::test::USER_ID,
+                                guild_id: crate::model::test::GUILD_ID,
+                                expiry_seconds: 86400,
+                            }]
+                        ),
+                        (
+                            2,
+                            vec![MessageAction::Timeout {
+                                user_id: crate::model::test::USER_ID,
+                                guild_id: crate::model::test::GUILD_ID,
+                                reason: "repeat offense: contains word `bad`".to_owned(),
+                                duration: 600,
+                            }]
+                        ),
+                    ],
+                }],
+                tier: None,
+                reason: "contains word `bad`".to_owned(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn crosspost_message() {
+        let filters = vec![MessageFilter {
+            name: "first".to_string(),
+            rules: vec![MessageFilterRule::Words {
+                words: Some(Regex::new("\\b(bad)\\b").unwrap()),
+                tiers: vec![],
+                require_match: false,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
+            }],
+            scoping: None,
+            actions: Some(vec![MessageFilterAction::Crosspost]),
+            actions_by_tier: None,
+            exempt: false,
+        }];
+
+        let message = crate::model::test::message("bad message");
+        let result = super::filter_message(
+            &filters,
+            None,
+            None,
+            None,
+            &reqwest::Client::new(),
+            &message,
+            "message create",
+        )
+        .await;
+        let failure = result.unwrap_err();
+        assert_eq!(
+            failure.actions,
+            vec![MessageAction::Crosspost {
+                channel_id: crate::model::test::CHANNEL_ID,
+                message_id: crate::model::test::MESSAGE_ID,
+            }]
+        );
+        assert!(!failure.actions[0].requires_armed());
+    }
+
+    #[tokio::test]
+    async fn substitute_message_link() {
+        let filters = vec![MessageFilter {
+            name: "first".to_string(),
+            rules: vec![MessageFilterRule::Words {
+                words: Some(Regex::new("\\b(bad)\\b").unwrap()),
+                tiers: vec![],
+                require_match: false,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
+            }],
+            scoping: None,
+            actions: Some(vec![MessageFilterAction::SendMessage {
+                channel_id: Id::new(1),
+                content: "$USER_ID sent $MESSAGE_LINK".to_string(),
+                requires_armed: false,
+            }]),
+            actions_by_tier: None,
+            exempt: false,
+        }];
+
+        let message = crate::model::test::message("bad message");
+        let result = super::filter_message(
+            &filters,
+            None,
+            None,
+            None,
+            &reqwest::Client::new(),
+            &message,
+            "message create",
+        )
+        .await;
+        assert_eq!(
+            result,
+            Err(MessageFilterFailure {
+                filter_name: "first".to_owned(),
+                context: "message create",
+                actions: vec![MessageAction::SendMessage {
+                    to: Id::new(1),
+                    content: "3 sent https://discord.com/channels/4/2/1".to_owned(),
+                    requires_armed: false,
+                }],
+                tier: None,
+                reason: "contains word `bad`".to_owned(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn use_default_scoping_if_no_scoping() {
         let filters = vec![MessageFilter {
             name: "first".to_string(),
             rules: vec![MessageFilterRule::Words {
-                words: Regex::new("\\b(bad)\\b").unwrap(),
+                words: Some(Regex::new("\\b(bad)\\b").unwrap()),
+                tiers: vec![],
+                require_match: false,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
             }],
             scoping: None,
-            actions: Some(vec![MessageFilterAction::Delete]),
+            actions: Some(vec![MessageFilterAction::Delete {
+                delay_seconds: None,
+            }]),
+            actions_by_tier: None,
+            exempt: false,
         }];
 
         let default_scoping = Scoping {
@@ -365,11 +1387,14 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
         let message = crate::model::test::message(crate::model::test::BAD_CONTENT);
         let result = super::filter_message(
             &filters,
+            None,
             Some(&default_scoping),
             None,
+            &reqwest::Client::new(),
             &message,
             "message create",
-        );
+        )
+        .await;
         assert_eq!(
             result,
             Err(MessageFilterFailure {
@@ -378,23 +1403,36 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
                 actions: vec![MessageAction::Delete {
                     message_id: crate::model::test::MESSAGE_ID,
                     channel_id: crate::model::test::CHANNEL_ID,
+                    delay_seconds: None,
+                    reason: Some("Filter first: contains word `bad`".to_owned()),
                 }],
+                tier: None,
+                reason: "contains word `bad`".to_owned(),
             })
         );
     }
 
-    #[test]
-    fn scoping_overrides_default_scoping() {
+    #[tokio::test]
+    async fn scoping_overrides_default_scoping() {
         let filters = vec![MessageFilter {
             name: "first".to_string(),
             rules: vec![MessageFilterRule::Words {
-                words: Regex::new("\\b(bad)\\b").unwrap(),
+                words: Some(Regex::new("\\b(bad)\\b").unwrap()),
+                tiers: vec![],
+                require_match: false,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
             }],
             scoping: Some(Scoping {
                 include_channels: Some(vec![crate::model::test::CHANNEL_ID]),
                 ..Default::default()
             }),
-            actions: Some(vec![MessageFilterAction::Delete]),
+            actions: Some(vec![MessageFilterAction::Delete {
+                delay_seconds: None,
+            }]),
+            actions_by_tier: None,
+            exempt: false,
         }];
 
         let default_scoping = Scoping {
@@ -405,11 +1443,14 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
         let message = crate::model::test::message(crate::model::test::BAD_CONTENT);
         let result = super::filter_message(
             &filters,
+            None,
             Some(&default_scoping),
             None,
+            &reqwest::Client::new(),
             &message,
             "message create",
-        );
+        )
+        .await;
         assert_eq!(
             result,
             Err(MessageFilterFailure {
@@ -418,29 +1459,51 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
                 actions: vec![MessageAction::Delete {
                     message_id: crate::model::test::MESSAGE_ID,
                     channel_id: crate::model::test::CHANNEL_ID,
+                    delay_seconds: None,
+                    reason: Some("Filter first: contains word `bad`".to_owned()),
                 }],
+                tier: None,
+                reason: "contains word `bad`".to_owned(),
             })
         );
     }
 
-    #[test]
-    fn evaluate_filters_in_order() {
+    #[tokio::test]
+    async fn evaluate_filters_in_order() {
         let filters = vec![
             MessageFilter {
                 name: "first".to_string(),
                 rules: vec![MessageFilterRule::Words {
-                    words: Regex::new("\\b(bad)\\b").unwrap(),
+                    words: Some(Regex::new("\\b(bad)\\b").unwrap()),
+                    tiers: vec![],
+                    require_match: false,
+                    reason: None,
+                    leetspeak: false,
+                    collapse_spacing: false,
                 }],
                 scoping: None,
-                actions: Some(vec![MessageFilterAction::Delete]),
+                actions: Some(vec![MessageFilterAction::Delete {
+                    delay_seconds: None,
+                }]),
+                actions_by_tier: None,
+                exempt: false,
             },
             MessageFilter {
                 name: "second".to_string(),
                 rules: vec![MessageFilterRule::Words {
-                    words: Regex::new("\\b(bad|special)\\b").unwrap(),
+                    words: Some(Regex::new("\\b(bad|special)\\b").unwrap()),
+                    tiers: vec![],
+                    require_match: false,
+                    reason: None,
+                    leetspeak: false,
+                    collapse_spacing: false,
                 }],
                 scoping: None,
-                actions: Some(vec![MessageFilterAction::Delete]),
+                actions: Some(vec![MessageFilterAction::Delete {
+                    delay_seconds: None,
+                }]),
+                actions_by_tier: None,
+                exempt: false,
             },
         ];
 
@@ -452,11 +1515,14 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
         let message = crate::model::test::message(crate::model::test::BAD_CONTENT);
         let result = super::filter_message(
             &filters,
+            None,
             Some(&default_scoping),
             None,
+            &reqwest::Client::new(),
             &message,
             "message create",
-        );
+        )
+        .await;
         assert_eq!(
             result,
             Err(MessageFilterFailure {
@@ -465,55 +1531,218 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
                 actions: vec![MessageAction::Delete {
                     message_id: crate::model::test::MESSAGE_ID,
                     channel_id: crate::model::test::CHANNEL_ID,
+                    delay_seconds: None,
+                    reason: Some("Filter first: contains word `bad`".to_owned()),
                 }],
+                tier: None,
+                reason: "contains word `bad`".to_owned(),
             })
         );
 
         let second_message = crate::model::test::message("special message");
         let result = super::filter_message(
             &filters,
+            None,
             Some(&default_scoping),
             None,
+            &reqwest::Client::new(),
             &second_message,
             "message create",
-        );
+        )
+        .await;
+        assert_eq!(
+            result,
+            Err(MessageFilterFailure {
+                filter_name: "second".to_owned(),
+                context: "message create",
+                actions: vec![MessageAction::Delete {
+                    message_id: crate::model::test::MESSAGE_ID,
+                    channel_id: crate::model::test::CHANNEL_ID,
+                    delay_seconds: None,
+                    reason: Some("Filter second: contains word `special`".to_owned()),
+                }],
+                tier: None,
+                reason: "contains word `special`".to_owned(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn exempt_filter_skips_later_filters() {
+        let filters = vec![
+            MessageFilter {
+                name: "exemption".to_string(),
+                rules: vec![MessageFilterRule::Substring {
+                    substrings: Regex::new("\\[modmail\\]").unwrap(),
+                    leetspeak: false,
+                    collapse_spacing: false,
+                }],
+                scoping: None,
+                actions: None,
+                actions_by_tier: None,
+                exempt: true,
+            },
+            MessageFilter {
+                name: "deny".to_string(),
+                rules: vec![MessageFilterRule::Words {
+                    words: Some(Regex::new("\\b(bad)\\b").unwrap()),
+                    tiers: vec![],
+                    require_match: false,
+                    reason: None,
+                    leetspeak: false,
+                    collapse_spacing: false,
+                }],
+                scoping: None,
+                actions: Some(vec![MessageFilterAction::Delete {
+                    delay_seconds: None,
+                }]),
+                actions_by_tier: None,
+                exempt: false,
+            },
+        ];
+
+        let exempt_message = crate::model::test::message("[modmail] user said bad things");
+        let result = super::filter_message(
+            &filters,
+            None,
+            None,
+            None,
+            &reqwest::Client::new(),
+            &exempt_message,
+            "message create",
+        )
+        .await;
+        assert_eq!(result, Ok(()));
+
+        let message = crate::model::test::message("bad message");
+        let result = super::filter_message(
+            &filters,
+            None,
+            None,
+            None,
+            &reqwest::Client::new(),
+            &message,
+            "message create",
+        )
+        .await;
+        assert_eq!(
+            result,
+            Err(MessageFilterFailure {
+                filter_name: "deny".to_owned(),
+                context: "message create",
+                actions: vec![MessageAction::Delete {
+                    message_id: crate::model::test::MESSAGE_ID,
+                    channel_id: crate::model::test::CHANNEL_ID,
+                    delay_seconds: None,
+                    reason: Some("Filter deny: contains word `bad`".to_owned()),
+                }],
+                tier: None,
+                reason: "contains word `bad`".to_owned(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn exempt_filter_honors_scoping() {
+        let filters = vec![
+            MessageFilter {
+                name: "exemption".to_string(),
+                rules: vec![MessageFilterRule::Substring {
+                    substrings: Regex::new("\\[modmail\\]").unwrap(),
+                    leetspeak: false,
+                    collapse_spacing: false,
+                }],
+                scoping: Some(Scoping {
+                    include_channels: Some(vec![Id::new(100)]),
+                    ..Default::default()
+                }),
+                actions: None,
+                actions_by_tier: None,
+                exempt: true,
+            },
+            MessageFilter {
+                name: "deny".to_string(),
+                rules: vec![MessageFilterRule::Words {
+                    words: Some(Regex::new("\\b(bad)\\b").unwrap()),
+                    tiers: vec![],
+                    require_match: false,
+                    reason: None,
+                    leetspeak: false,
+                    collapse_spacing: false,
+                }],
+                scoping: None,
+                actions: Some(vec![MessageFilterAction::Delete {
+                    delay_seconds: None,
+                }]),
+                actions_by_tier: None,
+                exempt: false,
+            },
+        ];
+
+        let message = crate::model::test::message("[modmail] user said bad things");
+        let result = super::filter_message(
+            &filters,
+            None,
+            None,
+            None,
+            &reqwest::Client::new(),
+            &message,
+            "message create",
+        )
+        .await;
         assert_eq!(
             result,
             Err(MessageFilterFailure {
-                filter_name: "second".to_owned(),
+                filter_name: "deny".to_owned(),
                 context: "message create",
                 actions: vec![MessageAction::Delete {
                     message_id: crate::model::test::MESSAGE_ID,
                     channel_id: crate::model::test::CHANNEL_ID,
+                    delay_seconds: None,
+                    reason: Some("Filter deny: contains word `bad`".to_owned()),
                 }],
+                tier: None,
+                reason: "contains word `bad`".to_owned(),
             })
         );
     }
 
-    #[test]
-    fn use_default_actions_if_no_actions() {
+    #[tokio::test]
+    async fn use_default_actions_if_no_actions() {
         let filters = vec![MessageFilter {
             name: "first".to_string(),
             rules: vec![MessageFilterRule::Words {
-                words: Regex::new("\\b(bad)\\b").unwrap(),
+                words: Some(Regex::new("\\b(bad)\\b").unwrap()),
+                tiers: vec![],
+                require_match: false,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
             }],
             scoping: Some(Scoping {
                 include_channels: Some(vec![crate::model::test::CHANNEL_ID]),
                 ..Default::default()
             }),
             actions: None,
+            actions_by_tier: None,
+            exempt: false,
         }];
 
-        let default_actions = vec![MessageFilterAction::Delete];
+        let default_actions = vec![MessageFilterAction::Delete {
+            delay_seconds: None,
+        }];
 
         let message = crate::model::test::message(crate::model::test::BAD_CONTENT);
         let result = super::filter_message(
             &filters,
             None,
+            None,
             Some(&default_actions),
+            &reqwest::Client::new(),
             &message,
             "message create",
-        );
+        )
+        .await;
         assert_eq!(
             result,
             Err(MessageFilterFailure {
@@ -522,43 +1751,70 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
                 actions: vec![MessageAction::Delete {
                     message_id: crate::model::test::MESSAGE_ID,
                     channel_id: crate::model::test::CHANNEL_ID,
+                    delay_seconds: None,
+                    reason: Some("Filter first: contains word `bad`".to_owned()),
                 }],
+                tier: None,
+                reason: "contains word `bad`".to_owned(),
             })
         );
     }
 
-    #[test]
-    fn use_no_actions_if_none_are_specified() {
+    #[tokio::test]
+    async fn use_no_actions_if_none_are_specified() {
         let filters = vec![MessageFilter {
             name: "first".to_string(),
             rules: vec![MessageFilterRule::Words {
-                words: Regex::new("\\b(bad)\\b").unwrap(),
+                words: Some(Regex::new("\\b(bad)\\b").unwrap()),
+                tiers: vec![],
+                require_match: false,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
             }],
             scoping: Some(Scoping {
                 include_channels: Some(vec![crate::model::test::CHANNEL_ID]),
                 ..Default::default()
             }),
             actions: None,
+            actions_by_tier: None,
+            exempt: false,
         }];
 
         let message = crate::model::test::message(crate::model::test::BAD_CONTENT);
-        let result = super::filter_message(&filters, None, None, &message, "message create");
+        let result = super::filter_message(
+            &filters,
+            None,
+            None,
+            None,
+            &reqwest::Client::new(),
+            &message,
+            "message create",
+        )
+        .await;
         assert_eq!(
             result,
             Err(MessageFilterFailure {
                 filter_name: "first".to_owned(),
                 context: "message create",
                 actions: vec![],
+                tier: None,
+                reason: "contains word `bad`".to_owned(),
             })
         );
     }
 
-    #[test]
-    fn actions_override_default_actions() {
+    #[tokio::test]
+    async fn actions_override_default_actions() {
         let filters = vec![MessageFilter {
             name: "first".to_string(),
             rules: vec![MessageFilterRule::Words {
-                words: Regex::new("\\b(bad)\\b").unwrap(),
+                words: Some(Regex::new("\\b(bad)\\b").unwrap()),
+                tiers: vec![],
+                require_match: false,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
             }],
             scoping: Some(Scoping {
                 include_channels: Some(vec![crate::model::test::CHANNEL_ID]),
@@ -569,18 +1825,25 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
                 content: "filtered".to_owned(),
                 requires_armed: false,
             }]),
+            actions_by_tier: None,
+            exempt: false,
         }];
 
-        let default_actions = vec![MessageFilterAction::Delete];
+        let default_actions = vec![MessageFilterAction::Delete {
+            delay_seconds: None,
+        }];
 
         let message = crate::model::test::message(crate::model::test::BAD_CONTENT);
         let result = super::filter_message(
             &filters,
             None,
+            None,
             Some(&default_actions),
+            &reqwest::Client::new(),
             &message,
             "message create",
-        );
+        )
+        .await;
         assert_eq!(
             result,
             Err(MessageFilterFailure {
@@ -591,34 +1854,349 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
                     content: "filtered".to_owned(),
                     requires_armed: false,
                 }],
+                tier: None,
+                reason: "contains word `bad`".to_owned(),
             })
         );
     }
 
-    #[test]
-    fn pass_if_no_filters_filter() {
+    #[tokio::test]
+    async fn tier_actions_override_actions() {
+        let filters = vec![MessageFilter {
+            name: "first".to_string(),
+            rules: vec![MessageFilterRule::Words {
+                words: Some(Regex::new("\\b(asdf)\\b").unwrap()),
+                tiers: vec![WordTier {
+                    name: "severe".to_owned(),
+                    words: Regex::new("\\b(bad)\\b").unwrap(),
+                }],
+                require_match: false,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
+            }],
+            scoping: None,
+            actions: Some(vec![MessageFilterAction::Delete {
+                delay_seconds: None,
+            }]),
+            actions_by_tier: Some(HashMap::from([(
+                "severe".to_owned(),
+                vec![MessageFilterAction::SendMessage {
+                    channel_id: Id::new(2),
+                    content: "$FILTER_TIER: $FILTER_REASON".to_owned(),
+                    requires_armed: false,
+                }],
+            )])),
+            exempt: false,
+        }];
+
+        let message = crate::model::test::message("bad message");
+        let result = super::filter_message(
+            &filters,
+            None,
+            None,
+            None,
+            &reqwest::Client::new(),
+            &message,
+            "message create",
+        )
+        .await;
+        assert_eq!(
+            result,
+            Err(MessageFilterFailure {
+                filter_name: "first".to_owned(),
+                context: "message create",
+                actions: vec![MessageAction::SendMessage {
+                    to: Id::new(2),
+                    content: "severe: contains word `bad`".to_owned(),
+                    requires_armed: false,
+                }],
+                tier: Some("severe".to_owned()),
+                reason: "contains word `bad`".to_owned(),
+            })
+        );
+
+        // Words outside of any tier use the filter's actions.
+        let message = crate::model::test::message("asdf message");
+        let result = super::filter_message(
+            &filters,
+            None,
+            None,
+            None,
+            &reqwest::Client::new(),
+            &message,
+            "message create",
+        )
+        .await;
+        assert_eq!(
+            result,
+            Err(MessageFilterFailure {
+                filter_name: "first".to_owned(),
+                context: "message create",
+                actions: vec![MessageAction::Delete {
+                    message_id: crate::model::test::MESSAGE_ID,
+                    channel_id: crate::model::test::CHANNEL_ID,
+                    delay_seconds: None,
+                    reason: Some("Filter first: contains word `asdf`".to_owned()),
+                }],
+                tier: None,
+                reason: "contains word `asdf`".to_owned(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn redact_content_in_actions() {
+        let filters = vec![MessageFilter {
+            name: "first".to_string(),
+            rules: vec![MessageFilterRule::CryptoAddresses {
+                bitcoin: true,
+                ethereum: false,
+                solana: false,
+                monero: false,
+            }],
+            scoping: None,
+            actions: Some(vec![
+                MessageFilterAction::SendMessage {
+                    channel_id: Id::new(1),
+                    content: "$MESSAGE_PREVIEW".to_string(),
+                    requires_armed: false,
+                },
+                MessageFilterAction::SendLog {
+                    channel_id: Id::new(1),
+                    color: None,
+                    footer: None,
+                },
+            ]),
+            actions_by_tier: None,
+            exempt: false,
+        }];
+
+        let message = crate::model::test::message("send to 1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2");
+        let result = super::filter_message(
+            &filters,
+            None,
+            None,
+            None,
+            &reqwest::Client::new(),
+            &message,
+            "message create",
+        )
+        .await;
+        assert_eq!(
+            result,
+            Err(MessageFilterFailure {
+                filter_name: "first".to_owned(),
+                context: "message create",
+                actions: vec![
+                    MessageAction::SendMessage {
+                        to: Id::new(1),
+                        content: "send to [redacted]".to_owned(),
+                        requires_armed: false,
+                    },
+                    MessageAction::SendLog {
+                        to: Id::new(1),
+                        filter_name: "first".to_owned(),
+                        message_channel: crate::model::test::CHANNEL_ID,
+                        content: "send to [redacted]".to_owned(),
+                        filter_reason: "contains Bitcoin address".to_owned(),
+                        author: crate::model::test::USER_ID,
+                        context: "message create",
+                        message_id: crate::model::test::MESSAGE_ID,
+                        guild_id: crate::model::test::GUILD_ID,
+                        attachments: vec![],
+                        stickers: vec![],
+                        previous_content: None,
+                        color: crate::action::LOG_COLOR_LOG,
+                        footer: None,
+                        dry_run: false,
+                    }
+                ],
+                tier: None,
+                reason: "contains Bitcoin address".to_owned(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn pass_if_no_filters_filter() {
         let filters = vec![MessageFilter {
             name: "first".to_string(),
             rules: vec![MessageFilterRule::Words {
-                words: Regex::new("\\b(bad)\\b").unwrap(),
+                words: Some(Regex::new("\\b(bad)\\b").unwrap()),
+                tiers: vec![],
+                require_match: false,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
             }],
             scoping: Some(Scoping {
                 include_channels: Some(vec![crate::model::test::CHANNEL_ID]),
                 ..Default::default()
             }),
-            actions: Some(vec![MessageFilterAction::Delete]),
+            actions: Some(vec![MessageFilterAction::Delete {
+                delay_seconds: None,
+            }]),
+            actions_by_tier: None,
+            exempt: false,
         }];
 
         let message = crate::model::test::message(crate::model::test::GOOD_CONTENT);
-        let result = super::filter_message(&filters, None, None, &message, "message create");
+        let result = super::filter_message(
+            &filters,
+            None,
+            None,
+            None,
+            &reqwest::Client::new(),
+            &message,
+            "message create",
+        )
+        .await;
+        assert_eq!(result, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn warn_before_spam_actions() {
+        let spam_config = SpamFilter {
+            duplicates: Some(3),
+            interval: 30,
+            actions: Some(vec![MessageFilterAction::Delete {
+                delay_seconds: None,
+            }]),
+            warn_actions: Some(vec![MessageFilterAction::SendMessage {
+                channel_id: Id::new(1),
+                content: "$FILTER_REASON".to_owned(),
+                requires_armed: false,
+            }]),
+            ..Default::default()
+        };
+
+        let spam_history = Arc::new(RwLock::new(HashMap::new()));
+        let raid_index = Arc::new(Mutex::new(RaidIndex::default()));
+        let mut results = Vec::new();
+        for sent_at in 1..=4 {
+            let message = crate::model::test::message_at_time("buy my stuff", sent_at);
+            results.push(
+                super::spam_check_message(
+                    &spam_config,
+                    None,
+                    None,
+                    spam_history.clone(),
+                    raid_index.clone(),
+                    &message,
+                    "message create",
+                    sent_at as u64 * 1_000_000,
+                )
+                .await
+                .map_err(|failure| failure.actions),
+            );
+        }
+
+        assert_eq!(
+            results,
+            vec![
+                Ok(()),
+                Ok(()),
+                Err(vec![MessageAction::SendMessage {
+                    to: Id::new(1),
+                    content: "nearly sent too many duplicate messages".to_owned(),
+                    requires_armed: false,
+                }]),
+                Err(vec![MessageAction::Delete {
+                    message_id: crate::model::test::MESSAGE_ID,
+                    channel_id: crate::model::test::CHANNEL_ID,
+                    delay_seconds: None,
+                    reason: Some("Filter Spam: sent too many duplicate messages".to_owned()),
+                }]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn raid_check() {
+        let spam_config = SpamFilter {
+            interval: 30,
+            actions: Some(vec![MessageFilterAction::SendLog {
+                channel_id: Id::new(1),
+                color: None,
+                footer: None,
+            }]),
+            raid: Some(RaidFilter {
+                identical_messages: 1,
+                window_seconds: 30,
+                min_characters: 0,
+                delete_earlier: true,
+                actions: Some(vec![MessageFilterAction::Delete {
+                    delay_seconds: None,
+                }]),
+            }),
+            ..Default::default()
+        };
+
+        let spam_history = Arc::new(RwLock::new(HashMap::new()));
+        let raid_index = Arc::new(Mutex::new(RaidIndex::default()));
+        let first_message = crate::model::test::message_at_time("free nitro", 10);
+        let result = super::spam_check_message(
+            &spam_config,
+            None,
+            None,
+            spam_history.clone(),
+            raid_index.clone(),
+            &first_message,
+            "message create",
+            10_000_000,
+        )
+        .await;
         assert_eq!(result, Ok(()));
+
+        let mut second_message = crate::model::test::message_at_time("free nitro", 11);
+        second_message.id = Id::new(100);
+        second_message.author_id = Id::new(100);
+        let result = super::spam_check_message(
+            &spam_config,
+            None,
+            None,
+            spam_history.clone(),
+            raid_index.clone(),
+            &second_message,
+            "message create",
+            11_000_000,
+        )
+        .await;
+        let reason =
+            Some("Filter Raid: part of a raid: 2 users posted the same message".to_owned());
+        assert_eq!(
+            result,
+            Err(MessageFilterFailure {
+                filter_name: super::RAID_FILTER_NAME.to_string(),
+                context: "message create",
+                actions: vec![
+                    MessageAction::Delete {
+                        message_id: Id::new(100),
+                        channel_id: crate::model::test::CHANNEL_ID,
+                        delay_seconds: None,
+                        reason: reason.clone(),
+                    },
+                    MessageAction::Delete {
+                        message_id: crate::model::test::MESSAGE_ID,
+                        channel_id: crate::model::test::CHANNEL_ID,
+                        delay_seconds: None,
+                        reason,
+                    }
+                ],
+                tier: None,
+                reason: "part of a raid: 2 users posted the same message".to_owned(),
+            })
+        );
     }
 
     #[tokio::test]
     async fn spam_check() {
         let spam_config = SpamFilter {
             duplicates: Some(1),
-            actions: Some(vec![MessageFilterAction::Delete]),
+            actions: Some(vec![MessageFilterAction::Delete {
+                delay_seconds: None,
+            }]),
             ..Default::default()
         };
 
@@ -629,6 +2207,7 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
             None,
             None,
             spam_history.clone(),
+            Arc::new(Mutex::new(RaidIndex::default())),
             &message,
             "message create",
             20,
@@ -643,6 +2222,7 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
             None,
             None,
             spam_history.clone(),
+            Arc::new(Mutex::new(RaidIndex::default())),
             &second_message,
             "message create",
             40,
@@ -656,7 +2236,11 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
                 actions: vec![MessageAction::Delete {
                     channel_id: crate::model::test::CHANNEL_ID,
                     message_id: crate::model::test::MESSAGE_ID,
-                }]
+                    delay_seconds: None,
+                    reason: Some("Filter Spam: sent too many duplicate messages".to_owned()),
+                }],
+                tier: None,
+                reason: "sent too many duplicate messages".to_owned(),
             })
         );
     }
@@ -665,7 +2249,9 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
     async fn spam_check_use_default_scoping_if_no_scoping() {
         let spam_config = SpamFilter {
             spoilers: Some(1),
-            actions: Some(vec![MessageFilterAction::Delete]),
+            actions: Some(vec![MessageFilterAction::Delete {
+                delay_seconds: None,
+            }]),
             ..Default::default()
         };
 
@@ -681,6 +2267,7 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
             Some(&default_scoping),
             None,
             spam_history.clone(),
+            Arc::new(Mutex::new(RaidIndex::default())),
             &message,
             "message create",
             20,
@@ -693,7 +2280,9 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
     async fn spam_check_scoping_overrides_default_scoping() {
         let spam_config = SpamFilter {
             spoilers: Some(1),
-            actions: Some(vec![MessageFilterAction::Delete]),
+            actions: Some(vec![MessageFilterAction::Delete {
+                delay_seconds: None,
+            }]),
             scoping: Some(Scoping {
                 include_channels: Some(vec![crate::model::test::CHANNEL_ID]),
                 ..Default::default()
@@ -713,6 +2302,7 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
             Some(&default_scoping),
             None,
             spam_history.clone(),
+            Arc::new(Mutex::new(RaidIndex::default())),
             &message,
             "message create",
             20,
@@ -726,7 +2316,11 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
                 actions: vec![MessageAction::Delete {
                     message_id: crate::model::test::MESSAGE_ID,
                     channel_id: crate::model::test::CHANNEL_ID,
-                }]
+                    delay_seconds: None,
+                    reason: Some("Filter Spam: sent too many spoilers".to_owned()),
+                }],
+                tier: None,
+                reason: "sent too many spoilers".to_owned(),
             })
         );
     }
@@ -740,7 +2334,9 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
             ..Default::default()
         };
 
-        let default_actions = vec![MessageFilterAction::Delete];
+        let default_actions = vec![MessageFilterAction::Delete {
+            delay_seconds: None,
+        }];
 
         let spam_history = Arc::new(RwLock::new(HashMap::new()));
         let message = crate::model::test::message_at_time("|| || || ||", 10);
@@ -749,6 +2345,7 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
             None,
             Some(&default_actions),
             spam_history.clone(),
+            Arc::new(Mutex::new(RaidIndex::default())),
             &message,
             "message create",
             20,
@@ -762,7 +2359,11 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
                 actions: vec![MessageAction::Delete {
                     message_id: crate::model::test::MESSAGE_ID,
                     channel_id: crate::model::test::CHANNEL_ID,
-                }]
+                    delay_seconds: None,
+                    reason: Some("Filter Spam: sent too many spoilers".to_owned()),
+                }],
+                tier: None,
+                reason: "sent too many spoilers".to_owned(),
             })
         );
     }
@@ -771,7 +2372,9 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
     async fn spam_check_actions_override_default_actions() {
         let spam_config = SpamFilter {
             spoilers: Some(1),
-            actions: Some(vec![MessageFilterAction::Delete]),
+            actions: Some(vec![MessageFilterAction::Delete {
+                delay_seconds: None,
+            }]),
             scoping: None,
             ..Default::default()
         };
@@ -785,6 +2388,7 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
             None,
             Some(&default_actions),
             spam_history.clone(),
+            Arc::new(Mutex::new(RaidIndex::default())),
             &message,
             "message create",
             20,
@@ -798,7 +2402,11 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
                 actions: vec![MessageAction::Delete {
                     message_id: crate::model::test::MESSAGE_ID,
                     channel_id: crate::model::test::CHANNEL_ID,
-                }]
+                    delay_seconds: None,
+                    reason: Some("Filter Spam: sent too many spoilers".to_owned()),
+                }],
+                tier: None,
+                reason: "sent too many spoilers".to_owned(),
             })
         );
     }
@@ -808,15 +2416,26 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
         let filters = vec![MessageFilter {
             name: "first".to_string(),
             rules: vec![MessageFilterRule::Words {
-                words: Regex::new("\\b(bad)\\b").unwrap(),
+                words: Some(Regex::new("\\b(bad)\\b").unwrap()),
+                tiers: vec![],
+                require_match: false,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
             }],
             scoping: None,
-            actions: Some(vec![MessageFilterAction::Delete]),
+            actions: Some(vec![MessageFilterAction::Delete {
+                delay_seconds: None,
+            }]),
+            actions_by_tier: None,
+            exempt: false,
         }];
 
         let spam_config = SpamFilter {
             duplicates: Some(1),
-            actions: Some(vec![MessageFilterAction::Delete]),
+            actions: Some(vec![MessageFilterAction::Delete {
+                delay_seconds: None,
+            }]),
             ..Default::default()
         };
 
@@ -827,7 +2446,10 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
             &filters,
             None,
             None,
+            None,
             spam_history.clone(),
+            Arc::new(Mutex::new(RaidIndex::default())),
+            &reqwest::Client::new(),
             &message,
             "message create",
             20,
@@ -841,7 +2463,11 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
                 actions: vec![MessageAction::Delete {
                     message_id: crate::model::test::MESSAGE_ID,
                     channel_id: crate::model::test::CHANNEL_ID,
-                }]
+                    delay_seconds: None,
+                    reason: Some("Filter first: contains word `bad`".to_owned()),
+                }],
+                tier: None,
+                reason: "contains word `bad`".to_owned(),
             })
         );
 
@@ -852,7 +2478,10 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
             &filters,
             None,
             None,
+            None,
             spam_history.clone(),
+            Arc::new(Mutex::new(RaidIndex::default())),
+            &reqwest::Client::new(),
             &second_message,
             "message create",
             40,
@@ -866,7 +2495,11 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
                 actions: vec![MessageAction::Delete {
                     message_id: crate::model::test::MESSAGE_ID,
                     channel_id: crate::model::test::CHANNEL_ID,
-                }]
+                    delay_seconds: None,
+                    reason: Some("Filter first: contains word `bad`".to_owned()),
+                }],
+                tier: None,
+                reason: "contains word `bad`".to_owned(),
             })
         );
     }
@@ -881,4 +2514,64 @@ asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̿
 
         assert_eq!(result, format!("Hey @{}", name));
     }
+
+    #[tokio::test]
+    async fn report_every_failure() {
+        let filter = |name: &str, word: &str| MessageFilter {
+            name: name.to_owned(),
+            rules: vec![MessageFilterRule::Words {
+                words: Some(Regex::new(&format!("\\b({})\\b", word)).unwrap()),
+                tiers: vec![],
+                require_match: false,
+                reason: None,
+                leetspeak: false,
+                collapse_spacing: false,
+            }],
+            ..Default::default()
+        };
+
+        let filters = vec![
+            filter("first", "bad"),
+            filter("passed", "good"),
+            filter("disabled", "asdf"),
+            filter("second", "message"),
+            MessageFilter {
+                exempt: true,
+                ..filter("exempt", "asdf")
+            },
+            filter("after exempt", "bad"),
+        ];
+        let disabled = std::iter::once("disabled".to_owned()).collect();
+        let spam_config = SpamFilter {
+            links: Some(0),
+            ..Default::default()
+        };
+
+        let message = crate::model::test::message(crate::model::test::BAD_CONTENT);
+        let failures = super::filter_message_every_failure(
+            Some(&spam_config),
+            &filters,
+            Some(&disabled),
+            None,
+            None,
+            &reqwest::Client::new(),
+            &message,
+            "message test",
+            0,
+        )
+        .await;
+
+        let failures: Vec<_> = failures
+            .iter()
+            .map(|failure| (failure.filter_name.as_str(), failure.reason.as_str()))
+            .collect();
+        assert_eq!(
+            failures,
+            vec![
+                ("first", "contains word `bad`"),
+                ("second", "contains word `message`"),
+                ("Spam", "sent too many links"),
+            ]
+        );
+    }
 }
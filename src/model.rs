@@ -1,24 +1,66 @@
 use twilight_model::{
-    channel::{message::sticker::MessageSticker, message::ReactionType, Attachment},
+    channel::{
+        message::sticker::MessageSticker, message::Embed, message::MessageReference,
+        message::ReactionType, Attachment,
+    },
     id::{
-        marker::{ChannelMarker, GuildMarker, MessageMarker, RoleMarker, UserMarker},
+        marker::{
+            ChannelMarker, GuildMarker, MessageMarker, RoleMarker, StickerMarker, UserMarker,
+        },
         Id,
     },
     util::datetime::Timestamp,
 };
 
-#[derive(Debug, PartialEq, Eq)]
+use crate::repost::LastMessage;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct MessageInfo<'a> {
     pub(crate) author_is_bot: bool,
+    /// Whether the message was sent by a webhook. Webhooks aren't members, so
+    /// they have no roles, and `author_id` is the webhook's ID.
+    pub(crate) is_webhook: bool,
     pub(crate) id: Id<MessageMarker>,
     pub(crate) author_id: Id<UserMarker>,
     pub(crate) channel_id: Id<ChannelMarker>,
+    /// If the message was sent in a thread, the channel the thread is in.
+    pub(crate) parent_channel_id: Option<Id<ChannelMarker>>,
     pub(crate) guild_id: Id<GuildMarker>,
     pub(crate) author_roles: &'a [Id<RoleMarker>],
+    pub(crate) account_created_at: Timestamp,
+    /// When the author joined the guild, if they're a member of it.
+    pub(crate) joined_at: Option<Timestamp>,
     pub(crate) content: &'a str,
+    /// For edits, what the message's content was before the edit: `Some(None)`
+    /// if the message wasn't cached, so its previous content isn't known.
+    /// `None` for messages that weren't edited.
+    pub(crate) previous_content: Option<Option<&'a str>>,
     pub(crate) timestamp: Timestamp,
     pub(crate) attachments: &'a [Attachment],
     pub(crate) stickers: &'a [MessageSticker],
+    pub(crate) embeds: &'a [Embed],
+    /// The stickers belonging to the guild the message was sent in, if they
+    /// have been fetched.
+    pub(crate) guild_stickers: Option<&'a [Id<StickerMarker>]>,
+    /// The channels in the guild the message was sent in, if they are cached.
+    pub(crate) guild_channels: Option<&'a [Id<ChannelMarker>]>,
+    /// The message this one replies to or was crossposted from, if any.
+    pub(crate) reference: Option<&'a MessageReference>,
+    /// The users mentioned in the message. `content` has already had user
+    /// mentions replaced with display names, so they can't be parsed from it.
+    pub(crate) mentioned_users: &'a [Id<UserMarker>],
+    /// The message the author sent in this channel before this one, if the
+    /// guild has `no_repost` rules and it's still remembered.
+    pub(crate) last_message: Option<&'a LastMessage>,
+}
+
+/// Gets the time a Discord ID was created at.
+pub(crate) fn snowflake_timestamp<T>(id: Id<T>) -> Timestamp {
+    const DISCORD_EPOCH_MILLISECONDS: u64 = 1_420_070_400_000;
+
+    let unix_milliseconds = (id.get() >> 22) + DISCORD_EPOCH_MILLISECONDS;
+    Timestamp::from_micros(unix_milliseconds as i64 * 1000)
+        .expect("snowflake timestamps are always in range")
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -28,8 +70,14 @@ pub(crate) struct ReactionInfo<'a> {
     pub(crate) author_id: Id<UserMarker>,
     pub(crate) message_id: Id<MessageMarker>,
     pub(crate) channel_id: Id<ChannelMarker>,
+    /// If the reacted message is in a thread, the channel the thread is in.
+    pub(crate) parent_channel_id: Option<Id<ChannelMarker>>,
     pub(crate) guild_id: Id<GuildMarker>,
     pub(crate) reaction: ReactionType,
+    /// How many distinct users have reacted to the message with this emoji,
+    /// including this reaction. Only tracked for guilds that use the `count`
+    /// reaction rule; zero otherwise.
+    pub(crate) reaction_count: usize,
 }
 
 #[cfg(test)]
@@ -37,6 +85,7 @@ pub(crate) mod test {
     use twilight_model::{
         channel::message::Mention,
         channel::message::ReactionType,
+        channel::Attachment,
         id::{
             marker::{ChannelMarker, GuildMarker, MessageMarker, UserMarker},
             Id,
@@ -53,9 +102,9 @@ pub(crate) mod test {
     pub(crate) const CHANNEL_ID: Id<ChannelMarker> = Id::new(2);
     pub(crate) const USER_ID: Id<UserMarker> = Id::new(3);
     pub(crate) const GUILD_ID: Id<GuildMarker> = Id::new(4);
-    pub(crate) const GOOD_CONTENT: &'static str =
+    pub(crate) const GOOD_CONTENT: &str =
         "this is an okay message https://discord.gg/ discord.gg/roblox";
-    pub(crate) const BAD_CONTENT: &'static str =
+    pub(crate) const BAD_CONTENT: &str =
         "asdf bad message z̷̢͈͓̥̤͕̰̤̔͒̄̂̒͋̔̀̒͑̈̅̍̐a̶̡̘̬̯̩̣̪̤̹̖͓͉̿l̷̼̬͊͊̀́̽̑̕g̵̝̗͇͇̈́̄͌̈́͊̌̋͋̑̌̕͘͘ơ̵̢̰̱̟͑̀̂͗́̈́̀  https://example.com/ discord.gg/evilserver";
 
     pub(crate) fn mention() -> Mention {
@@ -73,15 +122,41 @@ pub(crate) mod test {
     pub(crate) fn message(content: &'static str) -> MessageInfo<'static> {
         MessageInfo {
             author_is_bot: false,
+            is_webhook: false,
             id: MESSAGE_ID,
             author_id: USER_ID,
             channel_id: CHANNEL_ID,
             guild_id: GUILD_ID,
             author_roles: &[],
-            content: content,
+            account_created_at: Timestamp::from_secs(0).unwrap(),
+            joined_at: Some(Timestamp::from_secs(0).unwrap()),
+            content,
             timestamp: Timestamp::from_secs(100).unwrap(),
             attachments: &[],
             stickers: &[],
+            embeds: &[],
+            guild_stickers: None,
+            guild_channels: None,
+            reference: None,
+            mentioned_users: &[],
+            previous_content: None,
+            last_message: None,
+            parent_channel_id: None,
+        }
+    }
+
+    pub(crate) fn attachment(filename: &str, content_type: Option<&str>) -> Attachment {
+        Attachment {
+            content_type: content_type.map(str::to_owned),
+            ephemeral: false,
+            filename: filename.to_owned(),
+            description: None,
+            height: None,
+            id: Id::new(1),
+            proxy_url: "doesn't_matter".to_owned(),
+            size: 1,
+            url: "doesn't_matter".to_owned(),
+            width: None,
         }
     }
 
@@ -102,6 +177,8 @@ pub(crate) mod test {
             reaction: ReactionType::Unicode {
                 name: rxn.to_string(),
             },
+            reaction_count: 1,
+            parent_channel_id: None,
         }
     }
 }
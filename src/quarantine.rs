@@ -0,0 +1,136 @@
+use std::path::{Path, PathBuf};
+
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use twilight_model::id::{
+    marker::{GuildMarker, RoleMarker, UserMarker},
+    Id,
+};
+
+const QUARANTINES_FILE_NAME: &str = "quarantines.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Quarantine {
+    guild_id: Id<GuildMarker>,
+    user_id: Id<UserMarker>,
+    /// The roles to give back to the member when they're unquarantined.
+    roles: Vec<Id<RoleMarker>>,
+}
+
+/// Keeps track of members quarantined by the `quarantine` action, along with
+/// the roles they had beforehand. Like warnings, quarantines are saved in the
+/// state directory if one is configured.
+#[derive(Debug)]
+pub(crate) struct QuarantineStore {
+    path: Option<PathBuf>,
+    quarantines: Mutex<Vec<Quarantine>>,
+}
+
+impl QuarantineStore {
+    pub(crate) fn in_memory() -> Self {
+        Self {
+            path: None,
+            quarantines: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Loads the quarantines saved in `state_dir`. A missing quarantines file
+    /// is treated as nobody being quarantined yet.
+    pub(crate) fn load(state_dir: &Path) -> Result<Self> {
+        let path = state_dir.join(QUARANTINES_FILE_NAME);
+        let quarantines = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Self {
+            path: Some(path),
+            quarantines: Mutex::new(quarantines),
+        })
+    }
+
+    /// Records that a member has been quarantined, along with the roles to
+    /// restore when they're unquarantined. If the member is already
+    /// quarantined, the roles they had before the first quarantine are kept.
+    pub(crate) async fn quarantine(
+        &self,
+        guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+        roles: Vec<Id<RoleMarker>>,
+    ) -> Result<()> {
+        let mut quarantines = self.quarantines.lock().await;
+        if quarantines
+            .iter()
+            .any(|q| q.guild_id == guild_id && q.user_id == user_id)
+        {
+            return Ok(());
+        }
+
+        quarantines.push(Quarantine {
+            guild_id,
+            user_id,
+            roles,
+        });
+
+        self.save(&quarantines).await
+    }
+
+    /// Removes a member's quarantine, returning the roles to give back to
+    /// them, or `None` if they weren't quarantined.
+    pub(crate) async fn release(
+        &self,
+        guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+    ) -> Result<Option<Vec<Id<RoleMarker>>>> {
+        let mut quarantines = self.quarantines.lock().await;
+        let index = match quarantines
+            .iter()
+            .position(|q| q.guild_id == guild_id && q.user_id == user_id)
+        {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+
+        let quarantine = quarantines.remove(index);
+        self.save(&quarantines).await?;
+        Ok(Some(quarantine.roles))
+    }
+
+    async fn save(&self, quarantines: &[Quarantine]) -> Result<()> {
+        if let Some(path) = &self.path {
+            crate::warnings::write_state_file(path, serde_json::to_vec(quarantines)?).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use twilight_model::id::Id;
+
+    use super::QuarantineStore;
+
+    #[tokio::test]
+    async fn keep_roles_from_first_quarantine() {
+        let store = QuarantineStore::in_memory();
+
+        store
+            .quarantine(Id::new(1), Id::new(2), vec![Id::new(3), Id::new(4)])
+            .await
+            .unwrap();
+        store
+            .quarantine(Id::new(1), Id::new(2), vec![Id::new(5)])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.release(Id::new(1), Id::new(2)).await.unwrap(),
+            Some(vec![Id::new(3), Id::new(4)])
+        );
+        assert_eq!(store.release(Id::new(1), Id::new(2)).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,271 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet, VecDeque},
+    convert::TryInto,
+    hash::{Hash, Hasher},
+};
+
+use twilight_model::id::{
+    marker::{ChannelMarker, GuildMarker, MessageMarker, UserMarker},
+    Id,
+};
+
+use crate::{config::RaidFilter, filter::normalize_for_similarity, model::MessageInfo};
+
+#[derive(Debug)]
+struct RaidRecord {
+    author_id: Id<UserMarker>,
+    channel_id: Id<ChannelMarker>,
+    message_id: Id<MessageMarker>,
+    sent_at: u64,
+    /// Whether this message has already been acted on as part of a raid.
+    actioned: bool,
+}
+
+/// A message that was posted as part of a raid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RaidMessage {
+    pub(crate) channel_id: Id<ChannelMarker>,
+    pub(crate) message_id: Id<MessageMarker>,
+}
+
+/// The outcome of recording a message that's part of a raid.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct RaidDetection {
+    /// How many different users have posted the message.
+    pub(crate) users: usize,
+    /// Copies of the message posted before this one that haven't been acted
+    /// on yet, if the raid filter is set to `delete_earlier`.
+    pub(crate) earlier_messages: Vec<RaidMessage>,
+}
+
+/// Recently posted messages in each guild, by a hash of their normalized
+/// content, so that the same message being posted by many different users can
+/// be spotted.
+#[derive(Debug, Default)]
+pub(crate) struct RaidIndex {
+    guilds: HashMap<Id<GuildMarker>, HashMap<u64, VecDeque<RaidRecord>>>,
+}
+
+fn content_hash(normalized_content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    normalized_content.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Removes the records that were sent more than `window_seconds` before
+/// `now`, in microseconds.
+fn clear_expired_records(records: &mut VecDeque<RaidRecord>, window_seconds: u64, now: u64) {
+    let window = window_seconds.saturating_mul(1_000_000);
+    while let Some(front) = records.front() {
+        if now.saturating_sub(front.sent_at) > window {
+            records.pop_front();
+        } else {
+            break;
+        }
+    }
+}
+
+impl RaidIndex {
+    /// Records a message, returning a detection if it's been posted by more
+    /// than the raid filter's `identical_messages` different users within its
+    /// window. `now` is in microseconds.
+    pub(crate) fn record(
+        &mut self,
+        message: &MessageInfo<'_>,
+        config: &RaidFilter,
+        now: u64,
+    ) -> Option<RaidDetection> {
+        let normalized_content = normalize_for_similarity(message.content);
+        if normalized_content.is_empty()
+            || normalized_content.chars().count() < config.min_characters
+        {
+            return None;
+        }
+
+        let records = self
+            .guilds
+            .entry(message.guild_id)
+            .or_default()
+            .entry(content_hash(&normalized_content))
+            .or_default();
+
+        clear_expired_records(records, config.window_seconds, now);
+        records.push_back(RaidRecord {
+            author_id: message.author_id,
+            channel_id: message.channel_id,
+            message_id: message.id,
+            sent_at: message.timestamp.as_micros().try_into().unwrap_or(0),
+            actioned: false,
+        });
+
+        let users = records
+            .iter()
+            .map(|record| record.author_id)
+            .collect::<HashSet<_>>()
+            .len();
+        if users <= usize::from(config.identical_messages) {
+            return None;
+        }
+
+        let mut earlier_messages = Vec::new();
+        let (current, earlier) = records.make_contiguous().split_last_mut().unwrap();
+        current.actioned = true;
+        if config.delete_earlier {
+            for record in earlier.iter_mut().filter(|record| !record.actioned) {
+                record.actioned = true;
+                earlier_messages.push(RaidMessage {
+                    channel_id: record.channel_id,
+                    message_id: record.message_id,
+                });
+            }
+        }
+
+        Some(RaidDetection {
+            users,
+            earlier_messages,
+        })
+    }
+
+    /// Removes records older than `window_seconds`, which should be the
+    /// longest raid window of any guild, and then removes any content and
+    /// guilds that are left without records. Returns how many kinds of content
+    /// were removed.
+    pub(crate) fn prune(&mut self, window_seconds: u64, now: u64) -> usize {
+        let mut removed = 0;
+        for contents in self.guilds.values_mut() {
+            let before = contents.len();
+            contents.retain(|_, records| {
+                clear_expired_records(records, window_seconds, now);
+                !records.is_empty()
+            });
+            removed += before - contents.len();
+        }
+
+        self.guilds.retain(|_, contents| !contents.is_empty());
+        removed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use twilight_model::id::Id;
+
+    use super::{RaidDetection, RaidIndex, RaidMessage};
+    use crate::{config::RaidFilter, model::test::message_at_time};
+
+    const RAID_CONTENT: &str = "join my server for free nitro";
+
+    fn config(delete_earlier: bool) -> RaidFilter {
+        RaidFilter {
+            identical_messages: 2,
+            window_seconds: 30,
+            min_characters: 10,
+            delete_earlier,
+            actions: None,
+        }
+    }
+
+    fn record(
+        index: &mut RaidIndex,
+        config: &RaidFilter,
+        content: &'static str,
+        author: u64,
+        sent_at: i64,
+    ) -> Option<RaidDetection> {
+        let mut message = message_at_time(content, sent_at);
+        message.author_id = Id::new(author);
+        message.id = Id::new(sent_at as u64);
+        index.record(&message, config, sent_at as u64 * 1_000_000)
+    }
+
+    #[test]
+    fn detect_identical_messages_from_different_users() {
+        let mut index = RaidIndex::default();
+        let config = config(false);
+
+        assert_eq!(record(&mut index, &config, RAID_CONTENT, 1, 1), None);
+        // The same user posting again doesn't make it a raid.
+        assert_eq!(record(&mut index, &config, RAID_CONTENT, 1, 2), None);
+        // Normalized content is compared, so this is the same message.
+        assert_eq!(
+            record(&mut index, &config, "JOIN my  server for free nitro", 2, 3),
+            None
+        );
+        assert_eq!(
+            record(&mut index, &config, RAID_CONTENT, 3, 4),
+            Some(RaidDetection {
+                users: 3,
+                earlier_messages: vec![],
+            })
+        );
+        assert_eq!(
+            record(&mut index, &config, "something else entirely", 4, 5),
+            None
+        );
+    }
+
+    #[test]
+    fn forget_messages_outside_window() {
+        let mut index = RaidIndex::default();
+        let config = config(false);
+
+        assert_eq!(record(&mut index, &config, RAID_CONTENT, 1, 1), None);
+        assert_eq!(record(&mut index, &config, RAID_CONTENT, 2, 20), None);
+        // The first message is more than 30 seconds old by now.
+        assert_eq!(record(&mut index, &config, RAID_CONTENT, 3, 40), None);
+        assert!(record(&mut index, &config, RAID_CONTENT, 4, 45).is_some());
+    }
+
+    #[test]
+    fn ignore_short_messages() {
+        let mut index = RaidIndex::default();
+        let config = config(false);
+
+        for author in 1..=5 {
+            assert_eq!(record(&mut index, &config, "hello!", author, 1), None);
+        }
+    }
+
+    #[test]
+    fn delete_earlier_messages_once() {
+        let mut index = RaidIndex::default();
+        let config = config(true);
+
+        record(&mut index, &config, RAID_CONTENT, 1, 1);
+        record(&mut index, &config, RAID_CONTENT, 2, 2);
+        let earlier_message = |sent_at: u64| RaidMessage {
+            channel_id: crate::model::test::CHANNEL_ID,
+            message_id: Id::new(sent_at),
+        };
+
+        assert_eq!(
+            record(&mut index, &config, RAID_CONTENT, 3, 3),
+            Some(RaidDetection {
+                users: 3,
+                earlier_messages: vec![earlier_message(1), earlier_message(2)],
+            })
+        );
+        assert_eq!(
+            record(&mut index, &config, RAID_CONTENT, 4, 4),
+            Some(RaidDetection {
+                users: 4,
+                earlier_messages: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn prune_expired_content() {
+        let mut index = RaidIndex::default();
+        let config = config(false);
+
+        record(&mut index, &config, RAID_CONTENT, 1, 1);
+        record(&mut index, &config, "something else entirely", 1, 50);
+
+        assert_eq!(index.prune(30, 60 * 1_000_000), 1);
+        assert_eq!(index.guilds[&crate::model::test::GUILD_ID].len(), 1);
+        assert_eq!(index.prune(30, 100 * 1_000_000), 1);
+        assert!(index.guilds.is_empty());
+    }
+}
@@ -1,9 +1,201 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use twilight_model::{
+    channel::message::ReactionType,
+    id::{
+        marker::{GuildMarker, MessageMarker, UserMarker},
+        Id,
+    },
+};
+
 use crate::{
-    action::ReactionAction,
-    config::{MessageFilterAction, ReactionFilter, Scoping},
+    action::{default_log_color, EscalationCount, ReactionAction, Tempban, WebhookPayload},
+    config::{
+        MessageFilterAction, ReactionFilter, ReactionFilterRule, ReactionSpamFilter, Scoping,
+    },
     model::ReactionInfo,
 };
 
+/// Tracks which users have reacted to each message with each emoji, for the
+/// `count` reaction rule. Only reactions added while Chrysanthemum is running
+/// are counted, and emoji nobody has reacted with for a while are forgotten.
+#[derive(Debug, Default)]
+pub(crate) struct ReactionCounts {
+    reactors: HashMap<(Id<MessageMarker>, String), Reactors>,
+}
+
+#[derive(Debug, Default)]
+struct Reactors {
+    users: HashSet<Id<UserMarker>>,
+    /// When the last reaction was added, in microseconds.
+    last_added: u64,
+}
+
+/// Identifies an emoji independently of the metadata that comes with it, which
+/// can differ between add and remove events.
+fn reaction_key(reaction: &ReactionType) -> String {
+    match reaction {
+        ReactionType::Custom { id, .. } => id.to_string(),
+        ReactionType::Unicode { name } => name.clone(),
+    }
+}
+
+impl ReactionCounts {
+    /// Records a reaction added at `now`, in microseconds, and returns how
+    /// many distinct users have now reacted to the message with that emoji.
+    pub(crate) fn add(
+        &mut self,
+        message_id: Id<MessageMarker>,
+        reaction: &ReactionType,
+        user_id: Id<UserMarker>,
+        now: u64,
+    ) -> usize {
+        let reactors = self
+            .reactors
+            .entry((message_id, reaction_key(reaction)))
+            .or_default();
+        reactors.users.insert(user_id);
+        reactors.last_added = now;
+        reactors.users.len()
+    }
+
+    pub(crate) fn remove(
+        &mut self,
+        message_id: Id<MessageMarker>,
+        reaction: &ReactionType,
+        user_id: Id<UserMarker>,
+    ) {
+        let key = (message_id, reaction_key(reaction));
+        if let Some(reactors) = self.reactors.get_mut(&key) {
+            reactors.users.remove(&user_id);
+            if reactors.users.is_empty() {
+                self.reactors.remove(&key);
+            }
+        }
+    }
+
+    pub(crate) fn remove_emoji(&mut self, message_id: Id<MessageMarker>, reaction: &ReactionType) {
+        self.reactors.remove(&(message_id, reaction_key(reaction)));
+    }
+
+    pub(crate) fn remove_all(&mut self, message_id: Id<MessageMarker>) {
+        self.reactors.retain(|(id, _), _| *id != message_id);
+    }
+
+    /// Forgets the reactors of each emoji that nobody has reacted with in the
+    /// last `max_age_seconds`, since remove events aren't sent for messages
+    /// that are deleted or that leave the cache. Returns how many emoji were
+    /// forgotten.
+    pub(crate) fn prune(&mut self, max_age_seconds: u64, now: u64) -> usize {
+        let max_age = max_age_seconds.saturating_mul(1_000_000);
+        let before = self.reactors.len();
+        self.reactors
+            .retain(|_, reactors| now.saturating_sub(reactors.last_added) <= max_age);
+
+        before - self.reactors.len()
+    }
+}
+
+/// When each user has recently added reactions in each guild, in
+/// microseconds, for reaction spam filtering.
+#[derive(Debug, Default)]
+pub(crate) struct ReactionSpamHistory {
+    users: HashMap<(Id<GuildMarker>, Id<UserMarker>), VecDeque<u64>>,
+}
+
+/// Removes the timestamps that are more than `interval_seconds` before `now`.
+fn clear_expired_reactions(reactions: &mut VecDeque<u64>, interval_seconds: u64, now: u64) {
+    let interval = interval_seconds.saturating_mul(1_000_000);
+    while let Some(&front) = reactions.front() {
+        if now.saturating_sub(front) > interval {
+            reactions.pop_front();
+        } else {
+            break;
+        }
+    }
+}
+
+impl ReactionSpamHistory {
+    /// Records a reaction added at `now`, in microseconds, and returns how
+    /// many reactions the user has added within the last `interval_seconds`,
+    /// including this one.
+    pub(crate) fn add(
+        &mut self,
+        guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+        interval_seconds: u64,
+        now: u64,
+    ) -> usize {
+        let reactions = self.users.entry((guild_id, user_id)).or_default();
+        clear_expired_reactions(reactions, interval_seconds, now);
+        reactions.push_back(now);
+        reactions.len()
+    }
+
+    /// Removes reactions older than `interval_seconds`, which should be the
+    /// longest reaction spam interval of any guild, and then removes any users
+    /// who are left without reactions. Returns how many users were removed.
+    pub(crate) fn prune(&mut self, interval_seconds: u64, now: u64) -> usize {
+        let before = self.users.len();
+        self.users.retain(|_, reactions| {
+            clear_expired_reactions(reactions, interval_seconds, now);
+            !reactions.is_empty()
+        });
+
+        before - self.users.len()
+    }
+}
+
+/// Records a reaction for reaction spam filtering, and checks whether the user
+/// has now added too many reactions. `now` is in microseconds.
+#[tracing::instrument(skip(config, default_scoping, default_actions, history))]
+pub(crate) fn check_reaction_spam(
+    config: &ReactionSpamFilter,
+    default_scoping: Option<&Scoping>,
+    default_actions: Option<&[MessageFilterAction]>,
+    reaction: &ReactionInfo,
+    history: &mut ReactionSpamHistory,
+    now: u64,
+) -> Result<(), ReactionFilterFailure> {
+    if let Some(scoping) = config.scoping.as_ref().or(default_scoping) {
+        if !scoping.is_included(
+            reaction.channel_id,
+            reaction.parent_channel_id,
+            reaction.author_roles,
+        ) {
+            return Ok(());
+        }
+    }
+
+    let count = history.add(reaction.guild_id, reaction.author_id, config.interval, now);
+    if count <= usize::from(config.reactions_per_interval) {
+        return Ok(());
+    }
+
+    let filter_name = "Reaction spam";
+    let reason = "added too many reactions";
+    let filter_actions = config.actions.as_deref().or(default_actions).unwrap_or(&[]);
+    let actions = filter_actions
+        .iter()
+        .map(|a| map_filter_action_to_action(a, filter_actions, reaction, filter_name, reason))
+        .collect();
+
+    Err(ReactionFilterFailure {
+        filter_name: filter_name.to_owned(),
+        actions,
+    })
+}
+
+/// Whether any of the filters need reaction counts to be tracked.
+pub(crate) fn uses_reaction_counts(filters: &[ReactionFilter]) -> bool {
+    filters.iter().any(|filter| {
+        filter
+            .rules
+            .iter()
+            .any(|rule| matches!(rule, ReactionFilterRule::Count { .. }))
+    })
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub(crate) struct ReactionFilterFailure {
     pub(crate) filter_name: String,
@@ -12,12 +204,24 @@ pub(crate) struct ReactionFilterFailure {
 
 fn map_filter_action_to_action(
     filter_action: &MessageFilterAction,
+    filter_actions: &[MessageFilterAction],
     reaction: &ReactionInfo,
     filter_name: &str,
     filter_reason: &str,
 ) -> ReactionAction {
+    let map_nested = |actions: &[MessageFilterAction]| {
+        actions
+            .iter()
+            .map(|action| {
+                map_filter_action_to_action(action, actions, reaction, filter_name, filter_reason)
+            })
+            .collect()
+    };
+
     match filter_action {
-        MessageFilterAction::Delete => ReactionAction::Delete {
+        // Reactions are always deleted immediately; there's nothing for the
+        // user to read before they go.
+        MessageFilterAction::Delete { .. } => ReactionAction::Delete {
             message_id: reaction.message_id,
             channel_id: reaction.channel_id,
             reaction: reaction.reaction.clone(),
@@ -36,9 +240,39 @@ fn map_filter_action_to_action(
                 requires_armed: *requires_armed,
             }
         }
+        MessageFilterAction::Reply {
+            content,
+            delete_after_seconds,
+            requires_armed,
+        } => {
+            let formatted_content = content.replace("$USER_ID", &reaction.author_id.to_string());
+            let formatted_content = formatted_content.replace("$FILTER_REASON", filter_reason);
+
+            ReactionAction::Reply {
+                channel_id: reaction.channel_id,
+                message_id: reaction.message_id,
+                content: formatted_content,
+                delete_after_seconds: *delete_after_seconds,
+                requires_armed: *requires_armed,
+            }
+        }
+        MessageFilterAction::SendDm {
+            content,
+            requires_armed,
+        } => {
+            let formatted_content = content.replace("$USER_ID", &reaction.author_id.to_string());
+            let formatted_content = formatted_content.replace("$FILTER_REASON", filter_reason);
+
+            ReactionAction::SendDm {
+                user_id: reaction.author_id,
+                content: formatted_content,
+                requires_armed: *requires_armed,
+            }
+        }
         MessageFilterAction::Ban {
             delete_message_seconds,
             reason,
+            duration_seconds,
         } => {
             let formatted_reason = reason.replace("$FILTER_REASON", filter_reason);
             let formatted_reason = formatted_reason.replace("$FILTER_REASON", filter_reason);
@@ -48,6 +282,10 @@ fn map_filter_action_to_action(
                 guild_id: reaction.guild_id,
                 delete_message_seconds: *delete_message_seconds,
                 reason: formatted_reason,
+                tempban: duration_seconds.map(|duration_seconds| Tempban {
+                    duration_seconds,
+                    filter_name: filter_name.to_owned(),
+                }),
             }
         }
         MessageFilterAction::Kick { reason } => {
@@ -60,6 +298,116 @@ fn map_filter_action_to_action(
                 reason: formatted_reason,
             }
         }
+        MessageFilterAction::AddRole { role_id, reason } => {
+            let formatted_reason = reason.replace("$USER_ID", &reaction.author_id.to_string());
+            let formatted_reason = formatted_reason.replace("$FILTER_REASON", filter_reason);
+
+            ReactionAction::AddRole {
+                user_id: reaction.author_id,
+                guild_id: reaction.guild_id,
+                role_id: *role_id,
+                reason: formatted_reason,
+            }
+        }
+        MessageFilterAction::RemoveRole { role_id, reason } => {
+            let formatted_reason = reason.replace("$USER_ID", &reaction.author_id.to_string());
+            let formatted_reason = formatted_reason.replace("$FILTER_REASON", filter_reason);
+
+            ReactionAction::RemoveRole {
+                user_id: reaction.author_id,
+                guild_id: reaction.guild_id,
+                role_id: *role_id,
+                reason: formatted_reason,
+            }
+        }
+        MessageFilterAction::EscalatingTimeout {
+            reason,
+            durations,
+            window_seconds,
+        } => {
+            let formatted_reason = reason.replace("$USER_ID", &reaction.author_id.to_string());
+            let formatted_reason = formatted_reason.replace("$FILTER_REASON", filter_reason);
+
+            ReactionAction::EscalatingTimeout {
+                user_id: reaction.author_id,
+                guild_id: reaction.guild_id,
+                reason: formatted_reason,
+                durations: durations.clone(),
+                window_seconds: *window_seconds,
+            }
+        }
+        MessageFilterAction::Webhook { url, secret } => ReactionAction::Webhook {
+            url: url.clone(),
+            secret: secret.clone(),
+            payload: WebhookPayload {
+                guild_id: reaction.guild_id,
+                channel_id: reaction.channel_id,
+                message_id: reaction.message_id,
+                author_id: reaction.author_id,
+                filter_name: filter_name.to_owned(),
+                reason: filter_reason.to_owned(),
+                context: "reaction add",
+                content_preview: None,
+                reaction: Some(match &reaction.reaction {
+                    ReactionType::Custom { id, .. } => id.to_string(),
+                    ReactionType::Unicode { name } => name.clone(),
+                }),
+            },
+        },
+        MessageFilterAction::Quarantine {
+            quarantine_role,
+            restore_on_unquarantine,
+        } => ReactionAction::Quarantine {
+            user_id: reaction.author_id,
+            guild_id: reaction.guild_id,
+            quarantine_role: *quarantine_role,
+            restore_on_unquarantine: *restore_on_unquarantine,
+            reason: filter_reason.to_owned(),
+        },
+        MessageFilterAction::SetSlowmode {
+            seconds,
+            revert_after,
+        } => ReactionAction::SetSlowmode {
+            channel_id: reaction.channel_id,
+            seconds: *seconds,
+            revert_after: *revert_after,
+        },
+        MessageFilterAction::Crosspost => ReactionAction::Crosspost {
+            channel_id: reaction.channel_id,
+            message_id: reaction.message_id,
+        },
+        MessageFilterAction::Warn { expiry_seconds } => ReactionAction::Warn {
+            user_id: reaction.author_id,
+            guild_id: reaction.guild_id,
+            expiry_seconds: *expiry_seconds,
+        },
+        MessageFilterAction::Template { .. } => {
+            unreachable!("action templates are expanded when configurations are loaded")
+        }
+        MessageFilterAction::Escalate { thresholds } => ReactionAction::Escalate {
+            user_id: reaction.author_id,
+            guild_id: reaction.guild_id,
+            count: EscalationCount::Warnings,
+            thresholds: thresholds
+                .iter()
+                .map(|threshold| (threshold.warnings, map_nested(&threshold.actions)))
+                .collect(),
+        },
+        MessageFilterAction::EscalateOffenses {
+            window_seconds,
+            tiers,
+        } => ReactionAction::Escalate {
+            user_id: reaction.author_id,
+            guild_id: reaction.guild_id,
+            count: EscalationCount::FilterOffenses {
+                filter_name: filter_name.to_owned(),
+                window_seconds: *window_seconds,
+            },
+            thresholds: tiers
+                .iter()
+                .map(|tier| (tier.offenses, map_nested(&tier.actions)))
+                .collect(),
+        },
         MessageFilterAction::Timeout { duration, reason } => {
             let formatted_reason = reason.replace("$FILTER_REASON", filter_reason);
             let formatted_reason = formatted_reason.replace("$FILTER_REASON", filter_reason);
@@ -71,7 +419,13 @@ fn map_filter_action_to_action(
                 duration: *duration,
             }
         }
-        MessageFilterAction::SendLog { channel_id } => ReactionAction::SendLog {
+        // There's nothing to review for a reaction, which is either removed or
+        // not, so just log it to the review channel.
+        MessageFilterAction::SendLog {
+            channel_id,
+            color,
+            footer,
+        } => ReactionAction::SendLog {
             to: *channel_id,
             filter_name: filter_name.to_string(),
             message: reaction.message_id,
@@ -79,10 +433,75 @@ fn map_filter_action_to_action(
             author: reaction.author_id,
             filter_reason: filter_reason.to_string(),
             reaction: reaction.reaction.clone(),
+            guild_id: reaction.guild_id,
+            removed: false,
+            color: color.unwrap_or_else(|| default_log_color(filter_actions)),
+            footer: footer.clone(),
+            dry_run: false,
+        },
+        MessageFilterAction::FlagForReview { channel_id } => ReactionAction::SendLog {
+            to: *channel_id,
+            filter_name: filter_name.to_string(),
+            message: reaction.message_id,
+            channel: reaction.channel_id,
+            author: reaction.author_id,
+            filter_reason: filter_reason.to_string(),
+            reaction: reaction.reaction.clone(),
+            guild_id: reaction.guild_id,
+            removed: false,
+            color: default_log_color(filter_actions),
+            footer: None,
+            dry_run: false,
         },
     }
 }
 
+/// Gets the logs to send when a user removes a reaction that `actions` would
+/// have deleted, so that moderators can see attempts to get a reaction past
+/// the filters by removing it quickly. The other actions aren't taken, since
+/// the reaction is already gone.
+pub(crate) fn removal_logs(actions: Vec<ReactionAction>) -> Vec<ReactionAction> {
+    if !actions
+        .iter()
+        .any(|action| matches!(action, ReactionAction::Delete { .. }))
+    {
+        return Vec::new();
+    }
+
+    actions
+        .into_iter()
+        .filter_map(|action| match action {
+            ReactionAction::SendLog {
+                to,
+                filter_name,
+                guild_id,
+                message,
+                channel,
+                filter_reason,
+                author,
+                reaction,
+                color,
+                footer,
+                ..
+            } => Some(ReactionAction::SendLog {
+                to,
+                filter_name,
+                guild_id,
+                message,
+                channel,
+                filter_reason,
+                author,
+                reaction,
+                removed: true,
+                color,
+                footer,
+                dry_run: false,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
 #[tracing::instrument(skip(filters, default_scoping, default_actions))]
 pub(crate) fn filter_reaction(
     filters: &[ReactionFilter],
@@ -92,19 +511,22 @@ pub(crate) fn filter_reaction(
 ) -> Result<(), ReactionFilterFailure> {
     for filter in filters {
         if let Some(scoping) = filter.scoping.as_ref().or(default_scoping) {
-            if !scoping.is_included(reaction.channel_id, reaction.author_roles) {
+            if !scoping.is_included(
+                reaction.channel_id,
+                reaction.parent_channel_id,
+                reaction.author_roles,
+            ) {
                 continue;
             }
         }
 
-        if let Err(reason) = filter.filter_reaction(&reaction.reaction) {
-            let actions = filter
-                .actions
-                .as_deref()
-                .or(default_actions)
-                .unwrap_or(&[])
+        if let Err(reason) = filter.filter_reaction(reaction) {
+            let filter_actions = filter.actions.as_deref().or(default_actions).unwrap_or(&[]);
+            let actions = filter_actions
                 .iter()
-                .map(|a| map_filter_action_to_action(a, reaction, &filter.name, &reason))
+                .map(|a| {
+                    map_filter_action_to_action(a, filter_actions, reaction, &filter.name, &reason)
+                })
                 .collect();
 
             return Err(ReactionFilterFailure {
@@ -120,12 +542,15 @@ pub(crate) fn filter_reaction(
 #[cfg(test)]
 mod test {
     use pretty_assertions::assert_eq;
-    use twilight_model::id::Id;
+    use twilight_model::{channel::message::ReactionType, id::Id};
 
     use crate::{
         action::ReactionAction,
-        config::{FilterMode, MessageFilterAction, ReactionFilter, ReactionFilterRule, Scoping},
-        reaction::ReactionFilterFailure,
+        config::{
+            FilterMode, MessageFilterAction, ReactionFilter, ReactionFilterRule,
+            ReactionSpamFilter, Scoping,
+        },
+        reaction::{ReactionCounts, ReactionFilterFailure, ReactionSpamHistory},
     };
 
     #[test]
@@ -138,10 +563,13 @@ mod test {
             }],
             scoping: None,
             actions: Some(vec![
-                MessageFilterAction::Delete,
+                MessageFilterAction::Delete {
+                    delay_seconds: None,
+                },
                 MessageFilterAction::Ban {
                     delete_message_seconds: 0,
                     reason: "$FILTER_REASON".to_string(),
+                    duration_seconds: None,
                 },
                 MessageFilterAction::Kick {
                     reason: "$FILTER_REASON".to_string(),
@@ -150,8 +578,14 @@ mod test {
                     duration: 60_000,
                     reason: "$FILTER_REASON".to_string(),
                 },
+                MessageFilterAction::AddRole {
+                    role_id: Id::new(5),
+                    reason: "$USER_ID $FILTER_REASON".to_string(),
+                },
                 MessageFilterAction::SendLog {
                     channel_id: Id::new(3),
+                    color: None,
+                    footer: None,
                 },
                 MessageFilterAction::SendMessage {
                     channel_id: Id::new(3),
@@ -178,6 +612,7 @@ mod test {
                         guild_id: crate::model::test::GUILD_ID,
                         delete_message_seconds: 0,
                         reason: "reacted with denied emoji `🍆`".to_string(),
+                        tempban: None,
                     },
                     ReactionAction::Kick {
                         user_id: crate::model::test::USER_ID,
@@ -190,6 +625,12 @@ mod test {
                         reason: "reacted with denied emoji `🍆`".to_string(),
                         duration: 60_000,
                     },
+                    ReactionAction::AddRole {
+                        user_id: crate::model::test::USER_ID,
+                        guild_id: crate::model::test::GUILD_ID,
+                        role_id: Id::new(5),
+                        reason: "3 reacted with denied emoji `🍆`".to_string(),
+                    },
                     ReactionAction::SendLog {
                         to: Id::new(3),
                         filter_name: "first".to_string(),
@@ -198,6 +639,11 @@ mod test {
                         filter_reason: "reacted with denied emoji `🍆`".to_string(),
                         author: crate::model::test::USER_ID,
                         reaction: rxn.reaction.clone(),
+                        guild_id: crate::model::test::GUILD_ID,
+                        removed: false,
+                        color: crate::action::LOG_COLOR_BAN,
+                        footer: None,
+                        dry_run: false,
                     },
                     ReactionAction::SendMessage {
                         to: Id::new(3),
@@ -209,6 +655,74 @@ mod test {
         );
     }
 
+    #[test]
+    fn log_removed_reactions_that_would_be_deleted() {
+        let log = |removed| ReactionAction::SendLog {
+            to: Id::new(3),
+            filter_name: "first".to_string(),
+            message: crate::model::test::MESSAGE_ID,
+            channel: crate::model::test::CHANNEL_ID,
+            filter_reason: "reacted with denied emoji `🍆`".to_string(),
+            author: crate::model::test::USER_ID,
+            reaction: crate::model::test::default_reaction("🍆").reaction,
+            guild_id: crate::model::test::GUILD_ID,
+            removed,
+            color: crate::action::LOG_COLOR_LOG,
+            footer: None,
+            dry_run: false,
+        };
+        let kick = || ReactionAction::Kick {
+            user_id: crate::model::test::USER_ID,
+            guild_id: crate::model::test::GUILD_ID,
+            reason: "reacted with denied emoji `🍆`".to_string(),
+        };
+
+        assert_eq!(
+            super::removal_logs(vec![
+                ReactionAction::Delete {
+                    message_id: crate::model::test::MESSAGE_ID,
+                    channel_id: crate::model::test::CHANNEL_ID,
+                    reaction: crate::model::test::default_reaction("🍆").reaction,
+                },
+                kick(),
+                log(false),
+            ]),
+            vec![log(true)]
+        );
+        // Reactions that wouldn't have been deleted weren't removed to evade
+        // anything.
+        assert_eq!(super::removal_logs(vec![kick(), log(false)]), vec![]);
+    }
+
+    #[test]
+    fn only_log_in_dry_runs() {
+        let log = |dry_run| ReactionAction::SendLog {
+            to: Id::new(3),
+            filter_name: "first".to_string(),
+            message: crate::model::test::MESSAGE_ID,
+            channel: crate::model::test::CHANNEL_ID,
+            filter_reason: "reacted with denied emoji `🍆`".to_string(),
+            author: crate::model::test::USER_ID,
+            reaction: crate::model::test::default_reaction("🍆").reaction,
+            guild_id: crate::model::test::GUILD_ID,
+            removed: false,
+            color: crate::action::LOG_COLOR_LOG,
+            footer: None,
+            dry_run,
+        };
+
+        assert_eq!(log(false).into_dry_run(), Some(log(true)));
+        assert_eq!(
+            ReactionAction::Kick {
+                user_id: crate::model::test::USER_ID,
+                guild_id: crate::model::test::GUILD_ID,
+                reason: "reacted with denied emoji `🍆`".to_string(),
+            }
+            .into_dry_run(),
+            None
+        );
+    }
+
     #[test]
     fn use_default_scoping_if_no_scoping() {
         let filters = vec![ReactionFilter {
@@ -218,7 +732,9 @@ mod test {
                 emoji: vec!["🍆".to_string()],
             }],
             scoping: None,
-            actions: Some(vec![MessageFilterAction::Delete]),
+            actions: Some(vec![MessageFilterAction::Delete {
+                delay_seconds: None,
+            }]),
         }];
 
         let default_scoping = Scoping {
@@ -243,7 +759,9 @@ mod test {
                 include_channels: Some(vec![crate::model::test::CHANNEL_ID]),
                 ..Default::default()
             }),
-            actions: Some(vec![MessageFilterAction::Delete]),
+            actions: Some(vec![MessageFilterAction::Delete {
+                delay_seconds: None,
+            }]),
         }];
 
         let default_scoping = Scoping {
@@ -278,7 +796,9 @@ mod test {
             actions: None,
         }];
 
-        let default_actions = vec![MessageFilterAction::Delete];
+        let default_actions = vec![MessageFilterAction::Delete {
+            delay_seconds: None,
+        }];
 
         let rxn = crate::model::test::default_reaction("🍆");
         let result = super::filter_reaction(&filters, None, Some(&default_actions), &rxn);
@@ -304,11 +824,15 @@ mod test {
                 emoji: vec!["🍆".to_string()],
             }],
             scoping: None,
-            actions: Some(vec![MessageFilterAction::Delete]),
+            actions: Some(vec![MessageFilterAction::Delete {
+                delay_seconds: None,
+            }]),
         }];
 
         let default_actions = vec![MessageFilterAction::SendLog {
             channel_id: Id::new(2),
+            color: None,
+            footer: None,
         }];
 
         let rxn = crate::model::test::default_reaction("🍆");
@@ -336,7 +860,9 @@ mod test {
                     emoji: vec!["🍆".to_string()],
                 }],
                 scoping: None,
-                actions: Some(vec![MessageFilterAction::Delete]),
+                actions: Some(vec![MessageFilterAction::Delete {
+                    delay_seconds: None,
+                }]),
             },
             ReactionFilter {
                 name: "second".to_string(),
@@ -345,7 +871,9 @@ mod test {
                     emoji: vec!["🍆".to_string(), "💜".to_string()],
                 }],
                 scoping: None,
-                actions: Some(vec![MessageFilterAction::Delete]),
+                actions: Some(vec![MessageFilterAction::Delete {
+                    delay_seconds: None,
+                }]),
             },
         ];
 
@@ -417,4 +945,183 @@ mod test {
         let result = super::filter_reaction(&filters, None, None, &rxn);
         assert_eq!(result, Ok(()));
     }
+
+    #[test]
+    fn filter_reaction_count() {
+        let filters = vec![ReactionFilter {
+            name: "first".to_string(),
+            rules: vec![ReactionFilterRule::Count { max: 2 }],
+            scoping: None,
+            actions: None,
+        }];
+
+        let mut rxn = crate::model::test::default_reaction("🍆");
+        rxn.reaction_count = 2;
+        let result = super::filter_reaction(&filters, None, None, &rxn);
+        assert_eq!(result, Ok(()));
+
+        rxn.reaction_count = 3;
+        let result = super::filter_reaction(&filters, None, None, &rxn);
+        assert_eq!(
+            result,
+            Err(ReactionFilterFailure {
+                filter_name: "first".to_string(),
+                actions: vec![]
+            })
+        );
+    }
+
+    #[test]
+    fn filter_animated() {
+        let filters = vec![ReactionFilter {
+            name: "first".to_string(),
+            rules: vec![ReactionFilterRule::Animated { allow: false }],
+            scoping: None,
+            actions: None,
+        }];
+
+        let mut rxn = crate::model::test::default_reaction("🍆");
+        let result = super::filter_reaction(&filters, None, None, &rxn);
+        assert_eq!(result, Ok(()));
+
+        rxn.reaction = ReactionType::Custom {
+            animated: false,
+            id: Id::new(1),
+            name: Some("still".to_owned()),
+        };
+        let result = super::filter_reaction(&filters, None, None, &rxn);
+        assert_eq!(result, Ok(()));
+
+        rxn.reaction = ReactionType::Custom {
+            animated: true,
+            id: Id::new(1),
+            name: Some("moving".to_owned()),
+        };
+        let result = super::filter_reaction(&filters, None, None, &rxn);
+        assert_eq!(
+            result,
+            Err(ReactionFilterFailure {
+                filter_name: "first".to_string(),
+                actions: vec![]
+            })
+        );
+
+        let filters = vec![ReactionFilter {
+            name: "first".to_string(),
+            rules: vec![ReactionFilterRule::Animated { allow: true }],
+            scoping: None,
+            actions: None,
+        }];
+        let result = super::filter_reaction(&filters, None, None, &rxn);
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn count_distinct_reactors() {
+        let rxn = crate::model::test::default_reaction("🍆").reaction;
+        let other_rxn = crate::model::test::default_reaction("💜").reaction;
+        let message_id = crate::model::test::MESSAGE_ID;
+        let mut counts = ReactionCounts::default();
+
+        assert_eq!(counts.add(message_id, &rxn, Id::new(1), 0), 1);
+        assert_eq!(counts.add(message_id, &rxn, Id::new(2), 0), 2);
+        // Reacting twice doesn't count twice.
+        assert_eq!(counts.add(message_id, &rxn, Id::new(2), 0), 2);
+        assert_eq!(counts.add(message_id, &other_rxn, Id::new(1), 0), 1);
+
+        counts.remove(message_id, &rxn, Id::new(1));
+        assert_eq!(counts.add(message_id, &rxn, Id::new(3), 0), 2);
+
+        counts.remove_emoji(message_id, &rxn);
+        assert_eq!(counts.add(message_id, &rxn, Id::new(1), 0), 1);
+
+        counts.remove_all(message_id);
+        assert_eq!(counts.add(message_id, &other_rxn, Id::new(2), 0), 1);
+    }
+
+    #[test]
+    fn prune_stale_reaction_counts() {
+        let rxn = crate::model::test::default_reaction("🍆").reaction;
+        let other_rxn = crate::model::test::default_reaction("💜").reaction;
+        let message_id = crate::model::test::MESSAGE_ID;
+        let mut counts = ReactionCounts::default();
+
+        counts.add(message_id, &rxn, Id::new(1), 1_000_000);
+        counts.add(message_id, &other_rxn, Id::new(1), 1_000_000);
+        // A later reaction keeps the whole emoji around.
+        counts.add(message_id, &rxn, Id::new(2), 5_000_000);
+
+        assert_eq!(counts.prune(5, 7_000_000), 1);
+        assert_eq!(counts.add(message_id, &rxn, Id::new(3), 7_000_000), 3);
+        assert_eq!(counts.add(message_id, &other_rxn, Id::new(2), 7_000_000), 1);
+    }
+
+    #[test]
+    fn filter_reaction_spam() {
+        let config = ReactionSpamFilter {
+            reactions_per_interval: 2,
+            interval: 10,
+            actions: Some(vec![MessageFilterAction::Delete {
+                delay_seconds: None,
+            }]),
+            scoping: None,
+        };
+        let rxn = crate::model::test::default_reaction("🍆");
+        let mut history = ReactionSpamHistory::default();
+        let check = |history: &mut ReactionSpamHistory, seconds: u64| {
+            super::check_reaction_spam(&config, None, None, &rxn, history, seconds * 1_000_000)
+        };
+
+        assert_eq!(check(&mut history, 1), Ok(()));
+        assert_eq!(check(&mut history, 2), Ok(()));
+        assert_eq!(
+            check(&mut history, 3),
+            Err(ReactionFilterFailure {
+                filter_name: "Reaction spam".to_string(),
+                actions: vec![ReactionAction::Delete {
+                    channel_id: crate::model::test::CHANNEL_ID,
+                    message_id: crate::model::test::MESSAGE_ID,
+                    reaction: rxn.reaction.clone(),
+                }],
+            })
+        );
+
+        // The first two reactions are more than 10 seconds old by now.
+        assert_eq!(check(&mut history, 14), Ok(()));
+
+        // Reactions in excluded channels aren't counted at all.
+        let scoped_config = ReactionSpamFilter {
+            reactions_per_interval: 1,
+            scoping: Some(Scoping {
+                exclude_channels: Some(vec![crate::model::test::CHANNEL_ID]),
+                ..Default::default()
+            }),
+            ..config
+        };
+        for seconds in 15..20 {
+            let result = super::check_reaction_spam(
+                &scoped_config,
+                None,
+                None,
+                &rxn,
+                &mut history,
+                seconds * 1_000_000,
+            );
+            assert_eq!(result, Ok(()));
+        }
+    }
+
+    #[test]
+    fn prune_reaction_spam_history() {
+        let mut history = ReactionSpamHistory::default();
+        let guild_id = crate::model::test::GUILD_ID;
+
+        history.add(guild_id, Id::new(1), 10, 1_000_000);
+        history.add(guild_id, Id::new(2), 10, 8_000_000);
+        assert_eq!(history.prune(10, 15_000_000), 1);
+        assert_eq!(history.prune(10, 15_000_000), 0);
+        assert_eq!(history.prune(10, 30_000_000), 1);
+        // Pruned reactions no longer count.
+        assert_eq!(history.add(guild_id, Id::new(2), 10, 30_000_000), 1);
+    }
 }
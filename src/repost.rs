@@ -0,0 +1,139 @@
+use std::{collections::HashMap, convert::TryInto};
+
+use twilight_model::id::{
+    marker::{ChannelMarker, MessageMarker, UserMarker},
+    Id,
+};
+
+use crate::{filter::normalize_for_similarity, model::MessageInfo};
+
+/// A message a user sent in a channel, as remembered for `no_repost` rules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LastMessage {
+    pub(crate) id: Id<MessageMarker>,
+    /// The message's content, normalized the same way as for duplicate spam
+    /// detection.
+    pub(crate) content: String,
+    /// When the message was sent, in microseconds.
+    pub(crate) sent_at: u64,
+}
+
+impl LastMessage {
+    fn from_message(message: &MessageInfo<'_>) -> Self {
+        Self {
+            id: message.id,
+            content: normalize_for_similarity(message.content),
+            sent_at: message.timestamp.as_micros().try_into().unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ChannelAuthorMessages {
+    /// The message sent before `latest`, if it's still remembered.
+    previous: Option<LastMessage>,
+    latest: LastMessage,
+}
+
+/// The last couple of messages each user sent in each channel, so that users
+/// repeating themselves can be spotted without the full spam filter.
+#[derive(Debug, Default)]
+pub(crate) struct RepostHistory {
+    messages: HashMap<(Id<ChannelMarker>, Id<UserMarker>), ChannelAuthorMessages>,
+}
+
+impl RepostHistory {
+    /// Records a message, returning the message its author sent in the same
+    /// channel before it, if any. Edits replace the message's recorded
+    /// content without forgetting the message before it.
+    pub(crate) fn record(&mut self, message: &MessageInfo<'_>) -> Option<LastMessage> {
+        let current = LastMessage::from_message(message);
+        match self
+            .messages
+            .get_mut(&(message.channel_id, message.author_id))
+        {
+            Some(messages) if messages.latest.id == message.id => {
+                messages.latest = current;
+                messages.previous.clone()
+            }
+            Some(messages) => {
+                let latest = std::mem::replace(&mut messages.latest, current);
+                messages.previous = Some(latest.clone());
+                Some(latest)
+            }
+            None => {
+                self.messages.insert(
+                    (message.channel_id, message.author_id),
+                    ChannelAuthorMessages {
+                        previous: None,
+                        latest: current,
+                    },
+                );
+                None
+            }
+        }
+    }
+
+    /// Forgets the users and channels whose latest message is more than
+    /// `window_seconds` before `now`, in microseconds. `window_seconds` should
+    /// be the longest `no_repost` window of any guild. Returns how many were
+    /// removed.
+    pub(crate) fn prune(&mut self, window_seconds: u64, now: u64) -> usize {
+        let window = window_seconds.saturating_mul(1_000_000);
+        let before = self.messages.len();
+        self.messages
+            .retain(|_, messages| now.saturating_sub(messages.latest.sent_at) <= window);
+
+        before - self.messages.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use twilight_model::id::Id;
+
+    use super::RepostHistory;
+    use crate::model::test::message_at_time;
+
+    #[test]
+    fn remember_previous_message() {
+        let mut history = RepostHistory::default();
+
+        let mut first = message_at_time("Hello there", 1);
+        first.id = Id::new(1);
+        assert_eq!(history.record(&first), None);
+
+        let mut second = message_at_time("hello  there", 2);
+        second.id = Id::new(2);
+        let previous = history.record(&second).unwrap();
+        assert_eq!(previous.id, Id::new(1));
+        assert_eq!(previous.content, "hello there");
+        assert_eq!(previous.sent_at, 1_000_000);
+
+        // Editing the second message still compares it to the first.
+        let mut edited = message_at_time("something else", 2);
+        edited.id = Id::new(2);
+        assert_eq!(history.record(&edited).unwrap().id, Id::new(1));
+
+        // Other users and channels are remembered separately.
+        let mut other_user = message_at_time("Hello there", 3);
+        other_user.author_id = Id::new(100);
+        assert_eq!(history.record(&other_user), None);
+    }
+
+    #[test]
+    fn prune_quiet_users() {
+        let mut history = RepostHistory::default();
+
+        history.record(&message_at_time("first", 1));
+        let mut other_user = message_at_time("second", 8);
+        other_user.author_id = Id::new(100);
+        history.record(&other_user);
+
+        assert_eq!(history.prune(10, 15_000_000), 1);
+        assert_eq!(history.prune(10, 15_000_000), 0);
+        assert_eq!(history.prune(10, 30_000_000), 1);
+        assert!(history.messages.is_empty());
+    }
+}
@@ -0,0 +1,198 @@
+use std::path::{Path, PathBuf};
+
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use twilight_model::id::{
+    marker::{GuildMarker, UserMarker},
+    Id,
+};
+
+const TEMPBANS_FILE_NAME: &str = "tempbans.json";
+
+/// A ban that should be lifted at some point.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct ScheduledUnban {
+    pub(crate) guild_id: Id<GuildMarker>,
+    pub(crate) user_id: Id<UserMarker>,
+    /// The filter that issued the ban, for the unban's audit log reason.
+    pub(crate) filter_name: String,
+    /// When to lift the ban, as a Unix timestamp in seconds.
+    pub(crate) unban_at: i64,
+}
+
+/// Gets when a temporary ban issued at `now` should be lifted.
+pub(crate) fn unban_at(now: i64, duration_seconds: u64) -> i64 {
+    now.saturating_add(duration_seconds.min(i64::MAX as u64) as i64)
+}
+
+/// Keeps track of the bans issued by `ban` actions with a duration, so that
+/// they can be lifted later. Like warnings, these are saved in the state
+/// directory if one is configured, so that bans are still lifted after a
+/// restart.
+#[derive(Debug)]
+pub(crate) struct TempbanStore {
+    path: Option<PathBuf>,
+    unbans: Mutex<Vec<ScheduledUnban>>,
+}
+
+impl TempbanStore {
+    pub(crate) fn in_memory() -> Self {
+        Self {
+            path: None,
+            unbans: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Loads the unbans saved in `state_dir`. A missing tempbans file is
+    /// treated as there being no temporary bans yet.
+    pub(crate) fn load(state_dir: &Path) -> Result<Self> {
+        let path = state_dir.join(TEMPBANS_FILE_NAME);
+        let unbans = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Self {
+            path: Some(path),
+            unbans: Mutex::new(unbans),
+        })
+    }
+
+    /// Schedules a ban to be lifted. If the user already has an unban
+    /// scheduled in the guild, it's replaced, so the latest ban's duration
+    /// wins.
+    pub(crate) async fn schedule(&self, unban: ScheduledUnban) -> Result<()> {
+        let mut unbans = self.unbans.lock().await;
+        unbans.retain(|scheduled| {
+            scheduled.guild_id != unban.guild_id || scheduled.user_id != unban.user_id
+        });
+        unbans.push(unban);
+
+        self.save(&unbans).await
+    }
+
+    /// Gets every unban that's due at `now`. They stay scheduled until they're
+    /// [removed](Self::remove), so that bans that couldn't be lifted are tried
+    /// again.
+    pub(crate) async fn due(&self, now: i64) -> Vec<ScheduledUnban> {
+        self.unbans
+            .lock()
+            .await
+            .iter()
+            .filter(|unban| unban.unban_at <= now)
+            .cloned()
+            .collect()
+    }
+
+    /// Removes an unban once the ban has been lifted. If the user was banned
+    /// again in the meantime, the newer unban is kept.
+    pub(crate) async fn remove(&self, unban: &ScheduledUnban) -> Result<()> {
+        let mut unbans = self.unbans.lock().await;
+        let before = unbans.len();
+        unbans.retain(|scheduled| scheduled != unban);
+
+        if unbans.len() != before {
+            self.save(&unbans).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Cancels the user's scheduled unban in the guild, if they have one, for
+    /// when they're banned permanently.
+    pub(crate) async fn cancel(
+        &self,
+        guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+    ) -> Result<()> {
+        let mut unbans = self.unbans.lock().await;
+        let before = unbans.len();
+        unbans.retain(|scheduled| scheduled.guild_id != guild_id || scheduled.user_id != user_id);
+
+        if unbans.len() != before {
+            self.save(&unbans).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn save(&self, unbans: &[ScheduledUnban]) -> Result<()> {
+        if let Some(path) = &self.path {
+            crate::warnings::write_state_file(path, serde_json::to_vec(unbans)?).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use twilight_model::id::Id;
+
+    use super::{unban_at, ScheduledUnban, TempbanStore};
+
+    fn unban(user_id: u64, unban_at: i64) -> ScheduledUnban {
+        ScheduledUnban {
+            guild_id: Id::new(1),
+            user_id: Id::new(user_id),
+            filter_name: "first".to_owned(),
+            unban_at,
+        }
+    }
+
+    #[test]
+    fn compute_unban_time() {
+        assert_eq!(unban_at(100, 60), 160);
+        assert_eq!(unban_at(100, u64::MAX), i64::MAX);
+    }
+
+    #[test]
+    fn schedule_serialization() {
+        let json = serde_json::to_string(&[unban(2, 160)]).unwrap();
+        assert_eq!(
+            json,
+            r#"[{"guild_id":"1","user_id":"2","filter_name":"first","unban_at":160}]"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Vec<ScheduledUnban>>(&json).unwrap(),
+            vec![unban(2, 160)]
+        );
+    }
+
+    #[tokio::test]
+    async fn keep_unbans_until_removed() {
+        let store = TempbanStore::in_memory();
+        store.schedule(unban(2, 100)).await.unwrap();
+        store.schedule(unban(3, 200)).await.unwrap();
+        // Rescheduling replaces the earlier unban.
+        store.schedule(unban(2, 150)).await.unwrap();
+
+        assert_eq!(store.due(99).await, vec![]);
+        assert_eq!(store.due(150).await, vec![unban(2, 150)]);
+        // Unbans that weren't lifted are still due next time.
+        assert_eq!(store.due(150).await, vec![unban(2, 150)]);
+
+        store.remove(&unban(2, 150)).await.unwrap();
+        assert_eq!(store.due(300).await, vec![unban(3, 200)]);
+
+        // An unban that was rescheduled while the ban was being lifted isn't
+        // removed.
+        let due = store.due(300).await;
+        store.schedule(unban(3, 400)).await.unwrap();
+        store.remove(&due[0]).await.unwrap();
+        assert_eq!(store.due(400).await, vec![unban(3, 400)]);
+    }
+
+    #[tokio::test]
+    async fn cancel_unban_for_permanent_ban() {
+        let store = TempbanStore::in_memory();
+        store.schedule(unban(2, 100)).await.unwrap();
+        store.schedule(unban(3, 100)).await.unwrap();
+
+        store.cancel(Id::new(1), Id::new(2)).await.unwrap();
+        assert_eq!(store.due(100).await, vec![unban(3, 100)]);
+    }
+}
@@ -0,0 +1,235 @@
+use std::fmt;
+
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde_ignored::Path;
+
+/// A key in a configuration file that doesn't match any field, and so would be
+/// silently ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct UnknownKey {
+    /// Where the key is, like `messages[0].scopings`.
+    pub(crate) path: String,
+    /// The field the key is closest to, if it's close enough to be a typo.
+    pub(crate) suggestion: Option<&'static str>,
+}
+
+impl fmt::Display for UnknownKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown key `{}`", self.path)?;
+        if let Some(suggestion) = self.suggestion {
+            write!(f, "; did you mean `{}`?", suggestion)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Deserializes a value, also returning the keys that were ignored because
+/// they don't match any field of the struct they're in. Each key is suggested
+/// the closest of `fields` it could be a typo of.
+///
+/// Internally tagged and untagged enums buffer their contents before picking a
+/// variant, so keys ignored inside them can't be seen; those enums should use
+/// `deny_unknown_fields` instead.
+pub(crate) fn deserialize<'de, D, T>(
+    de: D,
+    fields: &[&'static str],
+) -> Result<(T, Vec<UnknownKey>), D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    let mut unknown = Vec::new();
+    let value = serde_ignored::deserialize(de, |path| {
+        let suggestion = match &path {
+            Path::Map { key, .. } => suggest(key, fields),
+            _ => None,
+        };
+
+        let mut rendered = String::new();
+        render_path(&path, &mut rendered);
+        unknown.push(UnknownKey {
+            path: rendered,
+            suggestion,
+        });
+    })?;
+
+    Ok((value, unknown))
+}
+
+/// Writes `path` the way it would be written in code, like `messages[0].name`.
+fn render_path(path: &Path, rendered: &mut String) {
+    match path {
+        Path::Root => {}
+        Path::Seq { parent, index } => {
+            render_path(parent, rendered);
+            rendered.push_str(&format!("[{}]", index));
+        }
+        Path::Map { parent, key } => {
+            render_path(parent, rendered);
+            if !rendered.is_empty() {
+                rendered.push('.');
+            }
+            rendered.push_str(key);
+        }
+        Path::Some { parent }
+        | Path::NewtypeStruct { parent }
+        | Path::NewtypeVariant { parent } => render_path(parent, rendered),
+    }
+}
+
+/// Gets the names of the fields of a struct that derives `Deserialize`, or
+/// nothing if `T` isn't one.
+pub(crate) fn struct_fields<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    match T::deserialize(FieldsDeserializer) {
+        Err(StructFields(fields)) => fields,
+        Ok(_) => &[],
+    }
+}
+
+/// Fails to deserialize anything, but reports the fields it was asked for.
+struct FieldsDeserializer;
+
+#[derive(Debug)]
+struct StructFields(&'static [&'static str]);
+
+impl fmt::Display for StructFields {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "struct with fields {:?}", self.0)
+    }
+}
+
+impl std::error::Error for StructFields {}
+
+impl de::Error for StructFields {
+    fn custom<T: fmt::Display>(_: T) -> Self {
+        StructFields(&[])
+    }
+}
+
+impl<'de> Deserializer<'de> for FieldsDeserializer {
+    type Error = StructFields;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, StructFields> {
+        Err(StructFields(&[]))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        fields: &'static [&'static str],
+        _: V,
+    ) -> Result<V::Value, StructFields> {
+        Err(StructFields(fields))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+/// Finds the field closest to `key`, if any is close enough that `key` is
+/// probably a typo of it.
+fn suggest(key: &str, fields: &[&'static str]) -> Option<&'static str> {
+    let max_distance = std::cmp::max(2, key.chars().count() / 3);
+    fields
+        .iter()
+        .map(|field| (edit_distance(key, field), *field))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, field)| field)
+}
+
+/// The Levenshtein distance between two strings, in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use serde::Deserialize;
+
+    use super::{edit_distance, struct_fields, suggest, UnknownKey};
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Filter {
+        name: String,
+        #[serde(default)]
+        scoping: Option<Scoping>,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Scoping {
+        include_channels: Vec<u64>,
+    }
+
+    #[test]
+    fn find_unknown_keys() {
+        let yaml = r#"
+filters:
+  - name: first
+    scopings:
+      include_channels: [1]
+  - name: second
+    scoping:
+      include_channels: [2]
+      channels: [3]
+    whatever: true
+"#;
+        let fields = [struct_fields::<Filter>(), struct_fields::<Scoping>()].concat();
+        let (filters, unknown): (std::collections::HashMap<String, Vec<Filter>>, _) =
+            super::deserialize(serde_yaml::Deserializer::from_str(yaml), &fields).unwrap();
+
+        assert_eq!(fields, vec!["name", "scoping", "include_channels"]);
+        assert!(struct_fields::<Vec<Filter>>().is_empty());
+        assert_eq!(filters["filters"][0].scoping, None);
+        assert_eq!(
+            unknown,
+            vec![
+                UnknownKey {
+                    path: "filters[0].scopings".to_owned(),
+                    suggestion: Some("scoping"),
+                },
+                UnknownKey {
+                    path: "filters[1].scoping.channels".to_owned(),
+                    suggestion: None,
+                },
+                UnknownKey {
+                    path: "filters[1].whatever".to_owned(),
+                    suggestion: None,
+                },
+            ]
+        );
+        assert_eq!(
+            unknown[0].to_string(),
+            "unknown key `filters[0].scopings`; did you mean `scoping`?"
+        );
+    }
+
+    #[test]
+    fn suggest_close_fields() {
+        const FIELDS: &[&str] = &["include_channels", "exclude_channels", "include_roles"];
+
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(suggest("include_chanels", FIELDS), Some("include_channels"));
+        assert_eq!(suggest("exclude_channel", FIELDS), Some("exclude_channels"));
+        assert_eq!(suggest("channels", FIELDS), None);
+    }
+}
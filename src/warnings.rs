@@ -0,0 +1,289 @@
+use std::path::{Path, PathBuf};
+
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use twilight_model::id::{
+    marker::{GuildMarker, UserMarker},
+    Id,
+};
+
+const WARNINGS_FILE_NAME: &str = "warnings.json";
+
+/// What a warning was issued for. Each kind is counted separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+enum WarningKind {
+    /// Issued by the `warn` action.
+    #[default]
+    Warning,
+    /// Recorded by the `escalating_timeout` action, to pick the next timeout.
+    TimeoutOffense,
+    /// Recorded by the `escalate_offenses` action whenever its filter matches,
+    /// to pick the next tier.
+    FilterOffense,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Warning {
+    guild_id: Id<GuildMarker>,
+    user_id: Id<UserMarker>,
+    #[serde(default)]
+    kind: WarningKind,
+    /// The filter a `FilterOffense` was recorded for. Other kinds count
+    /// towards every filter.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    filter_name: Option<String>,
+    /// When this warning stops counting, as a Unix timestamp in seconds.
+    expires_at: i64,
+}
+
+/// Keeps track of the warnings issued by the `warn` action, along with the
+/// offenses recorded by `escalating_timeout` and `escalate_offenses` actions. If a state directory is
+/// configured, warnings are saved there so that they survive restarts;
+/// otherwise they're only kept in memory.
+#[derive(Debug)]
+pub(crate) struct WarningStore {
+    path: Option<PathBuf>,
+    warnings: Mutex<Vec<Warning>>,
+}
+
+impl WarningStore {
+    pub(crate) fn in_memory() -> Self {
+        Self {
+            path: None,
+            warnings: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Loads the warnings saved in `state_dir`. A missing warnings file is
+    /// treated as there being no warnings yet.
+    pub(crate) fn load(state_dir: &Path) -> Result<Self> {
+        let path = state_dir.join(WARNINGS_FILE_NAME);
+        let warnings = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Self {
+            path: Some(path),
+            warnings: Mutex::new(warnings),
+        })
+    }
+
+    /// Issues a warning to a user, returning how many unexpired warnings they
+    /// now have in the guild.
+    pub(crate) async fn warn(
+        &self,
+        guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+        now: i64,
+        expiry_seconds: u64,
+    ) -> Result<usize> {
+        self.record(
+            WarningKind::Warning,
+            None,
+            guild_id,
+            user_id,
+            now,
+            expiry_seconds,
+        )
+        .await
+    }
+
+    /// Records that a user has committed an offense punished by an escalating
+    /// timeout, returning how many unexpired offenses they now have in the
+    /// guild.
+    pub(crate) async fn record_timeout_offense(
+        &self,
+        guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+        now: i64,
+        window_seconds: u64,
+    ) -> Result<usize> {
+        self.record(
+            WarningKind::TimeoutOffense,
+            None,
+            guild_id,
+            user_id,
+            now,
+            window_seconds,
+        )
+        .await
+    }
+
+    /// Records that a user's message or reaction has matched a filter,
+    /// returning how many unexpired offenses against that filter they now have
+    /// in the guild.
+    pub(crate) async fn record_filter_offense(
+        &self,
+        guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+        filter_name: &str,
+        now: i64,
+        window_seconds: u64,
+    ) -> Result<usize> {
+        self.record(
+            WarningKind::FilterOffense,
+            Some(filter_name),
+            guild_id,
+            user_id,
+            now,
+            window_seconds,
+        )
+        .await
+    }
+
+    async fn record(
+        &self,
+        kind: WarningKind,
+        filter_name: Option<&str>,
+        guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+        now: i64,
+        expiry_seconds: u64,
+    ) -> Result<usize> {
+        let mut warnings = self.warnings.lock().await;
+        warnings.retain(|warning| warning.expires_at > now);
+        warnings.push(Warning {
+            guild_id,
+            user_id,
+            kind,
+            filter_name: filter_name.map(str::to_owned),
+            expires_at: now + expiry_seconds as i64,
+        });
+
+        if let Some(path) = &self.path {
+            write_state_file(path, serde_json::to_vec(&*warnings)?).await?;
+        }
+
+        Ok(count_warnings(
+            &warnings,
+            kind,
+            filter_name,
+            guild_id,
+            user_id,
+            now,
+        ))
+    }
+
+    /// Gets how many unexpired warnings a user has in a guild.
+    pub(crate) async fn count(
+        &self,
+        guild_id: Id<GuildMarker>,
+        user_id: Id<UserMarker>,
+        now: i64,
+    ) -> usize {
+        count_warnings(
+            &self.warnings.lock().await,
+            WarningKind::Warning,
+            None,
+            guild_id,
+            user_id,
+            now,
+        )
+    }
+}
+
+/// Replaces a file in the state directory. The contents are written to a
+/// temporary file first, so that a crash partway through writing can't leave a
+/// corrupt file behind.
+pub(crate) async fn write_state_file(path: &Path, contents: Vec<u8>) -> Result<()> {
+    let temp_path = path.with_extension("json.tmp");
+    tokio::fs::write(&temp_path, contents).await?;
+    tokio::fs::rename(&temp_path, path).await?;
+    Ok(())
+}
+
+fn count_warnings(
+    warnings: &[Warning],
+    kind: WarningKind,
+    filter_name: Option<&str>,
+    guild_id: Id<GuildMarker>,
+    user_id: Id<UserMarker>,
+    now: i64,
+) -> usize {
+    warnings
+        .iter()
+        .filter(|warning| {
+            warning.kind == kind
+                && warning.filter_name.as_deref() == filter_name
+                && warning.guild_id == guild_id
+                && warning.user_id == user_id
+                && warning.expires_at > now
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use twilight_model::id::Id;
+
+    use super::WarningStore;
+
+    #[tokio::test]
+    async fn warnings_expire() {
+        let store = WarningStore::in_memory();
+
+        assert_eq!(store.warn(Id::new(1), Id::new(2), 0, 10).await.unwrap(), 1);
+        assert_eq!(store.warn(Id::new(1), Id::new(2), 5, 10).await.unwrap(), 2);
+        // Other users and guilds have their own counts.
+        assert_eq!(store.warn(Id::new(1), Id::new(3), 5, 10).await.unwrap(), 1);
+        assert_eq!(store.warn(Id::new(4), Id::new(2), 5, 10).await.unwrap(), 1);
+
+        assert_eq!(store.count(Id::new(1), Id::new(2), 9).await, 2);
+        assert_eq!(store.count(Id::new(1), Id::new(2), 10).await, 1);
+        assert_eq!(store.count(Id::new(1), Id::new(2), 15).await, 0);
+    }
+
+    #[tokio::test]
+    async fn timeout_offenses_are_counted_separately() {
+        let store = WarningStore::in_memory();
+
+        store.warn(Id::new(1), Id::new(2), 0, 10).await.unwrap();
+        assert_eq!(
+            store
+                .record_timeout_offense(Id::new(1), Id::new(2), 0, 10)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(store.count(Id::new(1), Id::new(2), 0).await, 1);
+    }
+
+    #[tokio::test]
+    async fn filter_offenses_are_counted_per_filter() {
+        let store = WarningStore::in_memory();
+
+        store.warn(Id::new(1), Id::new(2), 0, 10).await.unwrap();
+        let offense = |filter_name, now| {
+            store.record_filter_offense(Id::new(1), Id::new(2), filter_name, now, 10)
+        };
+        assert_eq!(offense("Invites", 0).await.unwrap(), 1);
+        assert_eq!(offense("Invites", 5).await.unwrap(), 2);
+        assert_eq!(offense("Slurs", 5).await.unwrap(), 1);
+        // Offenses aren't warnings.
+        assert_eq!(store.count(Id::new(1), Id::new(2), 5).await, 1);
+        // The first offense has expired by now.
+        assert_eq!(offense("Invites", 12).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn warnings_persist() {
+        let state_dir = std::env::temp_dir().join(format!(
+            "chrysanthemum-warnings-test-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&state_dir).unwrap();
+
+        let store = WarningStore::load(&state_dir).unwrap();
+        assert_eq!(store.count(Id::new(1), Id::new(2), 0).await, 0);
+        store.warn(Id::new(1), Id::new(2), 0, 10).await.unwrap();
+
+        let store = WarningStore::load(&state_dir).unwrap();
+        std::fs::remove_dir_all(&state_dir).unwrap();
+        assert_eq!(store.count(Id::new(1), Id::new(2), 0).await, 1);
+    }
+}
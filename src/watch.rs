@@ -0,0 +1,313 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use twilight_model::id::{marker::GuildMarker, Id};
+
+use crate::config::ConfigFormat;
+
+/// How long a guild configuration file has to go without changing before it's
+/// reloaded, so that files aren't reloaded halfway through being written.
+pub(crate) const WATCH_DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// The files each guild's configuration includes, as of when it was last
+/// loaded.
+pub(crate) type IncludedFiles = HashMap<Id<GuildMarker>, Vec<PathBuf>>;
+
+/// Resolves a path the same way whether or not the file it points to exists,
+/// by canonicalizing its directory, so that paths from the watcher can be
+/// compared with paths from configurations even after the file is removed.
+fn normalize(path: &Path) -> PathBuf {
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(file_name)) => parent
+            .canonicalize()
+            .map_or_else(|_| path.to_owned(), |parent| parent.join(file_name)),
+        _ => path.to_owned(),
+    }
+}
+
+/// What changed in the watched directories since the last time the watcher
+/// was asked.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Changes {
+    /// These files were created, written to, removed, or renamed.
+    Files(HashSet<PathBuf>),
+    /// The watcher missed some events, so any file could have changed.
+    Unknown,
+}
+
+impl Changes {
+    /// Gets the guilds among `guild_ids` whose configurations these changes
+    /// affect: those with a configuration file in `config_root` that changed,
+    /// in any format, since a new file can take priority over the old one, and
+    /// those that include a file that changed.
+    pub(crate) fn guilds(
+        &self,
+        config_root: &Path,
+        guild_ids: &[Id<GuildMarker>],
+        included_files: &IncludedFiles,
+    ) -> Vec<Id<GuildMarker>> {
+        let files = match self {
+            Changes::Files(files) => files,
+            Changes::Unknown => return guild_ids.to_vec(),
+        };
+
+        let config_root = config_root
+            .canonicalize()
+            .unwrap_or_else(|_| config_root.to_owned());
+        let changed_guild_files: HashSet<String> = files
+            .iter()
+            .filter(|path| path.parent() == Some(&config_root))
+            .filter(|path| ConfigFormat::from_path(path).is_some())
+            .filter_map(|path| Some(path.file_stem()?.to_str()?.to_owned()))
+            .collect();
+
+        guild_ids
+            .iter()
+            .copied()
+            .filter(|guild_id| {
+                changed_guild_files.contains(&guild_id.to_string())
+                    || included_files.get(guild_id).map_or(false, |included| {
+                        included.iter().any(|path| files.contains(&normalize(path)))
+                    })
+            })
+            .collect()
+    }
+}
+
+/// Watches the guild configuration directory, and the directories of the files
+/// guild configurations include, for changes. Directories are watched rather
+/// than the files themselves, since many editors save files by replacing them.
+pub(crate) struct ConfigWatcher {
+    watcher: RecommendedWatcher,
+    events: mpsc::UnboundedReceiver<DebouncedEvent>,
+    config_root: PathBuf,
+    watched: HashSet<PathBuf>,
+}
+
+impl ConfigWatcher {
+    /// Starts watching `config_root` and the directories of `included_files`,
+    /// reporting each file once it has gone `debounce` without changing.
+    /// Returns `None` if the directory can't be watched, in which case
+    /// configurations have to be reloaded on an interval instead.
+    pub(crate) fn new(
+        config_root: &Path,
+        included_files: &IncludedFiles,
+        debounce: Duration,
+    ) -> Option<Self> {
+        let (events_tx, events) = mpsc::unbounded_channel();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let watcher = notify::watcher(tx, debounce).and_then(|mut watcher| {
+            watcher.watch(config_root, RecursiveMode::NonRecursive)?;
+            Ok(watcher)
+        });
+        let config_root = config_root.canonicalize();
+        let (watcher, config_root) = match (watcher, config_root) {
+            (Ok(watcher), Ok(config_root)) => (watcher, config_root),
+            (Err(err), _) => {
+                tracing::warn!(?err, "Unable to watch guild configurations; falling back to reloading them on an interval");
+                return None;
+            }
+            (_, Err(err)) => {
+                tracing::warn!(?err, "Unable to watch guild configurations; falling back to reloading them on an interval");
+                return None;
+            }
+        };
+
+        // notify delivers events over a blocking channel, so they're passed on
+        // from a thread of their own. The thread ends once the watcher is
+        // dropped, or once nothing is listening any more.
+        std::thread::spawn(move || {
+            for event in rx {
+                if events_tx.send(event).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut watcher = Self {
+            watcher,
+            events,
+            watched: HashSet::from([config_root.clone()]),
+            config_root,
+        };
+        watcher.watch_included(included_files);
+        Some(watcher)
+    }
+
+    /// Watches the directories of `included_files`, and stops watching the
+    /// directories that were only watched for files that are no longer
+    /// included. Should be called whenever guild configurations are reloaded.
+    pub(crate) fn watch_included(&mut self, included_files: &IncludedFiles) {
+        let wanted: HashSet<PathBuf> = std::iter::once(self.config_root.clone())
+            .chain(
+                included_files
+                    .values()
+                    .flatten()
+                    .filter_map(|path| normalize(path).parent().map(Path::to_owned)),
+            )
+            .collect();
+
+        for directory in self.watched.difference(&wanted) {
+            if let Err(err) = self.watcher.unwatch(directory) {
+                tracing::debug!(?err, ?directory, "Unable to stop watching directory");
+            }
+        }
+
+        let mut watched = HashSet::from([self.config_root.clone()]);
+        for directory in wanted {
+            if self.watched.contains(&directory) {
+                watched.insert(directory);
+                continue;
+            }
+
+            match self.watcher.watch(&directory, RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    watched.insert(directory);
+                }
+                Err(err) => {
+                    tracing::warn!(
+                        ?err,
+                        ?directory,
+                        "Unable to watch included configuration files"
+                    )
+                }
+            }
+        }
+
+        self.watched = watched;
+    }
+
+    /// Waits for files in the watched directories to change, and returns
+    /// everything that has changed by then. Returns `None` if the watcher has
+    /// stopped, in which case nothing more will be reported.
+    pub(crate) async fn changed(&mut self) -> Option<Changes> {
+        let mut files = HashSet::new();
+        let mut event = self.events.recv().await?;
+        loop {
+            match event {
+                DebouncedEvent::Create(path)
+                | DebouncedEvent::Write(path)
+                | DebouncedEvent::Remove(path) => {
+                    files.insert(normalize(&path));
+                }
+                DebouncedEvent::Rename(from, to) => {
+                    files.insert(normalize(&from));
+                    files.insert(normalize(&to));
+                }
+                DebouncedEvent::Rescan => return Some(Changes::Unknown),
+                DebouncedEvent::Error(err, path) => {
+                    tracing::warn!(?err, ?path, "Error watching guild configurations");
+                }
+                // The notices come before the debounced events they announce,
+                // and permissions don't change what's in a file.
+                DebouncedEvent::NoticeWrite(_)
+                | DebouncedEvent::NoticeRemove(_)
+                | DebouncedEvent::Chmod(_) => {}
+            }
+
+            // Pick up everything that changed at the same time, so that the
+            // guilds it affects are reloaded together.
+            event = match self.events.try_recv() {
+                Ok(event) => event,
+                Err(_) if files.is_empty() => self.events.recv().await?,
+                Err(_) => return Some(Changes::Files(files)),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::{HashMap, HashSet},
+        path::PathBuf,
+        time::Duration,
+    };
+
+    use pretty_assertions::assert_eq;
+    use twilight_model::id::Id;
+
+    use super::{Changes, ConfigWatcher};
+
+    fn fixture_root(name: &str) -> PathBuf {
+        let config_root = std::env::temp_dir().join(format!(
+            "chrysanthemum-watch-{}-test-{}",
+            name,
+            std::process::id()
+        ));
+        std::fs::create_dir_all(config_root.join("shared")).unwrap();
+        config_root.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn map_changed_files_to_guilds() {
+        let config_root = fixture_root("guilds");
+        let guild_ids = [Id::new(1), Id::new(2), Id::new(3)];
+        let included_files = HashMap::from([
+            (
+                Id::new(2),
+                vec![config_root.join("shared/../shared/spam.yml")],
+            ),
+            (Id::new(3), vec![config_root.join("shared/spam.yml")]),
+        ]);
+        let guilds = |files: &[&str]| {
+            Changes::Files(files.iter().map(|file| config_root.join(file)).collect()).guilds(
+                &config_root,
+                &guild_ids,
+                &included_files,
+            )
+        };
+
+        let own_file = guilds(&["1.yml"]);
+        // A new file in another format could take priority over the old one.
+        let new_format = guilds(&["2.json"]);
+        let included = guilds(&["shared/spam.yml"]);
+        let unrelated = guilds(&["4.yml", "1.txt", "shared/1.yml"]);
+        let unknown = Changes::Unknown.guilds(&config_root, &guild_ids, &included_files);
+        std::fs::remove_dir_all(&config_root).unwrap();
+
+        assert_eq!(own_file, vec![Id::new(1)]);
+        assert_eq!(new_format, vec![Id::new(2)]);
+        assert_eq!(included, vec![Id::new(2), Id::new(3)]);
+        assert!(unrelated.is_empty());
+        assert_eq!(unknown, guild_ids.to_vec());
+    }
+
+    #[tokio::test]
+    async fn report_changed_files() {
+        let config_root = fixture_root("events");
+        let included_files =
+            HashMap::from([(Id::new(1), vec![config_root.join("shared/spam.yml")])]);
+        let mut watcher =
+            ConfigWatcher::new(&config_root, &included_files, Duration::from_millis(100))
+                .expect("couldn't watch fixture directory");
+
+        std::fs::write(config_root.join("1.yml"), "include_bots: true").unwrap();
+        std::fs::write(config_root.join("shared/spam.yml"), "spam: {}").unwrap();
+        let mut changed = HashSet::new();
+        while changed.len() < 2 {
+            let changes = tokio::time::timeout(Duration::from_secs(5), watcher.changed())
+                .await
+                .expect("timed out waiting for changes")
+                .expect("watcher stopped");
+            match changes {
+                Changes::Files(files) => changed.extend(files),
+                Changes::Unknown => panic!("watcher missed events"),
+            }
+        }
+        std::fs::remove_dir_all(&config_root).unwrap();
+
+        assert_eq!(
+            changed,
+            HashSet::from([
+                config_root.join("1.yml"),
+                config_root.join("shared/spam.yml")
+            ])
+        );
+    }
+}